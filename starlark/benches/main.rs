@@ -20,6 +20,7 @@ use starlark::{
     environment::{Globals, Module},
     eval::Evaluator,
     syntax::{AstModule, Dialect},
+    values::OwnedFrozenValue,
 };
 
 fn benchmark_run(globals: &Globals, code: &str) {
@@ -67,11 +68,143 @@ def bench():
 bench
 "#;
 
+// Small int arithmetic, which shouldn't need to allocate.
+const COUNTING_LOOP: &str = r#"
+def bench():
+    n = 0
+    x = 0
+    for i in range(10000):
+        n += 1
+        x = x + i * 2 - n
+    if x != 49985000:
+        fail("Wrong answer!")
+
+bench
+"#;
+
+const STRING_HEAVY: &str = r#"
+def bench():
+    xs = []
+    for i in range(10000):
+        xs.append("prefix_" + str(i % 100) + "_suffix")
+    return len(xs)
+bench()
+"#;
+
+const FORWARD_ARGS: &str = r#"
+def inner(a, b, c = 3, **kwargs):
+    return a + b + c
+
+def wrapper(*args, **kwargs):
+    return inner(*args, **kwargs)
+
+def bench():
+    x = 0
+    for i in range(10000):
+        x += wrapper(i, 2, c = 1, d = 4)
+    return x
+bench()
+"#;
+
+// Dictionaries with short string keys spend most of their time hashing.
+const STRING_DICT: &str = r#"
+def bench():
+    d = {}
+    for i in range(10000):
+        d["key" + str(i % 1000)] = i
+    n = 0
+    for i in range(10000):
+        if ("key" + str(i)) in d:
+            n += 1
+    return n
+bench()
+"#;
+
+// Comparing lists which differ in length, or in their first element, shouldn't look
+// at the rest of the elements.
+const UNEQUAL_LISTS: &str = r#"
+xs = list(range(100000))
+ys = list(range(100001))
+zs = [-1] + xs[1:]
+def bench():
+    if xs == ys or xs == zs:
+        fail("Wrong answer!")
+
+bench
+"#;
+
+// A large configuration, evaluated once and shared by every module which reads it.
+const LARGE_CONFIG: &str = r#"
+struct(targets = {
+    "target{}".format(i): struct(name = "target{}".format(i), deps = list(range(i % 10)))
+    for i in range(10000)
+})
+"#;
+
+const READ_CONFIG: &str = r#"
+len(config.targets["target9999"].deps)
+"#;
+
+fn benchmark_run_with_config(globals: &Globals, config: &OwnedFrozenValue, code: &str) {
+    let env = Module::new();
+    env.set_frozen("config", config);
+    let mut eval = Evaluator::new(&env);
+    let ast = AstModule::parse("benchmark.sky", code.to_owned(), &Dialect::Standard).unwrap();
+    eval.eval_module(ast, globals).unwrap();
+}
+
+fn benchmark_run_interned(globals: &Globals, code: &str) {
+    let env = Module::new();
+    let mut eval = Evaluator::new(&env);
+    eval.enable_string_interning();
+    let ast = AstModule::parse("benchmark.sky", code.to_owned(), &Dialect::Standard).unwrap();
+    eval.eval_module(ast, globals).unwrap();
+}
+
+fn benchmark_run_no_gc(globals: &Globals, code: &str) {
+    let env = Module::new();
+    let mut eval = Evaluator::new(&env);
+    eval.disable_gc();
+    let ast = AstModule::parse("benchmark.sky", code.to_owned(), &Dialect::Standard).unwrap();
+    eval.eval_module(ast, globals).unwrap();
+}
+
 pub fn criterion_general_benchmark(c: &mut Criterion, globals: &Globals) {
     c.bench_function("empty", |b| b.iter(|| benchmark_run(globals, EMPTY)));
     c.bench_function("bubble_sort", |b| {
         b.iter(|| benchmark_run(globals, BUBBLE_SORT))
     });
+    c.bench_function("string_heavy", |b| {
+        b.iter(|| benchmark_run(globals, STRING_HEAVY))
+    });
+    c.bench_function("string_heavy_interned", |b| {
+        b.iter(|| benchmark_run_interned(globals, STRING_HEAVY))
+    });
+    // Top-level statements are where GC points go.
+    let many_statements = format!("x = 0\n{}", "x = x + 1\n".repeat(1000));
+    c.bench_function("many_statements", |b| {
+        b.iter(|| benchmark_run(globals, &many_statements))
+    });
+    c.bench_function("many_statements_no_gc", |b| {
+        b.iter(|| benchmark_run_no_gc(globals, &many_statements))
+    });
+    c.bench_function("string_dict", |b| {
+        b.iter(|| benchmark_run(globals, STRING_DICT))
+    });
+    c.bench_function("forward_args", |b| {
+        b.iter(|| benchmark_run(globals, FORWARD_ARGS))
+    });
+    c.bench_function("shared_large_config", |b| {
+        let (_, config) = Module::new()
+            .eval_and_freeze(|env| {
+                let mut eval = Evaluator::new(env);
+                let ast =
+                    AstModule::parse("config.sky", LARGE_CONFIG.to_owned(), &Dialect::Standard)?;
+                eval.eval_module(ast, globals)
+            })
+            .unwrap();
+        b.iter(|| benchmark_run_with_config(globals, &config, READ_CONFIG))
+    });
 }
 
 pub fn criterion_parsing_benchmark(c: &mut Criterion) {
@@ -90,6 +223,30 @@ pub fn criterion_eval_benchmark(c: &mut Criterion, globals: &Globals) {
         let bench_function = eval.eval_module(ast, globals).unwrap();
         b.iter(move || eval.eval_function(bench_function, &[], &[]).unwrap())
     });
+    c.bench_function("run_counting_loop", |b| {
+        let env = Module::new();
+        let mut eval = Evaluator::new(&env);
+        let ast = AstModule::parse(
+            "benchmark.sky",
+            COUNTING_LOOP.to_owned(),
+            &Dialect::Standard,
+        )
+        .unwrap();
+        let bench_function = eval.eval_module(ast, globals).unwrap();
+        b.iter(move || eval.eval_function(bench_function, &[], &[]).unwrap())
+    });
+    c.bench_function("run_unequal_lists", |b| {
+        let env = Module::new();
+        let mut eval = Evaluator::new(&env);
+        let ast = AstModule::parse(
+            "benchmark.sky",
+            UNEQUAL_LISTS.to_owned(),
+            &Dialect::Standard,
+        )
+        .unwrap();
+        let bench_function = eval.eval_module(ast, globals).unwrap();
+        b.iter(move || eval.eval_function(bench_function, &[], &[]).unwrap())
+    });
 }
 
 pub fn criterion_benchmark(c: &mut Criterion) {
@@ -129,10 +129,15 @@ impl Context {
         filename: &str,
         content: String,
     ) -> impl Iterator<Item = Message> {
-        Self::err(
-            filename,
-            AstModule::parse(filename, content, &dialect()).map(|module| self.go(filename, module)),
-        )
+        // Report all the syntax errors, so an editor can show them at once.
+        match AstModule::parse_all_errors(filename, content, &dialect()) {
+            Ok(module) => Either::Left(self.go(filename, module)),
+            Err(errors) => Either::Right(
+                errors
+                    .into_map(|e| Message::from_anyhow(filename, e))
+                    .into_iter(),
+            ),
+        }
     }
 
     fn run(&self, file: &str, ast: AstModule) -> impl Iterator<Item = Message> {
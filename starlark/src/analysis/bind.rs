@@ -35,6 +35,7 @@ pub enum Assigner {
     Load,     // Obtained from `load`
     Argument, // From a function argument
     Assign,   // From an assignment
+    Loop,     // From the variable of a `for` loop or comprehension
 }
 
 #[derive(Debug)]
@@ -92,12 +93,12 @@ fn comprehension(
 ) {
     expr(&for_.over, res);
     let mut inner = Vec::new();
-    expr_lvalue(&for_.var, &mut inner);
+    expr_lvalue(&for_.var, Assigner::Loop, &mut inner);
     for clause in clauses {
         match clause {
             Clause::For(ForClause { var, over }) => {
                 expr(over, &mut inner);
-                expr_lvalue(var, &mut inner);
+                expr_lvalue(var, Assigner::Loop, &mut inner);
             }
             Clause::If(x) => expr(x, &mut inner),
         }
@@ -129,9 +130,9 @@ fn expr(x: &AstExpr, res: &mut Vec<Bind>) {
     }
 }
 
-fn expr_lvalue(x: &AstAssign, res: &mut Vec<Bind>) {
+fn expr_lvalue(x: &AstAssign, assigner: Assigner, res: &mut Vec<Bind>) {
     x.visit_expr(|x| expr(x, res));
-    x.visit_lvalue(|x| res.push(Bind::Set(Assigner::Assign, x.clone())))
+    x.visit_lvalue(|x| res.push(Bind::Set(assigner, x.clone())))
 }
 
 fn parameters(args: &[AstParameter], res: &mut Vec<Bind>, inner: &mut Vec<Bind>) {
@@ -187,7 +188,7 @@ fn stmt(x: &AstStmt, res: &mut Vec<Bind>) {
         }
         Stmt::Assign(lhs, rhs) => {
             expr(rhs, res);
-            expr_lvalue(lhs, res);
+            expr_lvalue(lhs, Assigner::Assign, res);
         }
         Stmt::AssignModify(lhs, _, rhs) => {
             // For a += b, we:
@@ -197,11 +198,11 @@ fn stmt(x: &AstStmt, res: &mut Vec<Bind>) {
             lhs.visit_expr(|x| expr(x, res));
             lhs.visit_lvalue(|x| res.push(Bind::Get(x.clone().into_map(|AssignIdentP(s, ())| s))));
             expr(rhs, res);
-            expr_lvalue(lhs, res);
+            expr_lvalue(lhs, Assigner::Assign, res);
         }
         Stmt::For(dest, box (inner, body)) => {
             expr(inner, res);
-            expr_lvalue(dest, res);
+            expr_lvalue(dest, Assigner::Loop, res);
             flow(res);
             stmt(body, res);
             flow(res)
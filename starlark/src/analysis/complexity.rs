@@ -0,0 +1,114 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use gazebo::variants::VariantName;
+use thiserror::Error;
+
+use crate::{
+    analysis::types::{LintT, LintWarning},
+    codemap::CodeMap,
+    syntax::{
+        ast::{AstExpr, Expr},
+        AstModule,
+    },
+};
+
+#[derive(Error, Debug, VariantName)]
+pub(crate) enum Complexity {
+    #[error("Expression is nested {0} levels deep, more than the maximum of {1}")]
+    DeeplyNestedExpression(usize, usize),
+}
+
+impl LintWarning for Complexity {
+    fn is_serious(&self) -> bool {
+        false
+    }
+}
+
+/// How deeply nested an expression is. A chain of the same operator, e.g. `a + b + c`,
+/// parses as nested expressions but reads as a flat list, so only counts once.
+fn depth(x: &AstExpr) -> usize {
+    let mut res = 0;
+    match &**x {
+        Expr::Op(l, op, r) => {
+            let l = match &***l {
+                Expr::Op(_, l_op, _) if l_op == op => depth(l) - 1,
+                _ => depth(l),
+            };
+            res = l.max(depth(r));
+        }
+        _ => x.visit_expr(|x| res = res.max(depth(x))),
+    }
+    res + 1
+}
+
+fn deeply_nested(module: &AstModule, max_depth: usize, res: &mut Vec<LintT<Complexity>>) {
+    fn check(codemap: &CodeMap, max_depth: usize, x: &AstExpr, res: &mut Vec<LintT<Complexity>>) {
+        let depth = depth(x);
+        if depth > max_depth {
+            res.push(LintT::new(
+                codemap,
+                x.span,
+                Complexity::DeeplyNestedExpression(depth, max_depth),
+            ))
+        }
+    }
+    module
+        .statement
+        .visit_expr(|x| check(&module.codemap, max_depth, x, res));
+}
+
+pub(crate) fn complexity(module: &AstModule, max_depth: usize) -> Vec<LintT<Complexity>> {
+    let mut res = Vec::new();
+    deeply_nested(module, max_depth, &mut res);
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use gazebo::prelude::*;
+
+    use super::*;
+    use crate::syntax::Dialect;
+
+    fn module(x: &str) -> AstModule {
+        AstModule::parse("bad.bzl", x.to_owned(), &Dialect::Extended).unwrap()
+    }
+
+    #[test]
+    fn test_lint_deeply_nested() {
+        let mut res = Vec::new();
+        deeply_nested(
+            &module(
+                r#"
+x = 1 + (2 * (3 - (4 // (5 % 6))))
+y = 1 + 2 + 3 + 4 + 5 + 6 + 7
+def f():
+    return [[[[[1]]]]]
+"#,
+            ),
+            4,
+            &mut res,
+        );
+        assert_eq!(
+            res.map(|x| x.to_string()),
+            &[
+                "bad.bzl:2:5-35: Expression is nested 6 levels deep, more than the maximum of 4",
+                "bad.bzl:5:12-23: Expression is nested 6 levels deep, more than the maximum of 4",
+            ]
+        );
+    }
+}
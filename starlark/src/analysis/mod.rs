@@ -20,6 +20,7 @@ pub use types::Lint;
 use crate::{analysis::types::LintT, syntax::AstModule};
 
 mod bind;
+mod complexity;
 mod dubious;
 mod exported;
 mod flow;
@@ -27,12 +28,47 @@ mod incompatible;
 mod names;
 mod performance;
 mod types;
+mod unbounded;
+
+/// Options to configure [`AstModule::lint_with_options`].
+#[derive(Debug, Clone)]
+pub struct LintOptions {
+    /// Warn about expressions nested more deeply than this, which are usually
+    /// hard to read (and often generated). Chains of the same operator, e.g. `a + b + c`,
+    /// only count as one level. Defaults to 100.
+    pub max_expression_depth: usize,
+    /// Warn about function parameters which are never used. Defaults to `true`.
+    pub unused_parameters: bool,
+    /// Warn about variables bound by a `for` loop or comprehension which are never used.
+    /// Defaults to `true`.
+    pub unused_loop_variables: bool,
+    /// Warn about code which can't finish: modifying a list or dictionary inside a `for` loop
+    /// over it, and functions which always call themselves. The checks are heuristic, so only
+    /// report code which is certain to fail, but are off by default.
+    pub unbounded: bool,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        Self {
+            max_expression_depth: 100,
+            unused_parameters: true,
+            unused_loop_variables: true,
+            unbounded: false,
+        }
+    }
+}
 
 impl AstModule {
     /// Run a static linter over the module. If the complete set of global variables are known
     /// they can be passed as the `globals` argument, resulting in name-resolution lint errors.
     /// The precise checks run by the linter are not considered stable between versions.
     pub fn lint(&self, globals: Option<&[&str]>) -> Vec<Lint> {
+        self.lint_with_options(globals, &LintOptions::default())
+    }
+
+    /// Like [`lint`](AstModule::lint), but with [`LintOptions`] to configure the checks.
+    pub fn lint_with_options(&self, globals: Option<&[&str]>, options: &LintOptions) -> Vec<Lint> {
         let mut res = Vec::new();
         res.extend(flow::flow_issues(self).into_iter().map(LintT::erase));
         res.extend(
@@ -42,11 +78,19 @@ impl AstModule {
         );
         res.extend(dubious::dubious(self).into_iter().map(LintT::erase));
         res.extend(
-            names::name_warnings(self, globals)
+            names::name_warnings(self, globals, options)
                 .into_iter()
                 .map(LintT::erase),
         );
         res.extend(performance::performance(self).into_iter().map(LintT::erase));
+        res.extend(
+            complexity::complexity(self, options.max_expression_depth)
+                .into_iter()
+                .map(LintT::erase),
+        );
+        if options.unbounded {
+            res.extend(unbounded::unbounded(self).into_iter().map(LintT::erase));
+        }
         res
     }
 }
@@ -25,6 +25,7 @@ use crate::{
         bind,
         bind::{Assigner, Bind, Scope},
         types::{LintT, LintWarning},
+        LintOptions,
     },
     codemap::{CodeMap, Span},
     syntax::{
@@ -68,7 +69,7 @@ impl NameWarning {
             match typ {
                 Assigner::Load => NameWarning::UnusedLoad(name),
                 Assigner::Argument => NameWarning::UnusedArgument(name),
-                Assigner::Assign => NameWarning::UnusedAssign(name),
+                Assigner::Assign | Assigner::Loop => NameWarning::UnusedAssign(name),
             },
         )
     }
@@ -77,10 +78,11 @@ impl NameWarning {
 pub(crate) fn name_warnings(
     module: &AstModule,
     globals: Option<&[&str]>,
+    options: &LintOptions,
 ) -> Vec<LintT<NameWarning>> {
     let mut res = Vec::new();
     let scope = bind::scope(module);
-    unused_variable(&module.codemap, &scope, true, &mut res);
+    unused_variable(&module.codemap, &scope, true, options, &mut res);
     duplicate_assign(&module.codemap, &scope, true, &mut res);
     unassigned_variable(&module.codemap, &scope, &mut res);
     if let Some(globals) = globals {
@@ -143,14 +145,26 @@ fn duplicate_assign(
     }
 }
 
-fn unused_variable(codemap: &CodeMap, scope: &Scope, top: bool, res: &mut Vec<LintT<NameWarning>>) {
+fn unused_variable(
+    codemap: &CodeMap,
+    scope: &Scope,
+    top: bool,
+    options: &LintOptions,
+    res: &mut Vec<LintT<NameWarning>>,
+) {
     let mut warnings = HashMap::new();
     for (x, (typ, span)) in &scope.bound {
-        let exported = top && *typ == Assigner::Assign && !x.starts_with('_');
+        let exported =
+            top && (*typ == Assigner::Assign || *typ == Assigner::Loop) && !x.starts_with('_');
         let ignored = !top && x.starts_with('_');
+        let allowed = match typ {
+            Assigner::Argument => !options.unused_parameters,
+            Assigner::Loop => !options.unused_loop_variables,
+            Assigner::Load | Assigner::Assign => false,
+        };
 
-        // We don't want to warn about exported things or ignored things
-        if !exported && !ignored {
+        // We don't want to warn about exported things, ignored things, or things the options allow
+        if !exported && !ignored && !allowed {
             warnings.insert(x, (*typ, *span));
         }
     }
@@ -162,7 +176,7 @@ fn unused_variable(codemap: &CodeMap, scope: &Scope, top: bool, res: &mut Vec<Li
                 warnings.remove(&x.node);
             }
             Bind::Scope(scope) => {
-                unused_variable(codemap, scope, false, res);
+                unused_variable(codemap, scope, false, options, res);
                 for x in scope.free.keys() {
                     warnings.remove(x);
                 }
@@ -332,12 +346,45 @@ def foo():
         );
         let mut res = Vec::new();
         let scope = bind::scope(&m);
-        unused_variable(&m.codemap, &scope, true, &mut res);
+        unused_variable(&m.codemap, &scope, true, &LintOptions::default(), &mut res);
         let mut res = res.map(|x| x.problem.about());
         res.sort();
         assert_eq!(res, &["_no2", "_no4", "_no6", "no1", "no3", "no5"]);
     }
 
+    #[test]
+    fn test_lint_unused_options() {
+        let m = module(
+            r#"
+for top in []:
+    pass
+def foo(arg):
+    for x in []:
+        pass
+    return [1 for y in []]
+"#,
+        );
+        let unused = |options: &LintOptions| {
+            let mut res = Vec::new();
+            let scope = bind::scope(&m);
+            unused_variable(&m.codemap, &scope, true, options, &mut res);
+            let mut res = res.map(|x| x.problem.about().clone());
+            res.sort();
+            res
+        };
+        assert_eq!(unused(&LintOptions::default()), &["arg", "x", "y"]);
+        let options = LintOptions {
+            unused_parameters: false,
+            ..LintOptions::default()
+        };
+        assert_eq!(unused(&options), &["x", "y"]);
+        let options = LintOptions {
+            unused_loop_variables: false,
+            ..LintOptions::default()
+        };
+        assert_eq!(unused(&options), &["arg"]);
+    }
+
     #[test]
     fn test_lint_duplicate_assign() {
         let m = module(
@@ -0,0 +1,358 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use gazebo::variants::VariantName;
+use thiserror::Error;
+
+use crate::{
+    analysis::types::{LintT, LintWarning},
+    codemap::{CodeMap, Span},
+    syntax::{
+        ast::{Assign, AstExpr, AstStmt, BinOp, Expr, Stmt},
+        uniplate::Visit,
+        AstModule,
+    },
+};
+
+#[derive(Error, Debug, VariantName)]
+pub(crate) enum Unbounded {
+    #[error("`{0}` is modified inside a `for` loop over it, which fails at runtime")]
+    ModifiedWhileIterating(String),
+    #[error("Function `{0}` always calls itself, so never returns")]
+    UnconditionalRecursion(String),
+}
+
+impl LintWarning for Unbounded {
+    fn is_serious(&self) -> bool {
+        true
+    }
+}
+
+/// Methods of `list` and `dict` which modify the value they are called on.
+const MUTATING_METHODS: &[&str] = &[
+    "append",
+    "clear",
+    "extend",
+    "insert",
+    "pop",
+    "popitem",
+    "remove",
+    "setdefault",
+    "update",
+];
+
+/// Does the statement bind `name`, other than in a nested `def`.
+fn binds(x: &AstStmt, name: &str) -> bool {
+    let mut res = false;
+    match &**x {
+        Stmt::Assign(lhs, _) | Stmt::For(lhs, _) => lhs.visit_lvalue(|x| res |= x.0 == name),
+        Stmt::Def(x, ..) => return x.0 == name,
+        Stmt::Load(load) => return load.args.iter().any(|(x, _)| x.0 == name),
+        _ => {}
+    }
+    x.visit_stmt(|x| {
+        if !matches!(&**x, Stmt::Def(..)) {
+            res |= binds(x, name)
+        }
+    });
+    res
+}
+
+/// Is the statement a call to `fail`, which always leaves the function.
+fn is_fail(x: &AstStmt) -> bool {
+    match &**x {
+        Stmt::Expression(e) => matches!(&**e, Expr::Call(box fun, _) if is_identifier(fun, "fail")),
+        _ => false,
+    }
+}
+
+/// Does the statement contain a `return` or a call to `fail`, other than in a nested `def`.
+fn returns(x: &AstStmt) -> bool {
+    match &**x {
+        Stmt::Return(_) => true,
+        _ if is_fail(x) => true,
+        Stmt::Def(..) => false,
+        _ => {
+            let mut res = false;
+            x.visit_stmt(|x| res |= returns(x));
+            res
+        }
+    }
+}
+
+fn is_identifier(x: &AstExpr, name: &str) -> bool {
+    matches!(&**x, Expr::Identifier(x, ()) if x.node == name)
+}
+
+// The list or dict a `for` loop iterates over can't be modified until the loop ends,
+// e.g. `for x in xs: xs.append(x)` fails on the first `append`.
+fn modified_while_iterating(module: &AstModule, res: &mut Vec<LintT<Unbounded>>) {
+    fn expr(codemap: &CodeMap, name: &str, x: &AstExpr, res: &mut Vec<LintT<Unbounded>>) {
+        match &**x {
+            Expr::Call(box fun, _) => match &**fun {
+                Expr::Dot(box object, method)
+                    if is_identifier(object, name)
+                        && MUTATING_METHODS.contains(&method.node.as_str()) =>
+                {
+                    res.push(LintT::new(
+                        codemap,
+                        x.span,
+                        Unbounded::ModifiedWhileIterating(name.to_owned()),
+                    ))
+                }
+                _ => {}
+            },
+            // The body of a lambda doesn't run as part of the loop.
+            Expr::Lambda(..) => return,
+            _ => {}
+        }
+        x.visit_expr(|x| expr(codemap, name, x, res));
+    }
+
+    fn body(codemap: &CodeMap, name: &str, x: &AstStmt, res: &mut Vec<LintT<Unbounded>>) {
+        let modified = match &**x {
+            // Neither does the body of a def.
+            Stmt::Def(..) => return,
+            Stmt::Assign(lhs, _) => match &**lhs {
+                Assign::ArrayIndirection(box (object, _)) => is_identifier(object, name),
+                _ => false,
+            },
+            // `xs += ys` extends the list `xs` in place.
+            Stmt::AssignModify(lhs, _, _) => match &**lhs {
+                Assign::ArrayIndirection(box (object, _)) => is_identifier(object, name),
+                Assign::Identifier(x) => x.0 == name,
+                _ => false,
+            },
+            _ => false,
+        };
+        if modified {
+            res.push(LintT::new(
+                codemap,
+                x.span,
+                Unbounded::ModifiedWhileIterating(name.to_owned()),
+            ));
+        }
+        x.visit_children(|x| match x {
+            Visit::Stmt(x) => body(codemap, name, x, res),
+            Visit::Expr(x) => expr(codemap, name, x, res),
+        });
+    }
+
+    fn stmt(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<Unbounded>>) {
+        if let Stmt::For(_, box (over, loop_body)) = &**x {
+            if let Expr::Identifier(name, ()) = &**over {
+                // If the name is rebound, later modifications may be to something else.
+                if !binds(x, name) {
+                    body(codemap, name, loop_body, res);
+                }
+            }
+        }
+        x.visit_stmt(|x| stmt(codemap, x, res));
+    }
+
+    stmt(&module.codemap, &module.statement, res)
+}
+
+// A function which calls itself before it can return recurses until it hits the
+// maximum call depth, e.g. `def f(x): return f(x - 1)`.
+fn unconditional_recursion(module: &AstModule, res: &mut Vec<LintT<Unbounded>>) {
+    /// The call to `name` in the parts of the expression which are always evaluated.
+    fn calls(x: &AstExpr, name: &str) -> Option<Span> {
+        match &**x {
+            Expr::Call(box fun, _) if is_identifier(fun, name) => return Some(x.span),
+            Expr::Lambda(..) => return None,
+            Expr::If(box (cond, _, _)) => return calls(cond, name),
+            Expr::Op(box l, BinOp::And | BinOp::Or, _) => return calls(l, name),
+            Expr::ListComprehension(_, box clause, _) => return calls(&clause.over, name),
+            Expr::DictComprehension(_, box clause, _) => return calls(&clause.over, name),
+            _ => {}
+        }
+        let mut res = None;
+        x.visit_expr(|x| res = res.or_else(|| calls(x, name)));
+        res
+    }
+
+    /// The call to `name` which is made every time the statement runs, if there is one,
+    /// and whether the statement always finishes without leaving the function.
+    fn always_calls(x: &AstStmt, name: &str) -> (Option<Span>, bool) {
+        match &**x {
+            Stmt::Statements(xs) => {
+                for x in xs {
+                    match always_calls(x, name) {
+                        (None, true) => {}
+                        res => return res,
+                    }
+                }
+                (None, true)
+            }
+            Stmt::Expression(e) if is_fail(x) => (calls(e, name), false),
+            Stmt::Expression(e) | Stmt::Assign(_, box e) | Stmt::AssignModify(_, _, box e) => {
+                (calls(e, name), true)
+            }
+            Stmt::Return(e) => (e.as_ref().and_then(|e| calls(e, name)), false),
+            Stmt::Break | Stmt::Continue => (None, false),
+            Stmt::Pass | Stmt::Def(..) | Stmt::Load(..) => (None, true),
+            Stmt::If(cond, _) => (calls(cond, name), !returns(x)),
+            Stmt::IfElse(cond, box (then_block, else_block)) => {
+                if let Some(call) = calls(cond, name) {
+                    return (Some(call), true);
+                }
+                match (
+                    always_calls(then_block, name),
+                    always_calls(else_block, name),
+                ) {
+                    ((Some(call), _), (Some(_), _)) => (Some(call), true),
+                    _ => (None, !returns(x)),
+                }
+            }
+            // The body of the loop may not run at all.
+            Stmt::For(_, box (over, _)) => (calls(over, name), !returns(x)),
+        }
+    }
+
+    fn stmt(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<Unbounded>>) {
+        if let Stmt::Def(name, params, _, body, _) = &**x {
+            // If the name is a local variable, the function doesn't call itself.
+            let shadowed = params
+                .iter()
+                .any(|x| matches!(x.split().0, Some(x) if x.0 == name.0));
+            if !shadowed && !binds(body, &name.0) {
+                if let (Some(call), _) = always_calls(body, &name.0) {
+                    res.push(LintT::new(
+                        codemap,
+                        call,
+                        Unbounded::UnconditionalRecursion(name.0.clone()),
+                    ));
+                }
+            }
+        }
+        x.visit_stmt(|x| stmt(codemap, x, res));
+    }
+
+    stmt(&module.codemap, &module.statement, res)
+}
+
+pub(crate) fn unbounded(module: &AstModule) -> Vec<LintT<Unbounded>> {
+    let mut res = Vec::new();
+    modified_while_iterating(module, &mut res);
+    unconditional_recursion(module, &mut res);
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use gazebo::prelude::*;
+
+    use super::*;
+    use crate::syntax::Dialect;
+
+    fn module(x: &str) -> AstModule {
+        AstModule::parse("bad.bzl", x.to_owned(), &Dialect::Extended).unwrap()
+    }
+
+    #[test]
+    fn test_lint_modified_while_iterating() {
+        let mut res = Vec::new();
+        modified_while_iterating(
+            &module(
+                r#"
+def f(xs, d, ys):
+    for x in xs:
+        xs.append(x)
+    for k in d:
+        d[k] = 1
+        if k:
+            y = d.pop(k)
+    for y in ys:
+        ys += [y]
+    for x in xs:
+        ys.append(x)
+        g = lambda: xs.clear()
+        def h():
+            xs.clear()
+    for x in xs:
+        xs = [x]
+        xs.append(x)
+"#,
+            ),
+            &mut res,
+        );
+        assert_eq!(
+            res.map(|x| x.to_string()),
+            &[
+                "bad.bzl:4:9-21: `xs` is modified inside a `for` loop over it, which fails at runtime",
+                "bad.bzl:6:9-17: `d` is modified inside a `for` loop over it, which fails at runtime",
+                "bad.bzl:8:17-25: `d` is modified inside a `for` loop over it, which fails at runtime",
+                "bad.bzl:10:9-18: `ys` is modified inside a `for` loop over it, which fails at runtime",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_unconditional_recursion() {
+        let mut res = Vec::new();
+        unconditional_recursion(
+            &module(
+                r#"
+def f(x):
+    return f(x - 1)
+def g(x):
+    print(x)
+    if x:
+        y = 1
+    else:
+        y = 2
+    g(y)
+def h(x):
+    if x:
+        return h(x - 1)
+    else:
+        return h(x + 1)
+def ok1(x):
+    if x == 0:
+        return 0
+    return ok1(x - 1)
+def ok2(x):
+    return x and ok2(x - 1)
+def ok3(xs):
+    for x in xs:
+        ok3(x)
+def ok4(ok4):
+    return ok4(1)
+def ok5(x):
+    return [ok5(y) for y in x]
+def ok6(x):
+    fail("no")
+    return ok6(x)
+def ok7(x):
+    if x:
+        fail("no")
+    return ok7(x)
+"#,
+            ),
+            &mut res,
+        );
+        assert_eq!(
+            res.map(|x| x.to_string()),
+            &[
+                "bad.bzl:3:12-20: Function `f` always calls itself, so never returns",
+                "bad.bzl:10:5-9: Function `g` always calls itself, so never returns",
+                "bad.bzl:13:16-24: Function `h` always calls itself, so never returns",
+            ]
+        );
+    }
+}
@@ -39,7 +39,7 @@ use crate::{
         lexer::{Lexer, Token},
         AstModule, Dialect,
     },
-    values::{none::NoneType, structs::Struct, OwnedFrozenValue, Value},
+    values::{diff, none::NoneType, structs::Struct, OwnedFrozenValue, Value},
 };
 
 fn mk_environment() -> GlobalsBuilder {
@@ -62,7 +62,16 @@ static ASSERT_STAR: Lazy<FrozenModule> = Lazy::new(|| {
 
 fn assert_equals<'v>(a: Value<'v>, b: Value<'v>) -> anyhow::Result<NoneType> {
     if !a.equals(b)? {
-        Err(anyhow!("assert_eq: expected {}, got {}", a, b))
+        match diff(a, b) {
+            // Only worth mentioning the difference if it's more precise than the values
+            Some(d) if d != format!("{} != {}", a, b) => Err(anyhow!(
+                "assert_eq: expected {}, got {} (differs at {})",
+                a,
+                b,
+                d
+            )),
+            _ => Err(anyhow!("assert_eq: expected {}, got {}", a, b)),
+        }
     } else {
         Ok(NoneType)
     }
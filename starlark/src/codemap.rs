@@ -276,6 +276,11 @@ impl CodeMap {
         }
     }
 
+    /// Gets the lines and columns of the start and end of a `Span`.
+    ///
+    /// The lines and columns are 0-indexed, and columns count characters, not bytes.
+    ///
+    /// Panics if `span` is not entirely within this file.
     pub fn resolve_span(&self, span: Span) -> ResolvedSpan {
         let begin = self.find_line_col(span.begin);
         let end = self.find_line_col(span.end);
@@ -324,10 +329,18 @@ impl fmt::Display for FileSpan {
 }
 
 impl FileSpan {
+    /// Gets the lines and columns of the span, see [`CodeMap::resolve_span`].
     pub fn resolve_span(&self) -> ResolvedSpan {
         self.file.resolve_span(self.span)
     }
 
+    /// Gets the source text of the span.
+    pub fn source_span(&self) -> &str {
+        self.file.source_span(self.span)
+    }
+
+    /// Gets the filename along with the lines and columns of the span,
+    /// which no longer refers to the `CodeMap`.
     pub fn resolve(&self) -> ResolvedFileSpan {
         ResolvedFileSpan {
             file: self.file.filename().to_owned(),
@@ -479,6 +492,25 @@ mod test {
         assert_eq!(codemap.source_line(2), "");
     }
 
+    #[test]
+    fn test_file_span() {
+        let codemap = CodeMap::new("test.star".to_owned(), "x = 1\ny = [x, x]\n".to_owned());
+        let file_span = codemap.file_span(codemap.full_span().subspan(10, 16));
+        assert_eq!(file_span.source_span(), "[x, x]");
+        assert_eq!(file_span.to_string(), "test.star:2:5-11");
+        let resolved = file_span.resolve();
+        assert_eq!(resolved.file, "test.star");
+        assert_eq!(
+            resolved.span,
+            ResolvedSpan {
+                begin_line: 1,
+                begin_column: 4,
+                end_line: 1,
+                end_column: 10
+            }
+        );
+    }
+
     #[test]
     fn test_multibyte() {
         let content = "65°00′N 18°00′W 汉语\n🔬";
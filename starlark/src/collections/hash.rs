@@ -21,7 +21,7 @@ use gazebo::{coerce::Coerce, prelude::*};
 use indexmap::Equivalent;
 
 use crate as starlark;
-use crate::collections::StarlarkHasher;
+use crate::collections::{SmallHasher, StarlarkHasher};
 
 /// A hash result.
 #[derive(Hash, Eq, PartialEq, Clone, Copy, Dupe, Debug, Default, Trace)]
@@ -47,7 +47,14 @@ impl SmallHashResult {
     /// Create a new [`SmallHashResult`] using the [`Hash`] trait
     /// for given key.
     pub fn new<K: Hash + ?Sized>(key: &K) -> Self {
-        let mut hasher = StarlarkHasher::new();
+        Self::new_with::<StarlarkHasher, K>(key)
+    }
+
+    /// Like [`new`](SmallHashResult::new), but using the hasher `H` rather than
+    /// [`StarlarkHasher`]. Starlark values always hash with [`StarlarkHasher`], so this is
+    /// only for collections whose keys are all hashed with `H`.
+    pub fn new_with<H: SmallHasher, K: Hash + ?Sized>(key: &K) -> Self {
+        let mut hasher = H::default();
         key.hash(&mut hasher);
         hasher.finish_small()
     }
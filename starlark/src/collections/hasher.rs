@@ -15,35 +15,179 @@
  * limitations under the License.
  */
 
-use std::{collections::hash_map::DefaultHasher, hash::Hasher};
+use std::{convert::TryInto, hash::Hasher};
 
 use crate::collections::SmallHashResult;
 
 /// A hasher used by Starlark implementation.
 ///
-/// Starlark relies on stable hashing, and this is the hasher.
+/// Starlark relies on stable hashing, and this is the hasher. The hash of a given input
+/// is the same on every platform and in every process, so iteration order and hashes don't
+/// vary between runs. The algorithm may change between releases, so hashes should not be
+/// persisted.
+///
+/// Uses the same algorithm as `FxHasher` from rustc, which is much faster than
+/// `DefaultHasher` on the short keys that dominate Starlark dictionaries.
+/// It is not resistant to keys chosen by an attacker to collide.
 #[derive(Default)]
-pub struct StarlarkHasher(DefaultHasher);
+pub struct StarlarkHasher {
+    hash: u64,
+}
+
+const MULTIPLIER: u64 = 0x51_7c_c1_b7_27_22_0a_95;
 
 impl StarlarkHasher {
     pub fn new() -> StarlarkHasher {
         StarlarkHasher::default()
     }
 
-    pub(crate) fn finish_small(self) -> SmallHashResult {
+    /// A hasher which produces different, but still deterministic, hashes for each `seed`.
+    /// Values hashed with different seeds must not be mixed in one collection.
+    pub fn with_seed(seed: u64) -> StarlarkHasher {
+        // Mix in the seed as if it were the first input.
+        let mut hasher = StarlarkHasher::new();
+        hasher.write_u64(seed);
+        hasher
+    }
+
+    #[inline]
+    fn add_to_hash(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(MULTIPLIER);
+    }
+}
+
+/// A [`Hasher`] which also produces the 32 bit hashes stored by
+/// [`SmallMap`](crate::collections::SmallMap) and [`SmallSet`](crate::collections::SmallSet),
+/// e.g. with [`SmallHashResult::new_with`].
+///
+/// The small hash must depend only on what was written, so that equal keys always have
+/// equal hashes, and must be well swizzled, as the collections use its bits directly.
+/// Keys hashed with different hashers must not be mixed in one collection.
+pub trait SmallHasher: Hasher + Default {
+    /// Reduce the hash to the 32 bits stored in collections.
+    fn finish_small(self) -> SmallHashResult {
         // NOTE: Here we throw away half the key material we are given,
-        // taking only the lower 32 bits.
-        // Not a problem because `DefaultHasher` produces well-swizzled bits.
-        SmallHashResult::new_unchecked(self.finish() as u32)
+        // keeping only 32 bits.
+        // In `StarlarkHasher` the multiplication only mixes bits upwards, so the upper half
+        // is the well-swizzled one, and we fold it into the lower half rather than truncate.
+        let hash = self.finish();
+        SmallHashResult::new_unchecked((hash ^ (hash >> 32)) as u32)
     }
 }
 
+impl SmallHasher for StarlarkHasher {}
+
 impl Hasher for StarlarkHasher {
+    #[inline]
     fn finish(&self) -> u64 {
-        self.0.finish()
+        self.hash
     }
 
+    #[inline]
     fn write(&mut self, bytes: &[u8]) {
-        self.0.write(bytes)
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.add_to_hash(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        let rest = chunks.remainder();
+        if !rest.is_empty() {
+            let mut word = [0; 8];
+            word[..rest.len()].copy_from_slice(rest);
+            self.add_to_hash(u64::from_le_bytes(word));
+        }
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.add_to_hash(i);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.add_to_hash(i as u64);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::hash::{Hash, Hasher};
+
+    use crate::{
+        collections::{Hashed, SmallHashResult, SmallHasher, StarlarkHasher},
+        values::Heap,
+    };
+
+    fn hash(mut hasher: StarlarkHasher, x: &str) -> u64 {
+        x.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_with_seed() {
+        assert_eq!(
+            hash(StarlarkHasher::with_seed(1), "x"),
+            hash(StarlarkHasher::with_seed(1), "x")
+        );
+        assert_ne!(
+            hash(StarlarkHasher::with_seed(1), "x"),
+            hash(StarlarkHasher::with_seed(2), "x")
+        );
+        assert_ne!(
+            hash(StarlarkHasher::new(), "x"),
+            hash(StarlarkHasher::with_seed(1), "x")
+        );
+    }
+
+    #[test]
+    fn test_stable() {
+        // Hashes are the same in every process, so changing them must be deliberate.
+        assert_eq!(0, StarlarkHasher::new().finish());
+        assert_eq!(12269146538841877247, hash(StarlarkHasher::new(), "a"));
+        assert_ne!(
+            hash(StarlarkHasher::new(), "a"),
+            hash(StarlarkHasher::new(), "b")
+        );
+        assert_ne!(
+            hash(StarlarkHasher::new(), "abcdefgh"),
+            hash(StarlarkHasher::new(), "abcdefghi")
+        );
+    }
+
+    #[test]
+    fn test_small_hash_stable() {
+        let mut hasher = StarlarkHasher::new();
+        "a".hash(&mut hasher);
+        assert_eq!(
+            SmallHashResult::new_unchecked(312928570),
+            hasher.finish_small()
+        );
+        // Everything which hashes a key with the default hasher agrees.
+        assert_eq!(
+            SmallHashResult::new_unchecked(312928570),
+            SmallHashResult::new("a")
+        );
+        assert_eq!(
+            SmallHashResult::new("a"),
+            SmallHashResult::new_with::<StarlarkHasher, _>("a")
+        );
+        assert_eq!(SmallHashResult::new("a"), Hashed::new("a").hash());
+        let heap = Heap::new();
+        let a = heap.alloc_str("a").get_hashed();
+        assert_eq!(SmallHashResult::new("a"), a.unwrap().hash());
     }
 }
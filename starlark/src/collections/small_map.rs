@@ -696,6 +696,37 @@ mod tests {
         assert_eq!(values1, values2);
     }
 
+    #[test]
+    fn test_insertion_order_after_removals() {
+        // Check against a list of entries, where inserting a new key appends it,
+        // inserting an existing key replaces the value in place, and removing closes the gap.
+        // The map starts small, then grows an index as more keys are live.
+        let mut m = SmallMap::new();
+        let mut model: Vec<(u32, u32)> = Vec::new();
+        let mut seed = 1u32;
+        for i in 0..2000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let key = (seed >> 8) % 40;
+            if (seed >> 24) % 3 == 0 {
+                let expected = model
+                    .iter()
+                    .position(|(k, _)| *k == key)
+                    .map(|p| model.remove(p).1);
+                assert_eq!(m.remove(&key), expected);
+            } else {
+                match model.iter_mut().find(|(k, _)| *k == key) {
+                    Some(e) => e.1 = i,
+                    None => model.push((key, i)),
+                }
+                m.insert(key, i);
+            }
+            assert!(m.iter().map(|(k, v)| (*k, *v)).eq(model.iter().copied()));
+            for (p, (k, _)) in model.iter().enumerate() {
+                assert_eq!(m.get_index_of(k), Some(p));
+            }
+        }
+    }
+
     #[test]
     fn test_smallmap_macro() {
         let map = smallmap![1 => "a", 3 => "b"];
@@ -25,7 +25,10 @@ use std::{
 use gazebo::prelude::*;
 use indexmap::Equivalent;
 
-use crate::collections::small_map::SmallMap;
+use crate::collections::{
+    hash::{BorrowHashed, Hashed},
+    small_map::SmallMap,
+};
 
 /// An memory-efficient set with determinstic order, based on [`SmallMap`].
 #[derive(Clone, Default_)]
@@ -110,6 +113,13 @@ impl<T> SmallSet<T> {
         self.0.insert(key, ()).is_none()
     }
 
+    pub fn insert_hashed(&mut self, key: Hashed<T>) -> bool
+    where
+        T: Eq,
+    {
+        self.0.insert_hashed(key, ()).is_none()
+    }
+
     /// Return a reference to the value stored in the set, if it is present,
     /// else `None`.
     ///
@@ -163,6 +173,14 @@ impl<T> SmallSet<T> {
         self.0.contains_key(key)
     }
 
+    pub fn contains_hashed<Q>(&self, key: BorrowHashed<Q>) -> bool
+    where
+        Q: Equivalent<T> + ?Sized,
+        T: Eq,
+    {
+        self.0.contains_key_hashed(key)
+    }
+
     pub fn clear(&mut self) {
         self.0.clear()
     }
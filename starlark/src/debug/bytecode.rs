@@ -0,0 +1,100 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::{
+    codemap::CodeMap,
+    eval::{bc::bytecode::Bc, Def, Evaluator, FrozenDef},
+    values::{Value, ValueLike},
+};
+
+fn to_bc<'v>(x: Value<'v>) -> Option<(&'v Bc, &'v CodeMap)> {
+    if x.unpack_frozen().is_some() {
+        x.downcast_ref::<FrozenDef>()
+            .map(|x| (x.bc(), &x.def_info.codemap))
+    } else {
+        x.downcast_ref::<Def>()
+            .map(|x| (x.bc(), &x.def_info.codemap))
+    }
+}
+
+impl<'v, 'a> Evaluator<'v, 'a> {
+    /// Obtain the bytecode instructions a function defined with `def` was compiled to,
+    /// as the byte offset and opcode name of each instruction, or [`None`] if `function`
+    /// is not defined with `def`. Frozen functions are optimised further when they are frozen,
+    /// so may differ from the unfrozen function.
+    ///
+    /// The opcodes, and how functions are compiled to them, change frequently.
+    /// The only legitimate use of this function is for debugging and analysis tools.
+    pub fn disassemble(&self, function: Value<'v>) -> Option<Vec<(u32, String)>> {
+        let instrs = &to_bc(function)?.0.instrs;
+        Some(
+            instrs
+                .opcodes_with_offsets()
+                .into_iter()
+                .map(|(addr, opcode)| (addr.0, format!("{:?}", opcode)))
+                .collect(),
+        )
+    }
+
+    /// Like [`disassemble`](Evaluator::disassemble), but as text with one instruction
+    /// per line, including its arguments and the `line:column` source location
+    /// it was compiled from.
+    pub fn disassemble_with_spans(&self, function: Value<'v>) -> Option<String> {
+        let (bc, codemap) = to_bc(function)?;
+        Some(bc.instrs.display_with_spans(codemap).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{assert::Assert, environment::Module, eval::Evaluator, values::Value};
+
+    #[test]
+    fn test_disassemble() {
+        let mut a = Assert::new();
+        let module = a.module("bc.star", "def f(x): return type(x)");
+        let f = module.get("f").unwrap();
+        let env = Module::new();
+        let eval = Evaluator::new(&env);
+        let instrs = eval.disassemble(f.value()).unwrap();
+        assert_eq!(
+            instrs.iter().map(|x| x.1.as_str()).collect::<Vec<_>>(),
+            vec!["LoadLocal", "Type", "Return", "EndOfBc"]
+        );
+        assert_eq!(instrs[0].0, 0);
+        assert!(instrs.windows(2).all(|x| x[0].0 < x[1].0));
+        assert_eq!(eval.disassemble(Value::new_int(1)), None);
+    }
+
+    #[test]
+    fn test_disassemble_with_spans() {
+        let mut a = Assert::new();
+        let module = a.module("bc.star", "def f(x): return type(x)");
+        let f = module.get("f").unwrap();
+        let env = Module::new();
+        let eval = Evaluator::new(&env);
+        let text = eval.disassemble_with_spans(f.value()).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4, "{}", text);
+        assert!(lines[0].starts_with("0: LoadLocal"), "{}", text);
+        assert!(lines[0].ends_with(" # 1:23-24"), "{}", text);
+        assert!(lines[1].contains("Type"), "{}", text);
+        assert!(lines[1].ends_with(" # 1:18-25"), "{}", text);
+        assert!(lines[3].ends_with(": END"), "{}", text);
+        assert_eq!(eval.disassemble_with_spans(Value::new_int(1)), None);
+    }
+}
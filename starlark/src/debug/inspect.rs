@@ -15,10 +15,12 @@
  * limitations under the License.
  */
 
+use gazebo::prelude::*;
+
 use crate::{
     collections::SmallMap,
     eval::{Def, Evaluator, FrozenDef, ScopeNames},
-    values::{Value, ValueLike},
+    values::{value_captured_get, Value, ValueCaptured, ValueLike},
 };
 
 pub(crate) fn to_scope_names<'v>(x: Value<'v>) -> Option<&'v ScopeNames> {
@@ -36,6 +38,25 @@ impl<'v, 'a> Evaluator<'v, 'a> {
     pub fn local_variables(&self) -> SmallMap<String, Value<'v>> {
         inspect_local_variables(self).unwrap_or_else(|| inspect_module_variables(self))
     }
+
+    /// Obtain all the variables of the function currently executing, in the order they
+    /// are stored, including those which are not yet assigned, which are `None`.
+    /// Returns `None` at top-level. The only legitimate use of this function is for debugging.
+    pub fn frame_variables(&self) -> Option<Vec<(String, Option<Value<'v>>)>> {
+        let xs = self.call_stack.to_function_values();
+        let names = xs.into_iter().rev().find_map(to_scope_names)?;
+        let slots = self
+            .local_variables
+            .get_named_slots_at(self.local_variables.base(), &names.used);
+        Some(slots.into_map(|(name, value)| {
+            // Variables captured by a nested function are stored in a cell.
+            let value = match value {
+                Some(v) if v.downcast_ref::<ValueCaptured>().is_some() => value_captured_get(v),
+                v => v,
+            };
+            (name.to_owned(), value)
+        }))
+    }
 }
 
 fn inspect_local_variables<'v>(eval: &Evaluator<'v, '_>) -> Option<SmallMap<String, Value<'v>>> {
@@ -76,6 +97,16 @@ mod tests {
             Ok(eval.call_stack().map(ToString::to_string))
         }
 
+        fn debug_inspect_frame() -> Value<'v> {
+            Ok(match eval.frame_variables() {
+                None => Value::new_none(),
+                Some(xs) => heap.alloc_list(&xs.into_map(|(k, v)| {
+                    let v = v.unwrap_or_else(|| heap.alloc("unbound"));
+                    heap.alloc((k, v))
+                })),
+            })
+        }
+
         fn debug_inspect_variables() -> Dict<'v> {
             let mut sm = SmallMap::new();
             for (k, v) in eval.local_variables() {
@@ -104,6 +135,31 @@ g()
         );
     }
 
+    #[test]
+    fn test_debug_frame() {
+        let mut a = assert::Assert::new();
+        a.globals_add(debugger);
+        a.pass(
+            r#"
+def f(x):
+    y = x + 1
+    frame = debug_inspect_frame()
+    z = 3
+    return frame
+assert_eq(f(1), [("x", 1), ("y", 2), ("frame", "unbound"), ("z", "unbound")])
+
+def g():
+    a = 1
+    frame = debug_inspect_frame()
+    h = lambda: a
+    return frame
+assert_eq(g(), [("a", 1), ("frame", "unbound"), ("h", "unbound")])
+
+assert_eq(debug_inspect_frame(), None)
+"#,
+        );
+    }
+
     #[test]
     fn test_debug_variables() {
         let mut a = assert::Assert::new();
@@ -16,5 +16,6 @@
  */
 
 mod breakpoint;
+mod bytecode;
 mod evaluate;
 mod inspect;
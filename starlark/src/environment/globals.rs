@@ -28,6 +28,7 @@ use crate::{
         symbol_map::{Symbol, SymbolMap},
         SmallMap,
     },
+    environment::FrozenModule,
     stdlib,
     values::{
         function::NativeAttribute, structs::FrozenStruct, AllocFrozenValue, FrozenHeap,
@@ -106,24 +107,29 @@ impl Globals {
         self.0.variables.get(name).copied()
     }
 
-    /// Get all the names defined in this environment.
+    /// Get all the names defined in this environment, in sorted order.
     pub fn names(&self) -> Vec<String> {
-        self.0
+        let mut res: Vec<String> = self
+            .0
             .variables
             .keys()
             .map(|x| x.as_str().to_owned())
-            .collect()
+            .collect();
+        // The underlying map iterates in hash order, so sort to be deterministic.
+        res.sort();
+        res
     }
 
     pub(crate) fn heap(&self) -> &FrozenHeapRef {
         &self.0.heap
     }
 
-    /// Print information about the values in this object.
+    /// Print information about the values in this object, sorted by name.
     pub fn describe(&self) -> String {
         self.0
             .variables
             .iter()
+            .sorted_by(|a, b| a.0.as_str().cmp(b.0.as_str()))
             .map(|(name, val)| val.to_value().describe(name.as_str()))
             .join("\n")
     }
@@ -187,6 +193,23 @@ impl GlobalsBuilder {
         self
     }
 
+    /// Add the symbols a `load` could import from the frozen `module` as globals, so they
+    /// are in scope without a `load`, e.g. helper functions written in Starlark for a DSL.
+    /// As with [`set`](GlobalsBuilder::set), they replace any globals of the same name added
+    /// earlier, and are replaced by any added later.
+    pub fn prelude(&mut self, module: &FrozenModule) {
+        self.heap.add_reference(module.frozen_heap());
+        for (name, value) in module.loadable_symbols() {
+            self.set(name, value);
+        }
+    }
+
+    /// A fluent API for modifying [`GlobalsBuilder`] using [`prelude`](GlobalsBuilder::prelude).
+    pub fn with_prelude(mut self, module: FrozenModule) -> Self {
+        self.prelude(&module);
+        self
+    }
+
     /// Called at the end to build a [`Globals`].
     pub fn build(self) -> Globals {
         Globals(Arc::new(GlobalsData {
@@ -261,7 +284,7 @@ impl GlobalsStatic {
         Self(OnceCell::new())
     }
 
-    fn globals(&'static self, x: impl FnOnce(&mut GlobalsBuilder)) -> &'static Globals {
+    pub(crate) fn globals(&'static self, x: impl FnOnce(&mut GlobalsBuilder)) -> &'static Globals {
         self.0.get_or_init(|| GlobalsBuilder::new().with(x).build())
     }
 
@@ -333,4 +356,32 @@ assert_eq(magic.my_type, "magic")
 assert_eq(magic.my_value, 42)"#,
         );
     }
+
+    #[test]
+    fn test_prelude() {
+        let prelude = Assert::new().pass_module(
+            r#"
+def double(x):
+    return x * 2
+def len(x):
+    return "prelude"
+_private = 1
+"#,
+        );
+        let globals = GlobalsBuilder::standard()
+            .with_prelude(prelude)
+            .with(|x| x.set("double", "builtin"))
+            .build();
+        let mut a = Assert::new();
+        a.globals(globals);
+        // Prelude symbols replace the earlier builtins, but not the later ones.
+        a.eq("len([])", "'prelude'");
+        a.eq("double", "'builtin'");
+        a.fail("_private", "not found");
+
+        let prelude = Assert::new().pass_module("def double(x): return x * 2");
+        let mut a = Assert::new();
+        a.globals(GlobalsBuilder::standard().with_prelude(prelude).build());
+        a.eq("double(21)", "42");
+    }
 }
@@ -49,6 +49,10 @@ pub(crate) enum EnvironmentError {
     ModuleHasNoSymbolDidYouMean(String, String),
     #[error("Module symbol `{0}` is not exported")]
     ModuleSymbolIsNotExported(String),
+    #[error("Module symbol `{0}` is not listed in the module's `__all__`")]
+    ModuleSymbolNotInAll(String),
+    #[error("Module `__all__` must be a list or tuple of strings, got `{0}`")]
+    InvalidExports(String),
     #[error("No imports are available, you tried `{0}` (no call to `Evaluator.set_loader`)")]
     NoImportsAvailable(String),
 }
@@ -20,7 +20,12 @@
 //! is the list of variable in the current scope. It can be frozen, after which
 //! all values from this environment become immutable.
 
-use std::{cell::RefCell, collections::HashMap, mem, sync::Arc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    mem,
+    sync::Arc,
+};
 
 use derive_more::Display;
 use gazebo::{any::AnyLifetime, prelude::*};
@@ -33,15 +38,30 @@ use crate::{
         EnvironmentError,
     },
     errors::did_you_mean::did_you_mean,
+    eval::{FrozenDef, ScratchEval},
     syntax::ast::Visibility,
     values::{
         docs,
         docs::{DocItem, DocString},
+        list::List,
+        tuple::Tuple,
         Freezer, FrozenHeap, FrozenHeapRef, FrozenValue, Heap, OwnedFrozenValue, SimpleValue,
-        StarlarkValue, Value,
+        StarlarkValue, Value, ValueLike,
     },
 };
 
+/// The names in a module's `__all__`, which may be a list or tuple of strings.
+fn unpack_exports(all: Value) -> Option<HashSet<String>> {
+    let names = match List::from_value(all) {
+        Some(xs) => xs.content(),
+        None => Tuple::from_value(all)?.content(),
+    };
+    names
+        .iter()
+        .map(|x| x.unpack_str().map(str::to_owned))
+        .collect()
+}
+
 /// The result of freezing a [`Module`], making it and its contained values immutable.
 ///
 /// The values of this [`FrozenModule`] are stored on a frozen heap, a reference to which
@@ -69,6 +89,9 @@ pub(crate) struct FrozenModuleData {
     pub(crate) names: FrozenNames,
     pub(crate) slots: FrozenSlots,
     docstring: Option<String>,
+    // The names listed in `__all__`, if the module assigned it,
+    // or its repr if it isn't a list or tuple of strings.
+    exports: Option<Result<HashSet<String>, String>>,
 }
 
 /// Container for the documentation for a module
@@ -80,6 +103,26 @@ pub struct ModuleDocs {
     pub members: HashMap<String, Option<DocItem>>,
 }
 
+/// The documentation for a function defined in Starlark, see [`FrozenModule::functions`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionDoc {
+    /// The name the function is exported under.
+    pub name: String,
+    /// The parameters, return type and docstring of the function.
+    pub function: docs::Function,
+}
+
+/// The size of the compiled bytecode of a function, see [`FrozenModule::bytecode_sizes`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionBytecodeSize {
+    /// The name the function is bound to in the module.
+    pub name: String,
+    /// The length of the bytecode in bytes.
+    pub bytes: usize,
+    /// The number of instructions.
+    pub instrs: usize,
+}
+
 /// A container for user values, used during execution.
 ///
 /// A module contains both a [`FrozenHeap`] and [`Heap`] on which different values are allocated.
@@ -127,6 +170,28 @@ impl FrozenModule {
         self.1.0.names()
     }
 
+    /// The exported variables with their values, in the order they were first defined.
+    pub fn bindings(&self) -> Vec<(String, OwnedFrozenValue)> {
+        self.names()
+            .filter_map(|name| Some((name.to_owned(), self.get(name)?)))
+            .collect()
+    }
+
+    /// The symbols `load` may import from this module, i.e. those without a leading
+    /// underscore which are listed in `__all__` if present. If `__all__` is invalid, there
+    /// are none. The values must not outlive the [`frozen_heap`](FrozenModule::frozen_heap).
+    pub(crate) fn loadable_symbols(&self) -> impl Iterator<Item = (&str, FrozenValue)> {
+        self.1.0.names.symbols().filter_map(move |(k, slot)| {
+            if Module::default_visibility(k) == Visibility::Public
+                && matches!(self.1.0.is_exported(k), Ok(true))
+            {
+                Some((k.as_str(), self.1.0.slots.get_slot(slot)?))
+            } else {
+                None
+            }
+        })
+    }
+
     /// Obtain the [`FrozenHeapRef`] which owns the storage of all values defined in this module.
     pub fn frozen_heap(&self) -> &FrozenHeapRef {
         &self.0
@@ -159,9 +224,56 @@ impl FrozenModule {
             members,
         }
     }
+
+    /// The documentation for every public `def` or `lambda` in the module, in the order they
+    /// were defined. Parameter defaults and type annotations are given by their `repr`.
+    pub fn functions(&self) -> Vec<FunctionDoc> {
+        self.names()
+            .filter(|n| Module::default_visibility(n) == Visibility::Public)
+            .filter_map(|n| {
+                let value = self.get(n)?;
+                value.value().downcast_ref::<FrozenDef>()?;
+                match value.value().get_ref().documentation()? {
+                    DocItem::Function(function) => Some(FunctionDoc {
+                        name: n.to_owned(),
+                        function,
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// The bytecode size of every `def` or `lambda` bound in the module, public or private,
+    /// in the order they were defined. Useful to find functions which compile to unusually
+    /// large bytecode.
+    pub fn bytecode_sizes(&self) -> Vec<FunctionBytecodeSize> {
+        self.names()
+            .filter_map(|n| {
+                let (value, _) = self.get_any_visibility(n)?;
+                let def = value.value().downcast_ref::<FrozenDef>()?;
+                let instrs = &def.bc().instrs;
+                Some(FunctionBytecodeSize {
+                    name: n.to_owned(),
+                    bytes: instrs.end().0 as usize,
+                    instrs: instrs.instr_count(),
+                })
+            })
+            .collect()
+    }
 }
 
 impl FrozenModuleData {
+    /// Whether `load` may import `name`, which is only restricted if the module assigned `__all__`.
+    /// Fails if `__all__` isn't a list or tuple of strings.
+    pub(crate) fn is_exported(&self, name: &str) -> anyhow::Result<bool> {
+        match &self.exports {
+            None => Ok(true),
+            Some(Ok(exports)) => Ok(exports.contains(name)),
+            Some(Err(all)) => Err(EnvironmentError::InvalidExports(all.clone()).into()),
+        }
+    }
+
     pub fn names(&self) -> impl Iterator<Item = &str> {
         self.names.symbols().map(|x| x.0.as_str())
     }
@@ -258,8 +370,33 @@ impl Module {
             })
     }
 
+    /// The exported variables which have been assigned, in the order they were first defined.
+    /// Useful to inspect what a module had computed when its evaluation failed.
+    pub fn bindings<'v>(&'v self) -> Vec<(String, Value<'v>)> {
+        self.names
+            .all_names()
+            .into_iter()
+            .filter_map(|(name, _)| {
+                let value = self.get(&name)?;
+                Some((name, value))
+            })
+            .collect()
+    }
+
     /// Freeze the environment, all its value will become immutable afterwards.
+    ///
+    /// If the module assigned `__all__` a list or tuple of names, `load` statements may only
+    /// import those names from the frozen module. If `__all__` is anything else, `load`
+    /// statements importing from the frozen module fail.
     pub fn freeze(self) -> anyhow::Result<FrozenModule> {
+        Ok(self.freeze_with(None)?.0)
+    }
+
+    /// Freeze the module, and `extra` along with it, which must live on this module's heap.
+    fn freeze_with(
+        self,
+        extra: Option<Value<'static>>,
+    ) -> anyhow::Result<(FrozenModule, Option<FrozenValue>)> {
         let Module {
             names,
             slots,
@@ -273,20 +410,65 @@ impl Module {
         // they are used.
         let freezer = Freezer::new(frozen_heap);
         let slots = slots.freeze(&freezer)?;
+        let extra = extra.map(|x| x.freeze(&freezer)).transpose()?;
+        let names = names.freeze();
+        let exports = match names
+            .get_name("__all__")
+            .and_then(|(slot, _)| slots.get_slot(slot))
+        {
+            None => None,
+            Some(all) => {
+                Some(unpack_exports(all.to_value()).ok_or_else(|| all.to_value().to_repr()))
+            }
+        };
         let rest = FrozenModuleRef(Arc::new(FrozenModuleData {
-            names: names.freeze(),
+            names,
             slots,
             docstring: docstring.into_inner(),
+            exports,
         }));
         let frozen_module_ref = freezer.heap.alloc_simple_frozen_ref(rest.dupe());
+        let scratch = ScratchEval::new();
         for frozen_def in freezer.frozen_defs.borrow().as_slice() {
-            frozen_def.post_freeze(frozen_module_ref);
+            frozen_def.post_freeze(frozen_module_ref, &freezer.heap, &scratch);
         }
         // The values MUST be alive up until this point (as the above line uses them),
         // but can now be dropped
         mem::drop(heap);
 
-        Ok(FrozenModule(freezer.into_ref(), rest))
+        Ok((FrozenModule(freezer.into_ref(), rest), extra))
+    }
+
+    /// Evaluate code in this module with `eval`, usually a call to
+    /// [`Evaluator::eval_module`](crate::eval::Evaluator::eval_module), then
+    /// [`freeze`](Module::freeze) the module along with the value `eval` returns.
+    /// The frozen value keeps the frozen module's heap alive, so can be cached and
+    /// shared between threads after the module is dropped.
+    ///
+    /// ```
+    /// use starlark::environment::{Globals, Module};
+    /// use starlark::eval::Evaluator;
+    ///
+    /// let (_module, value) = Module::new()
+    ///     .eval_and_freeze(|module| {
+    ///         let mut eval = Evaluator::new(module);
+    ///         eval.eval_expr("[1, 2]", &Globals::standard())
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(value.value().to_repr(), "[1, 2]");
+    /// ```
+    pub fn eval_and_freeze(
+        self,
+        eval: impl for<'v> FnOnce(&'v Module) -> anyhow::Result<Value<'v>>,
+    ) -> anyhow::Result<(FrozenModule, OwnedFrozenValue)> {
+        let value = eval(&self)?;
+        // This code is safe because the value lives on our heap, which `freeze_with`
+        // keeps alive until the value is frozen
+        let value = unsafe { transmute!(Value, Value<'static>, value) };
+        let (frozen, value) = self.freeze_with(Some(value))?;
+        // This code is safe because we know the frozen module ref keeps the value alive
+        let value = unsafe { OwnedFrozenValue::new(frozen.0.dupe(), value.unwrap()) };
+        Ok((frozen, value))
     }
 
     /// Set the value of a variable in the environment.
@@ -299,6 +481,13 @@ impl Module {
         slots.set_slot(slot, value);
     }
 
+    /// Set the value of a variable in the environment to a frozen value, without copying it.
+    /// The module keeps the heap owning the value alive, so a large value (e.g. a configuration
+    /// evaluated once) can be shared by many modules at no cost per module.
+    pub fn set_frozen(&self, name: &str, value: &OwnedFrozenValue) {
+        self.set(name, value.owned_value(self.frozen_heap()))
+    }
+
     /// Symbols starting with underscore are considered private.
     pub(crate) fn default_visibility(symbol: &str) -> Visibility {
         match symbol.starts_with('_') {
@@ -319,12 +508,8 @@ impl Module {
     /// Import symbols from a module, similar to what is done during `load()`.
     pub fn import_public_symbols(&self, module: &FrozenModule) {
         self.frozen_heap.add_reference(&module.0);
-        for (k, slot) in module.1.0.names.symbols() {
-            if Self::default_visibility(k) == Visibility::Public {
-                if let Some(value) = module.1.0.slots.get_slot(slot) {
-                    self.set_private(k, Value::new_frozen(value))
-                }
-            }
+        for (k, value) in module.loadable_symbols() {
+            self.set_private(k, Value::new_frozen(value))
         }
     }
 
@@ -336,6 +521,9 @@ impl Module {
         if Self::default_visibility(symbol) != Visibility::Public {
             return Err(EnvironmentError::CannotImportPrivateSymbol(symbol.to_owned()).into());
         }
+        if !module.1.0.is_exported(symbol)? {
+            return Err(EnvironmentError::ModuleSymbolNotInAll(symbol.to_owned()).into());
+        }
         match module.get_any_visibility(symbol) {
             None => Err({
                 match did_you_mean(symbol, module.names()) {
@@ -363,5 +551,84 @@ impl Module {
 fn test_send_sync()
 where
     FrozenModule: Send + Sync,
+    OwnedFrozenValue: Send + Sync,
+    crate::syntax::AstModule: Send + Sync,
 {
 }
+
+#[test]
+fn test_frozen_module_bindings() {
+    let m = crate::assert::Assert::new().module(
+        "bindings.star",
+        r#"
+b = [1, 2]
+a = "x"
+_private = 3
+def f():
+    pass
+"#,
+    );
+    let bindings = m.bindings();
+    let names: Vec<(&str, &str)> = bindings
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.value().get_type()))
+        .collect();
+    assert_eq!(
+        names,
+        vec![("b", "list"), ("a", "string"), ("f", "function")]
+    );
+    assert_eq!(bindings[0].1.value().to_repr(), "[1, 2]");
+}
+
+#[test]
+fn test_frozen_module_bindings_unpack() {
+    // Every target of an unpacking assignment is a binding of its own,
+    // however deeply nested, in the order the targets appear.
+    let m = crate::assert::Assert::new().module(
+        "unpack.star",
+        r#"
+(a, (b, c)) = (1, (2, 3))
+[d, [_e, f]] = [4, (5, 6)]
+g, h = 7, 8
+"#,
+    );
+    let bindings = m.bindings();
+    let names: Vec<(&str, i32)> = bindings
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.value().unpack_int().unwrap()))
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            ("a", 1),
+            ("b", 2),
+            ("c", 3),
+            ("d", 4),
+            ("f", 6),
+            ("g", 7),
+            ("h", 8)
+        ]
+    );
+    assert_eq!(m.get_any_visibility("_e").unwrap().0.value().to_repr(), "5");
+}
+
+#[test]
+fn test_frozen_module_bytecode_sizes() {
+    let m = crate::assert::Assert::new().module(
+        "sizes.star",
+        r#"
+x = 1
+def small():
+    pass
+def large(x):
+    return [x + 1, x * 2, str(x), {x: x}]
+_f = lambda: 1
+"#,
+    );
+    let sizes = m.bytecode_sizes();
+    let names: Vec<&str> = sizes.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(names, vec!["small", "large", "_f"]);
+    assert!(sizes[0].instrs < sizes[1].instrs);
+    assert!(sizes[0].bytes < sizes[1].bytes);
+    assert!(sizes.iter().all(|x| x.instrs > 0 && x.bytes > 0));
+}
@@ -27,7 +27,7 @@ use annotate_snippets::{
     snippet::{Annotation, AnnotationType, Slice, Snippet, SourceAnnotation},
 };
 
-pub use crate::analysis::Lint;
+pub use crate::analysis::{Lint, LintOptions};
 use crate::codemap::{CodeMap, FileSpan, Span};
 
 pub(crate) mod did_you_mean;
@@ -135,6 +135,28 @@ impl Diagnostic {
             Some(diag) => diagnostic_stderr(diag),
         }
     }
+
+    /// The source lines covered by the span of this [`Diagnostic`], with the span underlined
+    /// by carets, or [`None`] if there is no span. Spans over several lines show every line.
+    /// Useful for hosts which render errors themselves, but want to show the failing code.
+    ///
+    /// ```
+    /// use starlark::environment::{Globals, Module};
+    /// use starlark::errors::Diagnostic;
+    /// use starlark::eval::Evaluator;
+    /// use starlark::syntax::{AstModule, Dialect};
+    ///
+    /// let ast = AstModule::parse("x.star", "x = 1\ny = x + 'a'".to_owned(), &Dialect::Standard).unwrap();
+    /// let module = Module::new();
+    /// let mut eval = Evaluator::new(&module);
+    /// let err = eval.eval_module(ast, &Globals::standard()).unwrap_err();
+    /// let snippet = err.downcast_ref::<Diagnostic>().unwrap().source_snippet().unwrap();
+    /// assert!(snippet.contains("y = x + 'a'"));
+    /// assert!(snippet.contains("    ^^^^^^^"));
+    /// ```
+    pub fn source_snippet(&self) -> Option<String> {
+        self.span.as_ref().map(source_snippet)
+    }
 }
 
 impl Display for Diagnostic {
@@ -160,42 +182,42 @@ impl Display for CallStackFmt<'_> {
 // variants by doing a conversion using annotate-snippets
 // (https://github.com/rust-lang/annotate-snippets-rs)
 
+fn convert_span_to_range_relative_to_first_line(
+    diagnostic_span: Span,
+    start_column: usize,
+) -> (usize, usize) {
+    let span_length = diagnostic_span.len() as usize;
+    (start_column, start_column + span_length)
+}
+
+fn convert_span_to_slice<'a>(span: &'a FileSpan) -> Slice<'a> {
+    let region = span.resolve_span();
+
+    // we want the source_span to capture any whitespace ahead of the diagnostic span to
+    // get the column numbers correct in the DisplayList, and any trailing source code
+    // on the last line for context.
+    let first_line_span = span.file.line_span(region.begin_line);
+    let last_line_span = span.file.line_span(region.end_line);
+    let source_span = span.span.merge(first_line_span).merge(last_line_span);
+
+    Slice {
+        source: span.file.source_span(source_span),
+        line_start: 1 + region.begin_line,
+        origin: Some(span.file.filename()),
+        fold: false,
+        annotations: vec![SourceAnnotation {
+            label: "",
+            annotation_type: AnnotationType::Error,
+            range: convert_span_to_range_relative_to_first_line(span.span, region.begin_column),
+        }],
+    }
+}
+
 fn get_display_list_for_diagnostic<'a>(
     annotation_label: &'a str,
     x: &'a Diagnostic,
     color: bool,
 ) -> DisplayList<'a> {
-    fn convert_span_to_range_relative_to_first_line(
-        diagnostic_span: Span,
-        start_column: usize,
-    ) -> (usize, usize) {
-        let span_length = diagnostic_span.len() as usize;
-        (start_column, start_column + span_length)
-    }
-
-    fn convert_span_to_slice<'a>(span: &'a FileSpan) -> Slice<'a> {
-        let region = span.resolve_span();
-
-        // we want the source_span to capture any whitespace ahead of the diagnostic span to
-        // get the column numbers correct in the DisplayList, and any trailing source code
-        // on the last line for context.
-        let first_line_span = span.file.line_span(region.begin_line);
-        let last_line_span = span.file.line_span(region.end_line);
-        let source_span = span.span.merge(first_line_span).merge(last_line_span);
-
-        Slice {
-            source: span.file.source_span(source_span),
-            line_start: 1 + region.begin_line,
-            origin: Some(span.file.filename()),
-            fold: false,
-            annotations: vec![SourceAnnotation {
-                label: "",
-                annotation_type: AnnotationType::Error,
-                range: convert_span_to_range_relative_to_first_line(span.span, region.begin_column),
-            }],
-        }
-    }
-
     let slice = x.span.as_ref().map(convert_span_to_slice);
 
     let snippet = Snippet {
@@ -215,6 +237,16 @@ fn get_display_list_for_diagnostic<'a>(
     DisplayList::from(snippet)
 }
 
+fn source_snippet(span: &FileSpan) -> String {
+    let snippet = Snippet {
+        title: None,
+        footer: Vec::new(),
+        slices: vec![convert_span_to_slice(span)],
+        opt: FormatOptions::default(),
+    };
+    DisplayList::from(snippet).to_string()
+}
+
 fn diagnostic_display(diagnostic: &Diagnostic, f: &mut Formatter<'_>) -> fmt::Result {
     CallStackFmt(&diagnostic.call_stack).fmt(f)?;
     let annotation_label = format!("{:#}", diagnostic.message);
@@ -199,7 +199,7 @@ impl BcAddrOffset {
     pub(crate) const FORWARD: BcAddrOffset = BcAddrOffset(0xdeadbeef);
 
     /// Size of an instruction.
-    fn for_instr<I: BcInstr>() -> BcAddrOffset {
+    pub(crate) fn for_instr<I: BcInstr>() -> BcAddrOffset {
         <BcInstrRepr<I>>::assert_align();
         BcAddrOffset(mem::size_of::<BcInstrRepr<I>>() as u32)
     }
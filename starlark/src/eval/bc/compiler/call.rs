@@ -31,7 +31,8 @@ use crate::{
             instr_impl::{
                 InstrCall, InstrCallFrozen, InstrCallFrozenDef, InstrCallFrozenDefPos,
                 InstrCallFrozenNative, InstrCallFrozenNativePos, InstrCallFrozenPos,
-                InstrCallMethod, InstrCallMethodPos, InstrCallPos,
+                InstrCallMethod, InstrCallMethodFrozen, InstrCallMethodFrozenPos,
+                InstrCallMethodPos, InstrCallPos,
             },
             writer::BcWriter,
         },
@@ -142,6 +143,20 @@ impl Spanned<CallCompiled> {
                     bc.write_instr::<InstrCallMethod>(span, (ArgPopsStack1, symbol.clone(), args));
                 }
             }
+            CallCompiled::MethodFrozen(box (ref this, fun, ref args)) => {
+                this.write_bc(bc);
+                match Self::write_args(span, args, bc) {
+                    Either::Left(npops) => {
+                        bc.write_instr::<InstrCallMethodFrozenPos>(
+                            span,
+                            (ArgPopsStack1, npops, fun, span),
+                        );
+                    }
+                    Either::Right(args) => {
+                        bc.write_instr::<InstrCallMethodFrozen>(span, (ArgPopsStack1, fun, args));
+                    }
+                }
+            }
         }
     }
 }
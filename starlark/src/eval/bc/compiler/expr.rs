@@ -23,7 +23,7 @@ use gazebo::prelude::*;
 
 use crate::{
     codemap::{Span, Spanned},
-    collections::{Hashed, SmallMap},
+    collections::{Hashed, SmallMap, SmallSet},
     eval::{
         bc::{
             instr_arg::{ArgPopsStack, ArgPopsStack1, ArgPopsStackMaybe1},
@@ -32,9 +32,16 @@ use crate::{
         },
         fragment::expr::{CompareOp, ExprBinOp, ExprCompiledValue, MaybeNot},
     },
-    values::{FrozenValue, ValueLike},
+    values::{tuple::Tuple, FrozenValue, ValueLike},
 };
 
+/// Constant tuples at least this long are turned into a set for `x in (...)`.
+/// Shorter ones are faster to scan than to hash `x`.
+const IN_CONST_SET_MIN_LEN: usize = 6;
+
+/// Chains of `+` with at least this many operands are concatenated at once.
+const CONCAT_N_MIN_LEN: usize = 3;
+
 pub(crate) fn write_exprs<'a>(
     exprs: impl IntoIterator<Item = &'a Spanned<ExprCompiledValue>>,
     bc: &mut BcWriter,
@@ -80,6 +87,56 @@ impl Spanned<ExprCompiledValue> {
         Some(keys.into_boxed_slice())
     }
 
+    fn try_in_const_set(xs: &Spanned<ExprCompiledValue>) -> Option<SmallSet<FrozenValue>> {
+        let xs = Tuple::from_value(xs.as_value()?.to_value())?;
+        if xs.len() < IN_CONST_SET_MIN_LEN {
+            return None;
+        }
+        let mut res = SmallSet::with_capacity(xs.len());
+        for x in xs.content() {
+            // Elements of a frozen tuple are frozen.
+            let x = x.unpack_frozen()?.get_hashed().ok()?;
+            res.insert_hashed(x);
+        }
+        Some(res)
+    }
+
+    /// Can the expression be evaluated out of order with respect to a `+` to its left?
+    /// Only if it can't fail or have side effects, so the `+` fails first as it would
+    /// otherwise: a constant, or a read of a parameter, which is always assigned.
+    fn is_concat_operand(&self, bc: &BcWriter) -> bool {
+        match self.node {
+            ExprCompiledValue::Value(..) => true,
+            ExprCompiledValue::Local(slot) => bc.is_definitely_assigned(slot),
+            _ => false,
+        }
+    }
+
+    /// Flatten `x + y + z` into the operands and the span of each `+`, if it looks like
+    /// string concatenation, i.e. one of the operands is a constant string.
+    /// All the operands are evaluated before any addition, so the ones after the first
+    /// must be [`is_concat_operand`](Self::is_concat_operand).
+    fn try_concat_n(&self, bc: &BcWriter) -> Option<(Vec<&Spanned<ExprCompiledValue>>, Vec<Span>)> {
+        let mut xs = Vec::new();
+        let mut spans = Vec::new();
+        let mut e = self;
+        while let ExprCompiledValue::Op(ExprBinOp::Add, box (ref l, ref r)) = e.node {
+            if !r.is_concat_operand(bc) {
+                return None;
+            }
+            xs.push(r);
+            spans.push(e.span);
+            e = l;
+        }
+        xs.push(e);
+        if xs.len() < CONCAT_N_MIN_LEN || !xs.iter().any(|x| x.as_string().is_some()) {
+            return None;
+        }
+        xs.reverse();
+        spans.reverse();
+        Some((xs, spans))
+    }
+
     fn write_dict(
         span: Span,
         xs: &[(Spanned<ExprCompiledValue>, Spanned<ExprCompiledValue>)],
@@ -117,6 +174,16 @@ impl Spanned<ExprCompiledValue> {
                 bc.write_instr::<InstrLoadModule>(span, slot);
             }
             ExprCompiledValue::Equals(box (ref a, ref b), maybe_not) => {
+                // `EqConst` runs `x.equals(c)`, so only use it when the constant is on the
+                // right, as a user type's `equals` need not be symmetric.
+                if let Some(c) = b.as_value() {
+                    a.write_bc(bc);
+                    match maybe_not {
+                        MaybeNot::Id => bc.write_instr::<InstrEqConst>(span, c),
+                        MaybeNot::Not => bc.write_instr::<InstrNotEqConst>(span, c),
+                    }
+                    return;
+                }
                 a.write_bc(bc);
                 b.write_bc(bc);
                 match maybe_not {
@@ -190,10 +257,16 @@ impl Spanned<ExprCompiledValue> {
                     ),
                 );
             }
-            ExprCompiledValue::Not(box ref expr) => {
-                expr.write_bc(bc);
-                bc.write_instr::<InstrNot>(span, ());
-            }
+            ExprCompiledValue::Not(box ref expr) => match expr.node {
+                ExprCompiledValue::Not(box ref expr) => {
+                    expr.write_bc(bc);
+                    bc.write_instr::<InstrToBool>(span, ());
+                }
+                _ => {
+                    expr.write_bc(bc);
+                    bc.write_instr::<InstrNot>(span, ());
+                }
+            },
             ExprCompiledValue::Minus(box ref expr) => {
                 expr.write_bc(bc);
                 bc.write_instr::<InstrMinus>(span, ());
@@ -238,7 +311,25 @@ impl Spanned<ExprCompiledValue> {
                 });
             }
             ExprCompiledValue::Op(op, box (ref l, ref r)) => {
+                if let Some((xs, spans)) = self.try_concat_n(bc) {
+                    let n = ArgPopsStack(xs.len() as u32);
+                    let spans = bc.alloc_any(spans);
+                    write_exprs(xs, bc);
+                    bc.write_instr::<InstrConcatN>(span, (n, spans));
+                    return;
+                }
                 l.write_bc(bc);
+                let in_const_set = match op {
+                    ExprBinOp::In | ExprBinOp::NotIn => Self::try_in_const_set(r),
+                    _ => None,
+                };
+                if let Some(set) = in_const_set {
+                    bc.write_instr::<InstrInConstSet>(span, box set);
+                    if let ExprBinOp::NotIn = op {
+                        bc.write_instr::<InstrNot>(span, ());
+                    }
+                    return;
+                }
                 r.write_bc(bc);
                 match op {
                     ExprBinOp::In => bc.write_instr::<InstrIn>(span, ()),
@@ -264,6 +355,16 @@ impl Spanned<ExprCompiledValue> {
                 arg.write_bc(bc);
                 bc.write_instr::<InstrFormatOne>(span, (before, after));
             }
+            ExprCompiledValue::FormatMany(box (ref segments, ref args)) => {
+                write_exprs(args, bc);
+                bc.write_instr::<InstrFormatMany>(
+                    span,
+                    (
+                        ArgPopsStack(args.len() as u32),
+                        segments.clone().into_boxed_slice(),
+                    ),
+                );
+            }
             ExprCompiledValue::Call(ref call) => call.write_bc(bc),
             ExprCompiledValue::Def(ref def) => def.write_bc(bc),
         }
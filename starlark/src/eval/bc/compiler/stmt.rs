@@ -22,7 +22,7 @@ use crate::{
             bytecode::Bc,
             instr_impl::{
                 InstrBeforeStmt, InstrBreak, InstrContinue, InstrPossibleGc, InstrReturn,
-                InstrReturnNone,
+                InstrReturnLocal, InstrReturnNone,
             },
             writer::BcWriter,
         },
@@ -143,6 +143,11 @@ impl Spanned<StmtCompiledValue> {
                 bc.write_instr::<InstrReturnNone>(span, ());
             }
             StmtCompiledValue::Return(Some(ref expr)) => {
+                // `LoadLocal; Return` is common enough to have its own instruction.
+                if let ExprCompiledValue::Local(slot) = expr.node {
+                    bc.write_instr::<InstrReturnLocal>(expr.span, slot);
+                    return;
+                }
                 expr.write_bc(bc);
                 bc.write_instr::<InstrReturn>(span, ());
             }
@@ -177,8 +182,9 @@ impl Spanned<StmtCompiledValue> {
 }
 
 impl StmtsCompiled {
-    pub(crate) fn as_bc(&self, compiler: &StmtCompileContext) -> Bc {
-        let mut bc = BcWriter::new(compiler.bc_profile);
+    /// Write the bytecode for a function with `param_count` parameters, or a module, with none.
+    pub(crate) fn as_bc(&self, compiler: &StmtCompileContext, param_count: u32) -> Bc {
+        let mut bc = BcWriter::new(compiler.bc_profile, compiler.bc_trace, param_count);
         self.write_bc(compiler, &mut bc);
 
         // Small optimization: if the last statement is return,
@@ -26,7 +26,7 @@ use gazebo::dupe::Dupe;
 
 use crate::{
     codemap::Span,
-    collections::{symbol_map::Symbol, Hashed, SmallMap},
+    collections::{symbol_map::Symbol, Hashed, SmallMap, SmallSet},
     environment::slots::ModuleSlotId,
     eval::{
         bc::{
@@ -453,6 +453,50 @@ impl BcInstrArg for Box<[FrozenValue]> {
     }
 }
 
+impl BcInstrArg for Box<[FrozenStringValue]> {
+    fn fmt_append(param: &Self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, " [")?;
+        for (i, v) in param.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", v.unpack())?;
+        }
+        write!(f, "]")?;
+        Ok(())
+    }
+
+    fn pops_stack(_param: &Self) -> u32 {
+        0
+    }
+
+    fn pushes_stack(_param: &Self) -> u32 {
+        0
+    }
+}
+
+impl BcInstrArg for Box<SmallSet<FrozenValue>> {
+    fn fmt_append(param: &Self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, " {{")?;
+        for (i, v) in param.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", v)?;
+        }
+        write!(f, "}}")?;
+        Ok(())
+    }
+
+    fn pops_stack(_param: &Self) -> u32 {
+        0
+    }
+
+    fn pushes_stack(_param: &Self) -> u32 {
+        0
+    }
+}
+
 impl BcInstrArg for Box<[Hashed<FrozenValue>]> {
     fn fmt_append(param: &Self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, " [")?;
@@ -17,13 +17,13 @@
 
 //! Instruction implementations.
 
-use std::{cmp::Ordering, marker, mem::MaybeUninit};
+use std::{cmp::Ordering, io::Write, marker, mem::MaybeUninit, ptr};
 
 use gazebo::coerce::coerce;
 
 use crate::{
     codemap::{Span, Spanned},
-    collections::{symbol_map::Symbol, Hashed, SmallMap},
+    collections::{symbol_map::Symbol, Hashed, SmallMap, SmallSet},
     environment::slots::ModuleSlotId,
     eval::{
         bc::{
@@ -51,7 +51,7 @@ use crate::{
         dict::Dict,
         function::{BoundMethod, NativeAttribute, NativeFunction},
         list::List,
-        string::interpolation::{format_one, percent_s_one},
+        string::interpolation::{format_many, format_one, percent_s_one},
         typed::FrozenValueTyped,
         typing::TypeCompiled,
         AttrType, FrozenRef, FrozenStringValue, FrozenValue, Heap, StarlarkValue, Value, ValueLike,
@@ -543,6 +543,25 @@ impl InstrNoFlowAddSpanImpl for InstrArrayIndexNoPopImpl {
     }
 }
 
+/// Assign `array[index] = value`, which grows `array` if it is a dictionary,
+/// so check it would remain within the collection length limit.
+#[inline(always)]
+fn set_array_index<'v>(
+    eval: &Evaluator<'v, '_>,
+    array: Value<'v>,
+    index: Value<'v>,
+    value: Value<'v>,
+) -> anyhow::Result<()> {
+    if let Some(dict) = Dict::from_value(array) {
+        if let Err(e) = eval.heap().check_collection_length(dict.len() + 1) {
+            if dict.get(index)?.is_none() {
+                return Err(e);
+            }
+        }
+    }
+    array.set_at(index, value)
+}
+
 impl InstrNoFlowAddSpanImpl for InstrSetArrayIndexImpl {
     const OPCODE: BcOpcode = BcOpcode::SetArrayIndex;
     type Pop<'v> = [Value<'v>; 3];
@@ -551,12 +570,12 @@ impl InstrNoFlowAddSpanImpl for InstrSetArrayIndexImpl {
 
     #[inline(always)]
     fn run_with_args<'v>(
-        _eval: &mut Evaluator<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
         _stack: &mut BcStackPtr<'v, '_>,
         (): &(),
         [value, array, index]: [Value<'v>; 3],
     ) -> Result<(), anyhow::Error> {
-        array.set_at(index, value)
+        set_array_index(eval, array, index, value)
     }
 }
 
@@ -568,12 +587,12 @@ impl InstrNoFlowAddSpanImpl for InstrArrayIndexSetImpl {
 
     #[inline(always)]
     fn run_with_args<'v>(
-        _eval: &mut Evaluator<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
         _stack: &mut BcStackPtr<'v, '_>,
         (): &(),
         [array, index, value]: [Value<'v>; 3],
     ) -> Result<(), anyhow::Error> {
-        array.set_at(index, value)
+        set_array_index(eval, array, index, value)
     }
 }
 
@@ -688,12 +707,65 @@ impl InstrBinOpImpl for InstrNotEqImpl {
     }
 }
 
+pub(crate) struct InstrEqConstImpl;
+pub(crate) struct InstrNotEqConstImpl;
+
+pub(crate) type InstrEqConst = InstrNoFlowAddSpan<InstrEqConstImpl>;
+pub(crate) type InstrNotEqConst = InstrNoFlowAddSpan<InstrNotEqConstImpl>;
+
+/// `x == c` where `c` is a constant, most often a string or an int.
+#[inline(always)]
+fn equals_const(x: Value, c: FrozenValue) -> anyhow::Result<bool> {
+    if let Some(c) = c.to_value().unpack_str() {
+        if let Some(x) = x.unpack_str() {
+            return Ok(x == c);
+        }
+    }
+    x.equals(c.to_value())
+}
+
+impl InstrNoFlowAddSpanImpl for InstrEqConstImpl {
+    const OPCODE: BcOpcode = BcOpcode::EqConst;
+    type Pop<'v> = Value<'v>;
+    type Push<'v> = Value<'v>;
+    type Arg = FrozenValue;
+
+    #[inline(always)]
+    fn run_with_args<'v>(
+        _eval: &mut Evaluator<'v, '_>,
+        _stack: &mut BcStackPtr<'v, '_>,
+        c: &FrozenValue,
+        x: Value<'v>,
+    ) -> Result<Value<'v>, anyhow::Error> {
+        equals_const(x, *c).map(Value::new_bool)
+    }
+}
+
+impl InstrNoFlowAddSpanImpl for InstrNotEqConstImpl {
+    const OPCODE: BcOpcode = BcOpcode::NotEqConst;
+    type Pop<'v> = Value<'v>;
+    type Push<'v> = Value<'v>;
+    type Arg = FrozenValue;
+
+    #[inline(always)]
+    fn run_with_args<'v>(
+        _eval: &mut Evaluator<'v, '_>,
+        _stack: &mut BcStackPtr<'v, '_>,
+        c: &FrozenValue,
+        x: Value<'v>,
+    ) -> Result<Value<'v>, anyhow::Error> {
+        equals_const(x, *c).map(|v| Value::new_bool(!v))
+    }
+}
+
 pub(crate) struct InstrNotImpl;
+pub(crate) struct InstrToBoolImpl;
 pub(crate) struct InstrMinusImpl;
 pub(crate) struct InstrPlusImpl;
 pub(crate) struct InstrBitNotImpl;
 
 pub(crate) type InstrNot = InstrUnOp<InstrNotImpl>;
+pub(crate) type InstrToBool = InstrUnOp<InstrToBoolImpl>;
 pub(crate) type InstrMinus = InstrUnOp<InstrMinusImpl>;
 pub(crate) type InstrPlus = InstrUnOp<InstrPlusImpl>;
 pub(crate) type InstrBitNot = InstrUnOp<InstrBitNotImpl>;
@@ -707,6 +779,16 @@ impl InstrUnOpImpl for InstrNotImpl {
     }
 }
 
+/// `not not x`.
+impl InstrUnOpImpl for InstrToBoolImpl {
+    const OPCODE: BcOpcode = BcOpcode::ToBool;
+
+    #[inline(always)]
+    fn eval<'v>(v: Value<'v>, _heap: &'v Heap) -> Result<Value<'v>, anyhow::Error> {
+        Ok(Value::new_bool(v.to_bool()))
+    }
+}
+
 impl InstrUnOpImpl for InstrPlusImpl {
     const OPCODE: BcOpcode = BcOpcode::Plus;
 
@@ -818,6 +900,14 @@ impl InstrBinOpImpl for InstrAddImpl {
 
     #[inline(always)]
     fn eval<'v>(l: Value<'v>, r: Value<'v>, heap: &'v Heap) -> Result<Value<'v>, anyhow::Error> {
+        // Addition of ints is common in loops, and doesn't allocate, so avoid dispatching.
+        // On overflow fall through to the generic path, which reports the error.
+        if let (Some(li), Some(ri)) = (l.unpack_int(), r.unpack_int()) {
+            if let Some(x) = li.checked_add(ri) {
+                return Ok(Value::new_int(x));
+            }
+        }
+
         // Addition of string is super common and pretty cheap, so have a special case for it.
         if let Some(ls) = l.unpack_str() {
             if let Some(rs) = r.unpack_str() {
@@ -826,6 +916,7 @@ impl InstrBinOpImpl for InstrAddImpl {
                 } else if rs.is_empty() {
                     return Ok(l);
                 } else {
+                    heap.check_string_length(ls.len() + rs.len())?;
                     return Ok(heap.alloc_str_concat(ls, rs));
                 }
             }
@@ -849,6 +940,11 @@ impl InstrBinOpImpl for InstrSubImpl {
 
     #[inline(always)]
     fn eval<'v>(v0: Value<'v>, v1: Value<'v>, heap: &'v Heap) -> Result<Value<'v>, anyhow::Error> {
+        if let (Some(i0), Some(i1)) = (v0.unpack_int(), v1.unpack_int()) {
+            if let Some(x) = i0.checked_sub(i1) {
+                return Ok(Value::new_int(x));
+            }
+        }
         v0.sub(v1, heap)
     }
 }
@@ -858,6 +954,11 @@ impl InstrBinOpImpl for InstrMultiplyImpl {
 
     #[inline(always)]
     fn eval<'v>(v0: Value<'v>, v1: Value<'v>, heap: &'v Heap) -> Result<Value<'v>, anyhow::Error> {
+        if let (Some(i0), Some(i1)) = (v0.unpack_int(), v1.unpack_int()) {
+            if let Some(x) = i0.checked_mul(i1) {
+                return Ok(Value::new_int(x));
+            }
+        }
         v0.mul(v1, heap)
     }
 }
@@ -952,10 +1053,48 @@ impl InstrBinOpImpl for InstrNotInImpl {
     }
 }
 
+pub(crate) struct InstrInConstSetImpl;
+pub(crate) type InstrInConstSet = InstrNoFlowAddSpan<InstrInConstSetImpl>;
+
+impl InstrNoFlowAddSpanImpl for InstrInConstSetImpl {
+    const OPCODE: BcOpcode = BcOpcode::InConstSet;
+    type Pop<'v> = Value<'v>;
+    type Push<'v> = Value<'v>;
+    type Arg = Box<SmallSet<FrozenValue>>;
+
+    #[inline(always)]
+    fn run_with_args<'v>(
+        _eval: &mut Evaluator<'v, '_>,
+        _stack: &mut BcStackPtr<'v, '_>,
+        set: &Self::Arg,
+        v: Value<'v>,
+    ) -> Result<Value<'v>, anyhow::Error> {
+        let is_in = match v.get_hashed() {
+            Ok(v) => set.contains_hashed(v.borrow()),
+            // An unhashable value may still compare equal to some element.
+            Err(_) => {
+                let mut is_in = false;
+                for x in set.iter() {
+                    if x.equals(v)? {
+                        is_in = true;
+                        break;
+                    }
+                }
+                is_in
+            }
+        };
+        Ok(Value::new_bool(is_in))
+    }
+}
+
 pub(crate) struct InstrPercentSOneImpl;
 pub(crate) type InstrPercentSOne = InstrNoFlowAddSpan<InstrPercentSOneImpl>;
 pub(crate) struct InstrFormatOneImpl;
 pub(crate) type InstrFormatOne = InstrNoFlowAddSpan<InstrFormatOneImpl>;
+pub(crate) struct InstrFormatManyImpl;
+pub(crate) type InstrFormatMany = InstrNoFlow<InstrFormatManyImpl>;
+pub(crate) struct InstrConcatNImpl;
+pub(crate) type InstrConcatN = InstrNoFlow<InstrConcatNImpl>;
 
 impl InstrNoFlowAddSpanImpl for InstrPercentSOneImpl {
     const OPCODE: BcOpcode = BcOpcode::PercentSOne;
@@ -991,6 +1130,85 @@ impl InstrNoFlowAddSpanImpl for InstrFormatOneImpl {
     }
 }
 
+impl InstrNoFlowImpl for InstrFormatManyImpl {
+    const OPCODE: BcOpcode = BcOpcode::FormatMany;
+    type Pop<'v> = ();
+    type Push<'v> = Value<'v>;
+    type Arg = (ArgPopsStack, Box<[FrozenStringValue]>);
+
+    #[inline(always)]
+    fn run_with_args<'v>(
+        eval: &mut Evaluator<'v, '_>,
+        stack: &mut BcStackPtr<'v, '_>,
+        ip: BcPtrAddr,
+        (npops, segments): &Self::Arg,
+        _pops: (),
+    ) -> Result<Value<'v>, EvalException> {
+        let args = stack.pop_slice(*npops);
+        format_many(segments, args, eval.heap())
+            .map_err(|e| Bc::wrap_error_for_instr_ptr(ip, e, eval))
+    }
+}
+
+/// `x + y + z`, popping all the operands at once and concatenating them with a single
+/// allocation if they are all strings. The arg has the span of each `+`.
+impl InstrNoFlowImpl for InstrConcatNImpl {
+    const OPCODE: BcOpcode = BcOpcode::ConcatN;
+    type Pop<'v> = ();
+    type Push<'v> = Value<'v>;
+    type Arg = (ArgPopsStack, FrozenRef<Vec<Span>>);
+
+    fn run_with_args<'v>(
+        eval: &mut Evaluator<'v, '_>,
+        stack: &mut BcStackPtr<'v, '_>,
+        _: BcPtrAddr,
+        (npops, spans): &Self::Arg,
+        _pops: (),
+    ) -> Result<Value<'v>, EvalException> {
+        #[cold]
+        #[inline(never)]
+        fn add_pairwise<'v>(
+            eval: &mut Evaluator<'v, '_>,
+            xs: &[Value<'v>],
+            spans: &[Span],
+        ) -> Result<Value<'v>, EvalException> {
+            let mut res = xs[0];
+            for (x, span) in xs[1..].iter().zip(spans) {
+                res = match InstrAddImpl::eval(res, *x, eval.heap()) {
+                    Ok(res) => res,
+                    Err(e) => return Err(add_span_to_expr_error(e, *span, eval)),
+                };
+            }
+            Ok(res)
+        }
+
+        let xs = stack.pop_slice(*npops);
+        debug_assert!(xs.len() == spans.len() + 1);
+        let mut len = 0;
+        for x in xs {
+            match x.unpack_str() {
+                Some(x) => len += x.len(),
+                None => return add_pairwise(eval, xs, spans),
+            }
+        }
+        if len == 0 {
+            return Ok(Value::new_empty_string());
+        }
+        if let Err(e) = eval.heap().check_string_length(len) {
+            return Err(add_span_to_expr_error(e, spans[spans.len() - 1], eval));
+        }
+        Ok(eval.heap().alloc_str_init(len, |mut dest| {
+            for x in xs {
+                let x = x.unpack_str().unwrap();
+                unsafe {
+                    ptr::copy_nonoverlapping(x.as_ptr(), dest, x.len());
+                    dest = dest.add(x.len());
+                }
+            }
+        }))
+    }
+}
+
 pub(crate) trait InstrCompareImpl: 'static {
     const OPCODE: BcOpcode;
     fn eval_compare(ordering: Ordering) -> bool;
@@ -1117,7 +1335,7 @@ pub(crate) type InstrDictNew = InstrNoFlow<InstrDictNewImpl>;
 pub(crate) type InstrDictOfConsts = InstrNoFlow<InstrDictOfConstsImpl>;
 pub(crate) type InstrDictConstKeys = InstrNoFlow<InstrDictConstKeysImpl>;
 pub(crate) type InstrDictNPop = InstrNoFlow<InstrDictNPopImpl>;
-pub(crate) type InstrComprListAppend = InstrNoFlow<InstrComprListAppendImpl>;
+pub(crate) type InstrComprListAppend = InstrNoFlowAddSpan<InstrComprListAppendImpl>;
 pub(crate) type InstrComprDictInsert = InstrNoFlowAddSpan<InstrComprDictInsertImpl>;
 
 impl InstrNoFlowImpl for InstrTupleNPopImpl {
@@ -1288,7 +1506,7 @@ impl InstrNoFlowImpl for InstrDictNewImpl {
     }
 }
 
-impl InstrNoFlowImpl for InstrComprListAppendImpl {
+impl InstrNoFlowAddSpanImpl for InstrComprListAppendImpl {
     const OPCODE: BcOpcode = BcOpcode::ComprListAppend;
     type Pop<'v> = [Value<'v>; 2];
     type Push<'v> = Value<'v>;
@@ -1298,14 +1516,13 @@ impl InstrNoFlowImpl for InstrComprListAppendImpl {
     fn run_with_args<'v>(
         eval: &mut Evaluator<'v, '_>,
         _stack: &mut BcStackPtr<'v, '_>,
-        _: BcPtrAddr,
         (): &(),
         [list, item]: [Value<'v>; 2],
-    ) -> Result<Value<'v>, EvalException> {
+    ) -> Result<Value<'v>, anyhow::Error> {
         List::from_value_mut(list)
             .unwrap()
             .unwrap()
-            .push(item, eval.heap());
+            .push(item, eval.heap())?;
         Ok(list)
     }
 }
@@ -1318,16 +1535,14 @@ impl InstrNoFlowAddSpanImpl for InstrComprDictInsertImpl {
 
     #[inline(always)]
     fn run_with_args<'v>(
-        _eval: &mut Evaluator<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
         _stack: &mut BcStackPtr<'v, '_>,
         (): &(),
         [dict, key, value]: [Value<'v>; 3],
     ) -> Result<Value<'v>, anyhow::Error> {
         let key = key.get_hashed()?;
-        Dict::from_value_mut(dict)
-            .unwrap()
-            .unwrap()
-            .insert_hashed(key, value);
+        let mut d = Dict::from_value_mut(dict).unwrap().unwrap();
+        d.insert_hashed_bounded(key, value, eval.heap())?;
         Ok(dict)
     }
 }
@@ -1426,6 +1641,9 @@ impl BcInstr for InstrForLoop {
         let iter_ret = collection.with_iterator(eval.heap(), |iter| {
             let loop_start = ip.add_instr::<Self>();
             for item in iter {
+                if let Err(e) = eval.check_limits() {
+                    return LoopResult::Err(Bc::wrap_error_for_instr_ptr(ip, e, eval));
+                }
                 stack.push(item);
                 debug_assert!(stack.stack_offset() == ss);
                 match run_block(eval, stack, loop_start) {
@@ -1488,6 +1706,7 @@ impl BcInstr for InstrContinue {
 
 pub(crate) struct InstrReturnNone;
 pub(crate) struct InstrReturn;
+pub(crate) struct InstrReturnLocal;
 
 impl BcInstr for InstrReturnNone {
     const OPCODE: BcOpcode = BcOpcode::ReturnNone;
@@ -1523,6 +1742,26 @@ impl BcInstr for InstrReturn {
     }
 }
 
+impl BcInstr for InstrReturnLocal {
+    const OPCODE: BcOpcode = BcOpcode::ReturnLocal;
+    type Pop<'v> = ();
+    type Push<'v> = ();
+    type Arg = LocalSlotId;
+
+    #[inline(always)]
+    fn run<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        _stack: &mut BcStackPtr<'v, '_>,
+        ip: BcPtrAddr<'b>,
+        slot: &LocalSlotId,
+    ) -> InstrControl<'v, 'b> {
+        match eval.get_slot_local(*slot) {
+            Ok(v) => InstrControl::Return(v),
+            Err(e) => InstrControl::Err(Bc::wrap_error_for_instr_ptr(ip, e, eval)),
+        }
+    }
+}
+
 pub(crate) struct InstrDefImpl;
 pub(crate) type InstrDef = InstrNoFlow<InstrDefImpl>;
 
@@ -1635,6 +1874,8 @@ pub(crate) struct InstrCallFrozenImpl;
 pub(crate) struct InstrCallFrozenPosImpl;
 pub(crate) struct InstrCallMethodImpl;
 pub(crate) struct InstrCallMethodPosImpl;
+pub(crate) struct InstrCallMethodFrozenImpl;
+pub(crate) struct InstrCallMethodFrozenPosImpl;
 
 pub(crate) type InstrCall = InstrNoFlowAddSpan<InstrCallImpl>;
 pub(crate) type InstrCallPos = InstrNoFlowAddSpan<InstrCallPosImpl>;
@@ -1646,6 +1887,8 @@ pub(crate) type InstrCallFrozen = InstrNoFlowAddSpan<InstrCallFrozenImpl>;
 pub(crate) type InstrCallFrozenPos = InstrNoFlowAddSpan<InstrCallFrozenPosImpl>;
 pub(crate) type InstrCallMethod = InstrNoFlowAddSpan<InstrCallMethodImpl>;
 pub(crate) type InstrCallMethodPos = InstrNoFlowAddSpan<InstrCallMethodPosImpl>;
+pub(crate) type InstrCallMethodFrozen = InstrNoFlowAddSpan<InstrCallMethodFrozenImpl>;
+pub(crate) type InstrCallMethodFrozenPos = InstrNoFlowAddSpan<InstrCallMethodFrozenPosImpl>;
 
 impl InstrNoFlowAddSpanImpl for InstrCallImpl {
     const OPCODE: BcOpcode = BcOpcode::Call;
@@ -1854,13 +2097,55 @@ impl InstrNoFlowAddSpanImpl for InstrCallMethodPosImpl {
     }
 }
 
+impl InstrNoFlowAddSpanImpl for InstrCallMethodFrozenImpl {
+    const OPCODE: BcOpcode = BcOpcode::CallMethodFrozen;
+    type Pop<'v> = ();
+    type Push<'v> = Value<'v>;
+    type Arg = (ArgPopsStack1, FrozenValue, ArgsCompiledValueBc);
+
+    #[inline(always)]
+    fn run_with_args<'v>(
+        eval: &mut Evaluator<'v, '_>,
+        stack: &mut BcStackPtr<'v, '_>,
+        (_pop1, fun, args): &Self::Arg,
+        _pops: (),
+    ) -> Result<Value<'v>, anyhow::Error> {
+        let mut arguments = stack.pop_args(args);
+        let this = stack.pop();
+        arguments.this = Some(this);
+        fun.invoke(Some(args.span), arguments, eval)
+    }
+}
+
+impl InstrNoFlowAddSpanImpl for InstrCallMethodFrozenPosImpl {
+    const OPCODE: BcOpcode = BcOpcode::CallMethodFrozenPos;
+    type Pop<'v> = ();
+    type Push<'v> = Value<'v>;
+    type Arg = (ArgPopsStack1, ArgPopsStack, FrozenValue, Span);
+
+    #[inline(always)]
+    fn run_with_args<'v>(
+        eval: &mut Evaluator<'v, '_>,
+        stack: &mut BcStackPtr<'v, '_>,
+        (_pop1, npops, fun, span): &Self::Arg,
+        _pops: (),
+    ) -> Result<Value<'v>, anyhow::Error> {
+        let mut arguments = stack.pop_args_pos(*npops);
+        let this = stack.pop();
+        arguments.this = Some(this);
+        fun.invoke(Some(*span), arguments, eval)
+    }
+}
+
 pub(crate) struct InstrPossibleGcImpl;
 pub(crate) struct InstrBeforeStmtImpl;
 pub(crate) struct InstrProfileBcImpl;
+pub(crate) struct InstrTraceBcImpl;
 
 pub(crate) type InstrPossibleGc = InstrNoFlow<InstrPossibleGcImpl>;
 pub(crate) type InstrBeforeStmt = InstrNoFlow<InstrBeforeStmtImpl>;
 pub(crate) type InstrProfileBc = InstrNoFlow<InstrProfileBcImpl>;
+pub(crate) type InstrTraceBc = InstrNoFlow<InstrTraceBcImpl>;
 
 impl InstrNoFlowImpl for InstrPossibleGcImpl {
     const OPCODE: BcOpcode = BcOpcode::PossibleGc;
@@ -1916,6 +2201,50 @@ impl InstrNoFlowImpl for InstrProfileBcImpl {
     }
 }
 
+impl InstrNoFlowImpl for InstrTraceBcImpl {
+    const OPCODE: BcOpcode = BcOpcode::TraceBc;
+    type Pop<'v> = ();
+    type Push<'v> = ();
+    /// Opcode, address and span of the traced instruction,
+    /// and the number of values it pops from the stack.
+    type Arg = (BcOpcode, BcAddr, Span, u32);
+
+    fn run_with_args<'v>(
+        eval: &mut Evaluator<'v, '_>,
+        stack: &mut BcStackPtr<'v, '_>,
+        _ip: BcPtrAddr,
+        (opcode, addr, span, npops): &(BcOpcode, BcAddr, Span, u32),
+        (): (),
+    ) -> Result<(), EvalException> {
+        let res = match &mut eval.bc_trace {
+            None => Ok(()),
+            Some(out) => {
+                let mut operands = String::new();
+                for (i, x) in stack.top_slice(*npops).iter().enumerate() {
+                    if i != 0 {
+                        operands.push_str(", ");
+                    }
+                    x.collect_repr(&mut operands);
+                }
+                writeln!(
+                    out,
+                    "{} {:?} {} [{}]",
+                    addr,
+                    opcode,
+                    eval.def_info.codemap.file_span(*span),
+                    operands
+                )
+            }
+        };
+        res.map_err(|e| add_span_to_expr_error(e.into(), *span, eval))?;
+        if let Some(f) = eval.before_instr {
+            let file_span = eval.def_info.codemap.file_span(*span);
+            f(addr.0, file_span, eval).map_err(|e| add_span_to_expr_error(e, *span, eval))?;
+        }
+        Ok(())
+    }
+}
+
 /// Pseudo-instruction:
 /// * to store bytecode metadata (i.e. spans): when bytecode is evaluated, we only have IP,
 ///   we don't have a pointer to bytecode object. To obtain spans by IP, we scroll
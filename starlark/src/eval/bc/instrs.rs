@@ -18,6 +18,7 @@
 //! Instructions serialized in byte array.
 
 use std::{
+    collections::HashSet,
     convert::TryInto,
     fmt,
     fmt::{Display, Formatter},
@@ -25,18 +26,34 @@ use std::{
 };
 
 use either::Either;
+use thiserror::Error;
 
 use crate::{
-    codemap::Span,
+    codemap::{CodeMap, Span},
     eval::bc::{
         addr::{BcAddr, BcAddrOffset, BcPtrAddr},
         instr::BcInstr,
-        instr_impl::InstrEndOfBc,
+        instr_impl::{InstrBr, InstrEndOfBc, InstrForLoop, InstrIfBr, InstrIfNotBr},
         opcode::{BcOpcode, BcOpcodeHandler},
         repr::{BcInstrRepr, BC_INSTR_ALIGN},
     },
 };
 
+/// Why instructions failed [`BcInstrs::verify`].
+#[derive(Debug, Error)]
+enum BcVerifyError {
+    #[error("Invalid opcode {1} at {0}")]
+    InvalidOpcode(BcAddr, u32),
+    #[error("Instruction {1:?} at {0} overruns the bytecode")]
+    Overrun(BcAddr, BcOpcode),
+    #[error("Jump at {0} to @{1} is not to the start of an instruction")]
+    InvalidJump(BcAddr, usize),
+    #[error("`EndOfBc` at {0} is not the last instruction")]
+    EarlyEndOfBc(BcAddr),
+    #[error("Bytecode does not end with `EndOfBc`")]
+    NoEndOfBc,
+}
+
 impl BcOpcode {
     /// Drop instruction at given address.
     unsafe fn drop_in_place(self, ptr: BcPtrAddr) {
@@ -159,23 +176,122 @@ impl BcInstrs {
         self.start_ptr().offset(self.end())
     }
 
+    /// Check the instructions are well formed, without trusting them like evaluation does:
+    /// every opcode is valid, every instruction fits in the buffer, every jump lands on
+    /// the start of an instruction, and the last instruction is `EndOfBc`.
+    pub(crate) fn verify(&self) -> anyhow::Result<()> {
+        let len = mem::size_of_val::<[usize]>(&self.instrs);
+        let start = BcPtrAddr::for_slice_start(&self.instrs);
+        let mut starts = HashSet::new();
+        let mut jumps = Vec::new();
+        let mut addr = 0;
+        let mut last = None;
+        while addr < len {
+            let bc_addr = BcAddr(addr as u32);
+            if let Some((end, BcOpcode::EndOfBc)) = last {
+                return Err(BcVerifyError::EarlyEndOfBc(end).into());
+            }
+            // Read the opcode as a number, since not every number is a valid `BcOpcode`.
+            // Instructions are word aligned, so there are always enough bytes for a header.
+            let number = unsafe { *((self.instrs.as_ptr() as *const u8).add(addr) as *const u32) };
+            let opcode =
+                BcOpcode::by_number(number).ok_or(BcVerifyError::InvalidOpcode(bc_addr, number))?;
+            let size = opcode.size_of_repr();
+            if addr + size > len {
+                return Err(BcVerifyError::Overrun(bc_addr, opcode).into());
+            }
+            let ptr = start.add(addr);
+            let target = match opcode {
+                BcOpcode::Br => Some(ptr.get_instr::<InstrBr>().arg),
+                BcOpcode::IfBr => Some(ptr.get_instr::<InstrIfBr>().arg),
+                BcOpcode::IfNotBr => Some(ptr.get_instr::<InstrIfNotBr>().arg),
+                BcOpcode::ForLoop => Some(ptr.get_instr::<InstrForLoop>().arg),
+                _ => None,
+            };
+            if let Some(target) = target {
+                // Jumps are relative to the start of the jumping instruction.
+                jumps.push((bc_addr, addr + target.0 as usize));
+            }
+            starts.insert(addr);
+            last = Some((bc_addr, opcode));
+            addr += size;
+        }
+        if !matches!(last, Some((_, BcOpcode::EndOfBc))) {
+            return Err(BcVerifyError::NoEndOfBc.into());
+        }
+        for (from, to) in jumps {
+            if to % BC_INSTR_ALIGN != 0 || !starts.contains(&to) {
+                return Err(BcVerifyError::InvalidJump(from, to).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// The opcode of each instruction, including the final `EndOfBc`.
     #[cfg(test)]
     pub(crate) fn opcodes(&self) -> Vec<BcOpcode> {
+        self.opcodes_with_offsets()
+            .into_iter()
+            .map(|x| x.1)
+            .collect()
+    }
+
+    /// The opcode of each instruction with its address, as printed by `Display`,
+    /// including the final `EndOfBc`.
+    pub(crate) fn opcodes_with_offsets(&self) -> Vec<(BcAddr, BcOpcode)> {
         let mut opcodes = Vec::new();
+        self.for_each_opcode(|addr, opcode| opcodes.push((addr, opcode)));
+        opcodes
+    }
+
+    /// Number of instructions, excluding the final `EndOfBc`.
+    pub(crate) fn instr_count(&self) -> usize {
+        let mut count = 0;
+        self.for_each_opcode(|_, opcode| {
+            if opcode != BcOpcode::EndOfBc {
+                count += 1;
+            }
+        });
+        count
+    }
+
+    fn for_each_opcode(&self, mut f: impl FnMut(BcAddr, BcOpcode)) {
+        let start = BcPtrAddr::for_slice_start(&self.instrs);
         let end = BcPtrAddr::for_slice_end(&self.instrs);
-        let mut ptr = BcPtrAddr::for_slice_start(&self.instrs);
+        let mut ptr = start;
         while ptr != end {
             assert!(ptr < end);
             let opcode = ptr.get_opcode();
-            opcodes.push(opcode);
+            f(ptr.offset_from(start), opcode);
             ptr = ptr.add(opcode.size_of_repr());
         }
-        opcodes
     }
 }
 
-impl Display for BcInstrs {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+impl BcInstrs {
+    /// Source span of each instruction, as passed to [`BcInstrsWriter::finish`].
+    pub(crate) fn spans(&self) -> &[(BcAddr, Span)] {
+        let (addr, opcode) = *self.opcodes_with_offsets().last().unwrap();
+        assert!(opcode == BcOpcode::EndOfBc);
+        &self
+            .start_ptr()
+            .offset(addr)
+            .get_instr::<InstrEndOfBc>()
+            .arg
+            .1
+    }
+
+    /// Print the instructions one per line, each followed by its source location
+    /// resolved in `codemap`.
+    pub(crate) fn display_with_spans<'a>(&'a self, codemap: &'a CodeMap) -> impl Display + 'a {
+        BcInstrsWithSpans {
+            instrs: self,
+            codemap,
+        }
+    }
+
+    fn fmt_impl(&self, f: &mut Formatter<'_>, codemap: Option<&CodeMap>) -> fmt::Result {
+        let spans = codemap.map(|_| self.spans());
         let mut ptr = self.start_ptr();
         loop {
             assert!(ptr < self.end_ptr());
@@ -187,7 +303,15 @@ impl Display for BcInstrs {
             }
             write!(f, "{}: {:?}", ip.0, opcopde)?;
             opcopde.fmt_append_arg(ptr, f)?;
-            write!(f, "; ")?;
+            match (codemap, spans) {
+                (Some(codemap), Some(spans)) => {
+                    if let Some((_, span)) = spans.iter().find(|(addr, _)| *addr == ip) {
+                        write!(f, " # {}", codemap.resolve_span(*span))?;
+                    }
+                    writeln!(f)?;
+                }
+                _ => write!(f, "; ")?,
+            }
             ptr = ptr.add(opcopde.size_of_repr());
         }
         write!(f, "{}: END", ptr.offset_from(self.start_ptr()).0)?;
@@ -195,6 +319,23 @@ impl Display for BcInstrs {
     }
 }
 
+impl Display for BcInstrs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.fmt_impl(f, None)
+    }
+}
+
+struct BcInstrsWithSpans<'a> {
+    instrs: &'a BcInstrs,
+    codemap: &'a CodeMap,
+}
+
+impl Display for BcInstrsWithSpans<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.instrs.fmt_impl(f, Some(self.codemap))
+    }
+}
+
 impl BcInstrsWriter {
     pub(crate) fn new() -> BcInstrsWriter {
         BcInstrsWriter { instrs: Vec::new() }
@@ -263,9 +404,15 @@ impl BcInstrsWriter {
         let instrs = mem::take(&mut self.instrs);
         let instrs = instrs.into_boxed_slice();
         assert!((instrs.as_ptr() as usize) % BC_INSTR_ALIGN == 0);
-        BcInstrs {
+        let instrs = BcInstrs {
             instrs: Either::Left(instrs),
+        };
+        if cfg!(debug_assertions) {
+            if let Err(e) = instrs.verify() {
+                panic!("Compiler produced invalid bytecode: {:#}", e);
+            }
         }
+        instrs
     }
 }
 
@@ -273,14 +420,37 @@ impl BcInstrsWriter {
 mod test {
     use std::mem;
 
+    use either::Either;
+
     use crate::{
         eval::bc::{
-            instr_impl::{InstrConst, InstrPossibleGc, InstrReturn, InstrReturnNone},
+            addr::{BcAddr, BcAddrOffset},
+            instr_impl::{
+                InstrBr, InstrConst, InstrEndOfBc, InstrPossibleGc, InstrReturn, InstrReturnNone,
+            },
             instrs::{BcInstrs, BcInstrsWriter},
+            opcode::BcOpcode,
         },
         values::FrozenValue,
     };
 
+    /// Take the instructions without `finish`, which would verify them, and leak them,
+    /// since invalid instructions can't be dropped.
+    fn leak(mut bc: BcInstrsWriter) -> BcInstrs {
+        let instrs = mem::take(&mut bc.instrs);
+        BcInstrs {
+            instrs: Either::Right(Box::leak(instrs.into_boxed_slice())),
+        }
+    }
+
+    fn write_end(bc: &mut BcInstrsWriter) {
+        bc.write::<InstrEndOfBc>((bc.ip(), Vec::new()));
+    }
+
+    fn verify_err(bc: BcInstrs) -> String {
+        bc.verify().unwrap_err().to_string()
+    }
+
     #[test]
     fn write() {
         let mut bc = BcInstrsWriter::new();
@@ -294,6 +464,7 @@ mod test {
     #[test]
     fn default() {
         assert_eq!("0: END", BcInstrs::default().to_string());
+        assert_eq!(0, BcInstrs::default().instr_count());
     }
 
     #[test]
@@ -302,12 +473,65 @@ mod test {
         bc.write::<InstrConst>(FrozenValue::new_bool(true));
         bc.write::<InstrReturn>(());
         let bc = bc.finish(Vec::new());
+        assert_eq!(2, bc.instr_count());
         if mem::size_of::<usize>() == 8 {
             assert_eq!("0: Const True; 16: Return; 24: END", format!("{}", bc));
+            assert_eq!(
+                vec![
+                    (BcAddr(0), BcOpcode::Const),
+                    (BcAddr(16), BcOpcode::Return),
+                    (BcAddr(24), BcOpcode::EndOfBc)
+                ],
+                bc.opcodes_with_offsets()
+            );
         } else if mem::size_of::<usize>() == 4 {
             // Starlark doesn't work now on 32-bit CPU
         } else {
             panic!("unknown word size: {}", mem::size_of::<usize>());
         }
     }
+
+    #[test]
+    fn verify() {
+        assert!(BcInstrs::default().verify().is_ok());
+
+        let br = BcOpcode::Br.size_of_repr() as u32;
+        let mut bc = BcInstrsWriter::new();
+        bc.write::<InstrBr>(BcAddrOffset(br));
+        bc.write::<InstrReturnNone>(());
+        assert!(bc.finish(Vec::new()).verify().is_ok());
+    }
+
+    #[test]
+    fn verify_invalid() {
+        let mut bc = BcInstrsWriter::new();
+        bc.write::<InstrBr>(BcAddrOffset(4));
+        bc.write::<InstrReturnNone>(());
+        write_end(&mut bc);
+        assert!(verify_err(leak(bc)).contains("not to the start of an instruction"));
+
+        let mut bc = BcInstrsWriter::new();
+        bc.write::<InstrBr>(BcAddrOffset(1000));
+        write_end(&mut bc);
+        assert!(verify_err(leak(bc)).contains("not to the start of an instruction"));
+
+        let mut bc = BcInstrsWriter::new();
+        bc.write::<InstrReturnNone>(());
+        assert!(verify_err(leak(bc)).contains("does not end with `EndOfBc`"));
+
+        let mut bc = BcInstrsWriter::new();
+        write_end(&mut bc);
+        bc.write::<InstrReturnNone>(());
+        assert!(verify_err(leak(bc)).contains("is not the last instruction"));
+
+        let mut bc = BcInstrsWriter::new();
+        bc.write::<InstrConst>(FrozenValue::new_none());
+        bc.instrs.truncate(1);
+        assert!(verify_err(leak(bc)).contains("overruns the bytecode"));
+
+        let bc = BcInstrs {
+            instrs: Either::Right(Box::leak(vec![usize::MAX].into_boxed_slice())),
+        };
+        assert!(verify_err(bc).contains("Invalid opcode"));
+    }
 }
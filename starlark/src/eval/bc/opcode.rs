@@ -55,7 +55,10 @@ pub(crate) enum BcOpcode {
     ObjectSetField,
     Eq,
     NotEq,
+    EqConst,
+    NotEqConst,
     Not,
+    ToBool,
     Minus,
     Plus,
     BitNot,
@@ -65,6 +68,7 @@ pub(crate) enum BcOpcode {
     GreaterOrEqual,
     In,
     NotIn,
+    InConstSet,
     Add,
     AddAssign,
     Sub,
@@ -72,6 +76,8 @@ pub(crate) enum BcOpcode {
     Percent,
     PercentSOne,
     FormatOne,
+    FormatMany,
+    ConcatN,
     Divide,
     FloorDivide,
     BitAnd,
@@ -100,6 +106,7 @@ pub(crate) enum BcOpcode {
     Continue,
     Return,
     ReturnNone,
+    ReturnLocal,
     Call,
     CallPos,
     CallFrozenDef,
@@ -110,10 +117,13 @@ pub(crate) enum BcOpcode {
     CallFrozenPos,
     CallMethod,
     CallMethodPos,
+    CallMethodFrozen,
+    CallMethodFrozenPos,
     Def,
     PossibleGc,
     BeforeStmt,
     ProfileBc,
+    TraceBc,
     EndOfBc,
 }
 
@@ -146,13 +156,17 @@ impl BcOpcode {
             BcOpcode::TypeIs => handler.handle::<InstrTypeIs>(),
             BcOpcode::Len => handler.handle::<InstrLen>(),
             BcOpcode::Not => handler.handle::<InstrNot>(),
+            BcOpcode::ToBool => handler.handle::<InstrToBool>(),
             BcOpcode::Minus => handler.handle::<InstrMinus>(),
             BcOpcode::Plus => handler.handle::<InstrPlus>(),
             BcOpcode::BitNot => handler.handle::<InstrBitNot>(),
             BcOpcode::Eq => handler.handle::<InstrEq>(),
             BcOpcode::NotEq => handler.handle::<InstrNotEq>(),
+            BcOpcode::EqConst => handler.handle::<InstrEqConst>(),
+            BcOpcode::NotEqConst => handler.handle::<InstrNotEqConst>(),
             BcOpcode::In => handler.handle::<InstrIn>(),
             BcOpcode::NotIn => handler.handle::<InstrNotIn>(),
+            BcOpcode::InConstSet => handler.handle::<InstrInConstSet>(),
             BcOpcode::Add => handler.handle::<InstrAdd>(),
             BcOpcode::AddAssign => handler.handle::<InstrAddAssign>(),
             BcOpcode::Sub => handler.handle::<InstrSub>(),
@@ -160,6 +174,8 @@ impl BcOpcode {
             BcOpcode::Percent => handler.handle::<InstrPercent>(),
             BcOpcode::PercentSOne => handler.handle::<InstrPercentSOne>(),
             BcOpcode::FormatOne => handler.handle::<InstrFormatOne>(),
+            BcOpcode::FormatMany => handler.handle::<InstrFormatMany>(),
+            BcOpcode::ConcatN => handler.handle::<InstrConcatN>(),
             BcOpcode::Divide => handler.handle::<InstrDivide>(),
             BcOpcode::FloorDivide => handler.handle::<InstrFloorDivide>(),
             BcOpcode::BitAnd => handler.handle::<InstrBitAnd>(),
@@ -218,11 +234,15 @@ impl BcOpcode {
             BcOpcode::CallFrozenPos => handler.handle::<InstrCallFrozenPos>(),
             BcOpcode::CallMethod => handler.handle::<InstrCallMethod>(),
             BcOpcode::CallMethodPos => handler.handle::<InstrCallMethodPos>(),
+            BcOpcode::CallMethodFrozen => handler.handle::<InstrCallMethodFrozen>(),
+            BcOpcode::CallMethodFrozenPos => handler.handle::<InstrCallMethodFrozenPos>(),
             BcOpcode::Return => handler.handle::<InstrReturn>(),
             BcOpcode::ReturnNone => handler.handle::<InstrReturnNone>(),
+            BcOpcode::ReturnLocal => handler.handle::<InstrReturnLocal>(),
             BcOpcode::PossibleGc => handler.handle::<InstrPossibleGc>(),
             BcOpcode::BeforeStmt => handler.handle::<InstrBeforeStmt>(),
             BcOpcode::ProfileBc => handler.handle::<InstrProfileBc>(),
+            BcOpcode::TraceBc => handler.handle::<InstrTraceBc>(),
             BcOpcode::EndOfBc => handler.handle::<InstrEndOfBc>(),
         }
     }
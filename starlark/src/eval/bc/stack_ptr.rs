@@ -128,6 +128,12 @@ impl<'v, 's> BcStackPtr<'v, 's> {
         }
     }
 
+    /// The top `n` values of the stack, without popping them.
+    pub(crate) fn top_slice(&self, n: u32) -> &[Value<'v>] {
+        debug_assert!(self.stack_size_if_debug() >= n as usize);
+        unsafe { slice::from_raw_parts(self.ptr.get().sub(n as usize), n as usize) }
+    }
+
     pub(crate) fn pop_slice(&self, n: ArgPopsStack) -> &[Value<'v>] {
         self.sub_assign(n.0 as usize);
         unsafe { slice::from_raw_parts(self.ptr.get(), n.0 as usize) }
@@ -32,6 +32,7 @@ use crate::{
                 InstrBr, InstrConst, InstrConst2, InstrConst3, InstrConst4, InstrContinue,
                 InstrForLoop, InstrIfBr, InstrIfNotBr, InstrLoadLocal, InstrLoadLocal2,
                 InstrLoadLocal3, InstrLoadLocal4, InstrLoadLocalAndConst, InstrProfileBc,
+                InstrTraceBc,
             },
             instrs::{BcInstrsWriter, PatchAddr},
             opcode::BcOpcode,
@@ -45,6 +46,8 @@ use crate::{
 pub(crate) struct BcWriter {
     /// Insert bytecode profiling instructions.
     profile: bool,
+    /// Insert bytecode tracing instructions.
+    trace: bool,
 
     /// Serialized instructions.
     instrs: BcInstrsWriter,
@@ -61,13 +64,17 @@ pub(crate) struct BcWriter {
 
     /// Allocate various objects here.
     heap: FrozenHeap,
+
+    /// Number of local slots which hold parameters, so are always assigned.
+    param_count: u32,
 }
 
 impl BcWriter {
     /// Empty.
-    pub(crate) fn new(profile: bool) -> BcWriter {
+    pub(crate) fn new(profile: bool, trace: bool, param_count: u32) -> BcWriter {
         BcWriter {
             profile,
+            trace,
             instrs: BcInstrsWriter::new(),
             spans: Vec::new(),
             stack_size: 0,
@@ -75,6 +82,7 @@ impl BcWriter {
             queued_consts: Vec::new(),
             queued_locals: Vec::new(),
             heap: FrozenHeap::new(),
+            param_count,
         }
     }
 
@@ -82,6 +90,7 @@ impl BcWriter {
     pub(crate) fn finish(self) -> Bc {
         let BcWriter {
             profile: has_before_instr,
+            trace: _,
             instrs,
             spans,
             stack_size,
@@ -89,6 +98,7 @@ impl BcWriter {
             queued_consts,
             queued_locals,
             heap,
+            param_count: _,
         } = self;
         let _ = has_before_instr;
         assert!(queued_locals.is_empty());
@@ -190,6 +200,13 @@ impl BcWriter {
             // This instruction does not fail, so do not write span for it.
             self.instrs.write::<InstrProfileBc>(I::OPCODE);
         }
+        if self.trace {
+            // Address of the traced instruction, which follows this one.
+            let addr = self.ip() + BcAddrOffset::for_instr::<InstrTraceBc>().0;
+            let npops = I::npops(&arg);
+            self.instrs
+                .write::<InstrTraceBc>((I::OPCODE, addr, span, npops));
+        }
         self.spans.push((self.ip(), span));
         self.instrs.write::<I>(arg)
     }
@@ -228,6 +245,11 @@ impl BcWriter {
     }
 
     /// Write load local instruction.
+    /// Is the local always assigned, so reading it can't fail.
+    pub(crate) fn is_definitely_assigned(&self, slot: LocalSlotId) -> bool {
+        slot.0 < self.param_count
+    }
+
     pub(crate) fn write_load_local(&mut self, span: Span, slot: LocalSlotId) {
         // Consts must be queued after locals, so if any consts are queued, flush them.
         if !self.queued_consts.is_empty() {
@@ -17,10 +17,10 @@
 
 pub(crate) mod scope;
 
-use std::fmt::Debug;
+use std::{cell::RefCell, collections::HashMap, fmt::Debug};
 
-use gazebo::prelude::*;
-use once_cell::sync::Lazy;
+use gazebo::{cast, prelude::*};
+use once_cell::{sync::Lazy, unsync::OnceCell};
 
 use crate::{
     codemap::{CodeMap, Span},
@@ -30,7 +30,7 @@ use crate::{
         compiler::scope::{ScopeData, ScopeId},
         Evaluator, ScopeNames,
     },
-    values::{FrozenRef, FrozenValue},
+    values::{FrozenRef, FrozenValue, Heap},
 };
 
 /// Error of evaluation of an expression.
@@ -76,8 +76,21 @@ pub(crate) struct Compiler<'a> {
     pub(crate) globals: FrozenRef<Globals>,
     pub(crate) codemap: CodeMap,
     pub(crate) constants: Constants,
+    /// String literals already allocated on the module heap, shared between all their uses.
+    pub(crate) string_constants: HashMap<Box<str>, FrozenValue>,
     pub(crate) has_before_stmt: bool,
     pub(crate) bc_profile: bool,
+    pub(crate) bc_trace: bool,
+    /// Whether calls are recorded by the heap or flame profiler.
+    pub(crate) heap_or_flame_profile: bool,
+    /// Whether limits are checked on each call, see `Evaluator::has_call_limits`.
+    pub(crate) has_call_limits: bool,
+    /// Whether to insert GC points between top-level statements.
+    pub(crate) allow_gc: bool,
+    /// Errors found while compiling a top-level statement, e.g. a call to a known `def`
+    /// with the wrong arguments, which are reported before the statement runs.
+    pub(crate) errors: Vec<anyhow::Error>,
+    pub(crate) scratch: ScratchEval,
 }
 
 impl Compiler<'_> {
@@ -91,6 +104,49 @@ impl Compiler<'_> {
     }
 }
 
+/// A heap and evaluator for running code while compiling or freezing, e.g. to call a builtin
+/// with constant arguments. Creating an evaluator is relatively expensive, so one is shared by
+/// a whole compilation or freeze, and only created when first used. Values allocated here are
+/// garbage once it is dropped, so results must be frozen or copied to another heap.
+pub(crate) struct ScratchEval(OnceCell<ScratchEvalData>);
+
+struct ScratchEvalData {
+    // Declared before `module` so it is dropped first.
+    eval: RefCell<Evaluator<'static, 'static>>,
+    module: Box<Module>,
+}
+
+impl ScratchEval {
+    pub(crate) fn new() -> ScratchEval {
+        ScratchEval(OnceCell::new())
+    }
+
+    fn data(&self) -> &ScratchEvalData {
+        self.0.get_or_init(|| {
+            let module = box Module::new();
+            // SAFETY: the module is boxed, so doesn't move, and is dropped after the evaluator.
+            // The evaluator can only be reached through `with_eval`, whose result can't mention
+            // the lifetime of the values, so no value on the scratch heap outlives the module.
+            let eval = Evaluator::new(unsafe { cast::ptr_lifetime(&*module) });
+            ScratchEvalData {
+                eval: RefCell::new(eval),
+                module,
+            }
+        })
+    }
+
+    pub(crate) fn heap(&self) -> &Heap {
+        self.data().module.heap()
+    }
+
+    pub(crate) fn with_eval<R>(
+        &self,
+        f: impl for<'v> FnOnce(&mut Evaluator<'v, 'static>) -> R,
+    ) -> R {
+        f(&mut self.data().eval.borrow_mut())
+    }
+}
+
 #[derive(Clone, Copy, Dupe)]
 pub(crate) struct Constants {
     pub(crate) fn_len: FrozenValue,
@@ -15,7 +15,11 @@
  * limitations under the License.
  */
 
-use std::{collections::HashMap, convert::TryInto, iter, mem};
+use std::{
+    collections::{BTreeSet, HashMap},
+    convert::TryInto,
+    iter, mem,
+};
 
 use gazebo::dupe::Dupe;
 use indexmap::map::IndexMap;
@@ -47,7 +51,11 @@ pub(crate) struct Scope<'a> {
     unscopes: Vec<Unscope>,
     codemap: CodeMap,
     globals: FrozenRef<Globals>,
+    /// Values to use in place of disabled globals.
+    disabled_globals: &'a HashMap<String, FrozenValue>,
     pub(crate) errors: Vec<anyhow::Error>,
+    /// Names resolved to `globals`.
+    pub(crate) referenced_globals: BTreeSet<String>,
 }
 
 struct UnscopeBinding {
@@ -168,6 +176,7 @@ impl<'a> Scope<'a> {
         mut scope_data: ScopeData,
         code: &mut CstStmt,
         globals: FrozenRef<Globals>,
+        disabled_globals: &'a HashMap<String, FrozenValue>,
         codemap: CodeMap,
     ) -> Self {
         // Not really important, sanity check
@@ -203,7 +212,9 @@ impl<'a> Scope<'a> {
             unscopes: Vec::new(),
             codemap,
             globals,
+            disabled_globals,
             errors: Vec::new(),
+            referenced_globals: BTreeSet::new(),
         };
         scope.resolve_idents(code);
         scope
@@ -375,7 +386,15 @@ impl<'a> Scope<'a> {
                         self.errors.push(self.variable_not_found_err(ident));
                         return;
                     }
-                    Some(v) => ResolvedIdent::Global(v),
+                    Some(v) => {
+                        if !self.referenced_globals.contains(ident.node.as_str()) {
+                            self.referenced_globals.insert(ident.node.clone());
+                        }
+                        match self.disabled_globals.get(ident.node.as_str()) {
+                            Some(disabled) => ResolvedIdent::Global(*disabled),
+                            None => ResolvedIdent::Global(v),
+                        }
+                    }
                 }
             }
             Some(slot) => ResolvedIdent::Slot(slot),
@@ -787,7 +806,7 @@ pub(crate) type CstLoad = AstLoadP<CstPayload>;
 
 #[cfg(test)]
 mod test {
-    use std::fmt::Write;
+    use std::{collections::HashMap, fmt::Write};
 
     use crate::{
         environment::{names::MutableNames, Globals},
@@ -816,6 +835,7 @@ mod test {
             scope_data,
             &mut cst,
             FrozenRef::new(Globals::empty()),
+            &HashMap::new(),
             ast.codemap,
         );
         assert!(scope.errors.is_empty());
@@ -17,23 +17,32 @@
 
 //! Compile function calls.
 
+use std::collections::HashSet;
+
 use crate::{
     codemap::{Span, Spanned},
     collections::symbol_map::Symbol,
-    environment::FrozenModuleRef,
+    environment::Globals,
+    errors::Diagnostic,
     eval::{
         compiler::{
             scope::{CstArgument, CstExpr},
-            Compiler,
+            Compiler, Constants, ScratchEval,
+        },
+        fragment::{
+            compr::ComprCompiled,
+            expr::{compile_time_getattr, ExprCompiledValue, MaybeNot, OptimizeOnFreezeContext},
         },
-        fragment::expr::{ExprCompiledValue, MaybeNot},
         FrozenDef,
     },
-    gazebo::prelude::SliceExt,
-    syntax::ast::{ArgumentP, AstString, ExprP},
+    gazebo::prelude::{Dupe, SliceExt, VecExt},
+    syntax::ast::{ArgumentP, AstLiteral, AstString, ExprP},
     values::{
-        string::interpolation::parse_format_one, AttrType, FrozenStringValue, FrozenValue,
-        ValueLike,
+        dict::Dict,
+        function::NativeFunction,
+        list::List,
+        string::interpolation::{parse_format_many, parse_format_one},
+        AttrType, FrozenHeap, FrozenStringValue, FrozenValue, Value, ValueLike,
     },
 };
 
@@ -54,35 +63,117 @@ pub(crate) enum CallCompiled {
     Call(Box<(Spanned<ExprCompiledValue>, ArgsCompiledValue)>),
     Frozen(Box<(Option<FrozenValue>, FrozenValue, ArgsCompiledValue)>),
     Method(Box<(Spanned<ExprCompiledValue>, Symbol, ArgsCompiledValue)>),
+    /// Method call where the type of `this` is known at compile time,
+    /// so the method is resolved in advance.
+    MethodFrozen(Box<(Spanned<ExprCompiledValue>, FrozenValue, ArgsCompiledValue)>),
+}
+
+impl CallCompiled {
+    /// Call a function known when compiling the call or freezing the module.
+    /// A single positional argument to `type`, `len` or a function like
+    /// `lambda x: type(x) == "y"` is specialized, so it can fold into a constant.
+    /// A builtin without side effects called with constant arguments is evaluated now.
+    pub(crate) fn frozen(
+        span: Span,
+        fun: FrozenValue,
+        args: ArgsCompiledValue,
+        heap: &FrozenHeap,
+        scratch: &ScratchEval,
+    ) -> ExprCompiledValue {
+        if let Some([arg]) = args.pos_only() {
+            let constants = Constants::new();
+            if fun == constants.fn_type {
+                return ExprCompiledValue::typ(arg.clone());
+            } else if fun == constants.fn_len {
+                return ExprCompiledValue::len(arg.clone());
+            } else if let Some(def) = fun.downcast_ref::<FrozenDef>() {
+                if let Some(t) = def.def_info.returns_type_is {
+                    return ExprCompiledValue::type_is(arg.clone(), t, MaybeNot::Id);
+                }
+            }
+        }
+        if let Some(v) = CallCompiled::speculative_exec(fun, &args, heap, scratch) {
+            return ExprCompiledValue::Value(v);
+        }
+        ExprCompiledValue::Call(Spanned {
+            span,
+            node: CallCompiled::Frozen(box (None, fun, args)),
+        })
+    }
+
+    /// Call a builtin marked `#[speculative_exec_safe]` if all the arguments are constants,
+    /// returning the result if it can be stored as a constant on `heap`.
+    /// If the call fails, it is left to fail when evaluated.
+    fn speculative_exec(
+        fun: FrozenValue,
+        args: &ArgsCompiledValue,
+        heap: &FrozenHeap,
+        scratch: &ScratchEval,
+    ) -> Option<FrozenValue> {
+        if !fun
+            .downcast_ref::<NativeFunction>()?
+            .is_speculative_exec_safe()
+        {
+            return None;
+        }
+        let args = args
+            .pos_only()?
+            .iter()
+            .map(|a| Some(a.as_value()?.to_value()))
+            .collect::<Option<Vec<Value>>>()?;
+        scratch.with_eval(|eval| {
+            let res = fun.to_value().invoke_pos(None, &args, eval).ok()?;
+            // Builtins allocate on the heap, so a frozen result is static or one of the
+            // arguments. Of the rest only strings are copied, as a mutable result can't be
+            // shared between calls.
+            match res.unpack_frozen() {
+                Some(res) => Some(res),
+                None => res.unpack_str().map(|s| heap.alloc_str(s)),
+            }
+        })
+    }
 }
 
 impl Spanned<CallCompiled> {
-    pub(crate) fn optimize_on_freeze(&self, module: &FrozenModuleRef) -> ExprCompiledValue {
-        ExprCompiledValue::Call(self.map(|call| match *call {
+    pub(crate) fn optimize_on_freeze(&self, ctx: &OptimizeOnFreezeContext) -> ExprCompiledValue {
+        let call = match self.node {
             CallCompiled::Call(box (ref fun, ref args)) => {
-                let fun = fun.optimize_on_freeze(module);
-                let args = args.optimize_on_freeze(module);
-                if let Spanned {
-                    node: ExprCompiledValue::Value(fun),
-                    ..
-                } = fun
-                {
-                    CallCompiled::Frozen(box (None, fun, args))
-                } else {
-                    CallCompiled::Call(box (fun, args))
+                let fun = fun.optimize_on_freeze(ctx);
+                let args = args.optimize_on_freeze(ctx);
+                if let Some(fun) = fun.as_value() {
+                    // The function only became known on freeze, so specialize the call
+                    // as if it had been known when compiling. This happens before the
+                    // enclosing expression is rebuilt, so any fold it enables there
+                    // (e.g. `my_len(xs) == 0`) also happens in this pass.
+                    return CallCompiled::frozen(self.span, fun, args, ctx.heap, ctx.scratch);
                 }
+                CallCompiled::Call(box (fun, args))
+            }
+            CallCompiled::Frozen(box (None, fun, ref args)) => {
+                // Arguments reading module variables may have become constants.
+                let args = args.optimize_on_freeze(ctx);
+                return CallCompiled::frozen(self.span, fun, args, ctx.heap, ctx.scratch);
             }
             CallCompiled::Frozen(box (this, fun, ref args)) => {
-                let args = args.optimize_on_freeze(module);
+                let args = args.optimize_on_freeze(ctx);
                 CallCompiled::Frozen(box (this, fun, args))
             }
             CallCompiled::Method(box (ref this, ref field, ref args)) => {
-                let this = this.optimize_on_freeze(module);
+                let this = this.optimize_on_freeze(ctx);
                 let field = field.clone();
-                let args = args.optimize_on_freeze(module);
+                let args = args.optimize_on_freeze(ctx);
                 CallCompiled::Method(box (this, field, args))
             }
-        }))
+            CallCompiled::MethodFrozen(box (ref this, fun, ref args)) => {
+                let this = this.optimize_on_freeze(ctx);
+                let args = args.optimize_on_freeze(ctx);
+                CallCompiled::MethodFrozen(box (this, fun, args))
+            }
+        };
+        ExprCompiledValue::Call(Spanned {
+            span: self.span,
+            node: call,
+        })
     }
 }
 
@@ -95,7 +186,7 @@ impl ArgsCompiledValue {
         }
     }
 
-    fn optimize_on_freeze(&self, module: &FrozenModuleRef) -> ArgsCompiledValue {
+    fn optimize_on_freeze(&self, ctx: &OptimizeOnFreezeContext) -> ArgsCompiledValue {
         let ArgsCompiledValue {
             ref pos_named,
             ref names,
@@ -103,35 +194,83 @@ impl ArgsCompiledValue {
             ref kwargs,
         } = *self;
         ArgsCompiledValue {
-            pos_named: pos_named.map(|p| p.optimize_on_freeze(module)),
+            pos_named: pos_named.map(|p| p.optimize_on_freeze(ctx)),
             names: names.clone(),
-            args: args.as_ref().map(|a| a.optimize_on_freeze(module)),
-            kwargs: kwargs.as_ref().map(|a| a.optimize_on_freeze(module)),
+            args: args.as_ref().map(|a| a.optimize_on_freeze(ctx)),
+            kwargs: kwargs.as_ref().map(|a| a.optimize_on_freeze(ctx)),
         }
     }
 }
 
 impl Compiler<'_> {
+    fn arg_named(&mut self, res: &mut ArgsCompiledValue, name: &str, value: CstExpr) {
+        let fv = self.module_env.frozen_heap().alloc_string_value(name);
+        res.names.push((Symbol::new(name), fv));
+        res.pos_named.push(self.expr(value));
+    }
+
+    /// Can `**{...}` be passed as named arguments rather than spread at runtime:
+    /// every key must be a string literal, distinct from the other keys and from the
+    /// names already passed. Otherwise the runtime spread reports any duplicates.
+    fn is_kwargs_literal(
+        names: &[(Symbol, FrozenStringValue)],
+        pairs: &[(CstExpr, CstExpr)],
+    ) -> bool {
+        let mut seen: HashSet<&str> = names.iter().map(|(name, _)| name.as_str()).collect();
+        pairs.iter().all(|(k, _)| match &k.node {
+            ExprP::Literal(AstLiteral::String(k)) => seen.insert(k.node.as_str()),
+            _ => false,
+        })
+    }
+
     fn args(&mut self, args: Vec<CstArgument>) -> ArgsCompiledValue {
         let mut res = ArgsCompiledValue::default();
         for x in args {
             match x.node {
                 ArgumentP::Positional(x) => res.pos_named.push(self.expr(x)),
-                ArgumentP::Named(name, value) => {
-                    let fv = self
-                        .module_env
-                        .frozen_heap()
-                        .alloc_string_value(name.node.as_str());
-                    res.names.push((Symbol::new(&name.node), fv));
-                    res.pos_named.push(self.expr(value));
-                }
+                ArgumentP::Named(name, value) => self.arg_named(&mut res, &name.node, value),
                 ArgumentP::Args(x) => res.args = Some(self.expr(x)),
-                ArgumentP::KwArgs(x) => res.kwargs = Some(self.expr(x)),
+                ArgumentP::KwArgs(x) => match x.node {
+                    // With no `*args` in between, the values are evaluated in the same order
+                    // as named arguments, so avoid building and iterating a dict at call time.
+                    ExprP::Dict(pairs)
+                        if res.args.is_none() && Self::is_kwargs_literal(&res.names, &pairs) =>
+                    {
+                        for (k, v) in pairs {
+                            match k.node {
+                                ExprP::Literal(AstLiteral::String(k)) => {
+                                    self.arg_named(&mut res, &k.node, v)
+                                }
+                                _ => unreachable!(),
+                            }
+                        }
+                    }
+                    node => {
+                        res.kwargs = Some(self.expr(Spanned { node, span: x.span }));
+                    }
+                },
             }
         }
         res
     }
 
+    /// Calls to a `def` known at compile time, e.g. one loaded from another module, with
+    /// the wrong arguments are reported when compiling, rather than when the call runs.
+    /// Calls with `*args` or `**kwargs` can only be checked at runtime.
+    fn check_call_def(&mut self, span: Span, def: &FrozenDef, args: &ArgsCompiledValue) {
+        if args.args.is_some() || args.kwargs.is_some() {
+            return;
+        }
+        let pos = args.pos_named.len() - args.names.len();
+        if let Err(e) = def
+            .parameters
+            .check_call(pos, args.names.iter().map(|(name, _)| name))
+        {
+            self.errors
+                .push(Diagnostic::new(e, span, self.codemap.dupe()));
+        }
+    }
+
     fn expr_call_fun_frozen_no_special(
         &mut self,
         span: Span,
@@ -140,6 +279,15 @@ impl Compiler<'_> {
         args: Vec<CstArgument>,
     ) -> ExprCompiledValue {
         let args = self.args(args);
+        if this.is_none() {
+            if let Some(def) = fun.downcast_ref::<FrozenDef>() {
+                self.check_call_def(span, def, &args);
+            }
+            let heap = self.module_env.frozen_heap();
+            if let Some(v) = CallCompiled::speculative_exec(fun, &args, heap, &self.scratch) {
+                return ExprCompiledValue::Value(v);
+            }
+        }
         ExprCompiledValue::Call(Spanned {
             span,
             node: CallCompiled::Frozen(box (this, fun, args)),
@@ -156,8 +304,7 @@ impl Compiler<'_> {
         if left == self.constants.fn_type && one_positional {
             self.fn_type(args.pop().unwrap().node.into_expr())
         } else if left == self.constants.fn_len && one_positional {
-            let x = self.expr(args.pop().unwrap().node.into_expr());
-            ExprCompiledValue::Len(box x)
+            self.fn_len(args.pop().unwrap().node.into_expr())
         } else {
             if one_positional {
                 // Try to inline a function like `lambda x: type(x) == "y"`.
@@ -174,10 +321,56 @@ impl Compiler<'_> {
                     }
                 }
             }
+            if let Some(def) = left.downcast_ref::<FrozenDef>() {
+                if let Some((nparams, body)) = &def.def_info.inline_body {
+                    if args.len() == *nparams && args.iter().all(|a| a.is_positional()) {
+                        return self.expr_call_inline(span, left, body, args);
+                    }
+                }
+            }
             self.expr_call_fun_frozen_no_special(span, None, left, args)
         }
     }
 
+    /// Call a function with a `DefInfo::inline_body`
+    /// by copying the body, if the arguments are cheap reads which can't have side effects.
+    fn expr_call_inline(
+        &mut self,
+        span: Span,
+        fun: FrozenValue,
+        body: &Spanned<ExprCompiledValue>,
+        args: Vec<CstArgument>,
+    ) -> ExprCompiledValue {
+        let args = args.into_map(|a| self.expr(a.node.into_expr()));
+        let observed = self.compile_context().is_observed();
+        let simple = args.iter().all(|a| {
+            matches!(
+                a.node,
+                ExprCompiledValue::Value(..)
+                    | ExprCompiledValue::Local(..)
+                    | ExprCompiledValue::LocalCaptured(..)
+                    | ExprCompiledValue::Module(..)
+            )
+        });
+        if simple && !observed {
+            let e = body.inline(span, &mut |slot| args.get(slot.0 as usize).cloned());
+            if let Some(e) = e {
+                return e.node;
+            }
+        }
+        ExprCompiledValue::Call(Spanned {
+            span,
+            node: CallCompiled::Frozen(box (
+                None,
+                fun,
+                ArgsCompiledValue {
+                    pos_named: args,
+                    ..ArgsCompiledValue::default()
+                },
+            )),
+        })
+    }
+
     fn expr_call_fun_compiled(
         &mut self,
         span: Span,
@@ -195,6 +388,28 @@ impl Compiler<'_> {
         }
     }
 
+    /// Methods of the value of `e`, if its type is known from the shape of the expression,
+    /// e.g. `[f(x) for x in xs]` is always a list. Only literals, comprehensions and string
+    /// formatting are recognised: there is no type inference, so a local variable is never
+    /// known to have a type, even if it is only ever assigned a list.
+    fn known_type_methods(&self, e: &ExprCompiledValue) -> Option<&'static Globals> {
+        match e {
+            ExprCompiledValue::List(..) | ExprCompiledValue::Compr(ComprCompiled::List(..)) => {
+                Some(List::get_methods_static())
+            }
+            ExprCompiledValue::Dict(..) | ExprCompiledValue::Compr(ComprCompiled::Dict(..)) => {
+                Some(Dict::get_methods_static())
+            }
+            ExprCompiledValue::Type(..)
+            | ExprCompiledValue::PercentSOne(..)
+            | ExprCompiledValue::FormatOne(..)
+            | ExprCompiledValue::FormatMany(..) => {
+                Value::new_empty_string().get_ref().get_methods()
+            }
+            _ => None,
+        }
+    }
+
     fn expr_call_method(
         &mut self,
         span: Span,
@@ -221,11 +436,32 @@ impl Compiler<'_> {
                     }
                 }
             }
+
+            // Optimize `"aaa{}bbb{}ccc".format(x, y)`.
+            // Other argument counts are left to `format` itself to report.
+            if s.node == "format"
+                && args.len() >= 2
+                && args
+                    .iter()
+                    .all(|a| matches!(a.node, ArgumentP::Positional(..)))
+            {
+                if let Some(segments) = parse_format_many(&e) {
+                    if segments.len() == args.len() + 1 {
+                        let segments =
+                            segments.map(|s| self.module_env.frozen_heap().alloc_string_value(s));
+                        let args = args.into_map(|a| match a.node {
+                            ArgumentP::Positional(arg) => self.expr(arg),
+                            _ => unreachable!(),
+                        });
+                        return ExprCompiledValue::FormatMany(box (segments, args));
+                    }
+                }
+            }
         }
 
         let s = Symbol::new(&s.node);
         if let Some(e) = e.as_value() {
-            if let Some((at, fun)) = self.compile_time_getattr(e, &s) {
+            if let Some((at, fun)) = compile_time_getattr(e, &s, self.module_env.heap()) {
                 let this = match at {
                     AttrType::Field => None,
                     AttrType::Method => Some(e),
@@ -233,6 +469,16 @@ impl Compiler<'_> {
                 return self.expr_call_fun_frozen_no_special(span, this, fun, args);
             }
         }
+        if let Some(fun) = self
+            .known_type_methods(&e.node)
+            .and_then(|methods| methods.get_frozen_symbol(&s))
+        {
+            let args = self.args(args);
+            return ExprCompiledValue::Call(Spanned {
+                span,
+                node: CallCompiled::MethodFrozen(box (e, fun, args)),
+            });
+        }
         let args = self.args(args);
         ExprCompiledValue::Call(Spanned {
             span,
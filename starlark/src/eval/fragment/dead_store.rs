@@ -0,0 +1,271 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Remove assignments to local variables which are never read.
+//!
+//! The analysis is flow-insensitive: a slot is live if it is read anywhere
+//! in the function body, so a store is only dropped when no code at all can
+//! observe it. Captured slots are always kept, because nested functions
+//! read them through the captured cell, which is not visible in this body.
+
+use crate::{
+    codemap::Spanned,
+    eval::{
+        compiler::scope::Captured,
+        fragment::{
+            call::{ArgsCompiledValue, CallCompiled},
+            compr::{ClauseCompiled, ComprCompiled},
+            expr::ExprCompiledValue,
+            stmt::{AssignCompiledValue, AssignModifyLhs, StmtCompiledValue, StmtsCompiled},
+        },
+        runtime::slots::LocalSlotId,
+    },
+};
+
+/// Local slots read by a function body.
+#[derive(Default)]
+struct LocalReads(Vec<bool>);
+
+impl LocalReads {
+    fn read(&mut self, slot: LocalSlotId) {
+        let i = slot.0 as usize;
+        if self.0.len() <= i {
+            self.0.resize(i + 1, false);
+        }
+        self.0[i] = true;
+    }
+
+    fn is_read(&self, slot: LocalSlotId) -> bool {
+        self.0.get(slot.0 as usize).copied().unwrap_or(false)
+    }
+}
+
+impl Spanned<ExprCompiledValue> {
+    fn collect_reads(&self, reads: &mut LocalReads) {
+        match self.node {
+            ExprCompiledValue::Value(..) | ExprCompiledValue::Module(..) => {}
+            ExprCompiledValue::Local(slot) | ExprCompiledValue::LocalCaptured(slot) => {
+                reads.read(slot)
+            }
+            ExprCompiledValue::Type(box ref x)
+            | ExprCompiledValue::Len(box ref x)
+            | ExprCompiledValue::TypeIs(box ref x, ..)
+            | ExprCompiledValue::Dot(box ref x, _)
+            | ExprCompiledValue::Not(box ref x)
+            | ExprCompiledValue::Minus(box ref x)
+            | ExprCompiledValue::Plus(box ref x)
+            | ExprCompiledValue::BitNot(box ref x)
+            | ExprCompiledValue::PercentSOne(box (_, ref x, _))
+            | ExprCompiledValue::FormatOne(box (_, ref x, _)) => x.collect_reads(reads),
+            ExprCompiledValue::Equals(box (ref l, ref r), _)
+            | ExprCompiledValue::Compare(box (ref l, ref r), _)
+            | ExprCompiledValue::ArrayIndirection(box (ref l, ref r))
+            | ExprCompiledValue::And(box (ref l, ref r))
+            | ExprCompiledValue::Or(box (ref l, ref r))
+            | ExprCompiledValue::Op(_, box (ref l, ref r)) => {
+                l.collect_reads(reads);
+                r.collect_reads(reads);
+            }
+            ExprCompiledValue::Tuple(ref xs)
+            | ExprCompiledValue::List(ref xs)
+            | ExprCompiledValue::FormatMany(box (_, ref xs)) => {
+                for x in xs {
+                    x.collect_reads(reads);
+                }
+            }
+            ExprCompiledValue::Dict(ref xs) => {
+                for (k, v) in xs {
+                    k.collect_reads(reads);
+                    v.collect_reads(reads);
+                }
+            }
+            ExprCompiledValue::Compr(ref compr) => compr.collect_reads(reads),
+            ExprCompiledValue::If(box (ref c, ref t, ref f)) => {
+                c.collect_reads(reads);
+                t.collect_reads(reads);
+                f.collect_reads(reads);
+            }
+            ExprCompiledValue::Slice(box (ref v, ref start, ref stop, ref step)) => {
+                v.collect_reads(reads);
+                for x in [start, stop, step].iter().copied().flatten() {
+                    x.collect_reads(reads);
+                }
+            }
+            ExprCompiledValue::Call(ref call) => match call.node {
+                CallCompiled::Call(box (ref fun, ref args)) => {
+                    fun.collect_reads(reads);
+                    args.collect_reads(reads);
+                }
+                CallCompiled::Frozen(box (_, _, ref args)) => args.collect_reads(reads),
+                CallCompiled::Method(box (ref this, _, ref args))
+                | CallCompiled::MethodFrozen(box (ref this, _, ref args)) => {
+                    this.collect_reads(reads);
+                    args.collect_reads(reads);
+                }
+            },
+            ExprCompiledValue::Def(ref def) => {
+                // The body of a nested def only sees captured slots,
+                // but parameter defaults and types are evaluated here.
+                for p in &def.params {
+                    p.node.map_expr(|x| x.collect_reads(reads));
+                }
+                if let Some(box ref t) = def.return_type {
+                    t.collect_reads(reads);
+                }
+            }
+        }
+    }
+}
+
+impl ArgsCompiledValue {
+    fn collect_reads(&self, reads: &mut LocalReads) {
+        for x in &self.pos_named {
+            x.collect_reads(reads);
+        }
+        for x in self.args.iter().chain(self.kwargs.iter()) {
+            x.collect_reads(reads);
+        }
+    }
+}
+
+impl ComprCompiled {
+    fn collect_reads(&self, reads: &mut LocalReads) {
+        let clauses = match *self {
+            ComprCompiled::List(box ref x, ref clauses) => {
+                x.collect_reads(reads);
+                clauses
+            }
+            ComprCompiled::Dict(box (ref k, ref v), ref clauses) => {
+                k.collect_reads(reads);
+                v.collect_reads(reads);
+                clauses
+            }
+        };
+        for ClauseCompiled { var, over, ifs, .. } in clauses {
+            var.collect_reads(reads);
+            over.collect_reads(reads);
+            for x in ifs {
+                x.collect_reads(reads);
+            }
+        }
+    }
+}
+
+impl Spanned<AssignCompiledValue> {
+    /// Reads performed by the assignment target itself, e.g. `x` in `x.f = 1`.
+    fn collect_reads(&self, reads: &mut LocalReads) {
+        match self.node {
+            AssignCompiledValue::Dot(ref x, _) => x.collect_reads(reads),
+            AssignCompiledValue::ArrayIndirection(ref x, ref i) => {
+                x.collect_reads(reads);
+                i.collect_reads(reads);
+            }
+            AssignCompiledValue::Tuple(ref xs) => {
+                for x in xs {
+                    x.collect_reads(reads);
+                }
+            }
+            AssignCompiledValue::Local(..) | AssignCompiledValue::Module(..) => {}
+        }
+    }
+}
+
+impl StmtsCompiled {
+    fn collect_reads(&self, reads: &mut LocalReads) {
+        for stmt in self.stmts() {
+            match stmt.node {
+                StmtCompiledValue::PossibleGc
+                | StmtCompiledValue::Break
+                | StmtCompiledValue::Continue
+                | StmtCompiledValue::Return(None) => {}
+                StmtCompiledValue::Return(Some(ref x)) | StmtCompiledValue::Expr(ref x) => {
+                    x.collect_reads(reads)
+                }
+                StmtCompiledValue::Assign(ref lhs, ref rhs) => {
+                    lhs.collect_reads(reads);
+                    rhs.collect_reads(reads);
+                }
+                StmtCompiledValue::AssignModify(ref lhs, _, ref rhs) => {
+                    match lhs {
+                        AssignModifyLhs::Dot(x, _) => x.collect_reads(reads),
+                        AssignModifyLhs::Array(x, i) => {
+                            x.collect_reads(reads);
+                            i.collect_reads(reads);
+                        }
+                        AssignModifyLhs::Local(slot) => reads.read(slot.node.0),
+                        AssignModifyLhs::Module(..) => {}
+                    }
+                    rhs.collect_reads(reads);
+                }
+                StmtCompiledValue::If(box (ref c, ref t, ref f)) => {
+                    c.collect_reads(reads);
+                    t.collect_reads(reads);
+                    f.collect_reads(reads);
+                }
+                StmtCompiledValue::For(box (ref var, ref over, ref body)) => {
+                    var.collect_reads(reads);
+                    over.collect_reads(reads);
+                    body.collect_reads(reads);
+                }
+            }
+        }
+    }
+
+    fn drop_dead_stores(&self, reads: &LocalReads) -> StmtsCompiled {
+        let mut stmts = StmtsCompiled::empty();
+        for stmt in self.stmts() {
+            let span = stmt.span;
+            stmts.extend(match stmt.node {
+                StmtCompiledValue::Assign(
+                    Spanned {
+                        node: AssignCompiledValue::Local(slot, Captured::No),
+                        ..
+                    },
+                    ref rhs,
+                ) if !reads.is_read(slot) => Spanned::<StmtCompiledValue>::expr(rhs.clone()),
+                StmtCompiledValue::If(box (ref c, ref t, ref f)) => StmtsCompiled::one(Spanned {
+                    span,
+                    node: StmtCompiledValue::If(box (
+                        c.clone(),
+                        t.drop_dead_stores(reads),
+                        f.drop_dead_stores(reads),
+                    )),
+                }),
+                StmtCompiledValue::For(box (ref var, ref over, ref body)) => {
+                    StmtsCompiled::one(Spanned {
+                        span,
+                        node: StmtCompiledValue::For(box (
+                            var.clone(),
+                            over.clone(),
+                            body.drop_dead_stores(reads),
+                        )),
+                    })
+                }
+                _ => StmtsCompiled::one(stmt.clone()),
+            });
+        }
+        stmts
+    }
+
+    /// Replace `x = <expr>` with `<expr>` for every non-captured local `x`
+    /// which is never read in this function body.
+    pub(crate) fn eliminate_dead_stores(&self) -> StmtsCompiled {
+        let mut reads = LocalReads::default();
+        self.collect_reads(&mut reads);
+        self.drop_dead_stores(&reads)
+    }
+}
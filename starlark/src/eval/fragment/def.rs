@@ -19,6 +19,7 @@
 
 use std::{
     cell::UnsafeCell,
+    cmp::Ordering,
     collections::HashMap,
     fmt::{self, Display},
     mem, ptr,
@@ -31,17 +32,18 @@ use once_cell::sync::Lazy;
 
 use crate::{
     codemap::{CodeMap, Span, Spanned},
-    environment::{FrozenModuleRef, Globals},
+    environment::{slots::ModuleSlotId, FrozenModuleRef, Globals},
     eval::{
         bc::bytecode::Bc,
         compiler::{
             scope::{
                 Captured, CstAssignIdent, CstExpr, CstParameter, CstStmt, ScopeId, ScopeNames,
             },
-            Compiler, EvalException,
+            Compiler, EvalException, ScratchEval,
         },
         fragment::{
-            expr::{ExprCompiledValue, MaybeNot},
+            call::{ArgsCompiledValue, CallCompiled},
+            expr::{ExprCompiledValue, MaybeNot, OptimizeOnFreezeContext},
             stmt::{StmtCompileContext, StmtCompiledValue, StmtsCompiled},
         },
         runtime::{
@@ -57,8 +59,8 @@ use crate::{
         docs::{DocItem, DocString},
         function::FUNCTION_TYPE,
         typing::TypeCompiled,
-        AtomicFrozenRefOption, Freeze, Freezer, FrozenRef, FrozenStringValue, FrozenValue,
-        StarlarkValue, Trace, Tracer, Value, ValueLike,
+        AtomicFrozenRefOption, Freeze, Freezer, FrozenHeap, FrozenRef, FrozenStringValue,
+        FrozenValue, StarlarkValue, Trace, Tracer, Value, ValueLike,
     },
 };
 
@@ -158,6 +160,14 @@ impl<T> ParameterCompiled<T> {
     }
 }
 
+/// The function called by a `def` which only forwards its arguments,
+/// see [`DefInfo::forwards_args`].
+#[derive(Debug, Clone, Copy, Dupe)]
+pub(crate) enum ForwardTarget {
+    Value(FrozenValue),
+    Module(ModuleSlotId),
+}
+
 /// Static info for `def`, `lambda` or module.
 #[derive(Derivative, Display)]
 #[derivative(Debug)]
@@ -179,6 +189,13 @@ pub(crate) struct DefInfo {
     stmt_compile_context: StmtCompileContext,
     /// Function body is `type(x) == "y"`
     pub(crate) returns_type_is: Option<FrozenStringValue>,
+    /// Function is `def f(x, y): return <expr>` with `<expr>` simple enough to be
+    /// copied to the call site, see `Spanned::<ExprCompiledValue>::inline`.
+    /// Stores the number of parameters and `<expr>`.
+    pub(crate) inline_body: Option<(usize, Spanned<ExprCompiledValue>)>,
+    /// Function is `def f(*args, **kwargs): return g(*args, **kwargs)`,
+    /// so the arguments can be passed straight to `g` without collecting them.
+    pub(crate) forwards_args: Option<Spanned<ForwardTarget>>,
     /// Globals captured during function or module creation.
     /// Only needed for debugger evaluation.
     pub(crate) globals: FrozenRef<Globals>,
@@ -194,6 +211,8 @@ impl DefInfo {
             body_stmts: StmtsCompiled::empty(),
             stmt_compile_context: StmtCompileContext::default(),
             returns_type_is: None,
+            inline_body: None,
+            forwards_args: None,
             globals: FrozenRef::new(Globals::empty()),
         });
         FrozenRef::new(&EMPTY)
@@ -212,6 +231,8 @@ impl DefInfo {
             body_stmts: StmtsCompiled::empty(),
             stmt_compile_context: StmtCompileContext::default(),
             returns_type_is: None,
+            inline_body: None,
+            forwards_args: None,
             globals,
         }
     }
@@ -281,6 +302,102 @@ impl Compiler<'_> {
         }
     }
 
+    /// If a function is `def f(x, y): return <expr>`, where the parameters have no
+    /// defaults or types and are each used by `<expr>`, in order, and `<expr>` can't fail,
+    /// return `<expr>`.
+    fn is_inline_body(
+        params: &[Spanned<ParameterCompiled<Spanned<ExprCompiledValue>>>],
+        stmt: &StmtsCompiled,
+    ) -> Option<(usize, Spanned<ExprCompiledValue>)> {
+        for p in params {
+            match &p.node {
+                ParameterCompiled::Normal(name, None) if matches!(name.captured, Captured::No) => {}
+                _ => return None,
+            }
+        }
+        let body = match stmt.first().map(|s| &s.node) {
+            Some(StmtCompiledValue::Return(Some(body))) => body,
+            _ => return None,
+        };
+        // Slots `0..params.len()` are the slots for the parameters.
+        // Each parameter must first be read in order, so the arguments are read
+        // in the same order as a call, and an unbound variable fails the same way.
+        // An unused parameter would mean its argument is never evaluated.
+        let mut used = 0;
+        body.inline(body.span, &mut |slot| {
+            match (slot.0 as usize).cmp(&used) {
+                Ordering::Less => {}
+                Ordering::Equal if used < params.len() => used += 1,
+                _ => return None,
+            }
+            Some(Spanned {
+                node: ExprCompiledValue::Local(slot),
+                span: body.span,
+            })
+        })?;
+        if used == params.len() {
+            Some((params.len(), body.clone()))
+        } else {
+            None
+        }
+    }
+
+    /// If a function is `def f(*args, **kwargs): return g(*args, **kwargs)`,
+    /// where `g` is a global or module variable, return `g`.
+    fn is_forward_args(
+        params: &[Spanned<ParameterCompiled<Spanned<ExprCompiledValue>>>],
+        stmt: &StmtsCompiled,
+    ) -> Option<Spanned<ForwardTarget>> {
+        match params {
+            [Spanned {
+                node: ParameterCompiled::Args(args, None),
+                ..
+            }, Spanned {
+                node: ParameterCompiled::KwArgs(kwargs, None),
+                ..
+            }] if matches!(args.captured, Captured::No)
+                && matches!(kwargs.captured, Captured::No) => {}
+            _ => return None,
+        }
+        let call = match stmt.first().map(|s| &s.node) {
+            Some(StmtCompiledValue::Return(Some(Spanned {
+                node: ExprCompiledValue::Call(call),
+                ..
+            }))) => call,
+            _ => return None,
+        };
+        let (target, args) = match call.node {
+            CallCompiled::Call(box (ref fun, ref args)) => match fun.node {
+                ExprCompiledValue::Value(v) => (ForwardTarget::Value(v), args),
+                ExprCompiledValue::Module(slot) => (ForwardTarget::Module(slot), args),
+                _ => return None,
+            },
+            CallCompiled::Frozen(box (None, fun, ref args)) => (ForwardTarget::Value(fun), args),
+            _ => return None,
+        };
+        match args {
+            // Slots 0 and 1 are the slots for `*args` and `**kwargs`.
+            ArgsCompiledValue {
+                pos_named,
+                names,
+                args:
+                    Some(Spanned {
+                        node: ExprCompiledValue::Local(LocalSlotId(0)),
+                        ..
+                    }),
+                kwargs:
+                    Some(Spanned {
+                        node: ExprCompiledValue::Local(LocalSlotId(1)),
+                        ..
+                    }),
+            } if pos_named.is_empty() && names.is_empty() => Some(Spanned {
+                span: call.span,
+                node: target,
+            }),
+            _ => None,
+        }
+    }
+
     pub fn function(
         &mut self,
         name: &str,
@@ -311,14 +428,36 @@ impl Compiler<'_> {
             None
         };
 
+        let context = self.compile_context();
+        // Forwarding and inlining skip the body, so can't be used if anything observes it running.
+        let observed = context.is_observed();
+        let forwards_args = if return_type.is_none() && !observed {
+            Self::is_forward_args(&params, &body)
+        } else {
+            None
+        };
+        let inline_body = if return_type.is_none() && !observed {
+            Self::is_inline_body(&params, &body)
+        } else {
+            None
+        };
+
+        // Each parameter but `*` has a slot, from zero.
+        let param_count = params
+            .iter()
+            .filter(|p| !matches!(p.node, ParameterCompiled::NoArgs))
+            .count() as u32;
+
         let info = self.module_env.frozen_heap().alloc_any(DefInfo {
             codemap: self.codemap.dupe(),
             docstring,
             scope_names,
-            stmt_compiled: body.as_bc(&self.compile_context()),
+            stmt_compiled: body.as_bc(&context, param_count),
             body_stmts: body,
             returns_type_is,
-            stmt_compile_context: self.compile_context(),
+            inline_body,
+            forwards_args,
+            stmt_compile_context: context,
             globals: self.globals,
         });
 
@@ -336,8 +475,8 @@ impl Compiler<'_> {
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub(crate) struct DefGen<V> {
-    parameters: ParametersSpec<V>, // The parameters, **kwargs etc including defaults (which are evaluated afresh each time)
-    parameter_captures: Vec<u32>,  // Indices of parameters, which are captured in nested defs
+    pub(crate) parameters: ParametersSpec<V>, // The parameters, **kwargs etc including defaults (which are evaluated afresh each time)
+    parameter_captures: Vec<u32>, // Indices of parameters, which are captured in nested defs
     parameter_types: Vec<(u32, String, V, TypeCompiled)>, // The types of the parameters (sparse indexed array, (0, argm T) implies parameter 0 named arg must have type T)
     return_type: Option<(V, TypeCompiled)>, // The return type annotation for the function
     pub(crate) def_info: FrozenRef<DefInfo>, // The source code and metadata for this function
@@ -565,6 +704,11 @@ where
         args: Arguments<'v, '_>,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>> {
+        if let Some(target) = self.def_info.forwards_args {
+            return eval.with_call_stack(me, location, |eval| {
+                self.invoke_forward_args(target, args, eval)
+            });
+        }
         let local_slots = self.def_info.scope_names.used.len() as u32;
         let slot_base = eval.local_variables.reserve(local_slots);
         let slots = eval.local_variables.get_slots_at(slot_base);
@@ -589,6 +733,21 @@ where
         }
     }
 
+    fn invoke_forward_args(
+        &self,
+        target: Spanned<ForwardTarget>,
+        args: Arguments<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        eval.with_function_context(self.module.load_relaxed(), self.def_info, |eval| {
+            let fun = match target.node {
+                ForwardTarget::Value(v) => v.to_value(),
+                ForwardTarget::Module(slot) => eval.get_slot_module(slot)?,
+            };
+            fun.invoke(Some(target.span), Arguments { this: None, ..args }, eval)
+        })
+    }
+
     fn invoke_raw(
         &self,
         locals: LocalSlotBase,
@@ -655,7 +814,12 @@ where
 }
 
 impl FrozenDef {
-    pub(crate) fn post_freeze(&self, module: FrozenRef<FrozenModuleRef>) {
+    pub(crate) fn post_freeze(
+        &self,
+        module: FrozenRef<FrozenModuleRef>,
+        heap: &FrozenHeap,
+        scratch: &ScratchEval,
+    ) {
         // Module passed to this function is not always module where the function is declared:
         // A function can be created in a frozen module and frozen later in another module.
         // `def_module` variable contains a module where this `def` is declared.
@@ -669,11 +833,21 @@ impl FrozenDef {
 
         // Now perform the optimization of function body with fully frozen module:
         // all module variables are frozen, so we can inline more aggressively.
-        let body_optimized = self
+        let context = &self.def_info.stmt_compile_context;
+        let mut body = self
             .def_info
             .body_stmts
-            .optimize_on_freeze(def_module.as_ref())
-            .as_bc(&self.def_info.stmt_compile_context);
+            .optimize_on_freeze(&OptimizeOnFreezeContext {
+                module: def_module.as_ref(),
+                heap,
+                scratch,
+            });
+        // Optimization may have removed the last read of some variables,
+        // and dropped stores would be visible to a debugger or profiler.
+        if !context.is_observed() {
+            body = body.eliminate_dead_stores();
+        }
+        let body_optimized = body.as_bc(context, self.parameters.len() as u32);
 
         // Store the optimized body.
         // This is (relatively) safe because we know that during freeze
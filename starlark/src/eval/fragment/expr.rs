@@ -29,7 +29,7 @@ use crate::{
     eval::{
         compiler::{
             scope::{AssignCount, Captured, CstExpr, ResolvedIdent, Slot},
-            Compiler,
+            Compiler, ScratchEval,
         },
         fragment::{
             call::CallCompiled, compr::ComprCompiled, def::DefCompiled, known::list_to_tuple,
@@ -38,8 +38,8 @@ use crate::{
     },
     syntax::ast::{AstExprP, AstLiteral, AstPayload, AstString, BinOp, ExprP, StmtP},
     values::{
-        string::interpolation::parse_percent_s_one, AttrType, FrozenHeap, FrozenStringValue,
-        FrozenValue, Heap, Value, ValueError, ValueLike,
+        string::interpolation::{parse_percent_s_many, parse_percent_s_one},
+        AttrType, FrozenHeap, FrozenStringValue, FrozenValue, Heap, Value, ValueError, ValueLike,
     },
 };
 
@@ -103,6 +103,31 @@ pub(crate) enum ExprBinOp {
     RightShift,
 }
 
+impl ExprBinOp {
+    /// Apply an arithmetic or bitwise operator the way the bytecode does,
+    /// or [`None`] for the other operators.
+    fn eval<'v>(
+        self,
+        l: Value<'v>,
+        r: Value<'v>,
+        heap: &'v Heap,
+    ) -> Option<anyhow::Result<Value<'v>>> {
+        Some(match self {
+            ExprBinOp::Sub => l.sub(r, heap),
+            ExprBinOp::Add => l.add(r, heap),
+            ExprBinOp::Multiply => l.mul(r, heap),
+            ExprBinOp::Percent => l.percent(r, heap),
+            ExprBinOp::FloorDivide => l.floor_div(r, heap),
+            ExprBinOp::BitAnd => l.bit_and(r),
+            ExprBinOp::BitOr => l.bit_or(r),
+            ExprBinOp::BitXor => l.bit_xor(r),
+            ExprBinOp::LeftShift => l.left_shift(r),
+            ExprBinOp::RightShift => l.right_shift(r),
+            ExprBinOp::In | ExprBinOp::NotIn | ExprBinOp::Divide => return None,
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) enum ExprCompiledValue {
     Value(FrozenValue),
@@ -175,6 +200,9 @@ pub(crate) enum ExprCompiledValue {
             FrozenStringValue,
         )>,
     ),
+    /// `"aaa{}bbb{}ccc".format(x, y)` or `"aaa%sbbb%sccc" % (x, y)`,
+    /// with one more segment than args.
+    FormatMany(Box<(Vec<FrozenStringValue>, Vec<Spanned<ExprCompiledValue>>)>),
     Call(Spanned<CallCompiled>),
     Def(DefCompiled),
 }
@@ -193,10 +221,20 @@ impl ExprCompiledValue {
     }
 }
 
+/// What `optimize_on_freeze` has access to when a `def` body is optimized.
+pub(crate) struct OptimizeOnFreezeContext<'a> {
+    /// The module the `def` was declared in, now frozen.
+    pub(crate) module: &'a FrozenModuleRef,
+    /// The heap the `def` is frozen into, which owns values created while optimizing.
+    pub(crate) heap: &'a FrozenHeap,
+    /// Shared by all the `def`s frozen with the module.
+    pub(crate) scratch: &'a ScratchEval,
+}
+
 impl Spanned<ExprCompiledValue> {
     pub(crate) fn optimize_on_freeze(
         &self,
-        module: &FrozenModuleRef,
+        ctx: &OptimizeOnFreezeContext,
     ) -> Spanned<ExprCompiledValue> {
         let span = self.span;
         let expr = match self.node {
@@ -204,7 +242,7 @@ impl Spanned<ExprCompiledValue> {
             | ExprCompiledValue::Local(..)
             | ExprCompiledValue::LocalCaptured(..)) => e.clone(),
             ExprCompiledValue::Module(slot) => {
-                match module.get_module_data().get_slot(slot) {
+                match ctx.module.get_module_data().get_slot(slot) {
                     None => {
                         // Let if fail at runtime.
                         ExprCompiledValue::Module(slot)
@@ -213,97 +251,139 @@ impl Spanned<ExprCompiledValue> {
                 }
             }
             ExprCompiledValue::Equals(box (ref l, ref r), maybe_not) => {
-                let l = l.optimize_on_freeze(module);
-                let r = r.optimize_on_freeze(module);
+                let l = l.optimize_on_freeze(ctx);
+                let r = r.optimize_on_freeze(ctx);
                 eval_equals(l, r, maybe_not)
             }
             ExprCompiledValue::Compare(box (ref l, ref r), cmp) => {
-                let l = l.optimize_on_freeze(module);
-                let r = r.optimize_on_freeze(module);
+                let l = l.optimize_on_freeze(ctx);
+                let r = r.optimize_on_freeze(ctx);
                 ExprCompiledValue::Compare(box (l, r), cmp)
             }
             ExprCompiledValue::Type(box ref e) => {
-                ExprCompiledValue::Type(box e.optimize_on_freeze(module))
+                ExprCompiledValue::typ(e.optimize_on_freeze(ctx))
             }
             ExprCompiledValue::Len(box ref e) => {
-                ExprCompiledValue::Len(box e.optimize_on_freeze(module))
+                ExprCompiledValue::len(e.optimize_on_freeze(ctx))
             }
             ExprCompiledValue::TypeIs(box ref e, t, maybe_not) => {
-                ExprCompiledValue::TypeIs(box e.optimize_on_freeze(module), t, maybe_not)
+                ExprCompiledValue::type_is(e.optimize_on_freeze(ctx), t, maybe_not)
             }
             ExprCompiledValue::Tuple(ref xs) => {
-                ExprCompiledValue::Tuple(xs.map(|e| e.optimize_on_freeze(module)))
+                ExprCompiledValue::Tuple(xs.map(|e| e.optimize_on_freeze(ctx)))
             }
             ExprCompiledValue::List(ref xs) => {
-                ExprCompiledValue::List(xs.map(|e| e.optimize_on_freeze(module)))
+                ExprCompiledValue::List(xs.map(|e| e.optimize_on_freeze(ctx)))
             }
             ExprCompiledValue::Dict(ref kvs) => ExprCompiledValue::Dict(
-                kvs.map(|(k, v)| (k.optimize_on_freeze(module), v.optimize_on_freeze(module))),
+                kvs.map(|(k, v)| (k.optimize_on_freeze(ctx), v.optimize_on_freeze(ctx))),
             ),
-            ExprCompiledValue::Compr(ref compr) => compr.optimize_on_freeze(module),
+            ExprCompiledValue::Compr(ref compr) => compr.optimize_on_freeze(ctx),
             ExprCompiledValue::Dot(box ref object, ref field) => {
-                ExprCompiledValue::Dot(box object.optimize_on_freeze(module), field.clone())
+                // Only frozen fields are taken, so nothing is kept from the scratch heap.
+                ExprCompiledValue::dot(object.optimize_on_freeze(ctx), field, ctx.scratch.heap())
             }
             ExprCompiledValue::ArrayIndirection(box (ref array, ref index)) => {
-                let array = array.optimize_on_freeze(module);
-                let index = index.optimize_on_freeze(module);
+                let array = array.optimize_on_freeze(ctx);
+                let index = index.optimize_on_freeze(ctx);
                 ExprCompiledValue::ArrayIndirection(box (array, index))
             }
             ExprCompiledValue::If(box (ref cond, ref t, ref f)) => {
-                let cond = cond.optimize_on_freeze(module);
-                let t = t.optimize_on_freeze(module);
-                let f = f.optimize_on_freeze(module);
+                let cond = cond.optimize_on_freeze(ctx);
+                let t = t.optimize_on_freeze(ctx);
+                let f = f.optimize_on_freeze(ctx);
                 return ExprCompiledValue::if_expr(cond, t, f);
             }
             ExprCompiledValue::Slice(box (ref v, ref start, ref stop, ref step)) => {
-                let v = v.optimize_on_freeze(module);
-                let start = start.as_ref().map(|x| x.optimize_on_freeze(module));
-                let stop = stop.as_ref().map(|x| x.optimize_on_freeze(module));
-                let step = step.as_ref().map(|x| x.optimize_on_freeze(module));
+                let v = v.optimize_on_freeze(ctx);
+                let start = start.as_ref().map(|x| x.optimize_on_freeze(ctx));
+                let stop = stop.as_ref().map(|x| x.optimize_on_freeze(ctx));
+                let step = step.as_ref().map(|x| x.optimize_on_freeze(ctx));
                 ExprCompiledValue::Slice(box (v, start, stop, step))
             }
             ExprCompiledValue::Not(box ref e) => {
-                let e = e.optimize_on_freeze(module);
+                let e = e.optimize_on_freeze(ctx);
                 return ExprCompiledValue::not(span, e);
             }
             ExprCompiledValue::Minus(box ref e) => {
-                let e = e.optimize_on_freeze(module);
+                let e = e.optimize_on_freeze(ctx);
                 ExprCompiledValue::minus(e)
             }
             ExprCompiledValue::Plus(box ref e) => {
-                ExprCompiledValue::Plus(box e.optimize_on_freeze(module))
+                ExprCompiledValue::Plus(box e.optimize_on_freeze(ctx))
             }
             ExprCompiledValue::BitNot(box ref e) => {
-                ExprCompiledValue::BitNot(box e.optimize_on_freeze(module))
+                ExprCompiledValue::BitNot(box e.optimize_on_freeze(ctx))
             }
             ExprCompiledValue::And(box (ref l, ref r)) => {
-                let l = l.optimize_on_freeze(module);
-                let r = r.optimize_on_freeze(module);
+                let l = l.optimize_on_freeze(ctx);
+                let r = r.optimize_on_freeze(ctx);
                 return ExprCompiledValue::and(l, r);
             }
             ExprCompiledValue::Or(box (ref l, ref r)) => {
-                let l = l.optimize_on_freeze(module);
-                let r = r.optimize_on_freeze(module);
+                let l = l.optimize_on_freeze(ctx);
+                let r = r.optimize_on_freeze(ctx);
                 return ExprCompiledValue::or(l, r);
             }
             ExprCompiledValue::Op(op, box (ref l, ref r)) => {
-                let l = l.optimize_on_freeze(module);
-                let r = r.optimize_on_freeze(module);
-                ExprCompiledValue::Op(op, box (l, r))
+                let l = l.optimize_on_freeze(ctx);
+                let r = r.optimize_on_freeze(ctx);
+                ExprCompiledValue::op(op, l, r, ctx.scratch.heap())
             }
             ExprCompiledValue::PercentSOne(box (before, ref arg, after)) => {
-                let arg = arg.optimize_on_freeze(module);
+                let arg = arg.optimize_on_freeze(ctx);
                 ExprCompiledValue::PercentSOne(box (before, arg, after))
             }
             ExprCompiledValue::FormatOne(box (before, ref arg, after)) => {
-                let arg = arg.optimize_on_freeze(module);
+                let arg = arg.optimize_on_freeze(ctx);
                 ExprCompiledValue::FormatOne(box (before, arg, after))
             }
+            ExprCompiledValue::FormatMany(box (ref segments, ref args)) => {
+                let args = args.map(|a| a.optimize_on_freeze(ctx));
+                ExprCompiledValue::FormatMany(box (segments.clone(), args))
+            }
             ref d @ ExprCompiledValue::Def(..) => d.clone(),
-            ExprCompiledValue::Call(ref call) => call.optimize_on_freeze(module),
+            ExprCompiledValue::Call(ref call) => call.optimize_on_freeze(ctx),
         };
         Spanned { node: expr, span }
     }
+
+    /// Copy the body of an inlined function to a call site at `span`,
+    /// replacing each read of a local with `local(slot)`.
+    ///
+    /// Returns `None` if the expression might fail, since the error would be reported
+    /// without the function on the call stack, or if it depends on the frame or module
+    /// of the function. Only a few expressions which can't fail are copied,
+    /// e.g. `(x, type(y) == "list")`.
+    pub(crate) fn inline(
+        &self,
+        span: Span,
+        local: &mut dyn FnMut(LocalSlotId) -> Option<Spanned<ExprCompiledValue>>,
+    ) -> Option<Spanned<ExprCompiledValue>> {
+        let mut inline_all = |xs: &[Spanned<ExprCompiledValue>]| {
+            xs.iter()
+                .map(|x| x.inline(span, local))
+                .collect::<Option<Vec<_>>>()
+        };
+        let expr = match self.node {
+            ExprCompiledValue::Value(x) => ExprCompiledValue::Value(x),
+            ExprCompiledValue::Local(slot) => return local(slot),
+            ExprCompiledValue::Type(box ref e) => {
+                ExprCompiledValue::Type(box e.inline(span, local)?)
+            }
+            ExprCompiledValue::TypeIs(box ref e, t, maybe_not) => {
+                ExprCompiledValue::TypeIs(box e.inline(span, local)?, t, maybe_not)
+            }
+            ExprCompiledValue::Tuple(ref xs) => ExprCompiledValue::Tuple(inline_all(xs)?),
+            ExprCompiledValue::List(ref xs) => ExprCompiledValue::List(inline_all(xs)?),
+            ExprCompiledValue::Not(box ref e) => ExprCompiledValue::Not(box e.inline(span, local)?),
+            // Operators, indexing, attributes and formatting can all fail for some values,
+            // e.g. `x + y` with an int and a string.
+            _ => return None,
+        };
+        // Spans of the body are in the file of the function, so use the call site instead.
+        Some(Spanned { node: expr, span })
+    }
 }
 
 impl ExprCompiledValue {
@@ -316,6 +396,17 @@ impl ExprCompiledValue {
                 node: value!(FrozenValue::new_bool(!x.to_value().to_bool())),
                 span,
             },
+            // `not not not x` is `not x`.
+            Spanned {
+                node: ExprCompiledValue::Not(box Spanned {
+                    node: ExprCompiledValue::Not(box x),
+                    ..
+                }),
+                ..
+            } => Spanned {
+                node: ExprCompiledValue::Not(box x),
+                span,
+            },
             expr => Spanned {
                 node: ExprCompiledValue::Not(box expr),
                 span,
@@ -323,6 +414,38 @@ impl ExprCompiledValue {
         }
     }
 
+    /// `l op r`, computed now if both are ints and so is the result, e.g. `10 * 60`.
+    /// If the operation fails, e.g. on overflow or division by zero, it fails at runtime.
+    /// Anything the operation allocates is left on the scratch `heap`.
+    fn op(
+        op: ExprBinOp,
+        l: Spanned<ExprCompiledValue>,
+        r: Spanned<ExprCompiledValue>,
+        heap: &Heap,
+    ) -> ExprCompiledValue {
+        if let (Some(lv), Some(rv)) = (l.as_value(), r.as_value()) {
+            if lv.unpack_int().is_some() && rv.unpack_int().is_some() {
+                if let Some(Ok(x)) = op.eval(lv.to_value(), rv.to_value(), heap) {
+                    if let Some(x) = x.unpack_int() {
+                        return value!(FrozenValue::new_int(x));
+                    }
+                }
+            }
+        }
+        ExprCompiledValue::Op(op, box (l, r))
+    }
+
+    /// Read a field of a constant (e.g. a struct) now, as it can't change.
+    /// Methods and other receivers are looked up at runtime.
+    fn dot(object: Spanned<ExprCompiledValue>, field: &Symbol, heap: &Heap) -> ExprCompiledValue {
+        if let Some(v) = object.as_value() {
+            if let Some((AttrType::Field, v)) = compile_time_getattr(v, field, heap) {
+                return value!(v);
+            }
+        }
+        ExprCompiledValue::Dot(box object, field.clone())
+    }
+
     fn or(
         l: Spanned<ExprCompiledValue>,
         r: Spanned<ExprCompiledValue>,
@@ -498,16 +621,6 @@ fn eval_equals(
     ExprCompiledValue::Equals(box (l, r), maybe_not)
 }
 
-impl AstLiteral {
-    fn compile(&self, heap: &FrozenHeap) -> FrozenValue {
-        match self {
-            AstLiteral::Int(i) => FrozenValue::new_int(i.node),
-            AstLiteral::Float(f) => heap.alloc(f.node),
-            AstLiteral::String(x) => heap.alloc(x.node.as_str()),
-        }
-    }
-}
-
 impl<P: AstPayload> ExprP<P> {
     fn unpack_string_literal(&self) -> Option<&str> {
         match self {
@@ -579,25 +692,24 @@ pub(crate) fn get_attr_hashed<'v>(
     }
 }
 
+pub(crate) fn compile_time_getattr(
+    left: FrozenValue,
+    attr: &Symbol,
+    heap: &Heap,
+) -> Option<(AttrType, FrozenValue)> {
+    // We assume `getattr` has no side effects.
+    let (attr_type, field) = get_attr_hashed(left.to_value(), attr, heap).ok()?;
+    // We take only frozen values, so if getattr returns fresh object on each call,
+    // we are discarding the result.
+    let field = field.unpack_frozen()?;
+    Some((attr_type, field))
+}
+
 impl Compiler<'_> {
     pub fn expr_opt(&mut self, expr: Option<Box<CstExpr>>) -> Option<Spanned<ExprCompiledValue>> {
         expr.map(|v| self.expr(*v))
     }
 
-    pub(crate) fn compile_time_getattr(
-        &mut self,
-        left: FrozenValue,
-        attr: &Symbol,
-    ) -> Option<(AttrType, FrozenValue)> {
-        // We assume `getattr` has no side effects.
-        let (attr_type, field) =
-            get_attr_hashed(left.to_value(), attr, self.module_env.heap()).ok()?;
-        // We take only frozen values, so if getattr returns fresh object on each call,
-        // we are discarding the result.
-        let field = field.unpack_frozen()?;
-        Some((attr_type, field))
-    }
-
     fn expr_ident(
         &mut self,
         ident: AstString,
@@ -637,19 +749,46 @@ impl Compiler<'_> {
         }
     }
 
+    /// `l op r`, computed now if possible, with anything allocated left on the scratch heap.
+    fn op(
+        &self,
+        op: ExprBinOp,
+        l: Spanned<ExprCompiledValue>,
+        r: Spanned<ExprCompiledValue>,
+    ) -> ExprCompiledValue {
+        ExprCompiledValue::op(op, l, r, self.scratch.heap())
+    }
+
     fn percent(
         &mut self,
         l: Spanned<ExprCompiledValue>,
         r: Spanned<ExprCompiledValue>,
     ) -> ExprCompiledValue {
         if let Some(v) = l.as_string() {
+            // `"aaa%sbbb%sccc" % (x, y)` is the same as `"aaa{}bbb{}ccc".format(x, y)`.
+            // Other arities are left to `%` itself to report.
+            if let ExprCompiledValue::Tuple(xs) = &r.node {
+                if xs.len() >= 2 {
+                    if let Some(segments) = parse_percent_s_many(&v) {
+                        if segments.len() == xs.len() + 1 {
+                            let segments = segments
+                                .map(|s| self.module_env.frozen_heap().alloc_string_value(s));
+                            let xs = match r.node {
+                                ExprCompiledValue::Tuple(xs) => xs,
+                                _ => unreachable!(),
+                            };
+                            return ExprCompiledValue::FormatMany(box (segments, xs));
+                        }
+                    }
+                }
+            }
             if let Some((before, after)) = parse_percent_s_one(&v) {
                 let before = self.module_env.frozen_heap().alloc_string_value(&before);
                 let after = self.module_env.frozen_heap().alloc_string_value(&after);
                 return ExprCompiledValue::PercentSOne(box (before, r, after));
             }
         }
-        ExprCompiledValue::Op(ExprBinOp::Percent, box (l, r))
+        self.op(ExprBinOp::Percent, l, r)
     }
 
     pub(crate) fn expr(&mut self, expr: CstExpr) -> Spanned<ExprCompiledValue> {
@@ -691,22 +830,8 @@ impl Compiler<'_> {
             ExprP::Dot(left, right) => {
                 let left = self.expr(*left);
                 let s = Symbol::new(&right.node);
-
-                if let Some(left) = left.as_value() {
-                    if let Some((attr_type, v)) = self.compile_time_getattr(left, &s) {
-                        if attr_type == AttrType::Field {
-                            return Spanned {
-                                span,
-                                node: ExprCompiledValue::Value(v),
-                            };
-                        } else {
-                            // TODO: maybe call attribute at compile time
-                            // TODO: maybe create bound method at compile time
-                        }
-                    }
-                }
-
-                ExprCompiledValue::Dot(box left, s)
+                // TODO: maybe create bound method at compile time
+                ExprCompiledValue::dot(left, &s, self.module_env.heap())
             }
             ExprP::Call(box left, args) => self.expr_call(span, left, args),
             ExprP::ArrayIndirection(box (array, index)) => {
@@ -765,20 +890,20 @@ impl Compiler<'_> {
                         BinOp::GreaterOrEqual => eval_compare(l, r, CompareOp::GreaterOrEqual),
                         BinOp::In => ExprCompiledValue::Op(ExprBinOp::In, box (l, r)),
                         BinOp::NotIn => ExprCompiledValue::Op(ExprBinOp::NotIn, box (l, r)),
-                        BinOp::Subtract => ExprCompiledValue::Op(ExprBinOp::Sub, box (l, r)),
-                        BinOp::Add => ExprCompiledValue::Op(ExprBinOp::Add, box (l, r)),
-                        BinOp::Multiply => ExprCompiledValue::Op(ExprBinOp::Multiply, box (l, r)),
+                        BinOp::Subtract => self.op(ExprBinOp::Sub, l, r),
+                        BinOp::Add => self.op(ExprBinOp::Add, l, r),
+                        BinOp::Multiply => self.op(ExprBinOp::Multiply, l, r),
                         BinOp::Percent => self.percent(l, r),
-                        BinOp::Divide => ExprCompiledValue::Op(ExprBinOp::Divide, box (l, r)),
+                        BinOp::Divide => self.op(ExprBinOp::Divide, l, r),
                         BinOp::FloorDivide => {
-                            ExprCompiledValue::Op(ExprBinOp::FloorDivide, box (l, r))
+                            self.op(ExprBinOp::FloorDivide, l, r)
                         }
-                        BinOp::BitAnd => ExprCompiledValue::Op(ExprBinOp::BitAnd, box (l, r)),
-                        BinOp::BitOr => ExprCompiledValue::Op(ExprBinOp::BitOr, box (l, r)),
-                        BinOp::BitXor => ExprCompiledValue::Op(ExprBinOp::BitXor, box (l, r)),
-                        BinOp::LeftShift => ExprCompiledValue::Op(ExprBinOp::LeftShift, box (l, r)),
+                        BinOp::BitAnd => self.op(ExprBinOp::BitAnd, l, r),
+                        BinOp::BitOr => self.op(ExprBinOp::BitOr, l, r),
+                        BinOp::BitXor => self.op(ExprBinOp::BitXor, l, r),
+                        BinOp::LeftShift => self.op(ExprBinOp::LeftShift, l, r),
                         BinOp::RightShift => {
-                            ExprCompiledValue::Op(ExprBinOp::RightShift, box (l, r))
+                            self.op(ExprBinOp::RightShift, l, r)
                         }
                     }
                 }
@@ -790,10 +915,27 @@ impl Compiler<'_> {
                 self.dict_comprehension(k, v, for_, clauses)
             }
             ExprP::Literal(x) => {
-                let val = x.compile(self.module_env.frozen_heap());
+                let val = self.literal(x);
                 value!(val)
             }
         };
         Spanned { node: expr, span }
     }
+
+    fn literal(&mut self, x: &AstLiteral) -> FrozenValue {
+        let heap = self.module_env.frozen_heap();
+        match x {
+            AstLiteral::Int(i) => FrozenValue::new_int(i.node),
+            AstLiteral::Float(f) => heap.alloc(f.node),
+            // Strings are immutable, so identical literals can share a single allocation.
+            AstLiteral::String(x) => match self.string_constants.get(x.node.as_str()) {
+                Some(v) => *v,
+                None => {
+                    let v = heap.alloc(x.node.as_str());
+                    self.string_constants.insert(x.node.as_str().into(), v);
+                    v
+                }
+            },
+        }
+    }
 }
@@ -21,10 +21,10 @@ use crate::{
     codemap::Spanned,
     eval::{
         compiler::{scope::CstExpr, Compiler},
-        fragment::expr::ExprCompiledValue,
+        fragment::expr::{ExprCompiledValue, MaybeNot},
     },
     syntax::ast::ExprP,
-    values::{dict::Dict, list::List},
+    values::{dict::Dict, list::List, FrozenStringValue, FrozenValue},
 };
 
 /// Convert a list into a tuple. In many cases (iteration, `in`) these types
@@ -49,12 +49,29 @@ impl Compiler<'_> {
     /// Compile the operation `type(expr)`, trying to produce a constant
     /// where possible.
     pub fn fn_type(&mut self, expr: CstExpr) -> ExprCompiledValue {
+        let expr = self.expr(expr);
+        ExprCompiledValue::typ(expr)
+    }
+
+    /// Compile the operation `len(expr)`, trying to produce a constant
+    /// where possible.
+    pub fn fn_len(&mut self, expr: CstExpr) -> ExprCompiledValue {
+        let expr = self.expr(expr);
+        ExprCompiledValue::len(expr)
+    }
+}
+
+impl ExprCompiledValue {
+    /// The operation `type(expr)`, producing a constant where possible,
+    /// including when `expr` only becomes a constant on freeze.
+    pub(crate) fn typ(expr: Spanned<ExprCompiledValue>) -> ExprCompiledValue {
         let span = expr.span;
-        match self.expr(expr).node {
+        match expr.node {
             ExprCompiledValue::Value(x) => {
                 ExprCompiledValue::Value(x.to_value().get_type_value().unpack())
             }
-            ExprCompiledValue::List(xs) if xs.is_empty() => {
+            // A list of constants has no side effects to preserve.
+            ExprCompiledValue::List(xs) if xs.iter().all(|x| x.as_value().is_some()) => {
                 ExprCompiledValue::Value(List::get_type_value_static().unpack())
             }
             ExprCompiledValue::Dict(xs) if xs.is_empty() => {
@@ -66,4 +83,40 @@ impl Compiler<'_> {
             x => ExprCompiledValue::Type(box Spanned { node: x, span }),
         }
     }
+
+    /// The operation `len(expr)`, producing a constant where possible,
+    /// including when `expr` only becomes a constant on freeze.
+    pub(crate) fn len(expr: Spanned<ExprCompiledValue>) -> ExprCompiledValue {
+        let span = expr.span;
+        match expr.node {
+            // If `len` fails, let it fail at runtime.
+            ExprCompiledValue::Value(x) => match x.to_value().length() {
+                Ok(n) => ExprCompiledValue::Value(FrozenValue::new_int(n)),
+                Err(_) => ExprCompiledValue::Len(box Spanned {
+                    node: ExprCompiledValue::Value(x),
+                    span,
+                }),
+            },
+            // A literal with constant elements has no side effects to preserve.
+            ExprCompiledValue::List(xs) if xs.iter().all(|x| x.as_value().is_some()) => {
+                ExprCompiledValue::Value(FrozenValue::new_int(xs.len() as i32))
+            }
+            x => ExprCompiledValue::Len(box Spanned { node: x, span }),
+        }
+    }
+
+    /// The operation `maybe_not(type(expr) == t)`, producing a constant where possible.
+    pub(crate) fn type_is(
+        expr: Spanned<ExprCompiledValue>,
+        t: FrozenStringValue,
+        maybe_not: MaybeNot,
+    ) -> ExprCompiledValue {
+        match expr.node {
+            ExprCompiledValue::Value(x) => {
+                let is = x.to_value().get_type_value() == t;
+                ExprCompiledValue::Value(FrozenValue::new_bool(is == (maybe_not == MaybeNot::Id)))
+            }
+            _ => ExprCompiledValue::TypeIs(box expr, t, maybe_not),
+        }
+    }
 }
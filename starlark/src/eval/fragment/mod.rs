@@ -23,6 +23,7 @@ macro_rules! value {
 
 pub(crate) mod call;
 pub(crate) mod compr;
+pub(crate) mod dead_store;
 pub(crate) mod def;
 pub(crate) mod expr;
 pub(crate) mod known;
@@ -76,7 +76,17 @@ impl Compiler<'_> {
             StmtP::Statements(stmts) => {
                 let mut last = Value::new_none();
                 for stmt in stmts {
-                    last = self.eval_top_level_stmt(stmt, evaluator)?;
+                    last = match self.eval_top_level_stmt(stmt, evaluator) {
+                        Ok(v) => v,
+                        Err(e) => match &mut evaluator.top_level_errors {
+                            // See `Evaluator::eval_module_continue_on_error`.
+                            Some(errors) => {
+                                errors.push(e.0);
+                                Value::new_none()
+                            }
+                            None => return Err(e),
+                        },
+                    };
                 }
                 Ok(last)
             }
@@ -86,7 +96,11 @@ impl Compiler<'_> {
             }
             _ => {
                 let stmt = self.module_top_level_stmt(stmt);
-                let bc = stmt.as_bc(&self.compile_context());
+                if let Some(e) = self.errors.drain(..).next() {
+                    // Static errors, reported even if the code is not reached
+                    return Err(EvalException(e));
+                }
+                let bc = stmt.as_bc(&self.compile_context(), 0);
                 bc.run(evaluator)
             }
         }
@@ -31,13 +31,16 @@ use thiserror::Error;
 
 use crate::{
     codemap::{Span, Spanned},
-    environment::{slots::ModuleSlotId, FrozenModuleRef},
+    environment::slots::ModuleSlotId,
     eval::{
         compiler::{
             scope::{Captured, CstAssign, CstExpr, CstStmt, Slot},
             Compiler,
         },
-        fragment::{expr::ExprCompiledValue, known::list_to_tuple},
+        fragment::{
+            expr::{ExprCompiledValue, OptimizeOnFreezeContext},
+            known::list_to_tuple,
+        },
         runtime::{
             evaluator::{Evaluator, GC_THRESHOLD},
             slots::LocalSlotId,
@@ -78,38 +81,54 @@ pub(crate) enum StmtCompiledValue {
 pub(crate) struct StmtCompileContext {
     pub(crate) has_before_stmt: bool,
     pub(crate) bc_profile: bool,
+    pub(crate) bc_trace: bool,
+    pub(crate) heap_or_flame_profile: bool,
+    pub(crate) has_call_limits: bool,
+}
+
+impl StmtCompileContext {
+    /// Whether anything watches the body of a function run, or the call itself,
+    /// e.g. a `before_stmt` callback, a profiler or a limit checked on each call,
+    /// so optimisations which skip or remove parts of it are off.
+    pub(crate) fn is_observed(&self) -> bool {
+        self.has_before_stmt
+            || self.bc_profile
+            || self.bc_trace
+            || self.heap_or_flame_profile
+            || self.has_call_limits
+    }
 }
 
 impl Spanned<StmtCompiledValue> {
-    fn optimize_on_freeze(&self, module: &FrozenModuleRef) -> StmtsCompiled {
+    fn optimize_on_freeze(&self, ctx: &OptimizeOnFreezeContext) -> StmtsCompiled {
         let span = self.span;
         match self.node {
             StmtCompiledValue::Return(Some(ref e)) => StmtsCompiled::one(Spanned {
                 span,
-                node: StmtCompiledValue::Return(Some(e.optimize_on_freeze(module))),
+                node: StmtCompiledValue::Return(Some(e.optimize_on_freeze(ctx))),
             }),
             StmtCompiledValue::Expr(ref expr) => {
-                let expr = expr.optimize_on_freeze(module);
+                let expr = expr.optimize_on_freeze(ctx);
                 Self::expr(expr)
             }
             StmtCompiledValue::Assign(ref lhs, ref rhs) => {
-                let lhs = lhs.optimize_on_freeze(module);
-                let rhs = rhs.optimize_on_freeze(module);
+                let lhs = lhs.optimize_on_freeze(ctx);
+                let rhs = rhs.optimize_on_freeze(ctx);
                 StmtsCompiled::one(Spanned {
                     span,
                     node: StmtCompiledValue::Assign(lhs, rhs),
                 })
             }
             StmtCompiledValue::If(box (ref cond, ref t, ref f)) => {
-                let cond = cond.optimize_on_freeze(module);
-                let t = t.optimize_on_freeze(module);
-                let f = f.optimize_on_freeze(module);
+                let cond = cond.optimize_on_freeze(ctx);
+                let t = t.optimize_on_freeze(ctx);
+                let f = f.optimize_on_freeze(ctx);
                 Self::if_stmt(span, cond, t, f)
             }
             StmtCompiledValue::For(box (ref var, ref over, ref body)) => {
-                let var = var.optimize_on_freeze(module);
-                let over = over.optimize_on_freeze(module);
-                let body = body.optimize_on_freeze(module);
+                let var = var.optimize_on_freeze(ctx);
+                let over = over.optimize_on_freeze(ctx);
+                let body = body.optimize_on_freeze(ctx);
                 StmtsCompiled::one(Spanned {
                     span,
                     node: StmtCompiledValue::For(box (var, over, body)),
@@ -129,7 +148,7 @@ impl Spanned<StmtCompiledValue> {
         }
     }
 
-    fn expr(expr: Spanned<ExprCompiledValue>) -> StmtsCompiled {
+    pub(crate) fn expr(expr: Spanned<ExprCompiledValue>) -> StmtsCompiled {
         match expr {
             Spanned {
                 node: ExprCompiledValue::Value(..),
@@ -241,14 +260,14 @@ impl StmtsCompiled {
         self.0.extend(right.0);
     }
 
-    pub(crate) fn optimize_on_freeze(&self, module: &FrozenModuleRef) -> StmtsCompiled {
+    pub(crate) fn optimize_on_freeze(&self, ctx: &OptimizeOnFreezeContext) -> StmtsCompiled {
         let mut stmts = StmtsCompiled::empty();
         match &self.0 {
             SmallVec1::Empty => {}
-            SmallVec1::One(s) => stmts.extend(s.optimize_on_freeze(module)),
+            SmallVec1::One(s) => stmts.extend(s.optimize_on_freeze(ctx)),
             SmallVec1::Many(ss) => {
                 for s in ss {
-                    stmts.extend(s.optimize_on_freeze(module));
+                    stmts.extend(s.optimize_on_freeze(ctx));
                 }
             }
         }
@@ -291,22 +310,22 @@ pub(crate) enum AssignCompiledValue {
 impl Spanned<AssignCompiledValue> {
     pub(crate) fn optimize_on_freeze(
         &self,
-        module: &FrozenModuleRef,
+        ctx: &OptimizeOnFreezeContext,
     ) -> Spanned<AssignCompiledValue> {
         let span = self.span;
         let assign = match self.node {
             AssignCompiledValue::Dot(ref object, ref field) => {
-                let object = object.optimize_on_freeze(module);
+                let object = object.optimize_on_freeze(ctx);
                 let field = field.clone();
                 AssignCompiledValue::Dot(object, field)
             }
             AssignCompiledValue::ArrayIndirection(ref array, ref index) => {
-                let array = array.optimize_on_freeze(module);
-                let index = index.optimize_on_freeze(module);
+                let array = array.optimize_on_freeze(ctx);
+                let index = index.optimize_on_freeze(ctx);
                 AssignCompiledValue::ArrayIndirection(array, index)
             }
             AssignCompiledValue::Tuple(ref xs) => {
-                let xs = xs.map(|x| x.optimize_on_freeze(module));
+                let xs = xs.map(|x| x.optimize_on_freeze(ctx));
                 AssignCompiledValue::Tuple(xs)
             }
             ref e @ (AssignCompiledValue::Local(..) | AssignCompiledValue::Module(..)) => e.clone(),
@@ -487,6 +506,13 @@ pub(crate) fn add_assign<'v>(
     rhs: Value<'v>,
     heap: &'v Heap,
 ) -> anyhow::Result<Value<'v>> {
+    // Counters are super common, and ints never mutate, so have a special case
+    if let (Some(li), Some(ri)) = (lhs.unpack_int(), rhs.unpack_int()) {
+        if let Some(x) = li.checked_add(ri) {
+            return Ok(Value::new_int(x));
+        }
+    }
+
     // Addition of strings is super common, so have a special case
     if let Some(ls) = lhs.unpack_str() {
         if let Some(rs) = rhs.unpack_str() {
@@ -495,6 +521,7 @@ pub(crate) fn add_assign<'v>(
             } else if rs.is_empty() {
                 return Ok(lhs);
             } else {
+                heap.check_string_length(ls.len() + rs.len())?;
                 return Ok(heap.alloc_str_concat(ls, rs));
             }
         }
@@ -518,10 +545,10 @@ pub(crate) fn add_assign<'v>(
             let list = List::from_value_mut(lhs)?
                 .ok_or_else(|| anyhow!(ValueError::CannotMutateImmutableValue))?;
             if lhs.ptr_eq(rhs) {
-                list.double(heap);
+                list.double(heap)?;
             } else {
                 // TODO: if RHS is list, consider calling `List::extend_from_slice`.
-                rhs.with_iterator(heap, |it| list.extend(it, heap))?;
+                rhs.with_iterator(heap, |it| list.extend(it, heap))??;
             }
             Ok(lhs)
         } else {
@@ -535,6 +562,9 @@ impl Compiler<'_> {
         StmtCompileContext {
             has_before_stmt: self.has_before_stmt,
             bc_profile: self.bc_profile,
+            bc_trace: self.bc_trace,
+            heap_or_flame_profile: self.heap_or_flame_profile,
+            has_call_limits: self.has_call_limits,
         }
     }
 
@@ -570,9 +600,9 @@ impl Compiler<'_> {
                     // of the last statement-expression in module.
                     node: StmtP::Return(Some(expr)),
                 };
-                self.stmt(stmt, true)
+                self.stmt(stmt, self.allow_gc)
             }
-            _ => self.stmt(stmt, true),
+            _ => self.stmt(stmt, self.allow_gc),
         }
     }
 
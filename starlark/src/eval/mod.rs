@@ -18,15 +18,17 @@
 //! Evaluate some code, typically done by creating an [`Evaluator`], then calling
 //! [`eval_module`](Evaluator::eval_module).
 
-use std::{convert::TryInto, intrinsics::unlikely, mem};
+use std::{collections::HashMap, convert::TryInto, intrinsics::unlikely, mem, time::Duration};
 
-pub(crate) use compiler::scope::ScopeNames;
+pub(crate) use compiler::{scope::ScopeNames, ScratchEval};
 pub(crate) use fragment::def::{Def, FrozenDef};
 use gazebo::{cast, prelude::*};
+pub(crate) use runtime::evaluator::EvaluatorError;
 pub use runtime::{
     arguments::{Arguments, ParametersParser, ParametersSpec},
     evaluator::Evaluator,
-    file_loader::{FileLoader, ReturnFileLoader},
+    file_loader::{CachingFileLoader, FileLoader, ModuleSource, ReturnFileLoader, SourceLoader},
+    gc_stats::GcStats,
 };
 
 use crate::{
@@ -39,10 +41,33 @@ use crate::{
         },
         fragment::def::DefInfo,
     },
-    syntax::ast::AstModule,
-    values::{docs::DocString, Value},
+    syntax::{
+        ast::{AstModule, Stmt, StmtP},
+        Dialect,
+    },
+    values::{docs::DocString, function::DisabledBuiltin, FrozenValue, Value},
 };
 
+/// A failed evaluation from [`Evaluator::eval_module_with_timeout`].
+#[derive(Debug)]
+pub struct PartialEval<'v> {
+    /// Why evaluation failed.
+    pub error: anyhow::Error,
+    /// The exported module variables assigned before the failure, as returned by
+    /// [`Module::bindings`](crate::environment::Module::bindings).
+    pub bindings: Vec<(String, Value<'v>)>,
+}
+
+/// A failed evaluation from [`Evaluator::eval_module_continue_on_error`].
+#[derive(Debug)]
+pub struct PartialEvalErrors<'v> {
+    /// Why each failing top-level statement failed, in the order they were evaluated.
+    pub errors: Vec<anyhow::Error>,
+    /// The exported module variables assigned, as returned by
+    /// [`Module::bindings`](crate::environment::Module::bindings).
+    pub bindings: Vec<(String, Value<'v>)>,
+}
+
 pub(crate) mod bc;
 mod compiler;
 mod fragment;
@@ -55,6 +80,95 @@ impl<'v, 'a> Evaluator<'v, 'a> {
     /// Evaluate an [`AstModule`] with this [`Evaluator`], modifying the in-scope
     /// [`Module`](crate::environment::Module) as appropriate.
     pub fn eval_module(&mut self, ast: AstModule, globals: &Globals) -> anyhow::Result<Value<'v>> {
+        self.eval_module_impl(ast, globals, true)
+    }
+
+    /// Like [`eval_module`](Evaluator::eval_module), but fail once `timeout` has elapsed
+    /// (see [`set_timeout`](Evaluator::set_timeout)). If evaluation fails, for a timeout
+    /// or any other reason, the error is returned along with the module variables assigned
+    /// before the failure, so long-running scripts can report partial progress.
+    /// Any deadline set before the call is restored afterwards.
+    pub fn eval_module_with_timeout(
+        &mut self,
+        ast: AstModule,
+        globals: &Globals,
+        timeout: Duration,
+    ) -> Result<Value<'v>, PartialEval<'v>> {
+        let res = self.with_timeout(timeout, |eval| eval.eval_module(ast, globals));
+        res.map_err(|error| PartialEval {
+            error,
+            bindings: self.module_env.bindings(),
+        })
+    }
+
+    /// Like [`eval_module`](Evaluator::eval_module), but when a top-level statement fails
+    /// its error is recorded and evaluation carries on with the next top-level statement,
+    /// e.g. to report every broken target in a configuration file at once. Variables the
+    /// failed statements would have assigned stay unassigned. If any statement failed, the
+    /// errors are returned along with the module variables which were assigned. Errors found
+    /// before evaluation starts, such as references to undefined variables, stop the module
+    /// being evaluated at all, so are returned on their own.
+    pub fn eval_module_continue_on_error(
+        &mut self,
+        ast: AstModule,
+        globals: &Globals,
+    ) -> Result<Value<'v>, PartialEvalErrors<'v>> {
+        let old = mem::replace(&mut self.top_level_errors, Some(Vec::new()));
+        let res = self.eval_module(ast, globals);
+        let mut errors = mem::replace(&mut self.top_level_errors, old).unwrap();
+        match res {
+            Ok(v) if errors.is_empty() => Ok(v),
+            res => {
+                errors.extend(res.err());
+                Err(PartialEvalErrors {
+                    errors,
+                    bindings: self.module_env.bindings(),
+                })
+            }
+        }
+    }
+
+    /// Parse and evaluate a single expression, e.g. `1 + 2`, returning its value.
+    /// The expression is parsed with [`Dialect::Extended`], and may refer to variables in
+    /// the in-scope [`Module`](crate::environment::Module), but statements such as
+    /// assignments or `def` are an error. Trailing newlines and comments are allowed, but input
+    /// which is empty or only comments has no expression, so is also an error.
+    ///
+    /// ```
+    /// use starlark::environment::{Globals, Module};
+    /// use starlark::eval::Evaluator;
+    ///
+    /// let module = Module::new();
+    /// let mut eval = Evaluator::new(&module);
+    /// let res = eval.eval_expr("1 + 2 * 3", &Globals::standard()).unwrap();
+    /// assert_eq!(res.unpack_int(), Some(7));
+    /// ```
+    pub fn eval_expr(&mut self, expr: &str, globals: &Globals) -> anyhow::Result<Value<'v>> {
+        let ast = AstModule::parse("<expr>", expr.to_owned(), &Dialect::Extended)?;
+        let is_expr = match &ast.statement.node {
+            Stmt::Expression(_) => true,
+            Stmt::Statements(xs) => {
+                matches!(xs.as_slice(), [x] if matches!(x.node, Stmt::Expression(_)))
+            }
+            _ => false,
+        };
+        if !is_expr {
+            return Err(EvaluatorError::NotAnExpression(expr.to_owned()).into());
+        }
+        // A string literal expression is not a module docstring.
+        self.eval_module_impl(ast, globals, false)
+    }
+
+    fn eval_module_impl(
+        &mut self,
+        ast: AstModule,
+        globals: &Globals,
+        module_docstring: bool,
+    ) -> anyhow::Result<Value<'v>> {
+        let ast = match self.ast_transformer {
+            None => ast,
+            Some(transformer) => transformer(ast)?,
+        };
         let AstModule { codemap, statement } = ast;
 
         let globals = self.module_env.frozen_heap().alloc_any(globals.dupe());
@@ -65,8 +179,31 @@ impl<'v, 'a> Evaluator<'v, 'a> {
 
         let mut statement = statement.into_map_payload(&mut CompilerAstMap(&mut scope_data));
 
-        if let Some(docstring) = DocString::extract_raw_starlark_docstring(&statement) {
-            self.module_env.set_docstring(docstring)
+        if module_docstring {
+            if let Some(docstring) = DocString::extract_raw_starlark_docstring(&statement) {
+                self.module_env.set_docstring(docstring)
+            }
+        }
+
+        let disabled_globals: HashMap<String, FrozenValue> = self
+            .disabled_builtins
+            .iter()
+            .map(|name| {
+                let value = self
+                    .module_env
+                    .frozen_heap()
+                    .alloc_simple(DisabledBuiltin(name.clone()));
+                (name.clone(), value)
+            })
+            .collect();
+
+        if self.top_level_times.enabled() {
+            let stmts = match &statement.node {
+                StmtP::Statements(stmts) => stmts.map(|x| x.span),
+                _ => vec![statement.span],
+            };
+            self.top_level_times
+                .start_module(codemap.dupe(), stmts.into_iter());
         }
 
         let mut scope = Scope::enter_module(
@@ -75,6 +212,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             scope_data,
             &mut statement,
             globals,
+            &disabled_globals,
             codemap.dupe(),
         );
 
@@ -85,6 +223,10 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             return Err(e);
         }
 
+        if let Some(references) = &mut self.compiled_global_references {
+            references.append(&mut scope.referenced_globals);
+        }
+
         let span = statement.span;
 
         let (module_slots, scope_names, scope_data) = scope.exit_module();
@@ -126,11 +268,19 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             globals,
             codemap: codemap.dupe(),
             constants: Constants::new(),
+            string_constants: HashMap::new(),
             has_before_stmt: !self.before_stmt.is_empty(),
             bc_profile: self.bc_profile.enabled(),
+            bc_trace: self.bc_trace.is_some() || self.before_instr.is_some(),
+            heap_or_flame_profile: self.heap_or_flame_profile,
+            has_call_limits: self.has_call_limits(),
+            allow_gc: !self.disable_gc,
+            errors: Vec::new(),
+            scratch: ScratchEval::new(),
         };
 
         let res = compiler.eval_module(statement, self);
+        self.top_level_times.finish_module();
 
         // Clean up the world, putting everything back
         self.call_stack.pop();
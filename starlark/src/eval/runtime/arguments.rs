@@ -293,6 +293,61 @@ impl<V> ParametersSpec<V> {
         }
     }
 
+    /// Check that a call with `pos` positional arguments and the named arguments `names`,
+    /// but no `*args` or `**kwargs`, would fill in the parameters, without making the call.
+    /// Gives the same error [`collect`](ParametersSpec::collect) would.
+    pub(crate) fn check_call<'a>(
+        &self,
+        pos: usize,
+        names: impl Iterator<Item = &'a Symbol>,
+    ) -> anyhow::Result<()> {
+        let next_position = cmp::min(pos, self.positional);
+        let mut filled = vec![false; self.kinds.len()];
+        filled[..next_position].iter_mut().for_each(|x| *x = true);
+        let mut lowest_name = usize::MAX;
+        let mut extra_names = Vec::new();
+        for name in names {
+            match self.names.get(name) {
+                None => extra_names.push(name.as_str().to_owned()),
+                Some(i) => {
+                    filled[*i] = true;
+                    lowest_name = cmp::min(lowest_name, *i);
+                }
+            }
+        }
+
+        if next_position > lowest_name {
+            return Err(FunctionError::RepeatedParameter {
+                name: self.param_name_at(lowest_name),
+            }
+            .into());
+        }
+        for (index, kind) in self.kinds.iter().enumerate() {
+            if !filled[index] && matches!(kind, ParameterKind::Required) {
+                return Err(FunctionError::MissingParameter {
+                    name: self.param_name_at(index),
+                    function: self.signature(),
+                }
+                .into());
+            }
+        }
+        if self.args.is_none() && pos > self.positional {
+            return Err(FunctionError::ExtraPositionalParameters {
+                count: pos - self.positional,
+                function: self.signature(),
+            }
+            .into());
+        }
+        if self.kwargs.is_none() && !extra_names.is_empty() {
+            return Err(FunctionError::ExtraNamedParameters {
+                names: extra_names,
+                function: self.signature(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
     /// Iterate over the parameters
     ///
     /// Returns an iterator over (parameter index, name, kind)
@@ -70,6 +70,19 @@ impl BcProfileData {
         Ok(())
     }
 
+    /// Number of times each opcode was executed, most frequent first, omitting those never executed.
+    fn counts(&self) -> Vec<(BcOpcode, u64)> {
+        let mut counts: Vec<_> = self
+            .by_instr
+            .iter()
+            .enumerate()
+            .filter(|(_, st)| st.count != 0)
+            .map(|(i, st)| (BcOpcode::by_number(i as u32).unwrap(), st.count))
+            .collect();
+        counts.sort_by_key(|(_opcode, count)| u64::MAX - count);
+        counts
+    }
+
     fn gen_csv(&self) -> String {
         let mut by_instr: Vec<_> = self
             .by_instr
@@ -114,16 +127,25 @@ impl BcProfile {
         }
     }
 
+    pub(crate) fn counts(&self) -> anyhow::Result<Vec<(BcOpcode, u64)>> {
+        match self.data {
+            Some(ref data) => Ok(data.counts()),
+            None => Err(EvaluatorError::BcProfilingNotEnabled.into()),
+        }
+    }
+
     /// Called from bytecode.
     pub(crate) fn before_instr(&mut self, opcode: BcOpcode) {
         let data = self.data.as_mut().expect("enabled but not enabled");
 
+        // Count the instruction as it starts, so the last instruction executed is counted,
+        // but we only know its time when the next one starts.
         let now = Instant::now();
         if let Some((last_opcode, last_time)) = data.last {
             let last_duration = now.saturating_duration_since(last_time);
-            data.by_instr[last_opcode as usize].count += 1;
             data.by_instr[last_opcode as usize].total_time += last_duration;
         }
+        data.by_instr[opcode as usize].count += 1;
         data.last = Some((opcode, now));
     }
 }
@@ -154,4 +176,39 @@ mod test {
             csv
         );
     }
+
+    #[test]
+    fn test_counts() {
+        let module = Module::new();
+        let globals = Globals::standard();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_bytecode_profile();
+        eval.eval_module(
+            AstModule::parse(
+                "bc.star",
+                "def f(x): return type(x)
+[f(i) for i in range(10)]"
+                    .to_owned(),
+                &Dialect::Standard,
+            )
+            .unwrap(),
+            &globals,
+        )
+        .unwrap();
+        let counts = eval.bytecode_profile_counts().unwrap();
+        assert!(counts.windows(2).all(|x| x[0].1 >= x[1].1), "{:?}", counts);
+        assert!(counts.iter().all(|x| x.1 != 0));
+        assert!(
+            counts.contains(&(format!("{:?}", BcOpcode::Type), 10)),
+            "{:?}",
+            counts
+        );
+    }
+
+    #[test]
+    fn test_counts_not_enabled() {
+        let module = Module::new();
+        let eval = Evaluator::new(&module);
+        assert!(eval.bytecode_profile_counts().is_err());
+    }
 }
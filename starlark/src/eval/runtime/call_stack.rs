@@ -74,38 +74,29 @@ impl Debug for CheapFrame<'_> {
 /// Starlark call stack.
 #[derive(Debug)]
 pub(crate) struct CallStack<'v> {
-    count: usize,
-    stack: [CheapFrame<'v>; MAX_CALLSTACK_RECURSION],
+    stack: Vec<CheapFrame<'v>>,
+    /// Maximum number of frames, including the module frame.
+    max_size: usize,
 }
 
 impl<'v> Default for CallStack<'v> {
     fn default() -> Self {
         Self {
-            count: 0,
-            stack: [CheapFrame {
-                function: Value::new_none(),
-                file: None,
-                span: Span::default(),
-            }; MAX_CALLSTACK_RECURSION],
+            stack: Vec::new(),
+            max_size: DEFAULT_MAX_CALLSTACK_SIZE,
         }
     }
 }
 
 // At 50 we see the C stack overflowing, so limit to 40 (which seems quite
 // low...)
-const MAX_CALLSTACK_RECURSION: usize = 40;
+const DEFAULT_MAX_CALLSTACK_SIZE: usize = 40;
 
 unsafe impl<'v> Trace<'v> for CallStack<'v> {
     fn trace(&mut self, tracer: &Tracer<'v>) {
-        for x in self.stack[0..self.count].iter_mut() {
+        for x in self.stack.iter_mut() {
             x.function.trace(tracer);
         }
-        // Not required, but since we are chosing not to walk those above
-        // the current stack depth, it's good practice to blank those values out
-        for x in self.stack[self.count..].iter_mut() {
-            x.function = Value::new_none();
-            x.file = None;
-        }
     }
 }
 
@@ -118,43 +109,45 @@ impl<'v> CallStack<'v> {
         span: Span,
         file: Option<FrozenRef<DefInfo>>,
     ) -> anyhow::Result<()> {
-        if unlikely(self.count >= MAX_CALLSTACK_RECURSION) {
+        if unlikely(self.stack.len() >= self.max_size) {
             return Err(ControlError::TooManyRecursionLevel.into());
         }
-        self.stack[self.count] = CheapFrame {
+        self.stack.push(CheapFrame {
             function,
             file,
             span,
-        };
-        self.count += 1;
+        });
         Ok(())
     }
 
     /// Remove the top element from the stack. Called after `push`.
     pub(crate) fn pop(&mut self) {
-        debug_assert!(self.count >= 1);
-        // We could clear the elements, but don't need to bother
-        self.count -= 1;
+        let top = self.stack.pop();
+        debug_assert!(top.is_some());
+    }
+
+    pub(crate) fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
+
+    pub(crate) fn has_default_max_size(&self) -> bool {
+        self.max_size == DEFAULT_MAX_CALLSTACK_SIZE
     }
 
     /// The location at the top of the stack. May be `None` if
     /// either there the stack is empty, or the top of the stack lacks location
     /// information (e.g. called from Rust).
     pub fn top_location(&self) -> Option<FileSpan> {
-        if self.count == 0 {
-            None
-        } else {
-            self.stack[self.count - 1].location()
-        }
+        self.stack.last().and_then(CheapFrame::location)
     }
 
     pub fn to_diagnostic_frames(&self) -> Vec<Frame> {
         // The first entry is just the entire module, so skip it
-        self.stack[1..self.count].map(CheapFrame::to_frame)
+        self.stack[1..].map(CheapFrame::to_frame)
     }
 
     /// List the entries on the stack as values
     pub(crate) fn to_function_values(&self) -> Vec<Value<'v>> {
-        self.stack[1..self.count].map(|x| x.function)
+        self.stack[1..].map(|x| x.function)
     }
 }
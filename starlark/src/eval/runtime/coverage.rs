@@ -0,0 +1,114 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use gazebo::prelude::*;
+
+use crate::codemap::{CodeMap, Span};
+
+/// The statements which have been executed.
+// When not enabled, we want this to be small and cheap
+pub(crate) struct Coverage(Option<Box<CoverageData>>);
+
+struct CoverageData {
+    // The spans of the statements executed in each file, in the order the files were first seen.
+    // There are usually only a few files, so a linear search is fine.
+    files: Vec<(CodeMap, HashSet<Span>)>,
+}
+
+impl Coverage {
+    pub(crate) fn new() -> Self {
+        Self(None)
+    }
+
+    pub(crate) fn enable(&mut self) {
+        self.0 = Some(box CoverageData { files: Vec::new() })
+    }
+
+    pub(crate) fn before_stmt(&mut self, span: Span, codemap: &CodeMap) {
+        if let Some(box data) = &mut self.0 {
+            match data.files.iter_mut().rev().find(|(file, _)| file == codemap) {
+                Some((_, spans)) => {
+                    spans.insert(span);
+                }
+                None => data.files.push((codemap.dupe(), HashSet::from([span]))),
+            }
+        }
+    }
+
+    // None = not applicable because not enabled
+    pub(crate) fn lines(&self) -> Option<BTreeMap<String, Vec<usize>>> {
+        let data = self.0.as_ref()?;
+        let mut lines: BTreeMap<String, BTreeSet<usize>> = BTreeMap::new();
+        for (file, spans) in &data.files {
+            let file_lines = lines.entry(file.filename().to_owned()).or_default();
+            for span in spans {
+                file_lines.insert(file.find_line(span.begin()));
+            }
+        }
+        Some(
+            lines
+                .into_iter()
+                .map(|(file, lines)| (file, lines.into_iter().collect()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+    };
+
+    #[test]
+    fn test_coverage() -> anyhow::Result<()> {
+        let ast = AstModule::parse(
+            "foo.bzl",
+            r#"
+def f(x):
+    if x:
+        return 1
+    else:
+        return 2
+a = f(True)
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )?;
+        let globals = Globals::standard();
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_coverage();
+        eval.eval_module(ast, &globals)?;
+
+        let coverage = eval.coverage()?;
+        assert_eq!(coverage.len(), 1);
+        assert_eq!(coverage["foo.bzl"], vec![1, 2, 3, 6]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_coverage_not_enabled() {
+        let module = Module::new();
+        let eval = Evaluator::new(&module);
+        assert!(eval.coverage().is_err());
+    }
+}
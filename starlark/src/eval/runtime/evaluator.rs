@@ -17,16 +17,19 @@
 
 use std::{
     cell::Cell,
+    collections::{BTreeMap, BTreeSet},
     intrinsics::unlikely,
+    io,
     mem::{self, MaybeUninit},
     path::Path,
+    time::{Duration, Instant},
 };
 
-use gazebo::{any::AnyLifetime, cast};
+use gazebo::{any::AnyLifetime, cast, dupe::Dupe};
 use thiserror::Error;
 
 use crate::{
-    codemap::{FileSpan, Span},
+    codemap::{CodeMap, FileSpan, Span},
     collections::{alloca::Alloca, string_pool::StringPool},
     environment::{slots::ModuleSlotId, EnvironmentError, FrozenModuleRef, Module},
     errors::{Diagnostic, Frame},
@@ -35,14 +38,18 @@ use crate::{
         runtime::{
             bc_profile::BcProfile,
             call_stack::CallStack,
+            coverage::Coverage,
             flame_profile::FlameProfile,
+            gc_stats::{GcStats, GcStatsCollector},
             heap_profile::{HeapProfile, HeapProfileFormat},
             slots::{LocalSlotId, LocalSlots},
             stmt_profile::StmtProfile,
+            top_level_times::TopLevelTimes,
         },
-        FileLoader,
+        FileLoader, ModuleSource, SourceLoader,
     },
     stdlib::breakpoint::{BreakpointConsole, RealBreakpointConsole},
+    syntax::AstModule,
     values::{
         value_captured_get, FrozenHeap, FrozenRef, Heap, Trace, Tracer, Value, ValueCaptured,
         ValueLike,
@@ -58,13 +65,44 @@ pub(crate) enum EvaluatorError {
     StmtProfilingNotEnabled,
     #[error("Can't call `write_flame_profile` unless you first call `enable_flame_profile`.")]
     FlameProfilingNotEnabled,
-    #[error("Can't call `write_bc_profile` unless you first call `enable_bc_profile`.")]
+    #[error("Can't call `flame_profile_self_time` unless you first call `enable_flame_profile`.")]
+    FlameSelfTimeNotEnabled,
+    #[error(
+        "Can't call `write_bytecode_profile` or `bytecode_profile_counts` unless you first call `enable_bytecode_profile`."
+    )]
     BcProfilingNotEnabled,
+    #[error(
+        "Can't call `top_level_stmt_times` unless you first call `enable_top_level_stmt_times`."
+    )]
+    TopLevelStmtTimesNotEnabled,
+    #[error("Can't call `gc_stats` unless you first call `enable_gc_stats`.")]
+    GcStatsNotEnabled,
+    #[error("Can't call `coverage` unless you first call `enable_coverage`.")]
+    CoverageNotEnabled,
+    #[error("Exceeded the maximum of {0} allocated values")]
+    TooManyAllocations(u64),
+    #[error("Exceeded the maximum heap size of {0} bytes")]
+    HeapTooLarge(usize),
+    #[error("Expected an expression, but got statements: `{0}`")]
+    NotAnExpression(String),
+    #[error("Builtin `{0}` is disabled")]
+    DisabledBuiltin(String),
+    #[error("Evaluation timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("Can't read `{0}` unless you first call `set_source_loader`.")]
+    NoSourceLoader(String),
 }
 
 /// Number of bytes to allocate between GC's.
 pub(crate) const GC_THRESHOLD: usize = 100000;
 
+/// Number of local variables, summed over all frames, to allocate space for up front.
+const LOCAL_SLOTS_CAPACITY: usize = 1000;
+
+/// Number of limit checks between each time the clock is compared with the deadline,
+/// since reading the clock is much slower than the other checks.
+const DEADLINE_CHECK_INTERVAL: u32 = 1000;
+
 /// Holds everything about an ongoing evaluation (local variables, globals, module resolution etc).
 pub struct Evaluator<'v, 'a> {
     // The module that is being used for this evaluation
@@ -77,6 +115,10 @@ pub struct Evaluator<'v, 'a> {
     pub(crate) local_variables: LocalSlots<'v>,
     // How we deal with a `load` function.
     pub(crate) loader: Option<&'a dyn FileLoader>,
+    // Where builtins read files from.
+    pub(crate) source_loader: Option<&'a dyn SourceLoader>,
+    // Rewrites each module's AST before it is compiled.
+    pub(crate) ast_transformer: Option<&'a dyn Fn(AstModule) -> anyhow::Result<AstModule>>,
     // `DefInfo` of currently executed function or module.
     pub(crate) def_info: FrozenRef<DefInfo>,
     // Should we enable heap profiling or not
@@ -89,12 +131,47 @@ pub struct Evaluator<'v, 'a> {
     pub(crate) disable_gc: bool,
     // Size of the heap when we should next perform a GC.
     pub(crate) next_gc_level: usize,
+    // Whether any of the limits checked by `check_limits` are set.
+    has_limits: bool,
+    // Maximum number of values allocated on the heap.
+    max_allocations: u64,
+    // Maximum number of bytes allocated on the heap.
+    max_heap_bytes: usize,
+    // When evaluation must finish by, and the timeout which set it.
+    deadline: Option<(Instant, Duration)>,
+    // Number of limit checks left before the clock is next read.
+    deadline_countdown: Cell<u32>,
+    // Errors from failed top-level statements, if evaluation carries on past them.
+    pub(crate) top_level_errors: Option<Vec<anyhow::Error>>,
+    // Names from `Globals` referenced by compiled code, if we are recording them.
+    pub(crate) compiled_global_references: Option<BTreeSet<String>>,
+    // Names from `Globals` which fail when called.
+    pub(crate) disabled_builtins: Vec<String>,
+    // Should `round` round halfway cases away from zero, rather than to even.
+    pub(crate) round_half_up: bool,
+    // Seed for any builtin that makes random choices.
+    random_seed: u64,
     // Extra functions to run on each statement, usually empty
     pub(crate) before_stmt: Vec<&'a dyn Fn(Span, &mut Evaluator<'v, 'a>)>,
+    // Called before a statement on a different line to the previous one, if set.
+    before_line: Option<&'a dyn Fn(FileSpan, &mut Evaluator<'v, 'a>)>,
+    // The file and line of the last statement `before_line` was called for.
+    last_line: Option<(CodeMap, usize)>,
     // Used for line profiling
     stmt_profile: StmtProfile,
+    // Time taken by each top-level statement.
+    pub(crate) top_level_times: TopLevelTimes,
+    // Garbage collection statistics.
+    gc_stats: GcStatsCollector,
+    // The statements which have been executed.
+    pub(crate) coverage: Coverage,
     // Bytecode profile.
     pub(crate) bc_profile: BcProfile,
+    // Where to write a trace of the executed bytecode instructions.
+    pub(crate) bc_trace: Option<&'a mut dyn io::Write>,
+    // Called before each bytecode instruction, if set.
+    pub(crate) before_instr:
+        Option<&'a dyn Fn(u32, FileSpan, &mut Evaluator<'v, 'a>) -> anyhow::Result<()>>,
     // Used for stack-like allocation
     alloca: Alloca,
     // Another stack-like allocation
@@ -107,8 +184,9 @@ pub struct Evaluator<'v, 'a> {
     pub extra_v: Option<&'a dyn AnyLifetime<'v>>,
     /// Called to perform console IO each time `breakpoint` function is called.
     pub(crate) breakpoint_handler: Option<Box<dyn Fn() -> Box<dyn BreakpointConsole>>>,
+    /// Called with the line written by each `print`, instead of writing it to stderr.
+    pub(crate) print_handler: Option<&'a mut dyn FnMut(&str)>,
     // The Starlark-level call-stack of functions.
-    // Must go last because it's quite a big structure
     pub(crate) call_stack: CallStack<'v>,
 }
 
@@ -132,32 +210,204 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             call_stack: CallStack::default(),
             module_env: module,
             module_variables: None,
-            local_variables: LocalSlots::new(),
+            local_variables: LocalSlots::with_capacity(LOCAL_SLOTS_CAPACITY),
             loader: None,
+            source_loader: None,
+            ast_transformer: None,
             extra: None,
             extra_v: None,
             next_gc_level: GC_THRESHOLD,
             disable_gc: false,
+            has_limits: false,
+            max_allocations: u64::MAX,
+            max_heap_bytes: usize::MAX,
+            deadline: None,
+            deadline_countdown: Cell::new(0),
+            top_level_errors: None,
+            compiled_global_references: None,
+            disabled_builtins: Vec::new(),
+            round_half_up: false,
+            random_seed: 0,
             alloca: Alloca::new(),
             heap_profile: HeapProfile::new(),
             stmt_profile: StmtProfile::new(),
+            top_level_times: TopLevelTimes::new(),
+            gc_stats: GcStatsCollector::new(),
+            coverage: Coverage::new(),
             bc_profile: BcProfile::new(),
+            bc_trace: None,
+            before_instr: None,
             flame_profile: FlameProfile::new(),
             heap_or_flame_profile: false,
             before_stmt: Vec::new(),
+            before_line: None,
+            last_line: None,
             def_info: DefInfo::empty(), // Will be replaced before it is used
             string_pool: StringPool::default(),
             breakpoint_handler: None,
+            print_handler: None,
         }
     }
 
     /// Disables garbage collection from now onwards. Cannot be re-enabled.
     /// Usually called because you have captured [`Value`]'s unsafely, either in
     /// global variables or the [`extra`](Evaluator::extra) field.
+    ///
+    /// Modules evaluated afterwards are also compiled without the checks for whether to
+    /// collect, which makes short scripts slightly faster. Without collection the heap only
+    /// grows, so this is only suitable for trusted scripts which don't run for long.
     pub fn disable_gc(&mut self) {
         self.disable_gc = true;
     }
 
+    /// Limit the number of elements in a collection, both when created by materializing
+    /// an iterable, e.g. `list(range(n))` or `tuple(xs)`, and when a list or dictionary grows,
+    /// e.g. by `append`, `+=` or a comprehension. Exceeding the limit is an error, which is
+    /// raised before allocating whenever the length is known in advance.
+    /// By default there is no limit.
+    pub fn set_max_collection_length(&mut self, max: usize) {
+        self.heap().set_max_collection_length(max);
+    }
+
+    /// Limit the length in bytes of a string built by `+`, `*` or `join`, so a script
+    /// can't build a huge string before [`set_max_heap_bytes`](Evaluator::set_max_heap_bytes)
+    /// is next checked. By default there is no limit.
+    pub fn set_max_string_length(&mut self, max: usize) {
+        self.heap().set_max_string_length(max);
+    }
+
+    /// Limit the total number of values allocated on the heap, which is often a more
+    /// predictable measure of work than the number of bytes. The limit is checked on each
+    /// function call and loop iteration, raising an error once it has been exceeded.
+    /// Allocations are only counted from when a limit is first set, so there is no cost
+    /// without one. By default there is no limit.
+    pub fn set_max_allocations(&mut self, max: u64) {
+        self.heap().enable_allocation_count();
+        self.max_allocations = max;
+        self.has_limits = true;
+    }
+
+    /// Limit the number of bytes allocated on the heap, so a script building huge values
+    /// fails with an error rather than exhausting the memory of the process. Like
+    /// [`set_max_allocations`](Evaluator::set_max_allocations), the size is checked
+    /// on each function call and loop iteration. It is also checked before allocating
+    /// a string or collection whose size is known in advance, e.g. `"a" * n` or
+    /// `list(range(n))`, so a single huge value fails before it is allocated.
+    /// Memory reclaimed by garbage collection no longer counts towards the limit.
+    /// By default there is no limit.
+    pub fn set_max_heap_bytes(&mut self, max: usize) {
+        self.heap().set_max_bytes(max);
+        self.max_heap_bytes = max;
+        self.has_limits = true;
+    }
+
+    /// Limit the depth of nested function calls, so runaway recursion is an error
+    /// rather than overflowing the native stack. Each Starlark call uses a significant
+    /// amount of native stack, so raising the limit may require running on a thread
+    /// with a larger stack. The default is 40.
+    pub fn set_max_call_depth(&mut self, max: usize) {
+        self.call_stack.set_max_size(max);
+    }
+
+    /// Fail evaluation once `timeout` has elapsed, counting from when this function is called.
+    /// Like [`set_max_allocations`](Evaluator::set_max_allocations), the limit is checked
+    /// on function calls and loop iterations, but the clock is only read on every thousandth
+    /// check, so the error may come slightly after the timeout. By default there is no timeout.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.set_deadline_impl(Instant::now() + timeout, timeout);
+    }
+
+    /// Like [`set_timeout`](Evaluator::set_timeout), but fail once the wall-clock
+    /// `deadline` has passed, e.g. to share one deadline between several evaluations.
+    pub fn set_deadline(&mut self, deadline: Instant) {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.set_deadline_impl(deadline, timeout);
+    }
+
+    fn set_deadline_impl(&mut self, deadline: Instant, timeout: Duration) {
+        self.deadline = Some((deadline, timeout));
+        self.deadline_countdown.set(0);
+        self.has_limits = true;
+    }
+
+    /// Run `f` with a [`set_timeout`](Evaluator::set_timeout) of `timeout`, then put back
+    /// the previous deadline, so later code doesn't pay for checking limits it didn't set.
+    pub(crate) fn with_timeout<R>(
+        &mut self,
+        timeout: Duration,
+        f: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let old_deadline = self.deadline;
+        let old_countdown = self.deadline_countdown.get();
+        let old_has_limits = self.has_limits;
+        self.set_timeout(timeout);
+        let res = f(self);
+        self.deadline = old_deadline;
+        self.deadline_countdown.set(old_countdown);
+        self.has_limits = old_has_limits;
+        res
+    }
+
+    /// Record which names from [`Globals`](crate::environment::Globals) are referenced
+    /// by the code of the modules compiled from now on,
+    /// see [`compiled_global_references`](Evaluator::compiled_global_references).
+    /// Must be called before evaluating any code.
+    pub fn enable_compiled_global_references(&mut self) {
+        self.compiled_global_references = Some(BTreeSet::new());
+    }
+
+    /// The names from [`Globals`](crate::environment::Globals) referenced by the code of the
+    /// modules compiled since
+    /// [`enable_compiled_global_references`](Evaluator::enable_compiled_global_references)
+    /// was called, or an empty set if recording is not enabled.
+    ///
+    /// Globals are resolved to their values when a module is compiled, before any of it runs,
+    /// so this is a static record: a name is included if any code in the module refers to it,
+    /// even if that code is never executed. It is an upper bound on the globals a run can read,
+    /// e.g. to check a script only uses allowed builtins. Names shadowed by module or local
+    /// variables are not included.
+    pub fn compiled_global_references(&self) -> BTreeSet<String> {
+        self.compiled_global_references.clone().unwrap_or_default()
+    }
+
+    /// Disable the named functions from [`Globals`](crate::environment::Globals),
+    /// e.g. `print` or `fail`, so that calling them fails, while everything else in the
+    /// [`Globals`](crate::environment::Globals) still works.
+    /// Only affects modules evaluated after this call.
+    pub fn disable_builtins(&mut self, names: &[&str]) {
+        self.disabled_builtins
+            .extend(names.iter().map(|x| (*x).to_owned()));
+    }
+
+    /// Share a single allocation between identical strings created during evaluation,
+    /// saving memory for programs which create many duplicate strings, at the cost of a
+    /// lookup for every string allocated. Strings are immutable, so this has no effect
+    /// on the results of evaluation.
+    pub fn enable_string_interning(&mut self) {
+        self.heap().enable_string_interning();
+    }
+
+    /// Make the `round` function round values halfway between two choices away from zero,
+    /// e.g. `round(2.5) == 3`, rather than to the even choice as Python does. `round` is
+    /// added by [`LibraryExtension::Round`](crate::environment::LibraryExtension::Round).
+    pub fn enable_round_half_up(&mut self) {
+        self.round_half_up = true;
+    }
+
+    /// Set the seed used by builtins which make random choices.
+    ///
+    /// None of the standard builtins are random, so evaluating the same program twice
+    /// always produces the same result. Builtins which do introduce randomness must derive it
+    /// from [`random_seed`](Evaluator::random_seed), so that results can be reproduced.
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.random_seed = seed;
+    }
+
+    /// The seed set by [`set_random_seed`](Evaluator::set_random_seed), `0` by default.
+    pub fn random_seed(&self) -> u64 {
+        self.random_seed
+    }
+
     /// Set the [`FileLoader`] used to resolve `load()` statements.
     /// A list of all load statements can be obtained through
     /// [`AstModule::loads`](crate::syntax::AstModule::loads).
@@ -165,6 +415,35 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.loader = Some(loader);
     }
 
+    /// Set the [`SourceLoader`] which serves files, so hosts can provide sources from memory,
+    /// an archive or a network store without touching disk. Builtins which read files must go
+    /// through [`load_source`](Evaluator::load_source). To resolve `load()` statements from the
+    /// same files, pass a [`CachingFileLoader`](crate::eval::CachingFileLoader) wrapping it to
+    /// [`set_loader`](Evaluator::set_loader), which also sets it on each module it compiles.
+    pub fn set_source_loader(&mut self, source_loader: &'a dyn SourceLoader) {
+        self.source_loader = Some(source_loader);
+    }
+
+    /// Set a function to rewrite each [`AstModule`] between parsing and compilation, e.g. to
+    /// inject implicit imports with [`AstModule::add_load`]. It is applied by
+    /// [`eval_module`](Evaluator::eval_module) and friends, but not to modules evaluated by
+    /// the [`FileLoader`], which have their own [`Evaluator`].
+    pub fn set_ast_transformer(
+        &mut self,
+        transformer: &'a dyn Fn(AstModule) -> anyhow::Result<AstModule>,
+    ) {
+        self.ast_transformer = Some(transformer);
+    }
+
+    /// Read the file `path` through the [`SourceLoader`] set by
+    /// [`set_source_loader`](Evaluator::set_source_loader).
+    pub fn load_source(&self, path: &str) -> anyhow::Result<ModuleSource> {
+        match self.source_loader {
+            None => Err(EvaluatorError::NoSourceLoader(path.to_owned()).into()),
+            Some(source_loader) => source_loader.load_source(path),
+        }
+    }
+
     /// Enable profiling, allowing [`Evaluator::write_heap_profile`] to be used.
     /// Has the side effect of disabling garbage-collection.
     ///
@@ -205,6 +484,27 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.bc_profile.enable();
     }
 
+    /// Write a line to `out` for each bytecode instruction executed, giving its address
+    /// within the function, opcode, span and the `repr` of the operands it pops from the
+    /// stack, e.g. `@5 Add test.star:2:12-17 [1, 2]`, to help debug the interpreter.
+    /// Only code compiled afterwards is traced. Code compiled without tracing
+    /// pays no cost for it.
+    pub fn enable_bytecode_trace(&mut self, out: &'a mut dyn io::Write) {
+        self.bc_trace = Some(out);
+    }
+
+    /// Called before each bytecode instruction is run, with the address of the instruction
+    /// within its function, its [`FileSpan`] and a reference to the containing [`Evaluator`],
+    /// e.g. to implement a step debugger. Returning an error aborts evaluation with that error.
+    /// Like [`enable_bytecode_trace`](Evaluator::enable_bytecode_trace), only code compiled
+    /// afterwards calls `f`, and code compiled without it pays no cost.
+    pub fn before_instr(
+        &mut self,
+        f: &'a dyn Fn(u32, FileSpan, &mut Evaluator<'v, 'a>) -> anyhow::Result<()>,
+    ) {
+        self.before_instr = Some(f);
+    }
+
     /// Enable statement profiling, allowing [`Evaluator::write_flame_profile`] to be used.
     /// See [`Evaluator::enable_heap_profile`] for details about the types of Starlark profiles.
     pub fn enable_flame_profile(&mut self) {
@@ -251,6 +551,20 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.bc_profile.write_csv(filename.as_ref())
     }
 
+    /// The number of times each bytecode instruction was executed, identified by the name
+    /// of its opcode, most frequent first. Opcodes which were never executed are omitted.
+    /// The opcodes are an implementation detail, so change between versions.
+    /// Only valid if [`enable_bytecode_profile`](Self::enable_bytecode_profile) was called
+    /// before execution began.
+    pub fn bytecode_profile_counts(&self) -> anyhow::Result<Vec<(String, u64)>> {
+        Ok(self
+            .bc_profile
+            .counts()?
+            .into_iter()
+            .map(|(opcode, count)| (format!("{:?}", opcode), count))
+            .collect())
+    }
+
     /// Write a profile to a file, suitable as input to
     /// [flamegraph.pl](https://github.com/brendangregg/FlameGraph/blob/master/flamegraph.pl).
     /// Only valid if [`enable_flame_profile`](Evaluator::enable_flame_profile) was called before execution began.
@@ -261,6 +575,67 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             .unwrap_or_else(|| Err(EvaluatorError::FlameProfilingNotEnabled.into()))
     }
 
+    /// Record the time taken by each top-level statement of the modules evaluated, allowing
+    /// [`Evaluator::top_level_stmt_times`] to be used. Useful to find which part of a build
+    /// file is slow, without the detail of [`enable_stmt_profile`](Evaluator::enable_stmt_profile).
+    pub fn enable_top_level_stmt_times(&mut self) {
+        self.top_level_times.enable();
+        self.before_stmt(&|span, eval| {
+            eval.top_level_times
+                .before_stmt(span, &eval.def_info.codemap)
+        });
+    }
+
+    /// The span and time taken of each top-level statement executed, in the order they ran.
+    /// The time of a statement includes any functions it calls.
+    /// Only valid if [`enable_top_level_stmt_times`](Evaluator::enable_top_level_stmt_times)
+    /// was called before execution began.
+    pub fn top_level_stmt_times(&self) -> anyhow::Result<Vec<(FileSpan, Duration)>> {
+        self.top_level_times
+            .times()
+            .ok_or_else(|| EvaluatorError::TopLevelStmtTimesNotEnabled.into())
+    }
+
+    /// Record which statements are executed, see [`coverage`](Evaluator::coverage).
+    pub fn enable_coverage(&mut self) {
+        self.coverage.enable();
+        self.before_stmt(&|span, eval| eval.coverage.before_stmt(span, &eval.def_info.codemap));
+    }
+
+    /// The lines on which a statement was executed, sorted and numbered from 0 as in
+    /// [`ResolvedSpan`](crate::codemap::ResolvedSpan), for each file by name.
+    /// Only valid if [`enable_coverage`](Evaluator::enable_coverage) was called before
+    /// execution began.
+    pub fn coverage(&self) -> anyhow::Result<BTreeMap<String, Vec<usize>>> {
+        self.coverage
+            .lines()
+            .ok_or_else(|| EvaluatorError::CoverageNotEnabled.into())
+    }
+
+    /// Collect statistics about garbage collection, see [`gc_stats`](Evaluator::gc_stats).
+    pub fn enable_gc_stats(&mut self) {
+        self.gc_stats.enable(self.heap());
+    }
+
+    /// The number of garbage collections and bytes allocated and reclaimed since
+    /// [`enable_gc_stats`](Evaluator::enable_gc_stats) was called, which is useful
+    /// to judge how often garbage collection is worthwhile for a workload.
+    pub fn gc_stats(&self) -> anyhow::Result<GcStats> {
+        self.gc_stats
+            .stats(self.heap())
+            .ok_or_else(|| EvaluatorError::GcStatsNotEnabled.into())
+    }
+
+    /// Obtain the time spent in each function, excluding the time spent in the functions it
+    /// calls, sorted with the most expensive function first. Functions are identified by their
+    /// `repr`. Whereas the flame profile shows hot call paths, this shows hot functions.
+    /// Only valid if [`enable_flame_profile`](Evaluator::enable_flame_profile) was called before execution began.
+    pub fn flame_profile_self_time(&self) -> anyhow::Result<Vec<(String, Duration)>> {
+        self.flame_profile
+            .self_time()
+            .ok_or_else(|| EvaluatorError::FlameSelfTimeNotEnabled.into())
+    }
+
     /// Enable interactive `breakpoint()`. When enabled, `breakpoint()`
     /// reads commands from stdin and write to stdout.
     /// When disabled (default), `breakpoint()` function results in error.
@@ -268,6 +643,12 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.breakpoint_handler = Some(RealBreakpointConsole::factory());
     }
 
+    /// Send the output of `print` to `handler`, one call per `print`, without the
+    /// trailing newline. By default `print` writes to stderr.
+    pub fn set_print_handler(&mut self, handler: &'a mut dyn FnMut(&str)) {
+        self.print_handler = Some(handler);
+    }
+
     /// Obtain the current call-stack, suitable for use with [`Diagnostic`].
     pub fn call_stack(&self) -> Vec<Frame> {
         self.call_stack.to_diagnostic_frames()
@@ -288,6 +669,30 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.before_stmt.push(f)
     }
 
+    /// Called before a statement is run if it is on a different line to the previous statement,
+    /// with the [`FileSpan`] of the statement. That is what line coverage or line breakpoints
+    /// need, and is cheaper than [`before_stmt`](Evaluator::before_stmt) for the callback.
+    ///
+    /// This function may have no effect is called mid evaluation.
+    pub fn before_line(&mut self, f: &'a dyn Fn(FileSpan, &mut Evaluator<'v, 'a>)) {
+        self.before_line = Some(f);
+        self.before_stmt(&|span, eval| eval.before_line_stmt(span));
+    }
+
+    fn before_line_stmt(&mut self, span: Span) {
+        let codemap = self.def_info.codemap.dupe();
+        let line = codemap.find_line(span.begin());
+        if let Some((last_codemap, last_line)) = &self.last_line {
+            if *last_codemap == codemap && *last_line == line {
+                return;
+            }
+        }
+        self.last_line = Some((codemap.dupe(), line));
+        if let Some(f) = self.before_line {
+            f(codemap.file_span(span), self)
+        }
+    }
+
     /// Given a [`Span`] resolve it to a concrete [`FileSpan`] using
     /// whatever module is currently at the top of the stack.
     /// This function can be used in conjunction with [`before_stmt`](Evaluator::before_stmt).
@@ -301,6 +706,71 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         true
     }
 
+    /// Check that a collection of `len` elements is within
+    /// [`set_max_collection_length`](Evaluator::set_max_collection_length).
+    #[inline(always)]
+    pub(crate) fn check_collection_length(&self, len: usize) -> anyhow::Result<()> {
+        self.heap().check_collection_length(len)
+    }
+
+    /// Fail if more than [`set_max_allocations`](Evaluator::set_max_allocations)
+    /// values or [`set_max_heap_bytes`](Evaluator::set_max_heap_bytes) bytes have been
+    /// allocated, or the [`set_timeout`](Evaluator::set_timeout) deadline has passed.
+    #[inline(always)]
+    pub(crate) fn check_limits(&self) -> anyhow::Result<()> {
+        if unlikely(self.has_limits) {
+            self.check_limits_slow()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether any limit checked on each call is set, other than the default call depth.
+    pub(crate) fn has_call_limits(&self) -> bool {
+        self.has_limits || !self.call_stack.has_default_max_size()
+    }
+
+    #[inline(never)]
+    fn check_limits_slow(&self) -> anyhow::Result<()> {
+        if unlikely(self.heap().allocation_count() > self.max_allocations) {
+            return Err(EvaluatorError::TooManyAllocations(self.max_allocations).into());
+        }
+        if self.max_heap_bytes != usize::MAX
+            && unlikely(self.heap().allocated_bytes() > self.max_heap_bytes)
+        {
+            return Err(EvaluatorError::HeapTooLarge(self.max_heap_bytes).into());
+        }
+        if let Some((deadline, timeout)) = self.deadline {
+            let countdown = self.deadline_countdown.get();
+            if countdown > 0 {
+                self.deadline_countdown.set(countdown - 1);
+            } else {
+                self.deadline_countdown.set(DEADLINE_CHECK_INTERVAL);
+                if unlikely(Instant::now() >= deadline) {
+                    return Err(EvaluatorError::Timeout(timeout).into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Collect all the elements of an iterator, failing if there would be more than
+    /// [`set_max_collection_length`](Evaluator::set_max_collection_length) of them.
+    /// Uses the `size_hint` to fail before allocating where possible.
+    pub(crate) fn collect_bounded(
+        &self,
+        it: &mut dyn Iterator<Item = Value<'v>>,
+    ) -> anyhow::Result<Vec<Value<'v>>> {
+        let (lower, _) = it.size_hint();
+        self.check_collection_length(lower)?;
+        let mut res = Vec::with_capacity(lower);
+        for x in it {
+            self.check_collection_length(res.len() + 1)?;
+            res.push(x);
+        }
+        Ok(res)
+    }
+
     /// Called to add an entry to the call stack, by the function being invoked.
     /// Called for all types of function, including those written in Rust.
     #[inline(always)]
@@ -319,6 +789,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             })
         }
 
+        self.check_limits()?;
         self.call_stack.push(
             function,
             span.unwrap_or_default(),
@@ -505,7 +976,14 @@ impl<'v, 'a> Evaluator<'v, 'a> {
     /// and using them will lead to a segfault.
     /// Do not call during Starlark evaluation.
     pub unsafe fn garbage_collect(&mut self) {
-        self.heap().garbage_collect(|tracer| self.trace(tracer))
+        if self.gc_stats.enabled() {
+            let before = self.heap().allocated_bytes();
+            self.heap().garbage_collect(|tracer| self.trace(tracer));
+            let after = self.heap().allocated_bytes();
+            self.gc_stats.garbage_collected(before, after);
+        } else {
+            self.heap().garbage_collect(|tracer| self.trace(tracer))
+        }
     }
 
     /// Note that the `Drop` for the `T` will not be called. That's safe if there is no `Drop`,
@@ -18,12 +18,19 @@
 //! Define variants of the evaluation function with different support
 //! for the `load(...)` statement.
 
-use std::collections::HashMap;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use gazebo::prelude::*;
 
-use crate::environment::FrozenModule;
+use crate::{
+    environment::{FrozenModule, Globals, Module},
+    eval::Evaluator,
+    syntax::{AstModule, Dialect},
+};
 
 /// A trait for turning a `path` given by a `load()` statement into a [`FrozenModule`].
 pub trait FileLoader {
@@ -51,3 +58,140 @@ impl<'a> FileLoader for ReturnFileLoader<'a> {
         }
     }
 }
+
+/// The source code of a module, as returned by a [`SourceLoader`].
+pub struct ModuleSource {
+    /// The Starlark source code.
+    pub source: String,
+    /// A digest of the module, such as a hash of its contents. Modules with the
+    /// same digest are assumed to be identical, so will only be compiled once.
+    /// If `None`, the module is compiled every time it is loaded.
+    pub digest: Option<String>,
+}
+
+/// A virtual file system, turning a `path` into source code. Used by a
+/// [`CachingFileLoader`] to resolve `load()` statements, and by builtins which read
+/// files through [`Evaluator::load_source`].
+pub trait SourceLoader {
+    /// Get the contents of the file `path`, or an error if it does not exist.
+    fn load_source(&self, path: &str) -> anyhow::Result<ModuleSource>;
+}
+
+/// [`FileLoader`] that compiles modules from the source returned by a [`SourceLoader`],
+/// caching the resulting [`FrozenModule`] by the digest of the source.
+///
+/// Reusing a single loader across top-level evaluations means unchanged dependencies
+/// are not recompiled. Since the cache is keyed only by the digest, a digest should
+/// change whenever the module, or anything it transitively loads, changes.
+pub struct CachingFileLoader<'a> {
+    loader: &'a dyn SourceLoader,
+    globals: &'a Globals,
+    dialect: Dialect,
+    cache: RefCell<HashMap<String, FrozenModule>>,
+    // Paths currently being loaded, to detect cycles.
+    loading: RefCell<Vec<String>>,
+    // Number of modules compiled so far.
+    compiled: Cell<usize>,
+    max_load_depth: usize,
+    max_loads: usize,
+}
+
+impl<'a> CachingFileLoader<'a> {
+    /// Create a loader that compiles modules with the given [`Globals`] and [`Dialect`].
+    pub fn new(loader: &'a dyn SourceLoader, globals: &'a Globals, dialect: Dialect) -> Self {
+        Self {
+            loader,
+            globals,
+            dialect,
+            cache: RefCell::new(HashMap::new()),
+            loading: RefCell::new(Vec::new()),
+            compiled: Cell::new(0),
+            max_load_depth: usize::MAX,
+            max_loads: usize::MAX,
+        }
+    }
+
+    /// Fail if a chain of nested `load()` statements is deeper than `depth`, e.g. with
+    /// a depth of `1` modules loaded from the top-level module can't load anything.
+    /// Unlimited by default.
+    pub fn set_max_load_depth(&mut self, depth: usize) {
+        self.max_load_depth = depth;
+    }
+
+    /// Fail if more than `count` modules are compiled by this loader, protecting against
+    /// dependency graphs which repeatedly load modules that can't be cached.
+    /// Modules reused from the cache are not counted. Unlimited by default.
+    pub fn set_max_loads(&mut self, count: usize) {
+        self.max_loads = count;
+    }
+
+    // The chain of loads ending in `path`.
+    fn load_chain(&self, path: &str) -> String {
+        let mut res = self.loading.borrow().join(" -> ");
+        if !res.is_empty() {
+            res.push_str(" -> ");
+        }
+        res.push_str(path);
+        res
+    }
+
+    fn compile(&self, path: &str, source: String) -> anyhow::Result<FrozenModule> {
+        let ast = AstModule::parse(path, source, &self.dialect)?;
+        let module = Module::new();
+        {
+            let mut eval = Evaluator::new(&module);
+            eval.set_loader(self);
+            eval.set_source_loader(self.loader);
+            eval.eval_module(ast, self.globals)?;
+        }
+        module.freeze()
+    }
+}
+
+impl<'a> FileLoader for CachingFileLoader<'a> {
+    fn load(&self, path: &str) -> anyhow::Result<FrozenModule> {
+        let ModuleSource { source, digest } = self
+            .loader
+            .load_source(path)
+            .with_context(|| format!("When loading module `{}`", path))?;
+        if let Some(digest) = &digest {
+            if let Some(module) = self.cache.borrow().get(digest) {
+                return Ok(module.dupe());
+            }
+        }
+
+        if let Some(i) = self.loading.borrow().iter().position(|x| x == path) {
+            let cycle = self.loading.borrow()[i..].join(" -> ");
+            return Err(anyhow!(
+                "Cycle detected when loading module `{}`: {} -> {}",
+                path,
+                cycle,
+                path
+            ));
+        }
+        if self.loading.borrow().len() >= self.max_load_depth {
+            return Err(anyhow!(
+                "Exceeded the maximum load depth of {}: {}",
+                self.max_load_depth,
+                self.load_chain(path)
+            ));
+        }
+        if self.compiled.get() >= self.max_loads {
+            return Err(anyhow!(
+                "Exceeded the maximum of {} loaded modules: {}",
+                self.max_loads,
+                self.load_chain(path)
+            ));
+        }
+        self.compiled.set(self.compiled.get() + 1);
+        self.loading.borrow_mut().push(path.to_owned());
+        let res = self.compile(path, source);
+        self.loading.borrow_mut().pop();
+
+        let module = res?;
+        if let Some(digest) = digest {
+            self.cache.borrow_mut().insert(digest, module.dupe());
+        }
+        Ok(module)
+    }
+}
@@ -196,6 +196,40 @@ impl<'v> FlameProfile<'v> {
         })
     }
 
+    /// Time spent in each function excluding its callees, most expensive first.
+    pub(crate) fn self_time(&self) -> Option<Vec<(String, Duration)>> {
+        self.0.as_ref().map(|box x| Self::self_time_enabled(x))
+    }
+
+    fn self_time_enabled(x: &FlameData) -> Vec<(String, Duration)> {
+        // Whatever is on top of the stack is charged for the time until the next event,
+        // so time spent in a callee is never charged to its caller.
+        let mut times = vec![Duration::default(); x.values.len()];
+        let mut stack: Vec<ValueIndex> = Vec::new();
+        let mut last_time = None;
+        for (frame, time) in &x.frames {
+            if let (Some(top), Some(last)) = (stack.last(), last_time) {
+                times[top.0] += time.duration_since(last);
+            }
+            last_time = Some(*time);
+            match frame {
+                Frame::Push(i) => stack.push(*i),
+                Frame::Pop => {
+                    stack.pop();
+                }
+            }
+        }
+
+        // Distinct values can have the same name, so merge by name.
+        let mut by_name: HashMap<String, Duration> = HashMap::new();
+        for (value, time) in x.values.iter().zip(times) {
+            *by_name.entry(value.to_repr()).or_default() += time;
+        }
+        let mut res: Vec<(String, Duration)> = by_name.into_iter().collect();
+        res.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        res
+    }
+
     fn write_profile_to(x: &FlameData, file: impl Write) -> io::Result<()> {
         // Need to write out lines which look like:
         // root;calls1;calls2 1
@@ -204,3 +238,53 @@ impl<'v> FlameProfile<'v> {
         Stacks::new(&names, &x.frames).render(file)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+    };
+
+    #[test]
+    fn test_self_time() -> anyhow::Result<()> {
+        let ast = AstModule::parse(
+            "foo.bzl",
+            r#"
+def leaf():
+    x = 0
+    for i in range(100000):
+        x += i
+    return x
+def wrapper():
+    return leaf()
+wrapper()
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )?;
+        let globals = Globals::standard();
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_flame_profile();
+        eval.eval_module(ast, &globals)?;
+
+        let self_time = eval.flame_profile_self_time()?;
+        // The wrapper has a high total time, but all of that is spent in the leaf
+        assert!(self_time[0].0.starts_with("leaf"), "{:?}", self_time);
+        let wrapper = self_time
+            .iter()
+            .find(|x| x.0.starts_with("wrapper"))
+            .unwrap();
+        assert!(wrapper.1 < self_time[0].1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_self_time_not_enabled() {
+        let module = Module::new();
+        let eval = Evaluator::new(&module);
+        assert!(eval.flame_profile_self_time().is_err());
+    }
+}
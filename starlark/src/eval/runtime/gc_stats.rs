@@ -0,0 +1,78 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::values::Heap;
+
+/// Statistics about garbage collection, see [`Evaluator::gc_stats`](crate::eval::Evaluator::gc_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    /// Number of garbage collections performed.
+    pub cycles: usize,
+    /// Bytes allocated on the heap, including those later reclaimed.
+    pub allocated_bytes: usize,
+    /// Most bytes the heap has held at once.
+    pub peak_allocated_bytes: usize,
+    /// Bytes reclaimed by garbage collection.
+    pub reclaimed_bytes: usize,
+}
+
+// When not enabled, we want this to be small and cheap
+pub(crate) struct GcStatsCollector(Option<Box<GcStatsData>>);
+
+struct GcStatsData {
+    // Bytes allocated on the heap when collection was enabled.
+    start_bytes: usize,
+    cycles: usize,
+    reclaimed_bytes: usize,
+}
+
+impl GcStatsCollector {
+    pub(crate) fn new() -> Self {
+        Self(None)
+    }
+
+    pub(crate) fn enable(&mut self, heap: &Heap) {
+        self.0 = Some(box GcStatsData {
+            start_bytes: heap.allocated_bytes(),
+            cycles: 0,
+            reclaimed_bytes: 0,
+        })
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Record a garbage collection, which shrank the heap from `before` to `after` bytes.
+    pub(crate) fn garbage_collected(&mut self, before: usize, after: usize) {
+        if let Some(box data) = &mut self.0 {
+            data.cycles += 1;
+            data.reclaimed_bytes += before.saturating_sub(after);
+        }
+    }
+
+    pub(crate) fn stats(&self, heap: &Heap) -> Option<GcStats> {
+        let data = self.0.as_ref()?;
+        Some(GcStats {
+            cycles: data.cycles,
+            allocated_bytes: (heap.allocated_bytes() + data.reclaimed_bytes)
+                .saturating_sub(data.start_bytes),
+            peak_allocated_bytes: heap.peak_allocated_bytes(),
+            reclaimed_bytes: data.reclaimed_bytes,
+        })
+    }
+}
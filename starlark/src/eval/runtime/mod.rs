@@ -18,10 +18,13 @@
 pub(crate) mod arguments;
 pub(crate) mod bc_profile;
 pub(crate) mod call_stack;
+pub(crate) mod coverage;
 pub(crate) mod csv;
 pub(crate) mod evaluator;
 pub(crate) mod file_loader;
 pub(crate) mod flame_profile;
+pub(crate) mod gc_stats;
 pub(crate) mod heap_profile;
 pub(crate) mod slots;
 pub(crate) mod stmt_profile;
+pub(crate) mod top_level_times;
@@ -56,15 +56,19 @@ pub(crate) struct LocalSlots<'v> {
 }
 
 impl<'v> LocalSlots<'v> {
-    pub fn new() -> Self {
+    /// Slots for `capacity` variables across all frames are allocated up front,
+    /// so calls don't reallocate until the stack is deeper than that.
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            slots: Vec::new(),
+            slots: Vec::with_capacity(capacity),
             base: LocalSlotBase(0),
         }
     }
 
     pub fn reserve(&mut self, len: u32) -> LocalSlotBase {
         let res = LocalSlotBase(self.slots.len().try_into().unwrap());
+        // `resize` grows the capacity geometrically, and `release` never shrinks it,
+        // so the slots are reallocated at most a logarithmic number of times.
         self.slots
             .resize(self.slots.len() + len as usize, Cell::new(None));
         res
@@ -90,6 +94,25 @@ impl<'v> LocalSlots<'v> {
         &self.slots[base.0 as usize..]
     }
 
+    /// The base of the frame currently being executed.
+    pub fn base(&self) -> LocalSlotBase {
+        self.base
+    }
+
+    /// The slots of the frame at `base` paired with their names, which are indexed by
+    /// [`LocalSlotId`]. Slots which are not yet assigned are `None`.
+    pub fn get_named_slots_at<'n>(
+        &self,
+        base: LocalSlotBase,
+        names: &'n [String],
+    ) -> Vec<(&'n str, Option<Value<'v>>)> {
+        names
+            .iter()
+            .zip(self.get_slots_at(base))
+            .map(|(name, slot)| (name.as_str(), slot.get()))
+            .collect()
+    }
+
     /// Gets a local variable. Returns None to indicate the variable is not yet assigned.
     #[inline(always)]
     pub fn get_slot(&self, slot: LocalSlotId) -> Option<Value<'v>> {
@@ -100,3 +123,23 @@ impl<'v> LocalSlots<'v> {
         self.slots[self.base.0 as usize + slot.0 as usize].set(Some(value));
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::eval::runtime::slots::LocalSlots;
+
+    #[test]
+    fn test_reserve_within_capacity() {
+        let mut slots = LocalSlots::with_capacity(10);
+        let ptr = slots.slots.as_ptr();
+        let outer = slots.reserve(4);
+        let old = slots.utilise(outer);
+        let inner = slots.reserve(6);
+        let outer = slots.utilise(inner);
+        assert_eq!(10, slots.slots.len());
+        slots.release(outer);
+        slots.release(old);
+        assert_eq!(0, slots.slots.len());
+        assert_eq!(ptr, slots.slots.as_ptr());
+    }
+}
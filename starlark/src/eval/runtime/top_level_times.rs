@@ -0,0 +1,157 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use gazebo::prelude::*;
+
+use crate::codemap::{CodeMap, FileSpan, Span};
+
+/// Time taken by each top-level statement of the modules evaluated.
+// When not enabled, we want this to be small and cheap
+pub(crate) struct TopLevelTimes(Option<Box<TopLevelTimesData>>);
+
+struct TopLevelTimesData {
+    // The module being evaluated, and the spans of its top-level statements.
+    codemap: CodeMap,
+    stmts: HashSet<Span>,
+    // The top-level statement currently running, and when it started.
+    current: Option<(Span, Instant)>,
+    times: Vec<(FileSpan, Duration)>,
+}
+
+impl TopLevelTimesData {
+    fn finish_current(&mut self, now: Instant) {
+        if let Some((span, start)) = self.current.take() {
+            self.times
+                .push((self.codemap.file_span(span), now.duration_since(start)));
+        }
+    }
+}
+
+impl TopLevelTimes {
+    pub(crate) fn new() -> Self {
+        Self(None)
+    }
+
+    pub(crate) fn enable(&mut self) {
+        self.0 = Some(box TopLevelTimesData {
+            codemap: CodeMap::default(),
+            stmts: HashSet::new(),
+            current: None,
+            times: Vec::new(),
+        })
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Start evaluating a module whose top-level statements have the spans `stmts`.
+    pub(crate) fn start_module(&mut self, codemap: CodeMap, stmts: impl Iterator<Item = Span>) {
+        if let Some(box data) = &mut self.0 {
+            data.codemap = codemap;
+            data.stmts = stmts.collect();
+        }
+    }
+
+    pub(crate) fn finish_module(&mut self) {
+        if let Some(box data) = &mut self.0 {
+            data.finish_current(Instant::now());
+        }
+    }
+
+    pub(crate) fn before_stmt(&mut self, span: Span, codemap: &CodeMap) {
+        if let Some(box data) = &mut self.0 {
+            // Statements nested in the top-level ones, or in functions they call,
+            // count towards the top-level statement.
+            if *codemap == data.codemap && data.stmts.contains(&span) {
+                data.finish_current(Instant::now());
+                data.current = Some((span, Instant::now()));
+            }
+        }
+    }
+
+    // None = not applicable because not enabled
+    pub(crate) fn times(&self) -> Option<Vec<(FileSpan, Duration)>> {
+        self.0.as_ref().map(|data| data.times.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+    };
+
+    #[test]
+    fn test_top_level_times() -> anyhow::Result<()> {
+        let ast = AstModule::parse(
+            "foo.bzl",
+            r#"
+def slow():
+    x = 0
+    for i in range(200000):
+        x += i
+    return x
+a = 1
+b = slow()
+for i in range(3):
+    c = i
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )?;
+        let globals = Globals::standard();
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_top_level_stmt_times();
+        eval.eval_module(ast, &globals)?;
+
+        let times = eval.top_level_stmt_times()?;
+        let stmts: Vec<String> = times
+            .iter()
+            .map(|(span, _)| {
+                span.file
+                    .source_span(span.span)
+                    .lines()
+                    .next()
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect();
+        assert_eq!(
+            stmts,
+            &["def slow():", "a = 1", "b = slow()", "for i in range(3):"]
+        );
+        let slowest = times.iter().max_by_key(|x| x.1).unwrap();
+        assert_eq!(slowest.0.resolve_span().begin_line, 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_top_level_times_not_enabled() {
+        let module = Module::new();
+        let eval = Evaluator::new(&module);
+        assert!(eval.top_level_stmt_times().is_err());
+    }
+}
@@ -17,7 +17,13 @@
 
 //! Basic expression tests.
 
-use crate::{assert, assert::Assert, environment::GlobalsBuilder, values::OwnedFrozenValue};
+use crate::{
+    assert,
+    assert::Assert,
+    environment::{Globals, GlobalsBuilder, Module},
+    eval::Evaluator,
+    values::{OwnedFrozenValue, Value},
+};
 
 #[test]
 fn arithmetic_test() {
@@ -54,6 +60,41 @@ fn bitwise_test() {
     assert::fail("1 >> -13", "overflow");
 }
 
+#[test]
+fn test_int_overflow_boundary() {
+    assert::all_true(
+        r#"
+2147483646 + 1 == 2147483647
+-2147483647 - 1 < -2147483647
+65535 * 32768 == 2147450880
+-65536 * 32768 == -2147483647 - 1
+2147483647 + 0.5 == 2147483647.5
+"#,
+    );
+    assert::pass(
+        r#"
+def f():
+    x = 2147483640
+    for _ in range(7):
+        x += 1
+    return x
+assert_eq(f(), 2147483647)
+"#,
+    );
+    assert::fail("2147483647 + 1", "overflow");
+    assert::fail("-2147483647 - 2", "overflow");
+    assert::fail("65536 * 32768", "overflow");
+    assert::fail(
+        r#"
+def f():
+    x = 2147483647
+    x += 1
+f()
+"#,
+        "overflow",
+    );
+}
+
 #[test]
 fn test_equality() {
     assert::all_true(
@@ -191,6 +232,77 @@ xs == ys
     );
 }
 
+#[test]
+fn test_truthiness() {
+    // `bool`, `if`, `not`, `and`/`or`, conditional expressions and comprehension
+    // filters must all agree, whether the value is a constant or computed.
+    assert::pass(
+        r#"
+falsy = [None, False, 0, 0.0, -0.0, "", [], (), {}, range(0), range(3, 1), range(1, 3, -1)]
+truthy = [True, 1, -1, 0.5, float("nan"), "x", " ", [0], (None,), {0: 0}, range(1), range(3, 1, -1), struct(), len, lambda: None]
+def truth(x):
+    if x:
+        res = True
+    else:
+        res = False
+    assert_eq(res, bool(x))
+    assert_eq(not x, not bool(x))
+    assert_eq(True if x else False, bool(x))
+    assert_eq(x and True, x if not x else True)
+    assert_eq(x or False, x if x else False)
+    assert_eq([1 for _ in [0] if x], [1] if bool(x) else [])
+    return res
+for x in falsy:
+    assert_eq(truth(x), False)
+for x in truthy:
+    assert_eq(truth(x), True)
+assert_eq(bool(), False)
+assert_eq(any(falsy), False)
+assert_eq(all(truthy), True)
+# Constant folding in the compiler agrees too
+assert_eq(not range(0), True)
+assert_eq(True if () else False, False)
+assert_eq(0.0 or "default", "default")
+"#,
+    );
+}
+
+#[test]
+fn test_and_or_return_operand() {
+    // `and`/`or` return one of their operands, not a `bool`, and only evaluate
+    // the right operand when needed, both as expressions and as `if` conditions.
+    assert::pass(
+        r#"
+assert_eq(0 or "d", "d")
+assert_eq("" and 5, "")
+calls = []
+def f(x):
+    calls.append(x)
+    return x
+def check(a, b, c):
+    return f(a) and f(b) and f(c)
+assert_eq(check(1, "", 3), "")
+assert_eq(calls, [1, ""])
+calls.clear()
+assert_eq(f(0) or f([]) or f("x") or f(4), "x")
+assert_eq(calls, [0, [], "x"])
+calls.clear()
+def branch(a, b, c):
+    if f(a) and f(b) and f(c):
+        return "yes"
+    else:
+        return "no"
+assert_eq(branch(1, 0, 2), "no")
+assert_eq(calls, [1, 0])
+calls.clear()
+x = [] or {}
+assert_eq(x, {})
+y = [1] or 1 // 0
+assert_eq(y, [1])
+"#,
+    );
+}
+
 #[test]
 fn test_not_in_unhashable() {
     // Note that [] can't be hashed
@@ -224,3 +336,40 @@ x[y]
         "not hashable",
     );
 }
+
+#[test]
+fn test_eval_expr() {
+    let globals = Globals::standard();
+    let module = Module::new();
+    module.set("x", Value::new_int(10));
+    let mut eval = Evaluator::new(&module);
+    assert_eq!(
+        eval.eval_expr("1 + 2 * 3", &globals).unwrap().unpack_int(),
+        Some(7)
+    );
+    assert_eq!(
+        eval.eval_expr("[x, len('abc')]", &globals)
+            .unwrap()
+            .to_repr(),
+        "[10, 3]"
+    );
+    assert_eq!(
+        eval.eval_expr("'docs'", &globals).unwrap().unpack_str(),
+        Some("docs")
+    );
+    assert_eq!(
+        eval.eval_expr("x + 1  # comment\n\n", &globals)
+            .unwrap()
+            .unpack_int(),
+        Some(11)
+    );
+
+    for stmt in ["x = 1", "def f(): pass", "1\n2", "pass", "", "# comment\n"] {
+        let err = eval.eval_expr(stmt, &globals).unwrap_err();
+        assert!(
+            err.to_string().contains("Expected an expression"),
+            "{}",
+            err
+        );
+    }
+}
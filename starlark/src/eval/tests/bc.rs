@@ -17,13 +17,18 @@
 
 //! Bytecode generation tests.
 
+use std::cell::RefCell;
+
 use crate::{
     assert,
     assert::Assert,
-    eval::{bc::opcode::BcOpcode, FrozenDef},
+    codemap::FileSpan,
+    environment::{Globals, Module},
+    eval::{bc::opcode::BcOpcode, Evaluator, FrozenDef},
+    syntax::{AstModule, Dialect},
 };
 
-fn test_instrs(expected: &[BcOpcode], def_program: &str) {
+fn def_opcodes(def_program: &str) -> Vec<BcOpcode> {
     let mut a = Assert::new();
     let def = a
         .module("instrs.star", def_program)
@@ -33,7 +38,11 @@ fn test_instrs(expected: &[BcOpcode], def_program: &str) {
         .unwrap();
     let mut opcodes = def.bc().instrs.opcodes();
     assert_eq!(Some(BcOpcode::EndOfBc), opcodes.pop());
-    assert_eq!(expected, opcodes);
+    opcodes
+}
+
+fn test_instrs(expected: &[BcOpcode], def_program: &str) {
+    assert_eq!(expected, def_opcodes(def_program));
 }
 
 #[test]
@@ -44,6 +53,81 @@ fn test_type() {
     );
 }
 
+#[test]
+fn test_type_const() {
+    for body in [
+        "type(1)",
+        "type([1, 2])",
+        "type(1) == 'int'",
+        "type('x') != 'int'",
+    ] {
+        test_instrs(
+            &[BcOpcode::Const, BcOpcode::Return],
+            &format!("def test(): return {}", body),
+        );
+    }
+    // Module variables are constants once the module is frozen.
+    test_instrs(
+        &[BcOpcode::Const, BcOpcode::Return],
+        "X = 1\ndef test(): return type(X)",
+    );
+    test_instrs(
+        &[BcOpcode::Const, BcOpcode::Return],
+        "X = 1\ndef test(): return type(X) == 'int'",
+    );
+    // A list with side effects is still built.
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal,
+            BcOpcode::ListNPop,
+            BcOpcode::Type,
+            BcOpcode::Return,
+        ],
+        "def test(x): return type([x])",
+    );
+}
+
+#[test]
+fn test_type_const_eval() {
+    assert::pass(
+        r#"
+X = [1]
+def test():
+    return (type(1), type([1, 2]), type(X) == "list", type(X) != "list")
+assert_eq(test(), ("int", "list", True, False))
+"#,
+    );
+}
+
+#[test]
+fn test_len() {
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::Len, BcOpcode::Return],
+        "def test(x): return len(x)",
+    );
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal,
+            BcOpcode::ListNPop,
+            BcOpcode::Len,
+            BcOpcode::Return,
+        ],
+        "def test(x): return len([x])",
+    );
+}
+
+#[test]
+fn test_len_const() {
+    for expr in ["[1, 2, 3]", "(1, 2, 3)", "'abc'"] {
+        test_instrs(
+            &[BcOpcode::Const, BcOpcode::Return],
+            &format!("def test(): return len({})", expr),
+        );
+    }
+    // Constant arguments `len` rejects still fail at runtime.
+    assert::fail("def test(): return len(1)\ntest()", "not supported");
+}
+
 #[test]
 fn test_percent_s_one() {
     test_instrs(
@@ -60,6 +144,145 @@ fn test_format_one() {
     )
 }
 
+#[test]
+fn test_format_many() {
+    test_instrs(
+        &[BcOpcode::LoadLocal2, BcOpcode::FormatMany, BcOpcode::Return],
+        "def test(x, y): return '({}, {})'.format(x, y)",
+    )
+}
+
+#[test]
+fn test_format_many_eval() {
+    assert::pass(
+        r#"
+def test(x, y):
+    return "{{{}}} <{}>".format(x, y)
+
+assert_eq("{1} <a>", test(1, "a"))
+assert_eq("{[2]} <None>", test([2], None))
+"#,
+    );
+    // Argument counts which don't match still reach `format` and its errors.
+    assert::fail(
+        r#"
+def test(x, y):
+    return "{} {} {}".format(x, y)
+test(1, 2)
+"#,
+        "Not enough parameters",
+    );
+}
+
+#[test]
+fn test_list_of_consts() {
+    test_instrs(
+        &[BcOpcode::ListOfConsts, BcOpcode::Return],
+        "def test(): return [1, 2, 3]",
+    )
+}
+
+#[test]
+fn test_list_n() {
+    test_instrs(
+        &[BcOpcode::LoadLocal3, BcOpcode::ListNPop, BcOpcode::Return],
+        "def test(x, y, z): return [x, y, z]",
+    )
+}
+
+#[test]
+fn test_tuple_n() {
+    test_instrs(
+        &[BcOpcode::LoadLocal2, BcOpcode::TupleNPop, BcOpcode::Return],
+        "def test(x, y): return (x, y)",
+    )
+}
+
+#[test]
+fn test_dict_n() {
+    test_instrs(
+        &[BcOpcode::LoadLocal2, BcOpcode::DictNPop, BcOpcode::Return],
+        "def test(x, y): return {x: y}",
+    )
+}
+
+#[test]
+fn test_dict_const_keys() {
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal2,
+            BcOpcode::DictConstKeys,
+            BcOpcode::Return,
+        ],
+        "def test(x, y): return {'a': x, 'b': y}",
+    )
+}
+
+#[test]
+fn test_collection_literals_eval() {
+    assert::pass(
+        r#"
+def test(x, y):
+    return ([x, y, 3], (x, y, 3), {x: y, "k": 3}, {"a": x, "b": y}, [1, 2, 3])
+
+assert_eq(test(1, 2), ([1, 2, 3], (1, 2, 3), {1: 2, "k": 3}, {"a": 1, "b": 2}, [1, 2, 3]))
+# Each call creates new mutable collections
+xs = test(1, 2)
+xs[0].append(4)
+xs[4].append(4)
+assert_eq(test(1, 2)[0], [1, 2, 3])
+assert_eq(test(1, 2)[4], [1, 2, 3])
+"#,
+    );
+}
+
+#[test]
+fn test_percent_s_many() {
+    test_instrs(
+        &[BcOpcode::LoadLocal2, BcOpcode::FormatMany, BcOpcode::Return],
+        "def test(x, y): return '(%s, %s)' % (x, y)",
+    );
+    // Other conversions use the general `%`.
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal2,
+            BcOpcode::TupleNPop,
+            BcOpcode::Percent,
+            BcOpcode::Return,
+        ],
+        "def test(x, y): return '(%s, %d)' % (x, y)",
+    );
+}
+
+#[test]
+fn test_percent_s_many_eval() {
+    assert::pass(
+        r#"
+def test(x, y):
+    return "%s%% <%s>" % (x, y)
+
+assert_eq("1% <a>", test(1, "a"))
+assert_eq("(2,)% <None>", test((2,), None))
+"#,
+    );
+    assert::fail(
+        r#"
+def test(x, y):
+    return "%s %s %s" % (x, y)
+test(1, 2)
+"#,
+        "Not enough arguments",
+    );
+    assert::fail(
+        r#"
+def test(x, y):
+    return "%s" % (x, y)
+test(1, 2)
+"#,
+        "Too many arguments",
+    );
+}
+
 #[test]
 fn test_percent_s_one_format_one_eval() {
     assert::pass(
@@ -75,3 +298,713 @@ assert.eq(("<(1,)>", "<1>"), test((1,)))
 "#,
     );
 }
+
+#[test]
+fn test_string_literals_shared() {
+    let mut a = Assert::new();
+    let m = a.module(
+        "literals.star",
+        r#"
+x = "some string literal"
+def f():
+    return "some string literal"
+y = f()
+"#,
+    );
+    assert!(m
+        .get("x")
+        .unwrap()
+        .value()
+        .ptr_eq(m.get("y").unwrap().value()));
+}
+
+#[test]
+fn test_in_const_set() {
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::InConstSet, BcOpcode::Return],
+        "def test(x): return x in [1, 2, 3, 4, 5, 6]",
+    );
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal,
+            BcOpcode::InConstSet,
+            BcOpcode::Not,
+            BcOpcode::Return,
+        ],
+        "def test(x): return x not in ('a', 'b', 'c', 'd', 'e', 'f')",
+    );
+}
+
+#[test]
+fn test_in_const_set_eval() {
+    assert::pass(
+        r#"
+def test(x):
+    return x in ["a", "b", "c", "d", "e", 1, None]
+assert_true(test("c"))
+assert_true(test(1))
+assert_true(test(None))
+assert_false(test("z"))
+assert_false(test(True))
+assert_false(test([1]))
+assert_false(test({}))
+"#,
+    );
+}
+
+#[test]
+fn test_in_const_set_hash_collisions() {
+    // Ints hash to themselves, so `-1` has the same hash as infinity (`1e999`),
+    // and `0` the same as NaN, but they are not equal.
+    let program = r#"
+def test(x):
+    return x in [-1, 0, "a", "b", "c", "d"]
+def test_inf(x):
+    return x in [1e999, "a", "b", "c", "d", "e"]
+"#;
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::InConstSet, BcOpcode::Return],
+        program,
+    );
+    assert::pass(&format!(
+        "{}{}",
+        program,
+        r#"
+assert_true(test(-1))
+assert_true(test(-1.0))
+assert_true(test(0.0))
+assert_false(test(1e999))
+assert_false(test(float("nan")))
+assert_true(test_inf(1e999))
+assert_false(test_inf(-1))
+assert_false(test_inf(-1e999))
+"#
+    ));
+}
+
+#[test]
+fn test_call_method_known_type() {
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal,
+            BcOpcode::FormatOne,
+            BcOpcode::CallMethodFrozenPos,
+            BcOpcode::Return,
+        ],
+        "def test(x): return '<{}>'.format(x).upper()",
+    );
+    // Nothing is known about the type of a local.
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal,
+            BcOpcode::CallMethodPos,
+            BcOpcode::Return,
+        ],
+        "def test(x): return x.upper()",
+    );
+}
+
+#[test]
+fn test_call_method_known_type_eval() {
+    assert::pass(
+        r#"
+def test(xs):
+    ys = [x * 2 for x in xs].index(4)
+    zs = {x: x for x in xs}.get(3, 0)
+    return ('%s' % ys).join(['a', 'b']) + type(xs).upper() + str(zs)
+assert_eq("a1bLIST0", test([1, 2]))
+"#,
+    );
+    assert::fail(
+        r#"
+def test():
+    return [x for x in [1]].no_such_method()
+test()
+"#,
+        "no_such_method",
+    );
+}
+
+#[test]
+fn test_int_op_const() {
+    for body in [
+        "1 + 2",
+        "10 * 60 - 597",
+        "(1 << 4) >> 2",
+        "(6 & 3) | (5 ^ 1)",
+        "-7 // 2 % 5",
+    ] {
+        test_instrs(
+            &[BcOpcode::Const, BcOpcode::Return],
+            &format!("def test(): return {}", body),
+        );
+    }
+    // Module variables are constants once the module is frozen.
+    test_instrs(
+        &[BcOpcode::Const, BcOpcode::Return],
+        "X = 10\ndef test(): return X * 60",
+    );
+    // Failures are left to runtime.
+    for body in ["1 // 0", "2147483647 + 1", "1 << -1"] {
+        let opcodes = def_opcodes(&format!("def test(): return {}", body));
+        assert_eq!(BcOpcode::Const2, opcodes[0], "{}", body);
+        assert_eq!(3, opcodes.len(), "{}", body);
+    }
+    // `/` always gives a float, which is not folded.
+    test_instrs(
+        &[BcOpcode::Const2, BcOpcode::Divide, BcOpcode::Return],
+        "def test(): return 6 / 3",
+    );
+}
+
+#[test]
+fn test_int_op_const_eval() {
+    assert::pass(
+        r#"
+X = 10
+def test():
+    return (1 + 2, 10 * 60 - 597, (1 << 4) >> 2, (6 & 3) | (5 ^ 1), -7 // 2 % 5, X * 60, 6 / 3)
+assert_eq((3, 3, 4, 6, 1, 600, 2.0), test())
+"#,
+    );
+    assert::fail("def test(): return 1 // 0\ntest()", "zero");
+    assert::fail("def test(): return 2147483647 + 1\ntest()", "overflow");
+}
+
+#[test]
+fn test_dot_const() {
+    // Fields of frozen structs and records are constants.
+    test_instrs(
+        &[BcOpcode::Const, BcOpcode::Return],
+        "S = struct(x = 1)\ndef test(): return S.x",
+    );
+    test_instrs(
+        &[BcOpcode::Const, BcOpcode::Return],
+        "R = record(x = int.type)\nV = R(x = 1)\ndef test(): return V.x",
+    );
+    // Nothing is known about the type of a local.
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::ObjectField, BcOpcode::Return],
+        "def test(s): return s.x",
+    );
+}
+
+#[test]
+fn test_dot_const_eval() {
+    assert::pass(
+        r#"
+S = struct(x = [1], y = struct(z = "z"))
+def test():
+    return (S.x, S.y.z)
+assert_eq(([1], "z"), test())
+"#,
+    );
+    assert::fail(
+        r#"
+S = struct(x = 1)
+def test():
+    return S.y
+test()
+"#,
+        "has no attribute `y`",
+    );
+}
+
+#[test]
+fn test_dead_store() {
+    // Constant stores disappear completely.
+    test_instrs(
+        &[BcOpcode::ReturnLocal],
+        "def test(x):\n  y = 1\n  return x",
+    );
+    // The right hand side is still evaluated.
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::Pop, BcOpcode::ReturnLocal],
+        "def test(x):\n  y = x\n  return x",
+    );
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal,
+            BcOpcode::StoreLocal,
+            BcOpcode::ReturnLocal,
+        ],
+        "def test(x):\n  y = x\n  return y",
+    );
+}
+
+#[test]
+fn test_dead_store_eval() {
+    assert::pass(
+        r#"
+xs = []
+def f(x):
+    xs.append(x)
+    return x
+def test():
+    y = f(1)
+    for i in [2, 3]:
+        z = f(i)
+    return xs
+assert_eq([1, 2, 3], test())
+"#,
+    );
+    // Stores read by nested functions or later loop iterations are kept.
+    assert::pass(
+        r#"
+def test():
+    y = 1
+    g = lambda: y
+    return g()
+assert_eq(1, test())
+
+def loop():
+    r = []
+    for i in [1, 2]:
+        if i == 2:
+            r.append(x)
+        x = i
+    return r
+assert_eq([1], loop())
+"#,
+    );
+}
+
+#[test]
+fn test_string_literals_memory() {
+    fn allocated_bytes(literals: impl Iterator<Item = String>) -> usize {
+        let program = format!("xs = [{}]", literals.collect::<Vec<_>>().join(", "));
+        Assert::new()
+            .module("literals.star", &program)
+            .frozen_heap()
+            .allocated_bytes()
+    }
+
+    let repeated = allocated_bytes((0..1000).map(|_| "'literal 0000'".to_owned()));
+    let distinct = allocated_bytes((0..1000).map(|i| format!("'literal {:04}'", i)));
+    // Repeated literals only pay for the list, not a string per element.
+    assert!(repeated * 2 < distinct, "{} {}", repeated, distinct);
+}
+
+#[test]
+fn test_trace() {
+    let mut trace = Vec::new();
+    {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_bytecode_trace(&mut trace);
+        let ast = AstModule::parse(
+            "trace.star",
+            "def test(x):\n    return type(x)\ntest(1)".to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        eval.eval_module(ast, &Globals::standard()).unwrap();
+    }
+    let trace = String::from_utf8(trace).unwrap();
+    // Each line is `@addr Opcode file:span [operands]`
+    let body: Vec<(&str, &str)> = trace
+        .lines()
+        .filter(|line| line.contains("trace.star:2:"))
+        .map(|line| {
+            let parts: Vec<&str> = line.splitn(4, ' ').collect();
+            (parts[1], parts[3])
+        })
+        .collect();
+    assert_eq!(
+        body,
+        vec![
+            ("LoadLocal", "[]"),
+            ("Type", "[1]"),
+            ("Return", "[\"int\"]")
+        ],
+        "{}",
+        trace
+    );
+    assert!(trace.lines().all(|line| line.starts_with('@')), "{}", trace);
+}
+
+#[test]
+fn test_disable_gc_omits_gc_points() {
+    let trace_opcodes = |disable_gc: bool| {
+        let mut trace = Vec::new();
+        {
+            let module = Module::new();
+            let mut eval = Evaluator::new(&module);
+            if disable_gc {
+                eval.disable_gc();
+            }
+            eval.enable_bytecode_trace(&mut trace);
+            let ast = AstModule::parse(
+                "gc.star",
+                "x = [1]\ny = x + x\nlen(y)".to_owned(),
+                &Dialect::Extended,
+            )
+            .unwrap();
+            assert_eq!(
+                eval.eval_module(ast, &Globals::standard())
+                    .unwrap()
+                    .unpack_int(),
+                Some(2)
+            );
+        }
+        String::from_utf8(trace).unwrap()
+    };
+    assert!(trace_opcodes(false).contains(" PossibleGc "));
+    assert!(!trace_opcodes(true).contains(" PossibleGc "));
+}
+
+#[test]
+fn test_before_instr() {
+    let program = "def test(x):\n    return type(x)\ntest(1)\ntest(2)";
+    let instrs = RefCell::new(Vec::new());
+    let record = |addr: u32, span: FileSpan, _: &mut Evaluator| -> anyhow::Result<()> {
+        instrs
+            .borrow_mut()
+            .push((addr, span.resolve_span().begin_line));
+        Ok(())
+    };
+    {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.before_instr(&record);
+        let ast = AstModule::parse("step.star", program.to_owned(), &Dialect::Extended).unwrap();
+        eval.eval_module(ast, &Globals::standard()).unwrap();
+    }
+    let instrs = instrs.into_inner();
+    // The three instructions of the body run once per call, at the same addresses.
+    let body: Vec<u32> = instrs.iter().filter(|x| x.1 == 1).map(|x| x.0).collect();
+    assert_eq!(body.len(), 6, "{:?}", instrs);
+    assert_eq!(body[..3], body[3..]);
+
+    // The hook can abort evaluation.
+    let abort = |_: u32, span: FileSpan, _: &mut Evaluator| -> anyhow::Result<()> {
+        if span.resolve_span().begin_line == 1 {
+            Err(anyhow::anyhow!("Stopped by debugger"))
+        } else {
+            Ok(())
+        }
+    };
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    eval.before_instr(&abort);
+    let ast = AstModule::parse("step.star", program.to_owned(), &Dialect::Extended).unwrap();
+    let err = eval.eval_module(ast, &Globals::standard()).unwrap_err();
+    assert!(err.to_string().contains("Stopped by debugger"), "{}", err);
+}
+
+#[test]
+fn test_return_local() {
+    test_instrs(&[BcOpcode::ReturnLocal], "def test(x):\n  return x");
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::Len, BcOpcode::Return],
+        "def test(x):\n  return len(x)",
+    );
+}
+
+#[test]
+fn test_return_local_eval() {
+    assert::pass(
+        r#"
+def test(x):
+    if x:
+        y = 1
+    return y
+assert_eq(1, test(True))
+"#,
+    );
+    assert::fail(
+        r#"
+def test(x):
+    if x:
+        y = 1
+    return y
+test(False)
+"#,
+        "referenced before assignment",
+    );
+}
+
+#[test]
+fn test_concat_n() {
+    test_instrs(
+        &[
+            BcOpcode::Const,
+            BcOpcode::LoadLocalAndConst,
+            BcOpcode::ConcatN,
+            BcOpcode::Return,
+        ],
+        "def test(x):\n  return '<' + x + '>'",
+    );
+    // Calls may have side effects, so must not be evaluated before the `+` to their left.
+    test_instrs(
+        &[
+            BcOpcode::Const,
+            BcOpcode::LoadLocal,
+            BcOpcode::Add,
+            BcOpcode::LoadLocal,
+            BcOpcode::CallPos,
+            BcOpcode::Add,
+            BcOpcode::Return,
+        ],
+        "def test(x, f):\n  return '<' + x + f()",
+    );
+    // Other locals might be unassigned, so reading them could fail before the `+`.
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal,
+            BcOpcode::StoreLocal,
+            BcOpcode::Const,
+            BcOpcode::LoadLocal,
+            BcOpcode::Add,
+            BcOpcode::LoadLocal,
+            BcOpcode::Add,
+            BcOpcode::Return,
+        ],
+        "def test(x):\n  y = x\n  return '<' + x + y",
+    );
+}
+
+#[test]
+fn test_concat_n_eval() {
+    assert::pass(
+        r#"
+def test(x, y):
+    return x + "-" + y + "!"
+assert_eq("a-b!", test("a", "b"))
+assert_eq("-!", test("", ""))
+"#,
+    );
+    assert::fail(
+        r#"
+def test(x, y):
+    return x + "-" + y
+test("a", 1)
+"#,
+        "not supported",
+    );
+    // The first `+` fails before the unassigned variable is read.
+    assert::fail(
+        r#"
+def test(x):
+    if x:
+        y = "b"
+    return x + "-" + y
+test(1)
+"#,
+        "not supported",
+    );
+}
+
+#[test]
+fn test_eq_const() {
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::EqConst, BcOpcode::Return],
+        "def test(x):\n  return x == 'a'",
+    );
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::NotEqConst, BcOpcode::Return],
+        "def test(x):\n  return x != 0",
+    );
+    // `c == x` calls the `equals` of `c`, not `x`, so keeps the generic comparison.
+    test_instrs(
+        &[
+            BcOpcode::Const,
+            BcOpcode::LoadLocal,
+            BcOpcode::NotEq,
+            BcOpcode::Return,
+        ],
+        "def test(x):\n  return 0 != x",
+    );
+}
+
+#[test]
+fn test_eq_const_eval() {
+    assert::pass(
+        r#"
+def eq(x):
+    return x == "a"
+def ne(x):
+    return 1 != x
+assert_eq([True, False, False], [eq("a"), eq("b"), eq(1)])
+assert_eq([False, True, True], [ne(1), ne(2), ne("1")])
+"#,
+    );
+}
+
+#[test]
+fn test_not() {
+    test_instrs(
+        &[BcOpcode::Const, BcOpcode::Return],
+        "def test():\n  return not True",
+    );
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::Not, BcOpcode::Return],
+        "def test(x):\n  return not x",
+    );
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::ToBool, BcOpcode::Return],
+        "def test(x):\n  return not not x",
+    );
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::Not, BcOpcode::Return],
+        "def test(x):\n  return not not not x",
+    );
+}
+
+#[test]
+fn test_not_eval() {
+    assert::pass(
+        r#"
+def test(x):
+    return (not x, not not x, not not not x)
+assert_eq((True, False, True), test([]))
+assert_eq((False, True, False), test("a"))
+"#,
+    );
+}
+
+#[test]
+fn test_kwargs_literal() {
+    // `**` of a dict literal with string keys compiles like named arguments.
+    assert_eq!(
+        def_opcodes("def test(f, x): return f(x, a = 1, b = x)"),
+        def_opcodes("def test(f, x): return f(x, a = 1, **{'b': x})")
+    );
+    // Otherwise the dict is built and spread at runtime.
+    for call in [
+        "f(a = 1, **{'a': x})",
+        "f(**{'a': 1, x: 2})",
+        "f(*[x], **{'a': x})",
+    ] {
+        assert!(
+            def_opcodes(&format!("def test(f, x): return {}", call))
+                .iter()
+                .any(|o| format!("{:?}", o).starts_with("Dict")),
+            "{}",
+            call
+        );
+    }
+}
+
+#[test]
+fn test_kwargs_literal_eval() {
+    assert::pass(
+        r#"
+xs = []
+def v(x):
+    xs.append(x)
+    return x
+def f(a, b = 2, **kwargs):
+    return (a, b, kwargs)
+def test():
+    return f(v(1), **{'c': v(3), 'b': v(2)})
+assert_eq(test(), (1, 2, {'c': 3}))
+assert_eq(xs, [1, 3, 2])
+"#,
+    );
+    assert::fail(
+        "def f(a): pass
+def test(): f(a = 1, **{'a': 2})
+test()",
+        "occurs both",
+    );
+    assert::fail(
+        "def f(a): pass
+def test(): f(**{'a': 1, 'b': 2})
+test()",
+        "extra named",
+    );
+}
+
+#[test]
+fn test_call_specialized_on_freeze() {
+    // A call to a function only known once the module is frozen is specialized
+    // as if it had been known at compile time, and the result folds into the
+    // enclosing expression in the same pass.
+    test_instrs(
+        &[BcOpcode::Const, BcOpcode::Return],
+        "my_len = len\ndef test(): return my_len([1, 2]) == 2",
+    );
+    test_instrs(
+        &[BcOpcode::Const, BcOpcode::Return],
+        "my_type = type\nX = 1\ndef test(): return my_type(X) == 'int'",
+    );
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::Len, BcOpcode::Return],
+        "my_len = len\ndef test(x): return my_len(x)",
+    );
+    test_instrs(
+        &[BcOpcode::LoadLocal, BcOpcode::TypeIs, BcOpcode::Return],
+        "is_string = lambda x: type(x) == 'string'\ndef test(x): return is_string(x)",
+    );
+    // Lengths of module constants are folded on freeze too.
+    test_instrs(
+        &[BcOpcode::Const, BcOpcode::Return],
+        "XS = [1, 2, 3]\ndef test(): return len(XS)",
+    );
+}
+
+#[test]
+fn test_call_specialized_on_freeze_eval() {
+    assert::pass(
+        r#"
+my_len = len
+my_type = type
+is_string = lambda x: type(x) == 'string'
+XS = [1, 2, 3]
+def test(x):
+    return (my_len([1, 2]) == 2, my_len(x), my_type(x), is_string(x), len(XS))
+assert_eq(test("abc"), (True, 3, "string", True, 3))
+assert_eq(test([]), (True, 0, "list", False, 3))
+"#,
+    );
+    assert::fail(
+        "my_len = len\ndef test(x): return my_len(x)\ntest(1)",
+        "not supported",
+    );
+}
+
+#[test]
+fn test_speculative_exec() {
+    // Builtins without side effects are evaluated when all their arguments are constants.
+    for body in [
+        "len('abc')",
+        "str(1)",
+        "int('5')",
+        "bool(0)",
+        "repr('x')",
+        "chr(65)",
+    ] {
+        test_instrs(
+            &[BcOpcode::Const, BcOpcode::Return],
+            &format!("def test(): return {}", body),
+        );
+    }
+    // Including when the arguments only become constants on freeze.
+    test_instrs(
+        &[BcOpcode::Const, BcOpcode::Return],
+        "X = 1\ndef test(): return str(X)",
+    );
+    // Calls which fail are left for evaluation to report.
+    test_instrs(
+        &[
+            BcOpcode::Const,
+            BcOpcode::CallFrozenNativePos,
+            BcOpcode::Return,
+        ],
+        "def test(): return int('x')",
+    );
+}
+
+#[test]
+fn test_speculative_exec_eval() {
+    assert::pass(
+        r#"
+X = 10
+def test():
+    return (len('abc'), str(1), int('5'), bool(0), repr('x'), chr(65), str(X))
+assert_eq(test(), (3, '1', 5, False, '"x"', 'A', '10'))
+"#,
+    );
+    assert::fail("def test(): return int('x')\ntest()", "not a valid number");
+}
@@ -15,9 +15,10 @@
  * limitations under the License.
  */
 
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 
 use crate::{
+    codemap::FileSpan,
     environment::{Globals, Module},
     eval::Evaluator,
     syntax::{AstModule, Dialect},
@@ -44,3 +45,26 @@ f()            # 2
     evaluator.eval_module(ast, &globals).unwrap();
     assert_eq!(4, counter.get());
 }
+
+#[test]
+fn before_line() {
+    let module = Module::new();
+    let globals = Globals::standard();
+    let mut evaluator = Evaluator::new(&module);
+    let lines = RefCell::new(Vec::new());
+    let before_line = |span: FileSpan, _eval: &mut Evaluator<'_, '_>| {
+        lines.borrow_mut().push(span.resolve_span().begin_line);
+    };
+    evaluator.before_line(&before_line);
+
+    let program = "\
+x = 1; y = 2
+def f():
+  return x + y
+f(); f()
+for i in range(3): z = i
+";
+    let ast = AstModule::parse("a.star", program.to_owned(), &Dialect::Extended).unwrap();
+    evaluator.eval_module(ast, &globals).unwrap();
+    assert_eq!(vec![0, 1, 3, 2, 3, 2, 4], lines.into_inner());
+}
@@ -234,3 +234,44 @@ f(1)
         "Missing parameter `y`",
     );
 }
+
+#[test]
+fn test_call_known_def_checked_statically() {
+    let mut a = Assert::new();
+    a.module(
+        "helpers.star",
+        "\
+def add(x, y):
+  return x + y
+def opts(x, *args, **kwargs):
+  return x
+",
+    );
+    // Errors are reported when compiling, even though the calls never run.
+    for (call, err) in [
+        ("add(1, 2, 3)", "1 extra positional"),
+        ("add(1, z = 2)", "Found z extra named"),
+        ("add(1)", "Missing parameter `y`"),
+        ("add(y = 1)", "Missing parameter `x`"),
+        ("add(1, x = 2)", "Parameter `x` occurs both"),
+    ] {
+        a.fail(
+            &format!(
+                "load('helpers.star', 'add')\ndef test():\n  return {}\n",
+                call
+            ),
+            err,
+        );
+    }
+    a.pass(
+        "\
+load('helpers.star', 'add', 'opts')
+def test():
+  return (add(1, y = 2), opts(1, 2, 3, z = 4))
+assert_eq(test(), (3, 1))
+def dynamic():
+  # Only checked when called.
+  return add(*[1, 2, 3])
+",
+    );
+}
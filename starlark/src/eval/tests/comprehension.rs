@@ -65,6 +65,16 @@ fn test_dict() {
     check_comp(&["{x: 1 for x in [0,1,2]} == {0: 1, 1: 1, 2: 1}"]);
 }
 
+#[test]
+fn test_dict_duplicate_keys() {
+    // The last value wins, but the key keeps the position where it was first seen
+    check_comp(&["{x % 2: x for x in range(4)} == {0: 2, 1: 3}"]);
+    check_comp(&["{x % 2: x for x in range(4)}.items() == [(0, 2), (1, 3)]"]);
+    check_comp(&[
+        "{k: v for k, v in [('b', 1), ('a', 2), ('b', 3)]}.items() == [('b', 3), ('a', 2)]",
+    ]);
+}
+
 #[test]
 fn test_nested() {
     // Nested comprehensions
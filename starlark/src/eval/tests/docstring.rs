@@ -20,7 +20,7 @@
 use crate::{
     assert,
     assert::Assert,
-    environment::{Module, ModuleDocs},
+    environment::{FunctionDoc, Module, ModuleDocs},
     values::Value,
 };
 
@@ -383,3 +383,84 @@ Some extra details can go here,
     assert_eq!(expected_m2, m2_docs);
     assert_eq!(expected_m3, m3_docs);
 }
+
+#[test]
+fn test_module_functions() {
+    use crate::values::docs::{DocString, Function, Param, Return, Type};
+
+    let m = assert::pass_module(
+        r#"
+def add(x: "int", y: "int" = 1) -> "int":
+    """
+    Add two numbers
+
+    Args:
+        x: The first number
+        y: The second number
+    """
+    return x + y
+
+def _private():
+    pass
+
+not_a_function = 1
+double = lambda x: x * 2
+"#,
+    );
+
+    let expected = vec![
+        FunctionDoc {
+            name: "add".to_owned(),
+            function: Function {
+                docs: DocString::from_docstring(
+                    r#"Add two numbers
+
+    Args:
+        x: The first number
+        y: The second number"#,
+                ),
+                params: vec![
+                    Param::Arg {
+                        name: "x".to_owned(),
+                        docs: DocString::from_docstring("The first number"),
+                        typ: Some(Type {
+                            raw_type: "\"int\"".to_owned(),
+                        }),
+                        default_value: None,
+                    },
+                    Param::Arg {
+                        name: "y".to_owned(),
+                        docs: DocString::from_docstring("The second number"),
+                        typ: Some(Type {
+                            raw_type: "\"int\"".to_owned(),
+                        }),
+                        default_value: Some("1".to_owned()),
+                    },
+                ],
+                ret: Return {
+                    docs: None,
+                    typ: Some(Type {
+                        raw_type: "\"int\"".to_owned(),
+                    }),
+                },
+            },
+        },
+        FunctionDoc {
+            name: "double".to_owned(),
+            function: Function {
+                docs: None,
+                params: vec![Param::Arg {
+                    name: "x".to_owned(),
+                    docs: None,
+                    typ: None,
+                    default_value: None,
+                }],
+                ret: Return {
+                    docs: None,
+                    typ: None,
+                },
+            },
+        },
+    ];
+    assert_eq!(expected, m.functions());
+}
@@ -0,0 +1,139 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Test for `*args`/`**kwargs` forwarding optimizations.
+
+use crate as starlark;
+use crate::{
+    assert::Assert,
+    environment::GlobalsBuilder,
+    eval::{Def, FrozenDef},
+    values::{Value, ValueLike},
+};
+
+#[starlark_module]
+fn globals(builder: &mut GlobalsBuilder) {
+    fn forwards_args(value: Value<'v>) -> bool {
+        Ok(if let Some(def) = value.downcast_ref::<FrozenDef>() {
+            def.def_info.forwards_args.is_some()
+        } else if let Some(def) = value.downcast_ref::<Def>() {
+            def.def_info.forwards_args.is_some()
+        } else {
+            panic!("not def")
+        })
+    }
+}
+
+#[test]
+fn forwards_args() {
+    let mut a = Assert::new();
+    a.globals_add(globals);
+
+    a.module(
+        "wrappers.star",
+        "\
+def inner(a, b = 2, *args, **kwargs):
+  return (a, b, args, kwargs)
+def wrapper(*args, **kwargs):
+  return inner(*args, **kwargs)
+",
+    );
+
+    a.pass(
+        "\
+load('wrappers.star', 'wrapper')
+assert_true(forwards_args(wrapper))
+assert_eq(wrapper(1), (1, 2, (), {}))
+assert_eq(wrapper(1, 3, 4, c = 5), (1, 3, (4,), {'c': 5}))
+assert_eq(wrapper(*[1, 3], **{'c': 5}), (1, 3, (), {'c': 5}))
+assert_eq(wrapper(b = 3, a = 1), (1, 3, (), {}))
+    ",
+    );
+
+    // Calls which are not frozen, or which go to a builtin
+    a.pass(
+        "\
+def inner(a, b = 2):
+  return (a, b)
+def wrapper(*args, **kwargs):
+  return inner(*args, **kwargs)
+def wrapped_len(*args, **kwargs):
+  return len(*args, **kwargs)
+assert_true(forwards_args(wrapper))
+assert_true(forwards_args(wrapped_len))
+assert_eq(wrapper(1, b = 3), (1, 3))
+assert_eq(wrapped_len([1, 2, 3]), 3)
+    ",
+    );
+
+    // Errors are reported by the function being forwarded to
+    a.fail(
+        "\
+def inner(a):
+  return a
+def wrapper(*args, **kwargs):
+  return inner(*args, **kwargs)
+wrapper()
+",
+        "Missing parameter `a`",
+    );
+    a.fail(
+        "\
+def inner(a):
+  return a
+def wrapper(*args, **kwargs):
+  return inner(*args, **kwargs)
+wrapper(a = 1, **{'a': 2})
+",
+        "occurs both explicitly and in **kwargs",
+    );
+}
+
+#[test]
+fn does_not_forward_args() {
+    let mut a = Assert::new();
+    a.globals_add(globals);
+    a.pass(
+        "\
+def inner(*args, **kwargs):
+  return (args, kwargs)
+
+def extra_arg(*args, **kwargs):
+  return inner(1, *args, **kwargs)
+
+def swapped(*args, **kwargs):
+  return inner(*kwargs, **args)
+
+def extra_param(x, *args, **kwargs):
+  return inner(*args, **kwargs)
+
+def local_target(*args, **kwargs):
+  f = inner
+  return f(*args, **kwargs)
+
+def return_type(*args, **kwargs) -> 'tuple':
+  return inner(*args, **kwargs)
+
+assert_false(forwards_args(extra_arg))
+assert_false(forwards_args(swapped))
+assert_false(forwards_args(extra_param))
+assert_false(forwards_args(local_target))
+assert_false(forwards_args(return_type))
+assert_eq(extra_arg(2, x = 3), ((1, 2), {'x': 3}))
+    ",
+    );
+}
@@ -0,0 +1,205 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Test for inlining trivial functions at the call site.
+
+use crate as starlark;
+use crate::{
+    assert::Assert,
+    environment::GlobalsBuilder,
+    errors::Diagnostic,
+    eval::{Def, FrozenDef},
+    values::{Value, ValueLike},
+};
+
+#[starlark_module]
+fn globals(builder: &mut GlobalsBuilder) {
+    fn inline_body(value: Value<'v>) -> bool {
+        Ok(if let Some(def) = value.downcast_ref::<FrozenDef>() {
+            def.def_info.inline_body.is_some()
+        } else if let Some(def) = value.downcast_ref::<Def>() {
+            def.def_info.inline_body.is_some()
+        } else {
+            panic!("not def")
+        })
+    }
+}
+
+const HELPERS: &str = "\
+def pair(x, y):
+  return (x, y)
+def describe(x):
+  return [type(x), not x]
+def first_is_list(x, y):
+  return (type(x) == 'list', y)
+def add(x, y):
+  return x + y
+def swap(x, y):
+  return (y, x)
+";
+
+#[test]
+fn inline_body() {
+    let mut a = Assert::new();
+    a.globals_add(globals);
+    a.module("helpers.star", HELPERS);
+
+    let m = a.module(
+        "calls.star",
+        "\
+load('helpers.star', 'pair')
+def test(a, b):
+  return pair(a, b)
+",
+    );
+    let test = m.get("test").unwrap().downcast::<FrozenDef>().unwrap();
+    let opcodes = test.bc().instrs.opcodes();
+    assert!(
+        opcodes
+            .iter()
+            .all(|o| !format!("{:?}", o).starts_with("Call")),
+        "{:?}",
+        opcodes
+    );
+
+    a.pass(
+        "\
+load('helpers.star', 'pair', 'describe', 'first_is_list')
+assert_true(inline_body(pair))
+assert_true(inline_body(describe))
+assert_true(inline_body(first_is_list))
+def test(a, b):
+  return (pair(a, b), describe(a), first_is_list([a], b))
+assert_eq(test(1, 2), ((1, 2), ['int', False], (True, 2)))
+assert_eq(pair('a', 'b'), ('a', 'b'))
+    ",
+    );
+
+    // Arguments with side effects are still evaluated once, in order
+    a.pass(
+        "\
+load('helpers.star', 'pair')
+xs = []
+def f(x):
+  xs.append(x)
+  return x
+def test():
+  return pair(f(1), f(2))
+assert_eq(test(), (1, 2))
+assert_eq(xs, [1, 2])
+    ",
+    );
+
+    // Arguments are read in the order of the call, even if the body reads them in another
+    a.fail(
+        "\
+load('helpers.star', 'swap')
+def test(c):
+  if c:
+    a = 1
+    b = 2
+  return swap(a, b)
+test(False)
+",
+        "`a` referenced before assignment",
+    );
+    a.fail(
+        "\
+load('helpers.star', 'pair')
+def test(a):
+  return pair(a)
+test(1)
+",
+        "Missing parameter `y`",
+    );
+}
+
+#[test]
+fn inline_body_error_call_stack() {
+    let mut a = Assert::new();
+    a.globals_add(globals);
+    a.module("helpers.star", HELPERS);
+    // A body which can fail isn't inlined, so the error still shows the function.
+    let err = a.fail(
+        "\
+load('helpers.star', 'add')
+assert_false(inline_body(add))
+def test(a, b):
+  return add(a, b)
+test(1, 'x')
+",
+        "not supported",
+    );
+    let frames = err
+        .downcast_ref::<Diagnostic>()
+        .unwrap()
+        .call_stack
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    assert!(frames.iter().any(|x| x.starts_with("add(")), "{:?}", frames);
+}
+
+#[test]
+fn does_not_inline_body() {
+    let mut a = Assert::new();
+    a.globals_add(globals);
+    a.pass(
+        "\
+LIMIT = 10
+
+def default(x, y = 1):
+  return x + y
+def typed(x: 'int'):
+  return x + 1
+def return_type(x) -> 'int':
+  return x + 1
+def args(*args):
+  return args
+def unused(x, y):
+  return x
+def short_circuit(x, y):
+  return x and y
+def module_var(x):
+  return x + LIMIT
+def calls(x):
+  return str(x)
+def captured(x):
+  return lambda: x
+def statements(x):
+  x += 1
+  return x
+def fails(x, y):
+  return x + y
+def out_of_order(x, y):
+  return (y, x)
+
+assert_false(inline_body(default))
+assert_false(inline_body(typed))
+assert_false(inline_body(return_type))
+assert_false(inline_body(args))
+assert_false(inline_body(unused))
+assert_false(inline_body(short_circuit))
+assert_false(inline_body(module_var))
+assert_false(inline_body(calls))
+assert_false(inline_body(captured))
+assert_false(inline_body(statements))
+assert_false(inline_body(fails))
+assert_false(inline_body(out_of_order))
+    ",
+    );
+}
@@ -19,6 +19,7 @@
 
 use std::{
     cell::RefCell,
+    cmp::Ordering,
     collections::HashMap,
     sync::{Arc, Mutex},
 };
@@ -33,7 +34,7 @@ use crate::{
     environment::{GlobalsBuilder, Module},
     eval::Evaluator,
     syntax::{AstModule, Dialect},
-    values::{any::StarlarkAny, none::NoneType, Freeze, StarlarkValue, Value},
+    values::{any::StarlarkAny, none::NoneType, Freeze, StarlarkValue, Value, ValueError},
 };
 
 #[test]
@@ -450,3 +451,56 @@ fun
         .unwrap();
     assert_eq!(v.unpack_str(), Some("(8, \"hello\", 1)"))
 }
+
+#[test]
+fn test_custom_comparison() {
+    #[derive(Debug, Display, PartialEq, Eq, PartialOrd, Ord)]
+    #[display(fmt = "{}.{}.{}", _0, _1, _2)]
+    struct Version(i32, i32, i32);
+    starlark_simple_value!(Version);
+
+    impl<'v> StarlarkValue<'v> for Version {
+        starlark_type!("version");
+
+        fn equals(&self, other: Value<'v>) -> anyhow::Result<bool> {
+            Ok(Version::from_value(other).map_or(false, |other| self == other))
+        }
+
+        fn compare(&self, other: Value<'v>) -> anyhow::Result<Ordering> {
+            match Version::from_value(other) {
+                Some(other) => Ok(self.cmp(other)),
+                None => ValueError::unsupported_with(self, "compare", other),
+            }
+        }
+    }
+
+    #[starlark_module]
+    fn module(builder: &mut GlobalsBuilder) {
+        fn version(s: &str) -> Version {
+            let xs = s
+                .split('.')
+                .map(|x| x.parse())
+                .collect::<Result<Vec<i32>, _>>()?;
+            Ok(Version(xs[0], xs[1], xs[2]))
+        }
+    }
+
+    let mut a = Assert::new();
+    a.globals_add(module);
+    a.pass(
+        r#"
+vs = [version("1.10.0"), version("1.2.0"), version("1.2.10"), version("0.9.9")]
+# Sorted semantically, rather than as strings
+assert_eq(sorted(vs), [version("0.9.9"), version("1.2.0"), version("1.2.10"), version("1.10.0")])
+assert_eq(sorted(vs, reverse=True)[0], version("1.10.0"))
+assert_eq(max(vs), version("1.10.0"))
+assert_eq(min(vs), version("0.9.9"))
+assert_true(version("1.10.0") > version("1.9.0"))
+assert_true(version("1.2.0") <= version("1.2.0"))
+assert_true(version("1.2.0") != version("1.2.1"))
+assert_true(version("1.2.0") in vs)
+"#,
+    );
+    a.fail("version('1.0.0') < 1", "compare");
+    a.fail("sorted([version('1.0.0'), 'x'])", "compare");
+}
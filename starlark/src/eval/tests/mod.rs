@@ -15,10 +15,15 @@
  * limitations under the License.
  */
 
-use std::{cell::RefCell, fmt::Write};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Write,
+    sync::atomic::{self, AtomicUsize},
+};
 
 use derive_more::Display;
-use gazebo::any::AnyLifetime;
+use gazebo::{any::AnyLifetime, prelude::*};
 
 use crate::{
     self as starlark,
@@ -26,9 +31,14 @@ use crate::{
     collections::SmallMap,
     environment::{Globals, GlobalsBuilder, Module},
     errors::Diagnostic,
-    eval::Evaluator,
+    eval::{CachingFileLoader, Evaluator, ModuleSource, ReturnFileLoader, SourceLoader},
     syntax::{AstModule, Dialect},
-    values::{Freeze, Freezer, Heap, SimpleValue, StarlarkValue, Trace, UnpackValue, Value},
+    values::{
+        docs::{self, DocItem, DocString},
+        none::NoneType,
+        Freeze, Freezer, Heap, OwnedFrozenValue, SimpleValue, StarlarkValue, Trace, UnpackValue,
+        Value,
+    },
 };
 
 mod basic;
@@ -38,7 +48,9 @@ mod call;
 mod comprehension;
 mod def;
 mod docstring;
+mod forward_args;
 mod go;
+mod inline;
 mod interop;
 mod runtime;
 mod type_is;
@@ -460,6 +472,30 @@ error: Immutable
     );
 }
 
+#[test]
+fn test_source_snippet() {
+    let diag = assert::fail("x = 1\ny = x + 'a'", "not supported");
+    let snippet = diag
+        .downcast_ref::<Diagnostic>()
+        .unwrap()
+        .source_snippet()
+        .unwrap();
+    assert!(snippet.contains("y = x + 'a'"), "{}", snippet);
+    assert!(snippet.contains("|     ^^^^^^^"), "{}", snippet);
+    assert!(!snippet.contains("x = 1"), "{}", snippet);
+
+    // All the lines of a multi-line span are shown.
+    let diag = assert::fail("y = [\n  1,\n] + 'a'", "not supported");
+    let snippet = diag
+        .downcast_ref::<Diagnostic>()
+        .unwrap()
+        .source_snippet()
+        .unwrap();
+    assert!(snippet.contains("y = ["), "{}", snippet);
+    assert!(snippet.contains("  1,"), "{}", snippet);
+    assert!(snippet.contains("] + 'a'"), "{}", snippet);
+}
+
 #[test]
 fn test_load_reexport() {
     let mut a = Assert::new();
@@ -478,6 +514,427 @@ fn test_load_reexport() {
     );
 }
 
+#[test]
+fn test_load_all() {
+    let mut a = Assert::new();
+    a.module(
+        "a",
+        r#"
+__all__ = ["x", "f"]
+x = 1
+y = 2
+def f(): return x + y
+"#,
+    );
+    a.pass("load('a', 'x', 'f')\nassert_eq(f(), 3)");
+    a.fail(
+        "load('a', 'y')",
+        "Module symbol `y` is not listed in the module's `__all__`",
+    );
+
+    // A tuple works as well as a list.
+    a.module("b", "__all__ = ('x',)\nx = 1\ny = 2");
+    a.pass("load('b', 'x')\nassert_eq(x, 1)");
+    a.fail(
+        "load('b', 'y')",
+        "Module symbol `y` is not listed in the module's `__all__`",
+    );
+
+    // An invalid `__all__` doesn't stop the module freezing, only loading from it.
+    a.module("c", "__all__ = 1\nx = 1");
+    a.fail(
+        "load('c', 'x')",
+        "`__all__` must be a list or tuple of strings, got `1`",
+    );
+}
+
+#[test]
+fn test_load_alias() {
+    let mut a = Assert::new();
+    a.module("a.bzl", "x = 1\ndef f(): return x + 1");
+    a.pass(
+        r#"
+load("a.bzl", y = "x", g = "f", "f", z = "x")
+assert_eq(y, 1)
+assert_eq(z, 1)
+assert_eq(g(), 2)
+assert_eq(f(), 2)
+"#,
+    );
+    // Only the new name is bound
+    a.fail("load('a.bzl', y = 'x')\nx", "Variable `x` not found");
+    a.fail(
+        "load('a.bzl', y = 'missing')",
+        "Module has no symbol `missing`",
+    );
+}
+
+#[test]
+fn test_caching_file_loader() {
+    // Each compilation of a module calls `compiled()` once at the top level.
+    static COMPILED: AtomicUsize = AtomicUsize::new(0);
+
+    #[starlark_module]
+    fn module(builder: &mut GlobalsBuilder) {
+        fn compiled() -> NoneType {
+            COMPILED.fetch_add(1, atomic::Ordering::SeqCst);
+            Ok(NoneType)
+        }
+    }
+
+    struct Sources(RefCell<HashMap<String, (String, Option<String>)>>);
+
+    impl SourceLoader for Sources {
+        fn load_source(&self, path: &str) -> anyhow::Result<ModuleSource> {
+            let (source, digest) = self.0.borrow()[path].clone();
+            Ok(ModuleSource { source, digest })
+        }
+    }
+
+    let sources = Sources(RefCell::new(HashMap::new()));
+    let set = |path: &str, source: &str, digest: Option<&str>| {
+        sources.0.borrow_mut().insert(
+            path.to_owned(),
+            (source.to_owned(), digest.map(|x| x.to_owned())),
+        );
+    };
+    let globals = GlobalsBuilder::extended().with(module).build();
+    let loader = CachingFileLoader::new(&sources, &globals, Dialect::Extended);
+    let run = |program: &str| -> i32 {
+        let ast = AstModule::parse("main.bzl", program.to_owned(), &Dialect::Extended).unwrap();
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.set_loader(&loader);
+        let res = eval.eval_module(ast, &globals).unwrap();
+        res.unpack_int().unwrap()
+    };
+
+    set("a.bzl", "compiled()\nx = 1", Some("a1"));
+    set(
+        "b.bzl",
+        "load('a.bzl', 'x')\ncompiled()\ny = x + 1",
+        Some("b1"),
+    );
+    assert_eq!(run("load('b.bzl', 'y')\ny"), 2);
+    assert_eq!(COMPILED.load(atomic::Ordering::SeqCst), 2);
+
+    // Unchanged digests reuse the frozen modules
+    assert_eq!(run("load('a.bzl', 'x')\nload('b.bzl', 'y')\nx + y"), 3);
+    assert_eq!(COMPILED.load(atomic::Ordering::SeqCst), 2);
+
+    // A new digest is recompiled, even though the dependency is reused
+    set(
+        "b.bzl",
+        "load('a.bzl', 'x')\ncompiled()\ny = x + 2",
+        Some("b2"),
+    );
+    assert_eq!(run("load('b.bzl', 'y')\ny"), 3);
+    assert_eq!(COMPILED.load(atomic::Ordering::SeqCst), 3);
+
+    // Modules without a digest are never cached
+    set("c.bzl", "compiled()\nz = 4", None);
+    assert_eq!(run("load('c.bzl', 'z')\nz"), 4);
+    assert_eq!(run("load('c.bzl', 'z')\nz"), 4);
+    assert_eq!(COMPILED.load(atomic::Ordering::SeqCst), 5);
+}
+
+#[test]
+fn test_virtual_file_system() {
+    #[starlark_module]
+    fn module(builder: &mut GlobalsBuilder) {
+        fn read(path: &str) -> String {
+            Ok(eval.load_source(path)?.source)
+        }
+    }
+
+    struct Files(HashMap<&'static str, &'static str>);
+
+    impl SourceLoader for Files {
+        fn load_source(&self, path: &str) -> anyhow::Result<ModuleSource> {
+            match self.0.get(path) {
+                None => Err(anyhow::anyhow!("No such file `{}`", path)),
+                Some(source) => Ok(ModuleSource {
+                    source: (*source).to_owned(),
+                    digest: None,
+                }),
+            }
+        }
+    }
+
+    let files = Files(
+        vec![
+            (
+                "lib.bzl",
+                "load('util.bzl', 'greet')\nmessage = greet(read('name.txt'))",
+            ),
+            ("util.bzl", "def greet(x): return 'hello ' + x"),
+            ("name.txt", "world"),
+            ("cycle_a.bzl", "load('cycle_b.bzl', 'b')\na = 1"),
+            ("cycle_b.bzl", "load('cycle_a.bzl', 'a')\nb = 1"),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let globals = GlobalsBuilder::extended().with(module).build();
+    let loader = CachingFileLoader::new(&files, &globals, Dialect::Extended);
+    let run = |program: &str| -> anyhow::Result<String> {
+        let ast = AstModule::parse("main.bzl", program.to_owned(), &Dialect::Extended)?;
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.set_loader(&loader);
+        eval.set_source_loader(&files);
+        let res = eval.eval_module(ast, &globals)?;
+        Ok(res.to_str())
+    };
+
+    // Both `load` and builtins, including in loaded modules, read from the same files
+    assert_eq!(
+        run("load('lib.bzl', 'message')\nmessage").unwrap(),
+        "hello world"
+    );
+    assert_eq!(run("read('util.bzl')").unwrap(), files.0["util.bzl"]);
+
+    let err = format!("{:#}", run("load('missing.bzl', 'x')").unwrap_err());
+    assert!(err.contains("When loading module `missing.bzl`"), "{}", err);
+    assert!(err.contains("No such file `missing.bzl`"), "{}", err);
+    let err = format!("{:#}", run("read('missing.txt')").unwrap_err());
+    assert!(err.contains("No such file `missing.txt`"), "{}", err);
+    let err = format!("{:#}", run("load('cycle_a.bzl', 'a')").unwrap_err());
+    assert!(
+        err.contains("cycle_a.bzl -> cycle_b.bzl -> cycle_a.bzl"),
+        "{}",
+        err
+    );
+
+    // Without a source loader, builtins can't read files
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    assert!(eval.eval_expr("read('name.txt')", &globals).is_err());
+}
+
+#[test]
+fn test_load_limits() {
+    struct Chain;
+
+    // `m0.bzl` loads `m1.bzl`, which loads `m2.bzl` and so on, ending at `m5.bzl`.
+    // `fan.bzl` loads `m5.bzl` under many names.
+    impl SourceLoader for Chain {
+        fn load_source(&self, path: &str) -> anyhow::Result<ModuleSource> {
+            let source = if path == "fan.bzl" {
+                (0..10)
+                    .map(|i| format!("load('m5.bzl', x{} = 'x')\n", i))
+                    .collect()
+            } else {
+                let i: usize = path
+                    .trim_start_matches('m')
+                    .trim_end_matches(".bzl")
+                    .parse()?;
+                if i == 5 {
+                    "x = 5".to_owned()
+                } else {
+                    format!("load('m{}.bzl', 'x')", i + 1)
+                }
+            };
+            Ok(ModuleSource {
+                source,
+                digest: None,
+            })
+        }
+    }
+
+    let globals = Globals::standard();
+    let run = |loader: &CachingFileLoader, program: &str| -> anyhow::Result<()> {
+        let ast = AstModule::parse("main.bzl", program.to_owned(), &Dialect::Extended)?;
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.set_loader(loader);
+        eval.eval_module(ast, &globals)?;
+        Ok(())
+    };
+
+    let mut loader = CachingFileLoader::new(&Chain, &globals, Dialect::Extended);
+    loader.set_max_load_depth(6);
+    run(&loader, "load('m0.bzl', 'x')").unwrap();
+    loader.set_max_load_depth(3);
+    run(&loader, "load('m3.bzl', 'x')").unwrap();
+    let err = format!("{:#}", run(&loader, "load('m0.bzl', 'x')").unwrap_err());
+    assert!(
+        err.contains("Exceeded the maximum load depth of 3: m0.bzl -> m1.bzl -> m2.bzl -> m3.bzl"),
+        "{}",
+        err
+    );
+
+    let mut loader = CachingFileLoader::new(&Chain, &globals, Dialect::Extended);
+    loader.set_max_loads(5);
+    let err = format!("{:#}", run(&loader, "load('fan.bzl', 'x0')").unwrap_err());
+    assert!(
+        err.contains("Exceeded the maximum of 5 loaded modules: fan.bzl -> m5.bzl"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_ast_transformer() {
+    let globals = Globals::standard();
+    let prelude = Module::new();
+    prelude.set("greeting", prelude.heap().alloc("hello"));
+    let prelude = prelude.freeze().unwrap();
+    let modules = hashmap! {"prelude.star" => &prelude};
+    let loader = ReturnFileLoader { modules: &modules };
+
+    // Make `greet` available in every module, as though it were a builtin.
+    let transformer = |mut ast: AstModule| -> anyhow::Result<AstModule> {
+        ast.add_load("prelude.star", &[("greet", "greeting")]);
+        Ok(ast)
+    };
+
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    eval.set_loader(&loader);
+    eval.set_ast_transformer(&transformer);
+    let ast = AstModule::parse(
+        "main.star",
+        "\"\"\"Docs.\"\"\"\nmessage = greet + \" world\"".to_owned(),
+        &Dialect::Standard,
+    )
+    .unwrap();
+    eval.eval_module(ast, &globals).unwrap();
+    assert_eq!(
+        module.get("message").unwrap().unpack_str(),
+        Some("hello world")
+    );
+    // The injected load is private, and the docstring is still the first statement.
+    assert!(module.get("greet").is_none());
+    let module = module.freeze().unwrap();
+    assert_eq!(
+        module.documentation(),
+        Some(DocItem::Module(docs::Module {
+            docs: DocString::from_docstring("Docs.")
+        }))
+    );
+}
+
+#[test]
+fn test_parallel_evaluation() {
+    let globals = Globals::standard();
+    let shared = Module::new();
+    let ast = AstModule::parse(
+        "shared.star",
+        "items = [0]\ndef add(x): return items + [x]".to_owned(),
+        &Dialect::Standard,
+    )
+    .unwrap();
+    Evaluator::new(&shared).eval_module(ast, &globals).unwrap();
+    let shared = shared.freeze().unwrap();
+
+    let threads: Vec<_> = (1..=8)
+        .map(|i| {
+            let globals = globals.dupe();
+            let shared = shared.dupe();
+            std::thread::spawn(move || {
+                let modules = hashmap! {"shared.star" => &shared};
+                let loader = ReturnFileLoader { modules: &modules };
+                let module = Module::new();
+                let mut eval = Evaluator::new(&module);
+                eval.set_loader(&loader);
+                let code = format!(
+                    r#"
+load("shared.star", "add", "items")
+mine = add({})
+mine.append(-1)
+def mutate_shared(): items.append(1)
+"#,
+                    i
+                );
+                let ast = AstModule::parse("thread.star", code, &Dialect::Standard).unwrap();
+                eval.eval_module(ast, &globals).unwrap();
+                let mutate = module.get("mutate_shared").unwrap();
+                assert!(eval.eval_function(mutate, &[], &[]).is_err());
+                module.freeze().unwrap()
+            })
+        })
+        .collect();
+    for (i, thread) in (1..).zip(threads) {
+        let module = thread.join().unwrap();
+        assert_eq!(
+            module.get("mine").unwrap().value().to_repr(),
+            format!("[0, {}, -1]", i)
+        );
+    }
+    assert_eq!(shared.get("items").unwrap().value().to_repr(), "[0]");
+}
+
+#[test]
+fn test_eval_and_freeze() {
+    let globals = Globals::standard();
+    let (module, value) = Module::new()
+        .eval_and_freeze(|module| {
+            let mut eval = Evaluator::new(module);
+            let ast = AstModule::parse(
+                "frozen.star",
+                "def double(x): return x * 2\nresult = {'xs': [double(1)]}\nresult".to_owned(),
+                &Dialect::Extended,
+            )?;
+            eval.eval_module(ast, &globals)
+        })
+        .unwrap();
+    // The value is shared with the module, and usable from another thread once
+    // the evaluator and the module are gone.
+    assert_eq!(
+        module.get("result").unwrap().value().ptr_value(),
+        value.value().ptr_value()
+    );
+    drop(module);
+    let repr = std::thread::spawn(move || value.value().to_repr())
+        .join()
+        .unwrap();
+    assert_eq!(repr, r#"{"xs": [2]}"#);
+}
+
+#[test]
+fn test_set_frozen() {
+    let globals = Globals::extended();
+    let (_, config) = Module::new()
+        .eval_and_freeze(|module| {
+            let mut eval = Evaluator::new(module);
+            let ast = AstModule::parse(
+                "config.star",
+                "struct(name = 'config', toolchain = struct(flags = ['-O2', '-g']))".to_owned(),
+                &Dialect::Extended,
+            )?;
+            eval.eval_module(ast, &globals)
+        })
+        .unwrap();
+
+    let flags = |config: &OwnedFrozenValue| {
+        let module = Module::new();
+        module.set_frozen("config", config);
+        let mut eval = Evaluator::new(&module);
+        let ast = AstModule::parse(
+            "user.star",
+            "config.toolchain.flags".to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        let res = eval.eval_module(ast, &globals).unwrap();
+        assert_eq!(res.to_repr(), r#"["-O2", "-g"]"#);
+        res.ptr_value()
+    };
+    // Both modules see the same frozen value, rather than a copy.
+    assert_eq!(flags(&config), flags(&config));
+    // The module keeps the config alive after our reference is gone.
+    let module = Module::new();
+    module.set_frozen("config", &config);
+    drop(config);
+    let mut eval = Evaluator::new(&module);
+    let ast = AstModule::parse("user.star", "config.name".to_owned(), &Dialect::Extended).unwrap();
+    assert_eq!(
+        eval.eval_module(ast, &globals).unwrap().unpack_str(),
+        Some("config")
+    );
+}
+
 #[test]
 fn test_module_visibility_preserved_by_evaluator() -> anyhow::Result<()> {
     // Make sure that when we use a module in the evaluator, the entering / exiting the
@@ -609,6 +1066,25 @@ g()",
         "def f():\n [1 for _ in [1] for y in y]\nf()",
         &["referenced before assignment", "`y`"],
     );
+    // Each way of reading a local names the variable.
+    assert::fails(
+        "def f():\n x += 1\n x = 1\nf()",
+        &["Local variable `x` referenced before assignment"],
+    );
+    assert::fails(
+        "def f(a):\n y = (a, x)\n x = 1\nf(1)",
+        &["Local variable `x` referenced before assignment"],
+    );
+    assert::fails(
+        "
+def f():
+    def g():
+        return x
+    g()
+    x = 1
+f()",
+        &["Local variable `x` referenced before assignment"],
+    );
 }
 
 #[test]
@@ -640,6 +1116,45 @@ bar(["a","b","c"])
     );
 }
 
+#[test]
+fn test_str_differs_from_repr() {
+    #[derive(Debug, Display, Clone)]
+    #[display(fmt = "Path({:?})", _0)]
+    struct Path(String);
+    starlark_simple_value!(Path);
+
+    impl<'v> StarlarkValue<'v> for Path {
+        starlark_type!("path");
+        fn collect_str(&self, collector: &mut String) {
+            collector.push_str(&self.0)
+        }
+    }
+
+    #[starlark_module]
+    fn module(build: &mut GlobalsBuilder) {
+        fn path(x: &str) -> Path {
+            Ok(Path(x.to_owned()))
+        }
+    }
+
+    let mut a = Assert::new();
+    a.globals_add(module);
+    a.pass(
+        r#"
+p = path("a/b")
+assert_eq(repr(p), 'Path("a/b")')
+assert_eq(str(p), "a/b")
+assert_eq("%s %r" % (p, p), 'a/b Path("a/b")')
+assert_eq("{}".format(p), "a/b")
+# Containers always use the repr of their elements.
+assert_eq(str([p]), '[Path("a/b")]')
+"#,
+    );
+    let v = a.pass("path('c')");
+    assert_eq!(v.value().to_str(), "c");
+    assert_eq!(v.value().to_repr(), "Path(\"c\")");
+}
+
 #[test]
 fn test_label_assign() {
     // Test the a.b = c construct.
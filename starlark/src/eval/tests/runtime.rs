@@ -20,6 +20,7 @@
 use std::{
     mem,
     sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
 };
 
 use derive_more::Display;
@@ -29,7 +30,10 @@ use crate as starlark;
 use crate::{
     assert,
     assert::Assert,
-    environment::GlobalsBuilder,
+    environment::{Globals, GlobalsBuilder, Module},
+    errors::Diagnostic,
+    eval::Evaluator,
+    syntax::{AstModule, Dialect},
     values::{any::StarlarkAny, FrozenHeap, Heap},
 };
 
@@ -228,3 +232,480 @@ fn test_display_debug() {
     assert_eq!(format!("{:?}", v), "FrozenValue(\"test\")");
     assert_eq!(format!("{:#?}", v), "FrozenValue(\n    \"test\",\n)");
 }
+
+#[test]
+fn test_max_collection_length() {
+    let mut a = Assert::new();
+    a.setup_eval(|eval| eval.set_max_collection_length(1000));
+    // The length of a range is known, so this fails before allocating anything
+    a.fail("list(range(1000000000))", "maximum length of 1000");
+    a.fail("tuple(range(1000000000))", "maximum length of 1000");
+    // And also to other iterables
+    a.fail(
+        "list({x: None for x in range(1001)})",
+        "maximum length of 1000",
+    );
+    a.pass("assert_eq(len(list(range(1000))), 1000)");
+    a.pass("assert_eq(tuple({1: 2, 3: 4}), (1, 3))");
+    // Lists and dictionaries can't grow beyond the limit either
+    a.fail(
+        "def f():\n  xs = []\n  for x in range(2000):\n    xs.append(x)\nf()",
+        "maximum length of 1000",
+    );
+    a.fail(
+        "def f(n): return [1] * n\nf(1001)",
+        "maximum length of 1000",
+    );
+    a.fail(
+        "def f(n):\n  x = [1] * n\n  x += x\nf(600)",
+        "maximum length of 1000",
+    );
+    a.fail("[x for x in range(1001)]", "maximum length of 1000");
+    a.fail(
+        "def f():\n  d = {}\n  for x in range(2000):\n    d[x] = x\nf()",
+        "maximum length of 1000",
+    );
+    a.pass("def f(n):\n  x = [1] * n\n  x.extend(x)\n  return x\nassert_eq(len(f(500)), 1000)");
+    // A full dictionary can still have existing keys updated.
+    a.pass(
+        r#"
+def f():
+    d = {x: x for x in range(1000)}
+    d[0] = 1
+    d.update({1: 2})
+    d.setdefault(2, 3)
+    return d
+assert_eq(len(f()), 1000)
+"#,
+    );
+    a.fail(
+        "def f():\n  d = {x: x for x in range(1000)}\n  d.update({1000: 1})\nf()",
+        "maximum length of 1000",
+    );
+}
+
+#[test]
+fn test_max_string_length() {
+    let mut a = Assert::new();
+    a.setup_eval(|eval| eval.set_max_string_length(1000));
+    a.fail(
+        "def f(n): return 'x' * n\nf(1001)",
+        "maximum length of 1000 bytes",
+    );
+    a.fail(
+        "def f():\n  s = 'x'\n  for _ in range(20):\n    s += s\nf()",
+        "maximum length of 1000 bytes",
+    );
+    a.fail("'-'.join(['x' * 10] * 100)", "maximum length of 1000 bytes");
+    a.fail("'%2000d' % 1", "maximum length of 1000 bytes");
+    a.fail("'{:2000}'.format(1)", "maximum length of 1000 bytes");
+    a.fail(
+        "def f(x): return '{}!'.format(x)\nf('x' * 1000)",
+        "maximum length of 1000 bytes",
+    );
+    a.fail(
+        "def f(x): return '{} {}'.format(x, x)\nf('x' * 600)",
+        "maximum length of 1000 bytes",
+    );
+    a.pass("assert_eq(len('x' * 1000), 1000)");
+    a.pass("assert_eq(len('x' * 500 + 'y' * 500), 1000)");
+}
+
+#[test]
+fn test_deterministic() {
+    // Anything which depends on hashing, addresses or randomness would show up in the output.
+    let program = r#"
+s = struct(b = 1, a = [2, 3])
+d = {str(x): x * x for x in range(100)}
+d.update({"z": s, "a": dir(s)})
+(
+    repr(d),
+    d.keys()[::7],
+    dir(d),
+    dir(""),
+    [hash(k) for k in d.keys()[:5]],
+    s.to_json(),
+    sorted(d.keys()),
+)
+"#;
+    let run = |seed| {
+        let mut a = Assert::new();
+        a.setup_eval(move |eval| eval.set_random_seed(seed));
+        a.pass(program).value().to_repr()
+    };
+    let first = run(0);
+    assert_eq!(first, run(0));
+    // Nothing in the standard library is random, so the seed makes no difference.
+    assert_eq!(first, run(42));
+}
+
+#[test]
+fn test_max_allocations() {
+    let mut a = Assert::new();
+    a.setup_eval(|eval| eval.set_max_allocations(10000));
+    a.fail(
+        r#"
+xs = []
+for i in range(100000):
+    xs.append([i])
+"#,
+        "maximum of 10000 allocated values",
+    );
+    a.fail(
+        r#"
+def f(n):
+    return [n] if n == 0 else f(n - 1) + [n]
+def g():
+    for i in range(1000):
+        f(20)
+g()
+"#,
+        "maximum of 10000 allocated values",
+    );
+    a.pass(
+        r#"
+xs = []
+for i in range(100):
+    xs.append([i])
+"#,
+    );
+}
+
+#[test]
+fn test_max_heap_bytes() {
+    let mut a = Assert::new();
+    a.setup_eval(|eval| eval.set_max_heap_bytes(1_000_000));
+    a.fail(
+        r#"
+def f():
+    xs = []
+    for i in range(100000):
+        xs.append("x" * i)
+f()
+"#,
+        "maximum heap size of 1000000 bytes",
+    );
+    // A single allocation fails before it is made.
+    a.fail("'a' * 2000000000", "maximum heap size of 1000000 bytes");
+    a.fail(
+        "list(range(1000000000))",
+        "maximum heap size of 1000000 bytes",
+    );
+    a.pass(
+        r#"
+xs = []
+for i in range(100):
+    xs.append([i])
+"#,
+    );
+}
+
+#[test]
+fn test_gc_stats() {
+    let globals = Globals::standard();
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    assert!(eval.gc_stats().is_err());
+    eval.enable_gc_stats();
+    let ast = AstModule::parse(
+        "gc_stats.star",
+        r#"
+len([str(i) for i in range(10000)])
+len([str(i) for i in range(10000)])
+x = 1
+"#
+        .to_owned(),
+        &Dialect::Extended,
+    )
+    .unwrap();
+    eval.eval_module(ast, &globals).unwrap();
+    let stats = eval.gc_stats().unwrap();
+    assert!(stats.cycles >= 1, "{:?}", stats);
+    assert!(stats.reclaimed_bytes > 0, "{:?}", stats);
+    assert!(
+        stats.allocated_bytes >= stats.reclaimed_bytes,
+        "{:?}",
+        stats
+    );
+    assert!(stats.peak_allocated_bytes > 0, "{:?}", stats);
+}
+
+#[test]
+fn test_max_call_depth() {
+    let program = "def f(n):\n  return 0 if n == 0 else 1 + f(n - 1)\n";
+    let mut a = Assert::new();
+    a.setup_eval(|eval| eval.set_max_call_depth(10));
+    a.pass(&format!("{}assert_eq(5, f(5))", program));
+    a.fail(&format!("{}f(20)", program), "Too many recursion levels");
+    // The default limit still applies without a setter.
+    let a = Assert::new();
+    a.pass(&format!("{}assert_eq(20, f(20))", program));
+    a.fail(&format!("{}f(100)", program), "Too many recursion levels");
+}
+
+#[test]
+fn test_error_call_stack() {
+    let err = assert::fail("def f():\n  fail('oops')\ndef g():\n  f()\ng()", "oops");
+    let frames = err
+        .downcast_ref::<Diagnostic>()
+        .unwrap()
+        .call_stack
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    // Outermost call first, each with the location it was called from.
+    assert!(frames.len() >= 2, "{:?}", frames);
+    assert!(frames[0].starts_with("g("), "{:?}", frames);
+    assert!(frames[0].contains(":5:1"), "{:?}", frames);
+    assert!(frames[1].starts_with("f("), "{:?}", frames);
+    assert!(frames[1].contains(":4:3"), "{:?}", frames);
+}
+
+#[test]
+fn test_compiled_global_references() {
+    let globals = Globals::standard();
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    eval.enable_compiled_global_references();
+    let ast = AstModule::parse(
+        "test.star",
+        r#"
+def str(x):
+    return x
+def f(xs):
+    return [len(x) for x in xs]
+f(range(3)) if False else str(len([]))
+"#
+        .to_owned(),
+        &Dialect::Standard,
+    )
+    .unwrap();
+    eval.eval_module(ast, &globals).unwrap();
+    // `str` is shadowed by the module, so is not a global. `range` is only used by code
+    // which never runs, but is still referenced by the compiled module.
+    assert_eq!(
+        eval.compiled_global_references()
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec!["len".to_owned(), "range".to_owned()]
+    );
+}
+
+#[test]
+fn test_string_interning() {
+    let program = r#"
+xs = [str(i % 10) * 50 for i in range(1000)]
+ys = ["x" + x + "y" for x in xs]
+"#;
+    let checks = r#"
+assert_eq(len({x: None for x in xs}), 10)
+assert_eq(xs[3], xs[13])
+assert_eq(ys[3] == ys[13], True)
+assert_eq(ys[3] == ys[4], False)
+assert_eq(sorted(ys)[0], "x" + "0" * 50 + "y")
+"#;
+    let allocated = |intern| {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.disable_gc();
+        if intern {
+            eval.enable_string_interning();
+        }
+        let ast = AstModule::parse("test.star", program.to_owned(), &Dialect::Standard).unwrap();
+        eval.eval_module(ast, &Globals::extended()).unwrap();
+        module.heap().allocated_bytes()
+    };
+    let plain = allocated(false);
+    let interned = allocated(true);
+    assert!(interned * 2 < plain, "{} {}", interned, plain);
+
+    let mut a = Assert::new();
+    a.setup_eval(|eval| eval.enable_string_interning());
+    a.pass(&format!("{}{}", program, checks));
+}
+
+#[test]
+fn test_evaluation_order() {
+    // Operands are evaluated left to right, so with several failing operands
+    // it is always the leftmost error that is reported.
+    assert::pass(
+        r#"
+trace = []
+def t(x, v = None):
+    trace.append(x)
+    return v
+def f(*args, **kwargs):
+    pass
+f(t(1), t(2), c = t(3), *t(4, []), **t(5, {}))
+t(6, [0])[t(7, 0)]
+t(8, 1) + t(9, 1)
+t(10, 1) in t(11, [1])
+t(12, {}).get(t(13))
+[t(14), t(15)]
+{t(16, 1): t(17), t(18, 2): t(19)}
+t(20, [1, 2])[t(21, 0):t(22, 2):t(23, 1)]
+assert_eq(trace, list(range(1, 24)))
+"#,
+    );
+
+    let mut a = Assert::new();
+    a.module("bad.star", "def bad(x):\n    fail(x)");
+    let bad = |program: &str| format!("load('bad.star', 'bad')\n{}", program);
+    a.fail(&bad("bad('left') + bad('right')"), "left");
+    a.fail(&bad("bad('left') == bad('right')"), "left");
+    a.fail(&bad("bad('left') in bad('right')"), "left");
+    a.fail(&bad("bad('object')[bad('index')]"), "object");
+    a.fail(&bad("[1][bad('index')] + bad('right')"), "index");
+    a.fail(&bad("bad('function')(bad('argument'))"), "function");
+    a.fail(&bad("len(bad('first'), bad('second'))"), "first");
+    a.fail(&bad("len(x = bad('named'), *bad('args'))"), "named");
+    a.fail(&bad("[bad('first'), bad('second')]"), "first");
+    a.fail(&bad("{bad('key'): bad('value')}"), "key");
+    // Errors from operators, not just calls, are ordered too
+    a.fail("[][1] + {}['missing']", "out of bound");
+    a.fail("(1 + None) + (None - 1)", "`+`");
+}
+
+#[test]
+fn test_freeze_shares_values() {
+    fn frozen_bytes(program: &str) -> usize {
+        Assert::new()
+            .module("shared.star", program)
+            .frozen_heap()
+            .allocated_bytes()
+    }
+
+    // A value is frozen once however many values reference it, since freezing
+    // replaces it with a forward to its frozen copy.
+    let string = "s = 'x' * 100000\n";
+    let once = frozen_bytes(&format!("{}xs = [s]", string));
+    let many = frozen_bytes(&format!(
+        "{}xs = [s] * 1000\nys = [(s, s) for _ in range(100)]\nzs = {{'k': s}}",
+        string
+    ));
+    assert!(once > 100000, "{}", once);
+    assert!(many - once < 100000, "{} {}", once, many);
+}
+
+#[test]
+fn test_disable_builtins() {
+    let globals = Globals::standard();
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    eval.disable_builtins(&["print", "fail"]);
+    let mut run = |program: &str| {
+        let ast = AstModule::parse("disabled.star", program.to_owned(), &Dialect::Extended)?;
+        eval.eval_module(ast, &globals).map(|x| x.to_repr())
+    };
+    let err = run("print('x')").unwrap_err().to_string();
+    assert!(err.contains("Builtin `print` is disabled"), "{}", err);
+    // Disabled builtins only fail when called
+    assert_eq!(run("f = fail\nlen([1])").unwrap(), "1");
+    // Shadowing a disabled builtin is fine
+    assert_eq!(run("def print(x): return x\nprint(2)").unwrap(), "2");
+}
+
+#[test]
+fn test_timeout_returns_bindings() {
+    let globals = Globals::standard();
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    let ast = AstModule::parse(
+        "timeout.star",
+        r#"
+a = 1
+b = [a, 2]
+def f():
+    pass
+for x in range(2147483647):
+    f()
+c = "never assigned"
+"#
+        .to_owned(),
+        &Dialect::Extended,
+    )
+    .unwrap();
+    let err = eval
+        .eval_module_with_timeout(ast, &globals, Duration::from_millis(100))
+        .unwrap_err();
+    assert!(
+        err.error.to_string().contains("timed out after 100ms"),
+        "{}",
+        err.error
+    );
+    let names: Vec<&str> = err.bindings.iter().map(|(name, _)| name.as_str()).collect();
+    // `c` is compiled, but never assigned
+    assert_eq!(names, vec!["a", "b", "f", "x"]);
+    assert_eq!(err.bindings[1].1.to_repr(), "[1, 2]");
+    // The timeout only applied to that evaluation.
+    assert!(!eval.has_call_limits());
+}
+
+#[test]
+fn test_continue_on_error() {
+    let globals = Globals::standard();
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    let ast = AstModule::parse(
+        "errors.star",
+        r#"
+a = 1
+b = fail("first")
+c = a + 1
+def f():
+    return {}["missing"]
+d = f()
+e = b
+g = 3
+"#
+        .to_owned(),
+        &Dialect::Extended,
+    )
+    .unwrap();
+    let err = eval
+        .eval_module_continue_on_error(ast, &globals)
+        .unwrap_err();
+    let errors: Vec<String> = err.errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(errors.len(), 3, "{:?}", errors);
+    assert!(errors[0].contains("first"), "{}", errors[0]);
+    assert!(errors[1].contains("missing"), "{}", errors[1]);
+    // `b` was never assigned.
+    assert!(
+        errors[2].contains("referenced before assignment"),
+        "{}",
+        errors[2]
+    );
+    // Runtime errors carry the span of the failing code.
+    let d = err.errors[0].downcast_ref::<Diagnostic>().unwrap();
+    assert_eq!(d.span.as_ref().unwrap().resolve_span().begin_line, 2);
+    let names: Vec<&str> = err.bindings.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["a", "c", "f", "g"]);
+
+    // Without failures, the result is the same as `eval_module`.
+    let ast = AstModule::parse(
+        "ok.star",
+        "x = 1
+x + 1"
+            .to_owned(),
+        &Dialect::Extended,
+    )
+    .unwrap();
+    let res = eval.eval_module_continue_on_error(ast, &globals).unwrap();
+    assert_eq!(res.unpack_int(), Some(2));
+}
+
+#[test]
+fn test_deadline() {
+    let globals = Globals::standard();
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    eval.set_deadline(Instant::now() + Duration::from_millis(100));
+    let ast = AstModule::parse(
+        "deadline.star",
+        "def f():\n    pass\nfor x in range(2147483647):\n    f()\n".to_owned(),
+        &Dialect::Extended,
+    )
+    .unwrap();
+    let err = eval.eval_module(ast, &globals).unwrap_err();
+    assert!(err.to_string().contains("timed out"), "{}", err);
+}
@@ -247,6 +247,48 @@
 //! # fn main(){ run().unwrap(); }
 //! ```
 //!
+//! ## Reuse a prelude module across evaluations
+//!
+//! Values in a [`Module`](environment::Module) borrow it, with the lifetime `'v` of the
+//! module, so can't outlive it. Once the module is frozen with
+//! [`Module.freeze`](environment::Module::freeze), the resulting
+//! [`FrozenModule`](environment::FrozenModule) owns its values, which are immutable, so it can
+//! be kept for as long as required and shared by any number of later evaluations. A module
+//! which uses a frozen value keeps the heap which owns it alive. Here a prelude of helpers is
+//! compiled once, then made available to other modules as globals with
+//! [`GlobalsBuilder.prelude`](environment::GlobalsBuilder::prelude). Alternatively,
+//! [`Module.import_public_symbols`](environment::Module::import_public_symbols) makes them
+//! module variables, as a `load` would.
+//!
+//! ```
+//! # fn run() -> anyhow::Result<()> {
+//! use starlark::environment::{GlobalsBuilder, Module};
+//! use starlark::eval::Evaluator;
+//! use starlark::syntax::{AstModule, Dialect};
+//!
+//! let prelude = {
+//!     let code = "def double(x): return x * 2".to_owned();
+//!     let ast = AstModule::parse("prelude.star", code, &Dialect::Standard)?;
+//!     let module = Module::new();
+//!     let mut eval = Evaluator::new(&module);
+//!     eval.eval_module(ast, &GlobalsBuilder::standard().build())?;
+//!     module.freeze()?
+//! };
+//! let globals = GlobalsBuilder::standard().with_prelude(prelude).build();
+//!
+//! for i in 0..3 {
+//!     let code = format!("x = double({})", i);
+//!     let ast = AstModule::parse("config.star", code, &Dialect::Standard)?;
+//!     let module = Module::new();
+//!     let mut eval = Evaluator::new(&module);
+//!     eval.eval_module(ast, &globals)?;
+//!     assert_eq!(module.get("x").unwrap().unpack_int(), Some(i * 2));
+//! }
+//! # Ok(())
+//! # }
+//! # fn main(){ run().unwrap(); }
+//! ```
+//!
 //! ## Call a Starlark function from Rust
 //!
 //! You can extract functions from Starlark, and call them from Rust, using [`eval_function`](eval::Evaluator::eval_function).
@@ -280,6 +322,46 @@
 //! # fn main(){ run().unwrap(); }
 //! ```
 //!
+//! ## Evaluate modules in parallel
+//!
+//! A [`Module`](environment::Module) and its [`Evaluator`](eval::Evaluator) belong to a single
+//! thread, but [`Globals`](environment::Globals), [`AstModule`](syntax::AstModule),
+//! [`FrozenModule`](environment::FrozenModule) and
+//! [`OwnedFrozenValue`](values::OwnedFrozenValue) are all `Send` and `Sync`, and frozen values
+//! can't be mutated. So independent modules can be evaluated on separate threads, sharing the
+//! same [`Globals`](environment::Globals) and loaded modules, by creating a fresh
+//! [`Module`](environment::Module) on each thread and freezing it to send the results back.
+//!
+//! ```
+//! # fn run() -> anyhow::Result<()> {
+//! use std::thread;
+//! use starlark::environment::{Globals, Module};
+//! use starlark::eval::Evaluator;
+//! use starlark::syntax::{AstModule, Dialect};
+//!
+//! let globals = Globals::standard();
+//! let threads: Vec<_> = (0..4)
+//!     .map(|i| {
+//!         let globals = globals.clone();
+//!         thread::spawn(move || -> anyhow::Result<_> {
+//!             let code = format!("x = [{}] * 3", i);
+//!             let ast = AstModule::parse("parallel.star", code, &Dialect::Standard)?;
+//!             let module = Module::new();
+//!             let mut eval = Evaluator::new(&module);
+//!             eval.eval_module(ast, &globals)?;
+//!             Ok(module.freeze()?)
+//!         })
+//!     })
+//!     .collect();
+//! for (i, thread) in threads.into_iter().enumerate() {
+//!     let module = thread.join().unwrap()?;
+//!     assert_eq!(module.get("x").unwrap().value().to_repr(), format!("[{0}, {0}, {0}]", i));
+//! }
+//! # Ok(())
+//! # }
+//! # fn main(){ run().unwrap(); }
+//! ```
+//!
 //! ## Defining Rust objects that are used from Starlark
 //!
 //! Finally, we can define our own types in Rust which live in the Starlark heap.
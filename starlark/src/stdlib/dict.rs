@@ -258,7 +258,7 @@ pub(crate) fn dict_methods(registry: &mut GlobalsBuilder) {
             return Ok(r);
         }
         let def = default.unwrap_or_else(Value::new_none);
-        this.insert_hashed(key, def);
+        this.insert_hashed_bounded(key, def, heap)?;
         Ok(def)
     }
 
@@ -307,7 +307,7 @@ pub(crate) fn dict_methods(registry: &mut GlobalsBuilder) {
         if let Some(pairs) = pairs {
             if let Some(dict) = Dict::from_value(pairs) {
                 for (k, v) in dict.iter_hashed() {
-                    this.insert_hashed(k, v);
+                    this.insert_hashed_bounded(k, v, heap)?;
                 }
             } else {
                 for v in pairs.iterate(heap)? {
@@ -319,13 +319,13 @@ pub(crate) fn dict_methods(registry: &mut GlobalsBuilder) {
                             "dict.update expect a list of pairs or a dictionary as first argument, got a list of non-pairs.",
                         ));
                     };
-                    this.insert_hashed(k.unwrap().get_hashed()?, v.unwrap());
+                    this.insert_hashed_bounded(k.unwrap().get_hashed()?, v.unwrap(), heap)?;
                 }
             }
         }
 
         for (k, v) in kwargs.iter_hashed() {
-            this.insert_hashed(k, v);
+            this.insert_hashed_bounded(k, v, heap)?;
         }
         Ok(NoneType)
     }
@@ -366,6 +366,25 @@ mod tests {
         assert::fail("{1: 2} + {3: 4}", "not supported");
     }
 
+    #[test]
+    fn test_update() {
+        assert::pass(
+            r#"
+x = {"a": 1}
+x.update({"b": 2})
+x.update([("c", 3), ["d", 4]])
+x.update(e = 5, a = 0)
+x.update([("f", 6)], f = 7)
+x.update()
+assert_eq(x, {"a": 0, "b": 2, "c": 3, "d": 4, "e": 5, "f": 7})
+"#,
+        );
+        assert::fail("{}.update([(1, 2, 3)])", "list of pairs");
+        let mut a = assert::Assert::new();
+        a.module("m", "frozen = {}");
+        a.fail("load('m', 'frozen')\nfrozen.update(a = 1)", "Immutable");
+    }
+
     #[test]
     fn test_dict_with_duplicates() {
         // In Starlark spec this is a runtime error. In Python it's fine.
@@ -20,6 +20,7 @@ use std::{
     fmt::{self, Display},
 };
 
+use anyhow::anyhow;
 use gazebo::{
     any::AnyLifetime,
     cell::ARef,
@@ -35,9 +36,9 @@ use crate::{
     environment::GlobalsBuilder,
     eval::{Arguments, Evaluator},
     values::{
-        dict::Dict, function::FUNCTION_TYPE, none::NoneType, tuple::Tuple, Freeze, Freezer,
-        FrozenStringValue, FrozenValue, StarlarkValue, StringValue, StringValueLike, Trace, Value,
-        ValueLike,
+        dict::Dict, function::FUNCTION_TYPE, none::NoneType, num::Num, tuple::Tuple, Freeze,
+        Freezer, FrozenStringValue, FrozenValue, StarlarkValue, StringValue, StringValueLike,
+        Trace, Value, ValueLike,
     },
 };
 
@@ -126,8 +127,13 @@ pub fn dedupe(builder: &mut GlobalsBuilder) {
 #[starlark_module]
 pub fn print(builder: &mut GlobalsBuilder) {
     fn print(args: Vec<Value>) -> NoneType {
-        // In practice most users should want to put the print somewhere else, but this does for now
-        eprintln!("{}", args.iter().map(|x| x.to_str()).join(" "));
+        let line = args.iter().map(|x| x.to_str()).join(" ");
+        match &mut eval.print_handler {
+            Some(handler) => handler(&line),
+            // In practice most users should want to put the print somewhere else,
+            // see `Evaluator::set_print_handler`.
+            None => eprintln!("{}", line),
+        }
         Ok(NoneType)
     }
 }
@@ -146,6 +152,88 @@ pub fn abs(builder: &mut GlobalsBuilder) {
     }
 }
 
+/// Round to the nearest integer, with halfway cases going to the even integer.
+fn round_half_even(x: f64) -> f64 {
+    if (x - x.trunc()).abs() == 0.5 {
+        2.0 * (x / 2.0).round()
+    } else {
+        x.round()
+    }
+}
+
+#[starlark_module]
+pub fn round(builder: &mut GlobalsBuilder) {
+    /// [round](
+    /// https://docs.python.org/3/library/functions.html#round
+    /// ): round a number. _Not part of standard Starlark._
+    ///
+    /// `round(x)` returns the integer nearest to `x`. `round(x, ndigits)` returns
+    /// `x` rounded to `ndigits` digits after the decimal point, which may be negative,
+    /// with the same type as `x`.
+    ///
+    /// As in Python, a value exactly halfway between two choices is rounded to the even
+    /// choice, unless the evaluator was configured with
+    /// [`enable_round_half_up`](crate::eval::Evaluator::enable_round_half_up),
+    /// in which case it is rounded away from zero. Most decimal fractions can't be
+    /// represented exactly as floats, so `round(2.675, 2)` is `2.67`, because the
+    /// float nearest to `2.675` is slightly less than it.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// round(2.5) == 2
+    /// round(3.5) == 4
+    /// round(-2.5) == -2
+    /// round(2.4) == 2
+    /// round(7) == 7
+    /// round(2.675, 2) == 2.67
+    /// round(1250, -2) == 1200
+    /// # "#);
+    /// ```
+    fn round(ref x: Num, ref ndigits: Option<i32>) -> Value<'v> {
+        let half_up = eval.round_half_up;
+        let round = |x: f64| {
+            if half_up {
+                x.round()
+            } else {
+                round_half_even(x)
+            }
+        };
+        let to_int = |x: f64| {
+            Num::from(x)
+                .as_int()
+                .ok_or_else(|| anyhow!("round() result is not a valid integer: {}", x))
+        };
+        match (x, ndigits) {
+            (Num::Int(x), None) => Ok(Value::new_int(x)),
+            (Num::Int(x), Some(n)) if n >= 0 => Ok(Value::new_int(x)),
+            (Num::Int(x), Some(n)) => {
+                // All integers are smaller than 10^10, so more digits always round to 0
+                let p = 10f64.powi(n.saturating_neg().min(10));
+                Ok(Value::new_int(to_int(round(x as f64 / p) * p)?))
+            }
+            (Num::Float(x), None) => Ok(Value::new_int(to_int(round(x))?)),
+            (Num::Float(x), Some(n)) if n >= 0 => {
+                let p = 10f64.powi(n);
+                let y = x * p;
+                // If there are more digits than a float can represent, it is unchanged
+                Ok(heap.alloc(if y.is_finite() { round(y) / p } else { x }))
+            }
+            (Num::Float(x), Some(n)) => {
+                // All finite floats are smaller than 10^309, so more digits always round to 0
+                let n = n.saturating_neg();
+                Ok(heap.alloc(if !x.is_finite() {
+                    x
+                } else if n > 308 {
+                    0.0f64.copysign(x)
+                } else {
+                    let p = 10f64.powi(n);
+                    round(x / p) * p
+                }))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Coerce, Trace)]
 #[repr(C)]
 struct PartialGen<V, S> {
@@ -234,7 +322,12 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::assert;
+    use crate::{
+        assert::{self, Assert},
+        environment::{Globals, LibraryExtension, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+    };
 
     #[test]
     fn test_filter() {
@@ -323,6 +416,67 @@ assert_eq(dedupe([1,2,3,2,1]), [1,2,3])
 a = [1]
 b = [1]
 assert_eq(dedupe([a,b,a]), [a,b])
+"#,
+        );
+    }
+
+    #[test]
+    fn test_print_handler() {
+        let mut lines = Vec::new();
+        {
+            let mut handler = |line: &str| lines.push(line.to_owned());
+            let module = Module::new();
+            let globals = Globals::extended_by(&[LibraryExtension::Print]);
+            let mut eval = Evaluator::new(&module);
+            eval.set_print_handler(&mut handler);
+            let ast = AstModule::parse(
+                "print.star",
+                "print('hello', 1, [None])\nprint()".to_owned(),
+                &Dialect::Extended,
+            )
+            .unwrap();
+            eval.eval_module(ast, &globals).unwrap();
+        }
+        assert_eq!(lines, vec!["hello 1 [None]", ""]);
+    }
+
+    #[test]
+    fn test_round() {
+        assert::all_true(
+            r#"
+round(2.5) == 2 and type(round(2.5)) == "int"
+round(3.5) == 4
+round(0.5) == 0
+round(-0.5) == 0
+round(-3.5) == -4
+round(2.675, 2) == 2.67
+round(0.125, 2) == 0.12
+round(0.375, 2) == 0.38
+round(1.5, 0) == 2.0 and type(round(1.5, 0)) == "float"
+round(12345.678, -2) == 12300.0
+round(1.0, -400) == 0.0
+round(-1e300, -400) == 0.0
+round(1.25, 400) == 1.25
+round(1250, -2) == 1200
+round(1350, -2) == 1400
+round(1234, 2) == 1234
+round(5, -20) == 0
+"#,
+        );
+        assert::fail("round(1e100)", "not a valid integer");
+        assert::fail("round(float('nan'))", "not a valid integer");
+        assert::fail("round('1')", "doesn't match");
+
+        let mut a = Assert::new();
+        a.setup_eval(|eval| eval.enable_round_half_up());
+        a.all_true(
+            r#"
+round(2.5) == 3
+round(3.5) == 4
+round(-2.5) == -3
+round(0.125, 2) == 0.13
+round(1250, -2) == 1300
+round(2.4) == 2
 "#,
         );
     }
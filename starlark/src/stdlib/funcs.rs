@@ -39,7 +39,7 @@ use crate::{
         range::Range,
         string::STRING_TYPE,
         tuple::Tuple,
-        AttrType, Heap, Value, ValueError, ValueLike,
+        AttrType, Heap, UnpackValue, Value, ValueError, ValueLike,
     },
 };
 
@@ -80,10 +80,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
         let mut s = String::new();
         for x in args {
             s.push(' ');
-            match x.unpack_str() {
-                Some(x) => s.push_str(x),
-                None => x.collect_repr(&mut s),
-            }
+            x.collect_str(&mut s);
         }
         Err(anyhow!("fail:{}", s))
     }
@@ -171,6 +168,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// # "#);
     /// ```
     #[starlark_type(BOOL_TYPE)]
+    #[speculative_exec_safe]
     fn bool(ref x: Option<Value>) -> bool {
         match x {
             None => Ok(false),
@@ -195,6 +193,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// chr(0x1F63F) == '😿'
     /// # "#);
     /// ```
+    #[speculative_exec_safe]
     fn chr(ref i: Value) -> String {
         let cp = i.to_int()? as u32;
         match std::char::from_u32(cp) {
@@ -211,13 +210,14 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// ): creates a dictionary.
     ///
     /// `dict` creates a dictionary. It accepts up to one positional argument,
-    /// which is interpreted as an iterable of two-element sequences
-    /// (pairs), each specifying a key/value pair in the
+    /// which is either a dictionary to copy, or is interpreted as an iterable
+    /// of two-element sequences (pairs), each specifying a key/value pair in the
     /// resulting dictionary.
     ///
     /// `dict` also accepts any number of keyword arguments, each of which
     /// specifies a key/value pair in the resulting dictionary; each keyword
-    /// is treated as a string.
+    /// is treated as a string. The keyword arguments are added after the
+    /// positional argument, so override any entries with the same key.
     ///
     /// Examples:
     ///
@@ -230,6 +230,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// dict(one=1, two=2) == {'one': 1, 'two': 2}
     /// dict([(1, 2)], x=3) == {1: 2, 'x': 3}
     /// dict([('x', 2)], x=3) == {'x': 3}
+    /// dict({'a': 1}, a=2) == {'a': 2}
     /// # "#);
     /// # starlark::assert::is_true(r#"
     /// x = {'a': 1}
@@ -283,11 +284,15 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// methods) of its operand. The attributes of a value `x` are the names
     /// `f` such that `x.f` is a valid expression.
     ///
+    /// The names are always returned in sorted order, regardless of the order
+    /// in which the attributes were defined.
+    ///
     /// Examples:
     ///
     /// ```
     /// # starlark::assert::all_true(r#"
     /// "capitalize" in dir("abc")
+    /// dir(struct(b=1, a=2)) == ["a", "b", "to_json"]
     /// # "#);
     /// ```
     fn dir(ref x: Value) -> Vec<String> {
@@ -437,6 +442,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// hash("hello") != hash("world")
     /// # "#);
     /// ```
+    #[speculative_exec_safe]
     fn hash(ref a: &str) -> i32 {
         // From the starlark spec:
         // > the hash function for strings is the same as that implemented by java.lang.String.hashCode,
@@ -515,6 +521,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// # "#, "cannot convert float to integer");
     /// ```
     #[starlark_type(INT_TYPE)]
+    #[speculative_exec_safe]
     fn int(ref a: Option<Value>, base: Option<Value>) -> i32 {
         if a.is_none() {
             return Ok(0);
@@ -620,6 +627,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// len(True)    # error: not supported
     /// # "#, "not supported");
     /// ```
+    #[speculative_exec_safe]
     fn len(ref a: Value) -> i32 {
         a.length()
     }
@@ -637,6 +645,8 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// # starlark::assert::all_true(r#"
     /// list()        == []
     /// list((1,2,3)) == [1, 2, 3]
+    /// list(range(3)) == [0, 1, 2]
+    /// list({"a": 1, "b": 2}) == ["a", "b"]
     /// # "#);
     /// # starlark::assert::fail(r#"
     /// list("strings are not iterable") # error: not supported
@@ -648,7 +658,8 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
             if let Some(xs) = List::from_value(a) {
                 heap.alloc_list(xs.content())
             } else {
-                a.with_iterator(heap, |it| heap.alloc_list_iter(it))?
+                let xs = a.with_iterator(heap, |it| eval.collect_bounded(it))??;
+                heap.alloc_list(&xs)
             }
         } else {
             heap.alloc_list(&[])
@@ -784,6 +795,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// ord("😿")                               == 0x1F63F
     /// # "#);
     /// ```
+    #[speculative_exec_safe]
     fn ord(ref a: Value) -> i32 {
         if let Some(s) = a.unpack_str() {
             let mut chars = s.chars();
@@ -821,7 +833,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// With three arguments, `range(start, stop, step)` returns integers
     /// formed by successively adding `step` to `start` until the value meets or
     /// passes `stop`. A call to `range` fails if the value of `step` is
-    /// zero.
+    /// zero, or if any argument is not an integer.
     ///
     /// ```
     /// # starlark::assert::all_true(r#"
@@ -829,15 +841,31 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// list(range(3, 10))                      == [3, 4, 5, 6, 7, 8, 9]
     /// list(range(3, 10, 2))                   == [3, 5, 7, 9]
     /// list(range(10, 3, -2))                  == [10, 8, 6, 4]
+    /// list(range(3, 10, -1))                  == []
     /// # "#);
     /// ```
     #[starlark_type(Range::TYPE)]
-    fn range(ref a1: i32, ref a2: Option<i32>, ref step @ 1: i32) -> Range {
-        let start = match a2 {
-            None => 0,
-            Some(_) => a1,
+    fn range(ref a1: Value, ref a2: Option<Value>, ref step: Option<Value>) -> Range {
+        // We unpack the arguments ourselves, so errors can name them by their meaning,
+        // since the first argument is `stop` if it is the only one.
+        fn int(name: &str, x: Value) -> anyhow::Result<i32> {
+            i32::unpack_value(x).ok_or_else(|| {
+                anyhow!(
+                    "Argument `{}` of range must be an `int`, got `{}`",
+                    name,
+                    x.get_type()
+                )
+            })
+        }
+
+        let (start, stop) = match a2 {
+            None => (0, int("stop", a1)?),
+            Some(a2) => (int("start", a1)?, int("stop", a2)?),
+        };
+        let step = match step {
+            None => 1,
+            Some(step) => int("step", step)?,
         };
-        let stop = a2.unwrap_or(a1);
         let step = match NonZeroI32::new(step) {
             Some(step) => step,
             None => return Err(anyhow!("Third argument of range (step) cannot be zero")),
@@ -860,6 +888,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// repr("x\"y😿 \\'")      == "\"x\\\"y😿 \\\\\\'\""
     /// "#);
     /// ```
+    #[speculative_exec_safe]
     fn repr(ref a: Value) -> Value<'v> {
         let mut s = eval.string_pool.alloc();
         a.collect_repr(&mut s);
@@ -963,13 +992,14 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// # "#);
     /// ```
     #[starlark_type(STRING_TYPE)]
+    #[speculative_exec_safe]
     fn str(ref a: Value) -> Value<'v> {
         if a.unpack_str().is_some() {
             // Special case that can avoid reallocating, but is equivalent.
             Ok(a)
         } else {
             let mut s = eval.string_pool.alloc();
-            a.collect_repr(&mut s);
+            a.collect_str(&mut s);
             let r = eval.heap().alloc_str(&s);
             eval.string_pool.release(s);
             Ok(r)
@@ -986,16 +1016,17 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// # starlark::assert::all_true(r#"
     /// tuple() == ()
     /// tuple([1,2,3]) == (1, 2, 3)
+    /// tuple(range(3)) == (0, 1, 2)
     /// # "#);
     #[starlark_type(Tuple::TYPE)]
     fn tuple(ref a: Option<Value>) -> Value<'v> {
-        let mut l = Vec::new();
-        if let Some(a) = a {
-            a.with_iterator(heap, |it| {
-                l.extend(it);
-            })?;
+        match a {
+            None => Ok(heap.alloc_tuple(&[])),
+            Some(a) => {
+                let xs = a.with_iterator(heap, |it| eval.collect_bounded(it))??;
+                Ok(heap.alloc_tuple(&xs))
+            }
         }
-        Ok(heap.alloc_tuple(&l))
     }
 
     /// [type](
@@ -1011,6 +1042,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// type("hello")           == "string"
     /// # "#);
     /// ```
+    #[speculative_exec_safe]
     fn r#type(ref a: Value) -> Value<'v> {
         Ok(a.get_type_value().unpack().to_value())
     }
@@ -1070,6 +1102,60 @@ mod tests {
         assert::fail("chr(0x110000)", "not a valid UTF-8");
     }
 
+    #[test]
+    fn test_dict() {
+        assert::all_true(
+            r#"
+dict(a=1, b=2) == {"a": 1, "b": 2}
+dict([("a", 1)]) == {"a": 1}
+dict([("a", 1), ("a", 2)]) == {"a": 2}
+dict({"a": 1}, a=2) == {"a": 2}
+dict({"a": 1, "b": 2}, a=3) == {"a": 3, "b": 2}
+dict([("a", 1)], b=2, **{"c": 3}) == {"a": 1, "b": 2, "c": 3}
+"#,
+        );
+        assert::is_true(
+            r#"
+x = {"a": 1}
+y = dict(x, b=2)
+x == {"a": 1} and y == {"a": 1, "b": 2}
+"#,
+        );
+        assert::fail("dict([(1, 2, 3)])", "non-pair element");
+        assert::fail("dict({}, {})", "Wrong number of positional parameters");
+    }
+
+    #[test]
+    fn test_range() {
+        assert::all_true(
+            r#"
+list(range(5, 0, -2)) == [5, 3, 1]
+list(range(0, 5, -1)) == []
+list(range(5, 5, -1)) == []
+list(range(-1, -4, -1)) == [-1, -2, -3]
+len(range(10, 0, -3)) == 4
+"#,
+        );
+        assert::fail("range(1, 5, 0)", "step) cannot be zero");
+        assert::fail(
+            "range(1.5)",
+            "Argument `stop` of range must be an `int`, got `float`",
+        );
+        assert::fail(
+            "range(1.0, 5)",
+            "Argument `start` of range must be an `int`, got `float`",
+        );
+        assert::fail(
+            "range(1, 5.0)",
+            "Argument `stop` of range must be an `int`, got `float`",
+        );
+        assert::fail(
+            "range(1, 5, 0.5)",
+            "Argument `step` of range must be an `int`, got `float`",
+        );
+        assert::fail("range('3')", "got `string`");
+    }
+
     #[test]
     fn test_hash() {
         assert::eq("0", "hash('')");
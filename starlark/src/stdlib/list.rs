@@ -53,7 +53,7 @@ pub(crate) fn list_methods(builder: &mut GlobalsBuilder) {
     /// ```
     fn append(this: Value, ref el: Value) -> NoneType {
         let this = List::from_value_mut(this)?.unwrap();
-        this.push(el, heap);
+        this.push(el, heap)?;
         Ok(NoneType)
     }
 
@@ -104,11 +104,9 @@ pub(crate) fn list_methods(builder: &mut GlobalsBuilder) {
         if this.ptr_eq(other) {
             // If the types alias, we can't borrow the `other` for iteration.
             // But we can do something smarter to double the elements
-            res.double(heap);
+            res.double(heap)?;
         } else {
-            other.with_iterator(heap, |it| {
-                res.extend(it, heap);
-            })?;
+            other.with_iterator(heap, |it| res.extend(it, heap))??;
         }
         Ok(NoneType)
     }
@@ -183,7 +181,7 @@ pub(crate) fn list_methods(builder: &mut GlobalsBuilder) {
     fn insert(this: Value, ref index: i32, ref el: Value) -> NoneType {
         let this = List::from_value_mut(this)?.unwrap();
         let index = convert_index(this.len() as i32, index);
-        this.insert(index, el, heap);
+        this.insert(index, el, heap)?;
         Ok(NoneType)
     }
 
@@ -294,6 +292,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extend() {
+        assert::pass(
+            r#"
+x = [1]
+x.extend([2])
+x.extend((3,))
+x.extend({4: "a"})
+x.extend(range(5, 7))
+assert_eq(x, [1, 2, 3, 4, 5, 6])
+y = [1, 2]
+y.extend(y)
+assert_eq(y, [1, 2, 1, 2])
+"#,
+        );
+        assert::fail("x = []; x.extend(1)", "not supported");
+        let mut a = assert::Assert::new();
+        a.module("m", "frozen = [1]");
+        a.fail("load('m', 'frozen')\nfrozen.extend([2])", "Immutable");
+    }
+
     #[test]
     fn recursive_list() {
         assert::is_true(
@@ -71,6 +71,8 @@ pub enum LibraryExtension {
     Json,
     /// Add a function `abs()` which will take the absolute value of an int.
     Abs,
+    /// Add a function `round(x, ndigits)` which rounds a number, as in Python.
+    Round,
     // Make sure if you add anything new, you add it to `all` below.
 }
 
@@ -80,7 +82,7 @@ impl LibraryExtension {
         use LibraryExtension::*;
         &[
             StructType, RecordType, EnumType, Map, Filter, Partial, Dedupe, Debug, Print,
-            Breakpoint, Json, Abs,
+            Breakpoint, Json, Abs, Round,
         ]
     }
 
@@ -100,6 +102,7 @@ impl LibraryExtension {
             Breakpoint => breakpoint::global(builder),
             Json => extra::json(builder),
             Abs => extra::abs(builder),
+            Round => extra::round(builder),
         }
     }
 }
@@ -20,14 +20,18 @@
 use std::cmp;
 
 use anyhow::anyhow;
-use gazebo::prelude::*;
+use gazebo::{cell::ARef, prelude::*};
+use once_cell::sync::Lazy;
+use regex::Regex;
 
 use crate as starlark;
 use crate::{
+    collections::SmallMap,
     environment::GlobalsBuilder,
     eval::Arguments,
     stdlib::util::convert_indices,
     values::{
+        dict::Dict,
         none::NoneOr,
         string,
         string::{fast_string, interpolation},
@@ -334,12 +338,16 @@ pub(crate) fn string_methods(builder: &mut GlobalsBuilder) {
     ///
     /// The *conversion* specifies how to convert an argument value `x` to a
     /// string. It may be either `!r`, which converts the value using
-    /// `repr(x)`, or `!s`, which converts the value using `str(x)` and is
-    /// the default.
+    /// `repr(x)`, `!a`, which is `!r` with non-ASCII characters escaped,
+    /// or `!s`, which converts the value using `str(x)` and is the default.
     ///
     /// The *format specifier*, after a colon, specifies field width,
-    /// alignment, padding, and numeric precision.
-    /// Currently it must be empty, but it is reserved for future use.
+    /// alignment, padding, and numeric precision, as
+    /// [in Python](https://docs.python.org/3/library/string.html#format-specification-mini-language):
+    /// `[[fill]align][sign][#][0][width][.precision][type]`.
+    /// The alignment is one of `<`, `>`, `^` or `=` (pad after the sign),
+    /// and the type one of `s`, `d`, `b`, `o`, `x`, `X`, `e`, `E`, `f`, `F`,
+    /// `g`, `G` or `%`. The grouping options are not supported.
     ///
     /// Examples:
     ///
@@ -350,6 +358,9 @@ pub(crate) fn string_methods(builder: &mut GlobalsBuilder) {
     /// "a{}b{}c".format(1, 2) == "a1b2c"
     /// "({1}, {0})".format("zero", "one") == "(one, zero)"
     /// "Is {0!r} {0!s}?".format("heterological") == "Is \"heterological\" heterological?"
+    /// "[{:>6}]".format("abc") == "[   abc]"
+    /// "{:*^7}".format("abc") == "**abc**"
+    /// "{:05d} {:#x} {:.2f}".format(42, 255, 3.14159) == "00042 0xff 3.14"
     /// # "#);
     /// ```
     fn format(args: Arguments<'v, '_>) -> StringValue<'v> {
@@ -480,6 +491,34 @@ pub(crate) fn string_methods(builder: &mut GlobalsBuilder) {
         Ok(true)
     }
 
+    /// [string.isidentifier](
+    /// https://docs.python.org/3/library/stdtypes.html#str.isidentifier
+    /// ): test if a string is a valid identifier. _Not part of standard Starlark._
+    ///
+    /// `S.isidentifier()` reports whether the string S is non-empty, starts with a
+    /// Unicode letter or underscore, and consists only of Unicode letters, digits
+    /// and underscores. Keywords are not treated specially.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// "hello_world".isidentifier() == True
+    /// "_x1".isidentifier() == True
+    /// "1x".isidentifier() == False
+    /// "x-y".isidentifier() == False
+    /// "".isidentifier() == False
+    /// # "#);
+    /// ```
+    fn isidentifier(this: &str) -> bool {
+        let mut chars = this.chars();
+        match chars.next() {
+            Some(c) if c == '_' || c.is_alphabetic() => {}
+            _ => return Ok(false),
+        }
+        Ok(chars.all(|c| c == '_' || c.is_alphanumeric()))
+    }
+
     /// [string.islower](
     /// https://github.com/google/skylark/blob/3705afa472e466b8b061cce44b47c9ddc6db696d/doc/spec.md#string·islower
     /// ): test if all letters of a string are lowercase.
@@ -508,6 +547,31 @@ pub(crate) fn string_methods(builder: &mut GlobalsBuilder) {
         Ok(result)
     }
 
+    /// [string.isprintable](
+    /// https://docs.python.org/3/library/stdtypes.html#str.isprintable
+    /// ): test if a string contains only printable characters. _Not part of
+    /// standard Starlark._
+    ///
+    /// `S.isprintable()` reports whether every character of the string S is
+    /// printable, as in Python: not a control, format, surrogate, private use or
+    /// unassigned character, and not a separator other than the space ' '.
+    /// The empty string is printable.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// "hello, world".isprintable() == True
+    /// "".isprintable() == True
+    /// "one\ntwo".isprintable() == False
+    /// # "#);
+    /// ```
+    fn isprintable(this: &str) -> bool {
+        static NOT_PRINTABLE_RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"[\p{C}\p{Z}&&[^ ]]").unwrap());
+        Ok(!NOT_PRINTABLE_RE.is_match(this))
+    }
+
     /// [string.isspace](
     /// https://github.com/google/skylark/blob/3705afa472e466b8b061cce44b47c9ddc6db696d/doc/spec.md#string·isspace
     /// ): test if all characters of a string are whitespaces.
@@ -656,7 +720,9 @@ pub(crate) fn string_methods(builder: &mut GlobalsBuilder) {
                             for x in it {
                                 r.push_str(this);
                                 r.push_str(as_str(x)?);
+                                heap.check_string_length(r.len())?;
                             }
+                            heap.check_string_length(r.len())?;
                             Ok(heap.alloc(r))
                         }
                     }
@@ -1224,6 +1290,127 @@ pub(crate) fn string_methods(builder: &mut GlobalsBuilder) {
             Ok(this)
         }
     }
+
+    /// [string.maketrans](
+    /// https://docs.python.org/3/library/stdtypes.html#str.maketrans
+    /// ): build a translation table for `translate`. _Not part of standard Starlark._
+    ///
+    /// `S.maketrans(x, y)` returns a dictionary mapping the code point of each
+    /// character of `x` to the code point of the character at the same position
+    /// in `y`, which must have the same length. An optional third string lists
+    /// characters which are mapped to `None`, so `translate` deletes them.
+    ///
+    /// `S.maketrans(d)` takes a dictionary, and converts its keys which are single
+    /// characters to code points.
+    ///
+    /// The string `S` itself is not used, so this is usually written `"".maketrans(...)`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// "".maketrans("ab", "xy") == {97: 120, 98: 121}
+    /// "".maketrans("a", "x", "c") == {97: 120, 99: None}
+    /// "".maketrans({"a": "xyz", 98: None}) == {97: "xyz", 98: None}
+    /// # "#);
+    /// ```
+    fn maketrans(_this: &str, ref x: Value, ref y: Option<&str>, ref z: Option<&str>) -> Dict<'v> {
+        let mut res = SmallMap::new();
+        match y {
+            None => {
+                let x = Dict::from_value(x)
+                    .ok_or_else(|| anyhow!("maketrans with a single argument requires a dict"))?;
+                for (k, v) in x.iter() {
+                    let k = match k.unpack_str() {
+                        Some(s) => {
+                            let mut cs = s.chars();
+                            match (cs.next(), cs.next()) {
+                                (Some(c), None) => Value::new_int(c as i32),
+                                _ => {
+                                    return Err(anyhow!(
+                                        "maketrans keys must be strings of length 1, got {}",
+                                        k.to_repr()
+                                    ));
+                                }
+                            }
+                        }
+                        None if k.unpack_int().is_some() => k,
+                        None => {
+                            return Err(anyhow!(
+                                "maketrans keys must be strings or integers, got `{}`",
+                                k.get_type()
+                            ));
+                        }
+                    };
+                    res.insert_hashed(k.get_hashed()?, v);
+                }
+            }
+            Some(y) => {
+                let x = x.unpack_str().ok_or_else(|| {
+                    anyhow!("maketrans with two arguments requires the first to be a string")
+                })?;
+                if x.chars().count() != y.chars().count() {
+                    return Err(anyhow!(
+                        "maketrans arguments must have equal length, got {} and {}",
+                        x.chars().count(),
+                        y.chars().count()
+                    ));
+                }
+                for (a, b) in x.chars().zip(y.chars()) {
+                    res.insert_hashed(
+                        Value::new_int(a as i32).get_hashed()?,
+                        Value::new_int(b as i32),
+                    );
+                }
+                for c in z.unwrap_or_default().chars() {
+                    res.insert_hashed(Value::new_int(c as i32).get_hashed()?, Value::new_none());
+                }
+            }
+        }
+        Ok(Dict::new(res))
+    }
+
+    /// [string.translate](
+    /// https://docs.python.org/3/library/stdtypes.html#str.translate
+    /// ): map each character through a translation table. _Not part of standard Starlark._
+    ///
+    /// `S.translate(table)` returns a copy of S where each character is looked up
+    /// by its code point in the dictionary `table`. If the character is mapped to an
+    /// integer, that code point replaces it; if mapped to a string, that string
+    /// replaces it; and if mapped to `None` the character is deleted.
+    /// Characters not in `table` are left unchanged.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// "banana".translate("".maketrans("an", "on")) == "bonono"
+    /// "banana".translate("".maketrans("", "", "a")) == "bnn"
+    /// "a-b".translate({ord("-"): "_to_"}) == "a_to_b"
+    /// # "#);
+    /// ```
+    fn translate(this: &str, ref table: ARef<Dict>) -> String {
+        let mut res = String::with_capacity(this.len());
+        for c in this.chars() {
+            match table.get(Value::new_int(c as i32))? {
+                None => res.push(c),
+                Some(v) if v.is_none() => {}
+                Some(v) => {
+                    if let Some(s) = v.unpack_str() {
+                        res.push_str(s);
+                    } else if let Some(c) = v.unpack_int().and_then(|i| char::from_u32(i as u32)) {
+                        res.push(c);
+                    } else {
+                        return Err(anyhow!(
+                            "translate table values must be code points, strings or None, got {}",
+                            v.to_repr()
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(res)
+    }
 }
 
 #[cfg(test)]
@@ -1237,9 +1424,78 @@ mod tests {
         assert::fail(r#""bonbon".rindex("on", 2, 5)"#, "not found in");
     }
 
+    #[test]
+    fn test_find_bounds() {
+        assert::all_true(
+            r#"
+"//foo:bar".find(":") == 5 and "//foo:bar".rfind("/") == 1
+"//foo:bar".find("@") == -1 and "//foo:bar".rfind("@") == -1
+"a:b:c".find(":", 2) == 3 and "a:b:c".find(":", 4) == -1
+"a:b:c".rfind(":", 0, 3) == 1 and "a:b:c".rfind(":", 0, 1) == -1
+"a:b:c".find(":", -2) == 3 and "a:b:c".rfind(":", -4, -2) == 1
+"a:b:c".find(":", -100, 100) == 1 and "a:b:c".rfind(":", -100, 100) == 3
+"a:b:c".find(":", 3, 1) == -1
+"a:b:c".index(":") == 1 and "a:b:c".rindex(":") == 3
+"a:b:c".index(":", 2) == 3 and "a:b:c".rindex(":", 0, -2) == 1
+"a:b:c".index(":", -100, 100) == 1 and "a:b:c".rindex(":", -100, 100) == 3
+"#,
+        );
+        assert::fail(r#""a:b:c".index("@")"#, "not found in");
+        assert::fail(r#""a:b:c".index(":", 4)"#, "not found in");
+        assert::fail(r#""a:b:c".rindex("@")"#, "not found in");
+        assert::fail(r#""a:b:c".rindex(":", -1)"#, "not found in");
+    }
+
+    #[test]
+    fn test_isprintable() {
+        assert::all_true(
+            r#"
+"".isprintable()
+" ".isprintable() and "hello, world!".isprintable() and "héllo 世界".isprintable()
+not "tab\there".isprintable() and not "line\n".isprintable() and not "bell\x07".isprintable()
+not "zero\u200bwidth".isprintable() and not "no\u00a0break".isprintable()
+"#,
+        );
+    }
+
     #[test]
     fn test_opaque_iterator() {
         assert::is_true("type('foo'.elems()) != type([])");
         assert::is_true("type('foo'.codepoints()) != type([])");
     }
+
+    #[test]
+    fn test_predicates() {
+        assert::all_true(
+            r#"
+not "".isalnum() and not "".isalpha() and not "".isdigit() and not "".isidentifier()
+not "".islower() and not "".isupper() and not "".isspace()
+"héllo".isalpha() and "héllo".isidentifier() and "héllo".islower()
+"x2".isalnum() and not "x 2".isalnum()
+"٣".isdigit() and not "1.5".isdigit()
+"_".isidentifier() and "a_1".isidentifier() and not "a.b".isidentifier() and not "9a".isidentifier()
+"ABC 1".isupper() and not "AbC".isupper() and not "123".isupper()
+"abc 1".islower() and not "aBc".islower()
+" \t\n".isspace() and not " x ".isspace()
+"#,
+        );
+    }
+
+    #[test]
+    fn test_translate() {
+        assert::all_true(
+            r#"
+"hello world".translate("".maketrans("lo", "01")) == "he001 w1r0d"
+"hello world".translate("".maketrans("", "", "lo ")) == "hewrd"
+"hello".translate("".maketrans("h", "j", "o")) == "jell"
+"hello".translate({}) == "hello"
+"héllo".translate("".maketrans({"é": "e", "l": None})) == "heo"
+"abc".translate({ord("b"): "bbb", ord("c"): 0x263A}) == "abbb☺"
+"#,
+        );
+        assert::fail(r#""".maketrans("ab", "x")"#, "equal length");
+        assert::fail(r#""".maketrans({"ab": "x"})"#, "length 1");
+        assert::fail(r#""".maketrans("ab")"#, "requires a dict");
+        assert::fail(r#""a".translate({97: [1]})"#, "translate table values");
+    }
 }
@@ -338,7 +338,7 @@ impl Display for AssignOp {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match *self {
             AssignOp::Add => f.write_str(" += "),
-            AssignOp::Subtract => f.write_str(" += "),
+            AssignOp::Subtract => f.write_str(" -= "),
             AssignOp::Multiply => f.write_str(" *= "),
             AssignOp::Divide => f.write_str(" /= "),
             AssignOp::FloorDivide => f.write_str(" //= "),
@@ -391,7 +391,9 @@ impl Display for AstLiteral {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             AstLiteral::Int(i) => write!(f, "{}", &i.node),
-            AstLiteral::Float(n) => write!(f, "{}", &n.node),
+            // Always print a fraction or exponent, so floats don't read back as ints.
+            AstLiteral::Float(n) if n.node.is_infinite() => f.write_str("1e999"),
+            AstLiteral::Float(n) => write!(f, "{:?}", &n.node),
             AstLiteral::String(s) => fmt_string_literal(f, &s.node),
         }
     }
@@ -425,19 +427,18 @@ impl Display for Expr {
             }
             Expr::ArrayIndirection(box (e, i)) => write!(f, "{}[{}]", e.node, i.node),
             Expr::Slice(e, i1, i2, i3) => {
-                write!(f, "{}[]", e.node)?;
+                write!(f, "{}[", e.node)?;
                 if let Some(x) = i1 {
-                    write!(f, "{}:", x.node)?
-                } else {
-                    f.write_str(":")?
+                    write!(f, "{}", x.node)?
                 }
+                f.write_str(":")?;
                 if let Some(x) = i2 {
                     write!(f, "{}", x.node)?
                 }
                 if let Some(x) = i3 {
                     write!(f, ":{}", x.node)?
                 }
-                Ok(())
+                f.write_str("]")
             }
             Expr::Identifier(s, _) => write!(f, "{}", s.node),
             Expr::Not(e) => write!(f, "(not {})", e.node),
@@ -472,7 +473,7 @@ impl Display for Expr {
                 for x in c {
                     write!(f, "{}", x)?;
                 }
-                f.write_str("}}")
+                f.write_str("}")
             }
             Expr::Literal(x) => write!(f, "{}", x),
         }
@@ -590,15 +591,13 @@ impl Stmt {
             Stmt::Load(load) => {
                 write!(f, "{}load(", tab)?;
                 fmt_string_literal(f, &load.node.module.node)?;
-                comma_separated_fmt(
-                    f,
-                    &load.node.args,
-                    |x, f| {
-                        write!(f, "{} = ", x.0.node)?;
-                        fmt_string_literal(f, &(x.1.node))
-                    },
-                    false,
-                )?;
+                for (name, symbol) in &load.node.args {
+                    f.write_str(", ")?;
+                    if name.node.0 != symbol.node {
+                        write!(f, "{} = ", name.node)?;
+                    }
+                    fmt_string_literal(f, &symbol.node)?;
+                }
                 f.write_str(")\n")
             }
         }
@@ -26,15 +26,17 @@ use crate::{
 
 #[derive(Error, Debug)]
 enum DialectError {
-    #[error("`def` is not allowed in this dialect")]
+    #[error("`def` is not allowed in this dialect (requires `Dialect.enable_def`)")]
     Def,
-    #[error("`lambda` is not allowed in this dialect")]
+    #[error("`lambda` is not allowed in this dialect (requires `Dialect.enable_lambda`)")]
     Lambda,
-    #[error("`load` is not allowed in this dialect")]
+    #[error("`load` is not allowed in this dialect (requires `Dialect.enable_load`)")]
     Load,
-    #[error("* keyword-only-arguments is not allowed in this dialect")]
+    #[error(
+        "* keyword-only-arguments is not allowed in this dialect (requires `Dialect.enable_keyword_only_arguments`)"
+    )]
     KeywordOnlyArguments,
-    #[error("type annotations are not allowed in this dialect")]
+    #[error("type annotations are not allowed in this dialect (requires `Dialect.enable_types`)")]
     Types,
 }
 
@@ -66,6 +68,11 @@ pub struct Dialect {
     /// Are `for`, `if` and other statements allowed at the top level.
     /// Only enabled in [`Extended`](Dialect::Extended).
     pub enable_top_level_stmt: bool,
+    /// Are top-level functions allowed to call themselves, either directly or through one another.
+    /// If not, such functions are rejected when the module is parsed. Recursion through functions
+    /// passed around as values is still only caught by the call stack limit at runtime.
+    /// Enabled in both [`Standard`](Dialect::Standard) and [`Extended`](Dialect::Extended).
+    pub enable_recursion: bool,
 }
 
 // These are morally enumerations, so give them enumeration-like names
@@ -82,6 +89,7 @@ impl Dialect {
         enable_tabs: true,
         enable_load_reexport: true, // But they plan to change it
         enable_top_level_stmt: false,
+        enable_recursion: true,
     };
 
     /// A superset of [`Standard`](Dialect::Standard), including extra features (types, top-level statements etc).
@@ -94,6 +102,7 @@ impl Dialect {
         enable_tabs: true,
         enable_load_reexport: true,
         enable_top_level_stmt: true,
+        enable_recursion: true,
     };
 }
 
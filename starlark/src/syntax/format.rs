@@ -0,0 +1,213 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Printing an [`AstModule`] back to Starlark source.
+
+use std::fmt::Write;
+
+use itertools::Itertools;
+
+use crate::{
+    codemap::Span,
+    syntax::ast::{AstExpr, AstModule, AstStmt, Stmt},
+};
+
+const INDENT: &str = "    ";
+
+impl AstModule {
+    /// Print the module as Starlark source in a canonical layout: one statement per line,
+    /// blocks indented by four spaces, and every binary operator, `not`, `if` expression and
+    /// `lambda` in parentheses. Parsing the result gives back the same module, and formatting
+    /// already formatted source leaves it unchanged.
+    ///
+    /// Comments on their own lines are kept before the statement which follows them, and
+    /// comments at the end of a line stay at the end of the printed line. Comments inside
+    /// an expression which spans several lines are dropped. Runs of blank lines between
+    /// statements become a single blank line.
+    pub fn format(&self) -> String {
+        let mut printer = Printer {
+            source: self.codemap.source(),
+            printed: 0,
+            block_start: false,
+            out: String::new(),
+        };
+        printer.stmt(&self.statement, "");
+        printer.comments(printer.source.len(), "");
+        printer.out
+    }
+}
+
+/// Prints statements, carrying over the comments from the source between them.
+struct Printer<'a> {
+    source: &'a str,
+    /// Offset in `source` up to which the comments have been printed.
+    printed: usize,
+    /// Whether nothing has been printed since the last block header.
+    block_start: bool,
+    out: String,
+}
+
+impl Printer<'_> {
+    /// Print the comments in the source between where we are and `end`,
+    /// and return whether there was a blank line after the last of them.
+    fn comments(&mut self, end: usize, tab: &str) -> bool {
+        let gap = &self.source[self.printed..end];
+        self.printed = end;
+        let lines: Vec<&str> = gap.split('\n').collect();
+        let mut blank = false;
+        for (i, line) in lines.iter().enumerate() {
+            match line.find('#') {
+                // Unless we are at the start of the file, the first line is the end
+                // of the last one printed, so keep the comment there.
+                Some(x) if i == 0 && !self.out.is_empty() => {
+                    self.out.pop();
+                    self.out.push_str("  ");
+                    self.out.push_str(line[x..].trim_end());
+                    self.out.push('\n');
+                }
+                Some(x) => {
+                    if blank {
+                        self.blank_line();
+                        blank = false;
+                    }
+                    self.line(tab, line[x..].trim_end());
+                }
+                // The last line is the start of the line `end` is on, so is never blank.
+                None => blank |= i != 0 && i != lines.len() - 1 && line.trim().is_empty(),
+            }
+        }
+        blank
+    }
+
+    /// The offset of the next token in the source, skipping whitespace and comments.
+    fn next_token(&self) -> usize {
+        let mut rest = &self.source[self.printed..];
+        loop {
+            rest = rest.trim_start();
+            match rest.strip_prefix('#') {
+                Some(comment) => rest = comment.find('\n').map_or("", |x| &comment[x..]),
+                None => return self.source.len() - rest.len(),
+            }
+        }
+    }
+
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.block_start {
+            self.out.push('\n');
+        }
+    }
+
+    fn line(&mut self, tab: &str, text: &str) {
+        self.out.push_str(tab);
+        self.out.push_str(text);
+        self.out.push('\n');
+        self.block_start = false;
+    }
+
+    /// Print the line starting a block, whose source ends at `end`.
+    fn header(&mut self, tab: &str, text: &str, end: usize) {
+        self.line(tab, &format!("{}:", text));
+        self.printed = end;
+        self.block_start = true;
+    }
+
+    fn stmt(&mut self, stmt: &AstStmt, tab: &str) {
+        if let Stmt::Statements(xs) = &stmt.node {
+            for x in xs {
+                self.stmt(x, tab);
+            }
+            return;
+        }
+        if self.comments(begin(stmt.span), tab) {
+            self.blank_line();
+        }
+        match &stmt.node {
+            Stmt::If(cond, box then) => self.if_stmt("if", cond, then, None, tab),
+            Stmt::IfElse(cond, box (then, els)) => self.if_stmt("if", cond, then, Some(els), tab),
+            Stmt::For(var, box (over, body)) => {
+                let text = format!("for {} in {}", var.node, over.node);
+                self.header(tab, &text, end(over.span));
+                self.block(body, tab);
+            }
+            Stmt::Def(name, params, ret, body, _payload) => {
+                let mut text = format!(
+                    "def {}({})",
+                    name.node,
+                    params.iter().map(|x| &x.node).join(", ")
+                );
+                let mut header_end = end(name.span);
+                if let Some(x) = params.last() {
+                    header_end = end(x.span);
+                }
+                if let Some(ret) = ret {
+                    write!(text, " -> {}", ret.node).unwrap();
+                    header_end = end(ret.span);
+                }
+                self.header(tab, &text, header_end);
+                self.block(body, tab);
+            }
+            // `Display` prints the other statements on one line, ending with a newline.
+            x => {
+                self.out.push_str(tab);
+                write!(self.out, "{}", x).unwrap();
+                self.printed = end(stmt.span);
+                self.block_start = false;
+            }
+        }
+    }
+
+    fn block(&mut self, body: &AstStmt, tab: &str) {
+        self.stmt(body, &format!("{}{}", tab, INDENT))
+    }
+
+    /// Print an `if` or `elif`, and the `elif` or `else` which follows it, if any.
+    fn if_stmt(
+        &mut self,
+        keyword: &str,
+        cond: &AstExpr,
+        then: &AstStmt,
+        els: Option<&AstStmt>,
+        tab: &str,
+    ) {
+        self.header(tab, &format!("{} {}", keyword, cond.node), end(cond.span));
+        self.block(then, tab);
+        if let Some(els) = els {
+            // Comments before the `else` belong at the end of the block it follows.
+            let keyword = self.next_token();
+            self.comments(keyword, &format!("{}{}", tab, INDENT));
+            match &els.node {
+                // Only an `elif` gives an `if` which isn't in a block of its own.
+                Stmt::If(cond, box then) => self.if_stmt("elif", cond, then, None, tab),
+                Stmt::IfElse(cond, box (then, els)) => {
+                    self.if_stmt("elif", cond, then, Some(els), tab)
+                }
+                _ => {
+                    self.header(tab, "else", keyword);
+                    self.block(els, tab);
+                }
+            }
+        }
+    }
+}
+
+fn begin(span: Span) -> usize {
+    span.begin().get() as usize
+}
+
+fn end(span: Span) -> usize {
+    span.end().get() as usize
+}
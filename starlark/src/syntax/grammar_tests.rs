@@ -17,7 +17,12 @@
 
 use gazebo::prelude::*;
 
-use crate::{assert, assert::Assert, syntax::ast::Stmt};
+use crate::{
+    assert,
+    assert::Assert,
+    errors::Diagnostic,
+    syntax::{ast::Stmt, AstModule, Dialect},
+};
 
 #[test]
 fn test_empty() {
@@ -37,7 +42,7 @@ fn test_top_level_comment() {
 
 #[test]
 fn test_top_level_load() {
-    let want = "load(\"//top/level/load.bzl\"top-level = \"top-level\")\n";
+    let want = "load(\"//top/level/load.bzl\", \"top-level\")\n";
     assert_eq!(
         assert::parse("\nload(\"//top/level/load.bzl\", \"top-level\")\n"),
         want
@@ -112,6 +117,18 @@ fn test_top_level_statements() {
     assert_eq!(assert::parse("pass"), "pass\n");
 }
 
+#[test]
+fn test_recursion() {
+    let mut a = Assert::new();
+    a.dialect_set(|x| x.enable_recursion = false);
+    a.parse_fail("def f(x):\n  return !f!(x - 1)");
+    a.parse_fail("def f():\n  return g()\ndef g():\n  return [!f!() for x in []]");
+    a.parse("def g():\n  pass\ndef f():\n  return g()");
+    a.parse("def f(f):\n  return f()");
+    a.parse("def f():\n  return [f() for f in []]");
+    assert::parse("def f(x):\n  return f(x - 1)");
+}
+
 #[test]
 fn test_top_level_def_with_docstring() {
     assert_eq!(
@@ -299,3 +316,218 @@ fn test_bad_assignment() {
     assert::parse_fail("[!x or y!] = 1");
     assert::parse_fail("![x]! += 1");
 }
+
+#[test]
+fn test_trailing_comma() {
+    assert::pass(
+        r#"
+def f(a, b = 1,):
+    return (a, b,)
+def g(a, *args,):
+    return args
+def h(a, *, b,):
+    return b
+def k(**kwargs,):
+    return kwargs
+assert_eq(f(1, 2,), (1, 2))
+assert_eq(f(a = 1,), (1, 1))
+assert_eq(g(1, *[2, 3],), (2, 3))
+assert_eq(h(1, b = 2,), 2)
+assert_eq(k(**{"x": 1},), {"x": 1})
+assert_eq([1, 2,], [1, 2])
+assert_eq((1,), tuple([1]))
+assert_eq({1: 2, 3: 4,}, {1: 2, 3: 4})
+x, y, = 1, 2,
+assert_eq((x, y), (1, 2))
+assert_eq((lambda a, b,: a + b)(1, 2), 3)
+"#,
+    );
+}
+
+#[test]
+fn test_multiline_parens() {
+    assert::pass(
+        r#"
+x = (1 +
+     2
+       * 3)
+assert_eq(x, 7)
+y = [
+  1,
+    2,  # comment
+3
+]
+assert_eq(y, [1, 2, 3])
+z = {
+    "a": (
+        1
+    ),
+}
+assert_eq(z, {"a": 1})
+def f(
+    a,
+        b,
+):
+    return (a
+        + b)
+assert_eq(f(
+    1,
+  2), 3)
+if (x == 7 and
+    y == [1, 2, 3]):
+    pass
+else:
+    fail("multiline condition")
+"#,
+    );
+}
+
+#[test]
+fn test_dialect_error_names_flag() {
+    fn fail(f: impl FnOnce(&mut Dialect), program: &str, flag: &str) {
+        let mut dialect = Dialect::Extended;
+        f(&mut dialect);
+        let err = AstModule::parse("assert.bzl", program.to_owned(), &dialect).unwrap_err();
+        let msg = err.to_string();
+        assert!(
+            msg.contains(&format!("`Dialect.{}`", flag)),
+            "Expected error to mention `{}`, got: {}",
+            flag,
+            msg
+        );
+    }
+
+    fail(|d| d.enable_def = false, "def f(): pass", "enable_def");
+    fail(
+        |d| d.enable_lambda = false,
+        "f = lambda x: x",
+        "enable_lambda",
+    );
+    fail(|d| d.enable_load = false, "load('a', 'b')", "enable_load");
+    fail(
+        |d| d.enable_keyword_only_arguments = false,
+        "def f(*, x): pass",
+        "enable_keyword_only_arguments",
+    );
+    fail(
+        |d| d.enable_types = false,
+        "def f(x: int): pass",
+        "enable_types",
+    );
+}
+
+#[test]
+fn test_parse_all_errors() {
+    fn error_lines(program: &str) -> Vec<usize> {
+        AstModule::parse_all_errors("a.star", program.to_owned(), &Dialect::Extended)
+            .unwrap_err()
+            .into_map(|e| {
+                let d = e.downcast::<Diagnostic>().unwrap();
+                d.span.unwrap().resolve_span().begin_line
+            })
+    }
+
+    assert!(AstModule::parse_all_errors(
+        "a.star",
+        "x = 1\ndef f():\n  return x".to_owned(),
+        &Dialect::Extended
+    )
+    .is_ok());
+    // Errors in a block, or after a broken line which starts a block, are still found.
+    assert_eq!(
+        error_lines(
+            r#"
+x = 1 +
+y = 2
+def f(:
+    return 1
+    x = = 1
+z = 3 3
+"#
+        ),
+        vec![1, 3, 5, 6]
+    );
+    assert_eq!(
+        error_lines("def f():\n  x = = 1\n  return 1\nif True:\n  y = 2 2\n"),
+        vec![1, 4]
+    );
+    // The first error is the one `parse` reports.
+    let err = AstModule::parse(
+        "a.star",
+        "x = 1 +\ny = 2 2\n".to_owned(),
+        &Dialect::Extended,
+    )
+    .unwrap_err();
+    let d = err.downcast::<Diagnostic>().unwrap();
+    assert_eq!(0, d.span.unwrap().resolve_span().begin_line);
+}
+
+fn format(program: &str) -> String {
+    let ast = AstModule::parse("format.star", program.to_owned(), &Dialect::Extended).unwrap();
+    let res = ast.format();
+    // Formatting the result again changes nothing.
+    let again = AstModule::parse("format.star", res.clone(), &Dialect::Extended).unwrap();
+    assert_eq!(again.format(), res);
+    res
+}
+
+#[test]
+fn test_format() {
+    let program = r#"
+# Leading comment.
+load("a.star", "x", y = "z")
+
+
+def f(a, b = 1, *args, **kwargs):  # On the header.
+  """Docstring."""
+  if a: return b  # Trailing.
+  elif b:
+    pass
+  # End of the elif.
+  else:
+      x[1] = [i * 2 for i in args if i]
+
+
+  for k, v in kwargs.items():
+    a -= v
+  return lambda: not a
+
+# Final comment.
+"#;
+    let want = r#"# Leading comment.
+load("a.star", "x", y = "z")
+
+def f(a, b = 1, *args, **kwargs):  # On the header.
+    "Docstring."
+    if a:
+        return b  # Trailing.
+    elif b:
+        pass
+        # End of the elif.
+    else:
+        x[1] = [(i * 2) for i in args if i]
+
+    for (k, v) in kwargs.items():
+        a -= v
+    return (lambda : (not a))
+
+# Final comment.
+"#;
+    assert_eq!(format(program), want);
+    assert_eq!(format(want), want);
+    // An `if` in an `else` block is not an `elif`.
+    assert_eq!(
+        format("if a:\n  pass\nelse:\n  if b:\n    pass\n"),
+        "if a:\n    pass\nelse:\n    if b:\n        pass\n"
+    );
+    assert_eq!(format(""), "");
+    assert_eq!(format("\n# Only a comment"), "# Only a comment\n");
+}
+
+#[test]
+fn test_format_expressions() {
+    assert_eq!(
+        format("x = a[1:2:3] + a[::2] + a[:]\ny = {k: v for k, v in z}\nz = 1.0 + 1e999 + .5\n"),
+        "x = ((a[1:2:3] + a[::2]) + a[:])\ny = {k: v for (k, v) in z}\nz = ((1.0 + 1e999) + 0.5)\n"
+    );
+}
@@ -15,17 +15,17 @@
  * limitations under the License.
  */
 
-use std::{fs, path::Path};
+use std::{fs, mem, path::Path};
 
 use anyhow::anyhow;
 use gazebo::prelude::*;
 use lalrpop_util as lu;
 
 use crate::{
-    codemap::{CodeMap, FileSpan, Pos, Span},
+    codemap::{CodeMap, FileSpan, Pos, Span, Spanned},
     errors::Diagnostic,
     syntax::{
-        ast::{AstModule, AstStmt, Stmt},
+        ast::{AssignIdent, AstLiteral, AstModule, AstStmt, Expr, Load, Stmt, Visibility},
         dialect::Dialect,
         grammar::StarlarkParser,
         lexer::{Lexer, Token},
@@ -126,13 +126,56 @@ impl AstModule {
     /// ```
     pub fn parse(filename: &str, content: String, dialect: &Dialect) -> anyhow::Result<Self> {
         let codemap = CodeMap::new(filename.to_owned(), content);
-        let lexer = Lexer::new(codemap.source(), dialect, codemap.dupe());
-        match StarlarkParser::new().parse(&codemap, dialect, lexer) {
-            Ok(v) => Ok(AstModule::create(codemap, v, dialect)?),
-            Err(p) => Err(parse_error_add_span(p, codemap.source().len(), codemap)),
+        let (errors, res) = Self::parse_recovering(&codemap, dialect);
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(AstModule::create(codemap, res?, dialect)?),
         }
     }
 
+    /// Parse a Starlark module like [`parse`](AstModule::parse), but on failure return every
+    /// syntax error rather than only the first, e.g. so an editor can underline them all.
+    /// After a syntax error the parser skips to the end of the line (and any block the line
+    /// started), and carries on from the next statement. Errors are in the order they occur.
+    pub fn parse_all_errors(
+        filename: &str,
+        content: String,
+        dialect: &Dialect,
+    ) -> Result<Self, Vec<anyhow::Error>> {
+        let codemap = CodeMap::new(filename.to_owned(), content);
+        let (mut errors, res) = Self::parse_recovering(&codemap, dialect);
+        match res {
+            Err(e) => errors.push(e),
+            Ok(statement) if errors.is_empty() => {
+                return AstModule::create(codemap, statement, dialect).map_err(|e| vec![e]);
+            }
+            // Statements which failed to parse are replaced by `pass`, so are safe to validate.
+            Ok(statement) => {
+                if let Err(e) = Stmt::validate(&codemap, &statement, dialect) {
+                    errors.push(e);
+                }
+            }
+        }
+        Err(errors)
+    }
+
+    /// Parse a module, returning the syntax errors recovered from,
+    /// and the statement, or the error which stopped the parse.
+    fn parse_recovering(
+        codemap: &CodeMap,
+        dialect: &Dialect,
+    ) -> (Vec<anyhow::Error>, anyhow::Result<AstStmt>) {
+        let len = codemap.source().len();
+        let lexer = Lexer::new(codemap.source(), dialect, codemap.dupe());
+        let mut errors = Vec::new();
+        let res = StarlarkParser::new().parse(codemap, dialect, &mut errors, lexer);
+        let errors = errors.into_map(|e| parse_error_add_span(e.error, len, codemap.dupe()));
+        (
+            errors,
+            res.map_err(|e| parse_error_add_span(e, len, codemap.dupe())),
+        )
+    }
+
     /// Return the file names of all the `load` statements in the module.
     /// If the [`Dialect`] had [`enable_load`](Dialect::enable_load) set to [`false`] this will be an empty list.
     pub fn loads(&self) -> Vec<&str> {
@@ -155,8 +198,72 @@ impl AstModule {
         loads
     }
 
+    /// Add a `load(module, local = "symbol", ...)` statement for each `(local, symbol)`
+    /// pair in `symbols` at the top of the module (after any docstring), so the module can
+    /// use `local` without loading it. Intended for transformers passed to
+    /// [`set_ast_transformer`](crate::eval::Evaluator::set_ast_transformer) injecting
+    /// implicit imports. The statement has no source, so errors point at the start of the file.
+    pub fn add_load(&mut self, module: &str, symbols: &[(&str, &str)]) {
+        let span = Span::default();
+        let load = Spanned {
+            span,
+            node: Stmt::Load(Load {
+                module: Spanned {
+                    span,
+                    node: module.to_owned(),
+                },
+                args: symbols.map(|(local, symbol)| {
+                    (
+                        Spanned {
+                            span,
+                            node: AssignIdent((*local).to_owned(), ()),
+                        },
+                        Spanned {
+                            span,
+                            node: (*symbol).to_owned(),
+                        },
+                    )
+                }),
+                visibility: Visibility::Private,
+            }),
+        };
+
+        let statement = mem::replace(
+            &mut self.statement,
+            Spanned {
+                span,
+                node: Stmt::Pass,
+            },
+        );
+        let mut stmts = match statement.node {
+            Stmt::Statements(stmts) => stmts,
+            node => vec![Spanned {
+                span: statement.span,
+                node,
+            }],
+        };
+        let has_docstring = matches!(
+            stmts.first().map(|x| &x.node),
+            Some(Stmt::Expression(Spanned {
+                node: Expr::Literal(AstLiteral::String(_)),
+                ..
+            }))
+        );
+        stmts.insert(if has_docstring { 1 } else { 0 }, load);
+        self.statement = Spanned {
+            span: statement.span,
+            node: Stmt::Statements(stmts),
+        };
+    }
+
     /// Look up a [`Span`] contained in this module to a [`FileSpan`].
     pub fn file_span(&self, x: Span) -> FileSpan {
         self.codemap.file_span(x)
     }
+
+    /// The [`CodeMap`] of this module, which can resolve any [`Span`] within it
+    /// to a line and column, or to the source text.
+    pub fn codemap(&self) -> &CodeMap {
+        &self.codemap
+    }
 }
@@ -17,7 +17,10 @@
 
 //! AST for parsed starlark files.
 
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    slice,
+};
 
 use gazebo::prelude::*;
 use thiserror::Error;
@@ -28,8 +31,9 @@ use crate::{
     syntax::{
         ast::{
             Argument, Assign, AssignIdentP, AssignOp, AstArgument, AstAssign, AstAssignIdent,
-            AstExpr, AstParameter, AstStmt, AstString, Expr, Parameter, Stmt,
+            AstExpr, AstParameter, AstStmt, AstString, Clause, Expr, Parameter, Stmt,
         },
+        uniplate::Visit,
         Dialect,
     },
 };
@@ -52,6 +56,10 @@ enum ValidateError {
     InvalidLhs,
     #[error("left-hand-side of modifying assignment cannot be a list or tuple")]
     InvalidModifyLhs,
+    #[error(
+        "`{0}` is recursive, which is not allowed in this dialect (requires `Dialect.enable_recursion`)"
+    )]
+    Recursion(String),
 }
 
 #[derive(Eq, PartialEq, Ord, PartialOrd)]
@@ -342,6 +350,149 @@ impl Stmt {
             }
         }
 
-        f(codemap, dialect, stmt, true, false, false)
+        f(codemap, dialect, stmt, true, false, false)?;
+        if !dialect.enable_recursion {
+            check_recursion(codemap, stmt)?;
+        }
+        Ok(())
+    }
+}
+
+/// The names a `def` binds locally, and the functions it calls by name.
+#[derive(Default)]
+struct DefCalls<'a> {
+    bound: HashSet<&'a str>,
+    calls: Vec<&'a AstString>,
+}
+
+impl<'a> DefCalls<'a> {
+    fn params(&mut self, params: &'a [AstParameter]) {
+        for p in params {
+            match &p.node {
+                Parameter::Normal(n, ..)
+                | Parameter::WithDefaultValue(n, ..)
+                | Parameter::Args(n, ..)
+                | Parameter::KwArgs(n, ..) => {
+                    self.bound.insert(&n.node.0);
+                }
+                Parameter::NoArgs => {}
+            }
+        }
+    }
+
+    fn assign(&mut self, lhs: &'a AstAssign) {
+        lhs.visit_lvalue(|x| {
+            self.bound.insert(&x.node.0);
+        });
+    }
+
+    fn stmt(&mut self, stmt: &'a AstStmt) {
+        match &stmt.node {
+            Stmt::Def(name, params, ..) => {
+                self.bound.insert(&name.node.0);
+                self.params(params);
+            }
+            Stmt::For(lhs, _) | Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) => {
+                self.assign(lhs)
+            }
+            _ => {}
+        }
+        stmt.node.visit_children(|x| match x {
+            Visit::Stmt(x) => self.stmt(x),
+            Visit::Expr(x) => self.expr(x),
+        });
+    }
+
+    fn expr(&mut self, expr: &'a AstExpr) {
+        match &expr.node {
+            Expr::Call(
+                box Spanned {
+                    node: Expr::Identifier(name, _),
+                    ..
+                },
+                _,
+            ) => self.calls.push(name),
+            Expr::Lambda(params, ..) => self.params(params),
+            Expr::ListComprehension(_, for_, clauses)
+            | Expr::DictComprehension(_, for_, clauses) => {
+                self.assign(&for_.var);
+                for clause in clauses {
+                    if let Clause::For(x) = clause {
+                        self.assign(&x.var);
+                    }
+                }
+            }
+            _ => {}
+        }
+        expr.node.visit_expr(|x| self.expr(x));
+    }
+}
+
+/// Reject top-level functions which call themselves, directly or through other top-level functions.
+/// A name bound anywhere inside a function is treated as local to it, so we only report calls which
+/// must resolve to a top-level `def`.
+fn check_recursion(codemap: &CodeMap, stmt: &AstStmt) -> anyhow::Result<()> {
+    let top = match &stmt.node {
+        Stmt::Statements(xs) => xs.as_slice(),
+        _ => slice::from_ref(stmt),
+    };
+
+    let mut defs: HashMap<&str, Vec<&AstString>> = HashMap::new();
+    for x in top {
+        if let Stmt::Def(name, params, _, body, _) = &x.node {
+            let mut calls = DefCalls::default();
+            calls.params(params);
+            calls.stmt(body);
+            let DefCalls { bound, calls } = calls;
+            defs.insert(
+                &name.node.0,
+                calls
+                    .into_iter()
+                    .filter(|x| !bound.contains(x.node.as_str()))
+                    .collect(),
+            );
+        }
+    }
+
+    // Depth first search, returning the first call which closes a cycle.
+    fn visit<'a>(
+        name: &'a str,
+        defs: &HashMap<&'a str, Vec<&'a AstString>>,
+        visiting: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+    ) -> Option<&'a AstString> {
+        if done.contains(name) {
+            return None;
+        }
+        visiting.push(name);
+        for call in &defs[name] {
+            let callee = call.node.as_str();
+            if !defs.contains_key(callee) {
+                continue;
+            }
+            if visiting.contains(&callee) {
+                return Some(*call);
+            }
+            if let Some(call) = visit(callee, defs, visiting, done) {
+                return Some(call);
+            }
+        }
+        visiting.pop();
+        done.insert(name);
+        None
+    }
+
+    let mut done = HashSet::new();
+    for x in top {
+        if let Stmt::Def(name, ..) = &x.node {
+            if let Some(call) = visit(&name.node.0, &defs, &mut Vec::new(), &mut done) {
+                return Err(Diagnostic::new(
+                    ValidateError::Recursion(call.node.clone()),
+                    call.span,
+                    codemap.dupe(),
+                ));
+            }
+        }
     }
+    Ok(())
 }
@@ -0,0 +1,200 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A canonical rendering of values, independent of dictionary insertion order.
+
+use std::{fmt::Write, hash::Hasher};
+
+use thiserror::Error;
+
+use crate::{
+    collections::StarlarkHasher,
+    values::{dict::Dict, list::List, num::Num, stack_guard, structs::Struct, tuple::Tuple, Value},
+};
+
+#[derive(Debug, Error)]
+enum CanonicalError {
+    #[error("Value of type `{0}` has no canonical form")]
+    NoCanonicalForm(String),
+}
+
+/// Render a value in a canonical form, so values which compare equal render identically,
+/// e.g. for use as a cache key or to hash a result.
+///
+/// Unlike `repr`, the entries of dictionaries and the fields of structs are sorted by
+/// (the canonical form of) their keys, rather than kept in insertion order, and floats with an
+/// integer value are written as integers, so `{"b": 1.0, "a": 2}` and `{"a": 2, "b": 1}` are
+/// both rendered as `{"a": 2, "b": 1}`. `None`, booleans, numbers and strings are rendered with
+/// `repr`.
+///
+/// Fails if the value is too deeply nested, or cyclic, or contains any other type of value,
+/// e.g. a function, since two such values may render the same without being equal.
+pub fn canonical_repr(x: Value) -> anyhow::Result<String> {
+    let mut res = String::new();
+    collect_canonical(x, &mut res)?;
+    Ok(res)
+}
+
+/// A hash of [`canonical_repr`], so values which compare equal have the same hash.
+/// Unlike [`get_hashed`](Value::get_hashed) it accepts lists and dictionaries, and is the same
+/// in every process, so is suitable for persistent cache keys (but may change between versions
+/// of this crate). Fails for the same values as [`canonical_repr`].
+pub fn canonical_hash(x: Value) -> anyhow::Result<u64> {
+    let mut hasher = StarlarkHasher::new();
+    hasher.write(canonical_repr(x)?.as_bytes());
+    Ok(hasher.finish())
+}
+
+fn collect_sequence(xs: &[Value], res: &mut String) -> anyhow::Result<()> {
+    for (i, x) in xs.iter().enumerate() {
+        if i != 0 {
+            res.push_str(", ");
+        }
+        collect_canonical(*x, res)?;
+    }
+    Ok(())
+}
+
+fn collect_canonical(x: Value, res: &mut String) -> anyhow::Result<()> {
+    let _guard = stack_guard::stack_guard()?;
+    if let Some(xs) = Dict::from_value(x) {
+        let mut entries = Vec::with_capacity(xs.len());
+        for (k, v) in xs.iter() {
+            entries.push((canonical_repr(k)?, canonical_repr(v)?));
+        }
+        entries.sort();
+        res.push('{');
+        for (i, (k, v)) in entries.iter().enumerate() {
+            if i != 0 {
+                res.push_str(", ");
+            }
+            write!(res, "{}: {}", k, v).unwrap();
+        }
+        res.push('}');
+    } else if let Some(xs) = Struct::from_value(x) {
+        let mut fields: Vec<_> = xs.fields.iter().collect();
+        fields.sort_by_key(|(k, _)| k.as_str());
+        res.push_str("struct(");
+        for (i, (k, v)) in fields.into_iter().enumerate() {
+            if i != 0 {
+                res.push_str(", ");
+            }
+            write!(res, "{}=", k.as_str()).unwrap();
+            collect_canonical(*v, res)?;
+        }
+        res.push(')');
+    } else if let Some(xs) = List::from_value(x) {
+        res.push('[');
+        collect_sequence(xs.content(), res)?;
+        res.push(']');
+    } else if let Some(xs) = Tuple::from_value(x) {
+        res.push('(');
+        collect_sequence(xs.content(), res)?;
+        if xs.len() == 1 {
+            res.push(',');
+        }
+        res.push(')');
+    } else if let Some(Num::Float(f)) = x.unpack_num() {
+        // Equal to the int with the same value, so render it the same way.
+        if f.fract() == 0.0 && f.abs() <= i32::MAX as f64 {
+            write!(res, "{}", f as i32).unwrap();
+        } else {
+            x.collect_repr(res);
+        }
+    } else if x.is_none() || x.unpack_bool().is_some() || x.unpack_num().is_some() || x.is_str() {
+        x.collect_repr(res);
+    } else {
+        return Err(CanonicalError::NoCanonicalForm(x.get_type().to_owned()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+        values::{canonical_hash, canonical_repr},
+    };
+
+    fn canonical(x: &str) -> String {
+        let module = Module::new();
+        let globals = Globals::extended();
+        let mut eval = Evaluator::new(&module);
+        let ast = AstModule::parse("canonical.star", x.to_owned(), &Dialect::Extended).unwrap();
+        let res = eval.eval_module(ast, &globals).unwrap();
+        canonical_repr(res).unwrap()
+    }
+
+    #[test]
+    fn test_canonical_repr() {
+        assert_eq!(
+            canonical("{'b': 1, 'a': [2, {'y': 3, 'x': 4}]}"),
+            canonical("{'a': [2, {'x': 4, 'y': 3}], 'b': 1}")
+        );
+        assert_eq!(
+            canonical("{'b': 1, 'a': [2, {'y': 3, 'x': 4}]}"),
+            r#"{"a": [2, {"x": 4, "y": 3}], "b": 1}"#
+        );
+        assert_eq!(canonical("{2: 'x', 1.0: 'y'}"), r#"{1: "y", 2: "x"}"#);
+        assert_eq!(canonical("[1.0, 1.5, -0.0]"), "[1, 1.5, 0]");
+        assert_eq!(canonical("(1,)"), "(1,)");
+        assert_eq!(canonical("(1, 'a')"), r#"(1, "a")"#);
+        assert_eq!(canonical("struct(b = 1, a = 2)"), "struct(a=2, b=1)");
+        assert_eq!(canonical("None"), "None");
+        // Different values still differ.
+        assert_ne!(canonical("{'a': 1}"), canonical("{'a': '1'}"));
+        assert_ne!(canonical("[1, 2]"), canonical("[2, 1]"));
+    }
+
+    #[test]
+    fn test_canonical_hash() {
+        let hash = |x: &str| {
+            let module = Module::new();
+            let globals = Globals::extended();
+            let mut eval = Evaluator::new(&module);
+            let ast = AstModule::parse("canonical.star", x.to_owned(), &Dialect::Extended).unwrap();
+            canonical_hash(eval.eval_module(ast, &globals).unwrap())
+        };
+        assert_eq!(
+            hash("{'b': [1], 'a': {'y': 2, 'x': 3.0}}").unwrap(),
+            hash("{'a': {'x': 3, 'y': 2}, 'b': [1]}").unwrap()
+        );
+        assert_ne!(hash("[1, 2]").unwrap(), hash("[2, 1]").unwrap());
+        assert!(hash("def f(): pass\n[f]")
+            .unwrap_err()
+            .to_string()
+            .contains("no canonical form"));
+        assert!(hash("x = []\nx.append(x)\nx").is_err());
+    }
+
+    #[test]
+    fn test_canonical_repr_cyclic() {
+        let module = Module::new();
+        let globals = Globals::standard();
+        let mut eval = Evaluator::new(&module);
+        let ast = AstModule::parse(
+            "canonical.star",
+            "x = []\nx.append(x)\nx".to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        let res = eval.eval_module(ast, &globals).unwrap();
+        assert!(canonical_repr(res).is_err());
+    }
+}
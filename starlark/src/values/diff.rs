@@ -0,0 +1,157 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Structural differences between values, to give readable test failures.
+
+use std::fmt::Write;
+
+use crate::values::{dict::Dict, list::List, stack_guard, tuple::Tuple, Value};
+
+/// Describe where two values differ, or [`None`] if they are equal.
+///
+/// Dictionaries, lists and tuples are compared element by element, so rather than
+/// printing both values in full, the result names the path to the first difference,
+/// e.g. `["a"][1]: 2 != 3` or `["b"]: missing on the right`.
+pub fn diff<'v>(a: Value<'v>, b: Value<'v>) -> Option<String> {
+    diff_at(&mut String::new(), a, b)
+}
+
+fn describe(path: &str, msg: String) -> String {
+    if path.is_empty() {
+        msg
+    } else {
+        format!("{}: {}", path, msg)
+    }
+}
+
+fn sequence<'v>(x: Value<'v>) -> Option<&'v [Value<'v>]> {
+    match List::from_value(x) {
+        Some(x) => Some(x.content()),
+        None => Tuple::from_value(x).map(|x| x.content()),
+    }
+}
+
+fn diff_at<'v>(path: &mut String, a: Value<'v>, b: Value<'v>) -> Option<String> {
+    if matches!(a.equals(b), Ok(true)) {
+        return None;
+    }
+    // Cyclic values can't be walked, so give up on the structure once we're too deep.
+    let _guard = match stack_guard::stack_guard() {
+        Ok(guard) => guard,
+        Err(_) => return Some(describe(path, format!("{} != {}", a, b))),
+    };
+
+    if a.get_type() == b.get_type() {
+        if let (Some(xs), Some(ys)) = (Dict::from_value(a), Dict::from_value(b)) {
+            for (k, x) in xs.iter() {
+                let len = path.len();
+                write!(path, "[{}]", k).unwrap();
+                let res = match ys.get(k).ok().flatten() {
+                    None => Some(describe(path, "missing on the right".to_owned())),
+                    Some(y) => diff_at(path, x, y),
+                };
+                path.truncate(len);
+                if res.is_some() {
+                    return res;
+                }
+            }
+            for k in ys.keys() {
+                if xs.get(k).ok().flatten().is_none() {
+                    return Some(format!("{}[{}]: missing on the left", path, k));
+                }
+            }
+        } else if let (Some(xs), Some(ys)) = (sequence(a), sequence(b)) {
+            for (i, (x, y)) in xs.iter().zip(ys).enumerate() {
+                let len = path.len();
+                write!(path, "[{}]", i).unwrap();
+                let res = diff_at(path, *x, *y);
+                path.truncate(len);
+                if res.is_some() {
+                    return res;
+                }
+            }
+            if xs.len() != ys.len() {
+                return Some(describe(
+                    path,
+                    format!("length {} != {}", xs.len(), ys.len()),
+                ));
+            }
+        }
+    }
+    Some(describe(path, format!("{} != {}", a, b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assert,
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+        values::{diff, Value},
+    };
+
+    fn diff_exprs(a: &str, b: &str) -> Option<String> {
+        let module = Module::new();
+        let globals = Globals::standard();
+        let mut eval = Evaluator::new(&module);
+        let mut eval_str = |x: &str| -> Value {
+            let ast = AstModule::parse("diff.star", x.to_owned(), &Dialect::Extended).unwrap();
+            eval.eval_module(ast, &globals).unwrap()
+        };
+        let a = eval_str(a);
+        let b = eval_str(b);
+        diff(a, b)
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(diff_exprs("[1, 2]", "[1, 2]"), None);
+        assert_eq!(diff_exprs("1", "2"), Some("1 != 2".to_owned()));
+        assert_eq!(
+            diff_exprs(
+                "{'a': 1, 'b': {'c': [1, 2]}}",
+                "{'a': 1, 'b': {'c': [1, 3]}}"
+            ),
+            Some(r#"["b"]["c"][1]: 2 != 3"#.to_owned())
+        );
+        assert_eq!(
+            diff_exprs("{'a': 1, 'b': 2}", "{'a': 1}"),
+            Some(r#"["b"]: missing on the right"#.to_owned())
+        );
+        assert_eq!(
+            diff_exprs("{'a': 1}", "{'a': 1, 'b': 2}"),
+            Some(r#"["b"]: missing on the left"#.to_owned())
+        );
+        assert_eq!(
+            diff_exprs("(1, [2])", "(1, [2, 3])"),
+            Some("[1]: length 1 != 2".to_owned())
+        );
+        assert_eq!(
+            diff_exprs("[1, 2]", "(1, 2)"),
+            Some("[1, 2] != (1, 2)".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_assert_eq_reports_diff() {
+        assert::fail(
+            "assert_eq({'a': 1, 'b': [2]}, {'a': 1, 'b': [3]})",
+            r#"differs at ["b"][0]: 2 != 3"#,
+        );
+    }
+}
@@ -58,6 +58,17 @@ pub enum ValueError {
     NoAttrDidYouMean(String, String, String),
 }
 
+/// Errors raised when a value would exceed a limit set on the [`Heap`](crate::values::Heap).
+#[derive(Debug, Error)]
+pub(crate) enum HeapLimitError {
+    #[error("String would exceed the maximum length of {0} bytes")]
+    StringTooLong(usize),
+    #[error("Collection would exceed the maximum length of {0} elements")]
+    CollectionTooLarge(usize),
+    #[error("Allocation would exceed the maximum heap size of {0} bytes")]
+    HeapTooLarge(usize),
+}
+
 #[derive(Debug, Error)]
 pub(crate) enum ControlError {
     #[error("Value of type `{0}` is not hashable")]
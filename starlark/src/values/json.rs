@@ -0,0 +1,115 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parsing JSON into values, the inverse of [`to_json`](crate::values::ValueLike::to_json).
+
+use std::convert::TryFrom;
+
+use crate::{
+    collections::SmallMap,
+    values::{dict::Dict, Heap, Value},
+};
+
+/// Parse a JSON document into a value allocated on the heap.
+///
+/// Objects become dictionaries (with their keys in the same order), arrays become lists, and
+/// `null`, booleans and strings become `None`, `bool` and `str`. A number becomes an `int` if it
+/// is an integer which fits in an `int` (32 bits), and a `float` otherwise, so integers too large
+/// for an `int` are read approximately rather than rejected.
+///
+/// [`to_json`](crate::values::ValueLike::to_json) writes non-finite floats as `NaN`,
+/// `Infinity` and `-Infinity`, which are not valid JSON, so values containing them do not
+/// round-trip and are rejected here.
+pub fn json_to_value<'v>(json: &str, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+    let json: serde_json::Value = serde_json::from_str(json)?;
+    Ok(alloc_json(&json, heap))
+}
+
+// Recursion is bounded by the nesting limit `serde_json` applies while parsing.
+fn alloc_json<'v>(json: &serde_json::Value, heap: &'v Heap) -> Value<'v> {
+    match json {
+        serde_json::Value::Null => Value::new_none(),
+        serde_json::Value::Bool(x) => Value::new_bool(*x),
+        serde_json::Value::Number(x) => match x.as_i64().and_then(|x| i32::try_from(x).ok()) {
+            Some(x) => Value::new_int(x),
+            // Every JSON number can be represented as an `f64`, perhaps approximately.
+            None => heap.alloc(x.as_f64().unwrap()),
+        },
+        serde_json::Value::String(x) => heap.alloc_str(x),
+        serde_json::Value::Array(xs) => {
+            heap.alloc_list_iter(xs.iter().map(|x| alloc_json(x, heap)))
+        }
+        serde_json::Value::Object(xs) => {
+            let mut res = SmallMap::with_capacity(xs.len());
+            for (k, v) in xs {
+                res.insert_hashed(heap.alloc_str_hashed(k), alloc_json(v, heap));
+            }
+            heap.alloc(Dict::new(res))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assert,
+        values::{json_to_value, Heap, Value, ValueLike},
+    };
+
+    fn round_trip(json: &str) -> String {
+        let heap = Heap::new();
+        json_to_value(json, &heap).unwrap().to_json().unwrap()
+    }
+
+    #[test]
+    fn test_json_to_value() {
+        let heap = Heap::new();
+        let x = json_to_value(
+            r#"{"b": [1, 2.5, "x"], "a": {"d": true, "c": null}}"#,
+            &heap,
+        );
+        assert_eq!(
+            x.unwrap().to_repr(),
+            r#"{"b": [1, 2.5, "x"], "a": {"d": True, "c": None}}"#
+        );
+        assert_eq!(json_to_value("-7", &heap).unwrap(), Value::new_int(-7));
+        // Integers that don't fit in an `int` become floats.
+        let x = json_to_value("3000000000", &heap).unwrap();
+        assert_eq!(x.get_type(), "float");
+        assert_eq!(x, heap.alloc(3000000000.0));
+        assert!(json_to_value("{1: 2}", &heap).is_err());
+        assert!(json_to_value("NaN", &heap).is_err());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        for json in &[
+            "null",
+            "true",
+            "-12",
+            "1.5",
+            r#""a\"b""#,
+            r#"[1, [], {}, "x"]"#,
+            r#"{"a": [true, false, null], "z": 1}"#,
+            // Keys keep their order, rather than being sorted.
+            r#"{"z": 1, "a": {"y": 2, "b": 3}}"#,
+        ] {
+            assert_eq!(&round_trip(json), json);
+        }
+        assert::fail("json(lambda x: x)", "not supported");
+    }
+}
@@ -31,7 +31,7 @@ use gazebo::{any::AnyLifetime, cast, coerce::Coerce, prelude::*};
 
 use crate::{
     codemap::Span,
-    collections::{SmallHashResult, StarlarkHasher},
+    collections::{SmallHashResult, SmallHasher, StarlarkHasher},
     environment::Globals,
     eval::{Arguments, Evaluator, FrozenDef},
     values::{
@@ -806,6 +806,9 @@ impl<'v> StarlarkValueDyn<'v> for BlackHole {
     fn collect_repr(&self, _collector: &mut String) {
         panic!()
     }
+    fn collect_str(&self, _collector: &mut String) {
+        panic!()
+    }
     fn to_json(&self) -> anyhow::Result<String> {
         panic!()
     }
@@ -972,6 +975,9 @@ impl<'v, Mode: 'static, T: StarlarkValue<'v>> StarlarkValueDyn<'v> for AValueImp
     fn collect_repr(&self, collector: &mut String) {
         self.1.collect_repr(collector)
     }
+    fn collect_str(&self, collector: &mut String) {
+        self.1.collect_str(collector)
+    }
     fn to_json(&self) -> anyhow::Result<String> {
         self.1.to_json()
     }
@@ -1110,7 +1116,8 @@ mod test {
         List::from_value_mut(list)
             .unwrap()
             .unwrap()
-            .push(tuple, module.heap());
+            .push(tuple, module.heap())
+            .unwrap();
         module.set("t", tuple);
         module.freeze().unwrap();
     }
@@ -22,24 +22,26 @@ use std::{
     fmt,
     fmt::{Debug, Display, Formatter},
     hash::{Hash, Hasher},
-    intrinsics::copy_nonoverlapping,
+    intrinsics::{copy_nonoverlapping, unlikely},
     marker::PhantomData,
-    mem::MaybeUninit,
+    mem::{self, MaybeUninit},
     ops::Deref,
-    ptr,
+    ptr, str,
     sync::Arc,
     usize,
 };
 
 use either::Either;
 use gazebo::{cast, prelude::*};
+use hashbrown::raw::RawTable;
 
 use crate::{
-    collections::Hashed,
+    collections::{idhasher::mix_u32, Hashed},
     eval::FrozenDef,
     values::{
         any::StarlarkAny,
         array::Array,
+        error::HeapLimitError,
         layout::{
             arena::{AValueHeader, AValueRepr, Arena, HeapSummary, Reservation},
             avalue::{
@@ -61,6 +63,20 @@ use crate::{
 pub struct Heap {
     /// Peak memory seen when a garbage collection takes place (may be lower than currently allocated)
     peak_allocated: Cell<usize>,
+    /// Number of values allocated, which garbage collection does not reduce
+    allocation_count: Cell<u64>,
+    /// Whether to count allocations, which is only needed to enforce a limit
+    count_allocations: Cell<bool>,
+    /// If string interning is enabled, the strings allocated since the last GC, keyed by their hash.
+    string_interner: RefCell<Option<RawTable<Value<'static>>>>,
+    /// Whether `string_interner` is `Some`, so checking doesn't need to borrow it.
+    intern_strings: Cell<bool>,
+    /// Maximum length in bytes of a string built by concatenation, repetition or `join`.
+    max_string_length: Cell<Option<usize>>,
+    /// Maximum number of elements in a collection.
+    max_collection_length: Cell<Option<usize>>,
+    /// Maximum number of bytes allocated, checked before allocating a string or collection.
+    max_bytes: Cell<Option<usize>>,
     arena: RefCell<Arena>,
 }
 
@@ -363,7 +379,26 @@ impl Heap {
         self.arena.borrow().available_bytes()
     }
 
+    /// Number of values allocated on this heap, which is not reduced by garbage collection.
+    /// Allocations are only counted once a limit has been set with
+    /// [`set_max_allocations`](crate::eval::Evaluator::set_max_allocations).
+    pub fn allocation_count(&self) -> u64 {
+        self.allocation_count.get()
+    }
+
+    pub(crate) fn enable_allocation_count(&self) {
+        self.count_allocations.set(true);
+    }
+
+    #[inline(always)]
+    fn count_allocation(&self) {
+        if unlikely(self.count_allocations.get()) {
+            self.allocation_count.set(self.allocation_count.get() + 1);
+        }
+    }
+
     fn alloc_raw<'v, 'v2: 'v2>(&'v self, x: impl AValue<'v2, ExtraElem = ()>) -> Value<'v> {
+        self.count_allocation();
         let arena_ref = self.arena.borrow_mut();
         let arena = &*arena_ref;
         let v: &AValueRepr<_> = arena.alloc(x);
@@ -384,11 +419,116 @@ impl Heap {
         unsafe { ValueTyped::new_unchecked(self.alloc_raw(x)) }
     }
 
+    /// Make allocations of a string identical to one previously allocated return the
+    /// existing value, rather than allocating again. Strings are immutable, so this
+    /// is not observable, but it costs a hash table lookup for each allocation.
+    pub(crate) fn enable_string_interning(&self) {
+        let mut interner = self.string_interner.borrow_mut();
+        if interner.is_none() {
+            *interner = Some(RawTable::new());
+        }
+        self.intern_strings.set(true);
+    }
+
+    pub(crate) fn set_max_string_length(&self, max: usize) {
+        self.max_string_length.set(Some(max));
+    }
+
+    pub(crate) fn set_max_collection_length(&self, max: usize) {
+        self.max_collection_length.set(Some(max));
+    }
+
+    pub(crate) fn set_max_bytes(&self, max: usize) {
+        self.max_bytes.set(Some(max));
+    }
+
+    /// Check a string of `len` bytes is within the limit set by `set_max_string_length`,
+    /// and would fit within the limit set by `set_max_bytes`.
+    #[inline(always)]
+    pub(crate) fn check_string_length(&self, len: usize) -> anyhow::Result<()> {
+        match self.max_string_length.get() {
+            Some(max) if unlikely(len > max) => Err(HeapLimitError::StringTooLong(max).into()),
+            _ => self.check_bytes(len),
+        }
+    }
+
+    /// Check a collection of `len` elements is within the limit set by
+    /// `set_max_collection_length`, and would fit within the limit set by `set_max_bytes`.
+    #[inline(always)]
+    pub(crate) fn check_collection_length(&self, len: usize) -> anyhow::Result<()> {
+        match self.max_collection_length.get() {
+            Some(max) if unlikely(len > max) => Err(HeapLimitError::CollectionTooLarge(max).into()),
+            _ => self.check_bytes(len.saturating_mul(mem::size_of::<Value>())),
+        }
+    }
+
+    /// Check allocating another `bytes` would stay within the limit set by `set_max_bytes`.
+    #[inline(always)]
+    fn check_bytes(&self, bytes: usize) -> anyhow::Result<()> {
+        match self.max_bytes.get() {
+            Some(max) if unlikely(self.allocated_bytes().saturating_add(bytes) > max) => {
+                Err(HeapLimitError::HeapTooLarge(max).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn interner_hash(x: &str) -> u64 {
+        mix_u32(hash_string_result(x).get())
+    }
+
     pub(crate) fn alloc_str_init<'v>(
         &'v self,
         len: usize,
         init: impl FnOnce(*mut u8),
     ) -> Value<'v> {
+        if unlikely(self.intern_strings.get()) {
+            self.alloc_str_init_interned(len, init)
+        } else {
+            self.alloc_str_init_uninterned(len, init)
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn alloc_str_init_interned<'v>(&'v self, len: usize, init: impl FnOnce(*mut u8)) -> Value<'v> {
+        // Build the string outside the heap first, so we only allocate if it is new.
+        let mut buffer = Vec::<u8>::with_capacity(len);
+        init(buffer.as_mut_ptr());
+        unsafe { buffer.set_len(len) };
+        let x = unsafe { str::from_utf8_unchecked(&buffer) };
+
+        let hash = Self::interner_hash(x);
+        if let Some(v) = self
+            .string_interner
+            .borrow()
+            .as_ref()
+            .unwrap()
+            .get(hash, |v| v.unpack_str() == Some(x))
+        {
+            return unsafe { transmute!(Value<'static>, Value<'v>, *v) };
+        }
+        let v = self.alloc_str_init_uninterned(len, |dest| unsafe {
+            copy_nonoverlapping(x.as_ptr(), dest, len)
+        });
+        // The table is cleared whenever a GC could move the values.
+        let v_static = unsafe { transmute!(Value, Value<'static>, v) };
+        self.string_interner
+            .borrow_mut()
+            .as_mut()
+            .unwrap()
+            .insert(hash, v_static, |v| {
+                Self::interner_hash(v.unpack_str().unwrap())
+            });
+        v
+    }
+
+    fn alloc_str_init_uninterned<'v>(
+        &'v self,
+        len: usize,
+        init: impl FnOnce(*mut u8),
+    ) -> Value<'v> {
+        self.count_allocation();
         let arena_ref = self.arena.borrow_mut();
         let arena = &*arena_ref;
         let (v, extra) = arena.alloc_extra_non_drop::<_>(starlark_str(len));
@@ -453,6 +593,7 @@ impl Heap {
             return FrozenValue::new_repr(&VALUE_EMPTY_TUPLE).to_value();
         }
 
+        self.count_allocation();
         unsafe {
             let arena = self.arena.borrow();
             let (avalue, extra) = arena.alloc_extra_non_drop(tuple_avalue(elems.len()));
@@ -466,6 +607,7 @@ impl Heap {
             return FrozenValueTyped::new_repr(VALUE_EMPTY_ARRAY.repr()).to_value_typed();
         }
 
+        self.count_allocation();
         unsafe {
             let (avalue, _) = self
                 .arena
@@ -546,6 +688,11 @@ impl Heap {
         // Must rewrite all Value's so they point at the new heap
         let mut arena = self.arena.borrow_mut();
 
+        // Interned strings will be moved or freed, so forget them
+        if let Some(interner) = self.string_interner.borrow_mut().as_mut() {
+            interner.clear();
+        }
+
         let tracer = Tracer::<'v> {
             arena: Arena::default(),
             phantom: PhantomData,
@@ -41,8 +41,20 @@ use indexmap::Equivalent;
 pub use starlark_derive::{starlark_attrs, Freeze, StarlarkAttrs, Trace};
 
 pub use crate::values::{
-    alloc_value::*, error::*, freeze::*, frozen_ref::*, layout::*, owned::*, trace::*, traits::*,
-    typed::*, types::*, unpack::*,
+    alloc_value::*,
+    canonical::{canonical_hash, canonical_repr},
+    diff::diff,
+    error::*,
+    freeze::*,
+    frozen_ref::*,
+    json::json_to_value,
+    layout::*,
+    owned::*,
+    trace::*,
+    traits::*,
+    typed::*,
+    types::*,
+    unpack::*,
 };
 use crate::{
     codemap::Span,
@@ -57,12 +69,15 @@ mod comparison;
 // Submodules
 mod alloc_value;
 pub(crate) mod basic;
+mod canonical;
+mod diff;
 pub mod docs;
 mod error;
 mod freeze;
 mod frozen_ref;
 mod index;
 pub(crate) mod iter;
+mod json;
 mod layout;
 pub(crate) mod num;
 mod owned;
@@ -168,11 +183,7 @@ pub trait ValueLike<'v>: Eq + Copy + Debug + Default + Display + CoerceKey<Value
     fn collect_repr(self, collector: &mut String);
 
     fn collect_str(self, collector: &mut String) {
-        if let Some(s) = self.to_value().unpack_str() {
-            collector.push_str(s);
-        } else {
-            self.collect_repr(collector);
-        }
+        self.to_value().get_ref().collect_str(collector)
     }
 
     fn to_json(self) -> anyhow::Result<String>;
@@ -323,10 +334,14 @@ impl<'v> Value<'v> {
     }
 
     /// Implement the `str()` function - converts a string value to itself,
-    /// otherwise uses `repr()`.
+    /// otherwise uses [`StarlarkValue::collect_str`], which defaults to `repr()`.
     pub fn to_str(self) -> String {
         match self.unpack_str() {
-            None => self.to_repr(),
+            None => {
+                let mut s = String::new();
+                self.collect_str(&mut s);
+                s
+            }
             Some(s) => s.to_owned(),
         }
     }
@@ -371,7 +386,11 @@ impl<'v> Value<'v> {
         Ok(res.take().expect("with_iterator to call the callback"))
     }
 
-    /// Produce an iterable from a value.
+    /// Produce an iterable from a value. This works for any value Starlark can loop over,
+    /// e.g. lists, tuples, dictionaries (giving their keys) and ranges, so there is no need to
+    /// check the type first. Values which aren't iterable, such as strings, return an error.
+    ///
+    /// A mutable value can't be modified while the iterator is alive.
     pub fn iterate(
         self,
         heap: &'v Heap,
@@ -457,7 +476,7 @@ impl<'v> Value<'v> {
     }
 
     /// Get a list of all the attributes this function supports, used to implement the
-    /// `dir()` function.
+    /// `dir()` function. The result is sorted, so is deterministic.
     pub fn dir_attr(self) -> Vec<String> {
         let aref = self.get_ref();
         let mut result = if let Some(methods) = aref.get_methods() {
@@ -586,3 +605,36 @@ impl<'v> Value<'v> {
         self.get_ref().get_type_value()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+    };
+
+    #[test]
+    fn test_iterate() {
+        let module = Module::new();
+        let globals = Globals::standard();
+        let mut eval = Evaluator::new(&module);
+        let mut iterate = |x: &str| {
+            let ast = AstModule::parse("iterate.star", x.to_owned(), &Dialect::Standard).unwrap();
+            let res = eval.eval_module(ast, &globals).unwrap();
+            res.iterate(module.heap())
+                .map(|it| it.map(|x| x.to_repr()).collect::<Vec<_>>())
+        };
+        assert_eq!(iterate("[1, 'a']").unwrap(), vec!["1", "\"a\""]);
+        assert_eq!(iterate("(1, 2)").unwrap(), vec!["1", "2"]);
+        assert_eq!(iterate("{'x': 1, 'y': 2}").unwrap(), vec!["\"x\"", "\"y\""]);
+        assert_eq!(iterate("range(1, 7, 2)").unwrap(), vec!["1", "3", "5"]);
+        assert_eq!(
+            iterate("'abc'.elems()").unwrap(),
+            vec!["\"a\"", "\"b\"", "\"c\""]
+        );
+        assert!(iterate("'abc'").is_err());
+        assert!(iterate("1").is_err());
+        assert!(iterate("None").is_err());
+    }
+}
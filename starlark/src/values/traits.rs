@@ -309,6 +309,21 @@ pub trait StarlarkValue<'v>: 'v + AnyLifetime<'v> + Debug + Display {
         write!(collector, "{}", self).unwrap()
     }
 
+    /// Return a string representation of self, as returned by the `str()` function.
+    /// Defaults to [`collect_repr`](StarlarkValue::collect_repr), so only types whose
+    /// human-readable form differs from their `repr()` need to override it.
+    ///
+    /// # Examples:
+    /// ```rust
+    /// # starlark::assert::all_true(r#"
+    /// str("test") == 'test'
+    /// str(["test"]) == '["test"]'
+    /// # "#);
+    /// ```
+    fn collect_str(&self, collector: &mut String) {
+        self.collect_repr(collector)
+    }
+
     /// Convert the type to a JSON string.
     fn to_json(&self) -> anyhow::Result<String> {
         ValueError::unsupported(self, "to_json()")
@@ -354,16 +369,23 @@ pub trait StarlarkValue<'v>: 'v + AnyLifetime<'v> + Debug + Display {
         0
     }
 
-    /// Compare `self` with `other` for equality.
+    /// Compare `self` with `other` for equality, as used by `==`, `!=`, `in` and
+    /// dictionary lookup. If you implement this method, and the type is hashable,
+    /// make sure equal values have equal [`write_hash`](StarlarkValue::write_hash) results.
     /// Should only return an error on excessive recursion.
+    ///
+    /// The default implementation only considers a value equal to itself.
     fn equals(&self, _other: Value<'v>) -> anyhow::Result<bool> {
         // Type is only equal via a pointer
         Ok(false)
     }
 
-    /// Compare `self` with `other`.
+    /// Compare `self` with `other`, as used by `<`, `<=`, `>`, `>=`, `sorted`, `min` and `max`.
     /// This method returns a result of type [`Ordering`], or an [`Err`]
     /// if the two types differ.
+    ///
+    /// The default implementation returns an error saying the values are not comparable,
+    /// so implement this method for types whose values should be sortable.
     fn compare(&self, other: Value<'v>) -> anyhow::Result<Ordering> {
         ValueError::unsupported_with(self, "compare", other)
     }
@@ -716,6 +738,7 @@ pub(crate) trait StarlarkValueDyn<'v>: 'v {
     fn get_methods(&self) -> Option<&'static Globals>;
     fn documentation(&self) -> Option<DocItem>;
     fn collect_repr(&self, _collector: &mut String);
+    fn collect_str(&self, _collector: &mut String);
     fn to_json(&self) -> anyhow::Result<String>;
     fn to_bool(&self) -> bool;
     fn to_int(&self) -> anyhow::Result<i32>;
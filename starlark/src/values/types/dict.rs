@@ -64,7 +64,11 @@ impl<'v, T: DictLike<'v>> Display for DictGen<T> {
     }
 }
 
-/// Define the list type. See [`Dict`] and [`FrozenDict`] as the two possible representations.
+/// Define the dictionary type. See [`Dict`] and [`FrozenDict`] as the two possible representations.
+///
+/// Entries are kept in insertion order, which is the order they are iterated, printed and frozen
+/// in. Assigning to a key already present keeps its position, while a key which is removed and
+/// added again moves to the end. The order doesn't depend on hashes, so it is the same each run.
 #[derive(Clone, Default, Trace, Debug)]
 #[repr(transparent)]
 pub struct Dict<'v> {
@@ -72,7 +76,9 @@ pub struct Dict<'v> {
     content: SmallMap<Value<'v>, Value<'v>>,
 }
 
-/// Define the list type. See [`Dict`] and [`FrozenDict`] as the two possible representations.
+/// Define the dictionary type. See [`Dict`] and [`FrozenDict`] as the two possible representations.
+///
+/// Entries keep the order of the [`Dict`] that was frozen.
 #[derive(Clone, Default, Debug, AnyLifetime)]
 #[repr(transparent)]
 pub struct FrozenDict {
@@ -176,6 +182,12 @@ impl<'v> Dict<'v> {
         DictGen::<FrozenDict>::get_type_value_static()
     }
 
+    /// The methods of dictionaries, without needing a dictionary value.
+    pub(crate) fn get_methods_static() -> &'static Globals {
+        static RES: GlobalsStatic = GlobalsStatic::new();
+        RES.globals(crate::stdlib::dict::dict_methods)
+    }
+
     /// Create a new [`Dict`].
     pub fn new(content: SmallMap<Value<'v>, Value<'v>>) -> Self {
         Self { content }
@@ -189,7 +201,7 @@ impl<'v> Dict<'v> {
         self.content.is_empty()
     }
 
-    /// Iterate through the key/value pairs in the dictionary.
+    /// Iterate through the key/value pairs in the dictionary, in insertion order.
     pub fn iter<'a>(&'a self) -> impl Iterator<Item = (Value<'v>, Value<'v>)> + 'a {
         self.content.iter().map(|(l, r)| (*l, *r))
     }
@@ -204,12 +216,12 @@ impl<'v> Dict<'v> {
             .map(|(l, r)| (l.unborrow_copy(), *r))
     }
 
-    /// Iterator over keys.
+    /// Iterator over keys, in insertion order.
     pub fn keys<'a>(&'a self) -> impl Iterator<Item = Value<'v>> + 'a {
         self.content.keys().copied()
     }
 
-    /// Iterator over keys.
+    /// Iterator over values, in insertion order of their keys.
     pub fn values<'a>(&'a self) -> impl Iterator<Item = Value<'v>> + 'a {
         self.content.values().copied()
     }
@@ -269,6 +281,24 @@ impl<'v> Dict<'v> {
         self.content.insert_hashed(key, value);
     }
 
+    /// Like [`insert_hashed`](Dict::insert_hashed), but fail without inserting if a new key
+    /// would take the dictionary over the limit checked by `Heap::check_collection_length`.
+    pub(crate) fn insert_hashed_bounded(
+        &mut self,
+        key: Hashed<Value<'v>>,
+        value: Value<'v>,
+        heap: &Heap,
+    ) -> anyhow::Result<()> {
+        if let Err(e) = heap.check_collection_length(self.len() + 1) {
+            // Replacing the value of an existing key doesn't grow the dictionary.
+            if self.content.get_hashed(key.borrow()).is_none() {
+                return Err(e);
+            }
+        }
+        self.content.insert_hashed(key, value);
+        Ok(())
+    }
+
     pub fn remove_hashed(&mut self, key: Hashed<Value<'v>>) -> Option<Value<'v>> {
         self.content.remove_hashed(key.borrow())
     }
@@ -279,7 +309,7 @@ impl<'v> Dict<'v> {
 }
 
 impl FrozenDict {
-    /// Iterate through the key/value pairs in the dictionary.
+    /// Iterate through the key/value pairs in the dictionary, in insertion order.
     pub fn iter<'a>(&'a self) -> impl Iterator<Item = (FrozenValue, FrozenValue)> + 'a {
         self.content.iter().map(|(l, r)| (*l, *r))
     }
@@ -293,12 +323,12 @@ impl FrozenDict {
             .map(|(l, r)| (l.unborrow_copy(), *r))
     }
 
-    /// Iterator over keys.
+    /// Iterator over keys, in insertion order.
     pub fn keys<'a>(&'a self) -> impl Iterator<Item = FrozenValue> + 'a {
         self.content.keys().copied()
     }
 
-    /// Iterator over keys.
+    /// Iterator over values, in insertion order of their keys.
     pub fn values<'a>(&'a self) -> impl Iterator<Item = FrozenValue> + 'a {
         self.content.values().copied()
     }
@@ -366,8 +396,7 @@ where
     starlark_type!(Dict::TYPE);
 
     fn get_methods(&self) -> Option<&'static Globals> {
-        static RES: GlobalsStatic = GlobalsStatic::new();
-        RES.methods(crate::stdlib::dict::dict_methods)
+        Some(Dict::get_methods_static())
     }
 
     fn collect_repr(&self, r: &mut String) {
@@ -562,6 +591,41 @@ b1 and b2 and b3
         );
     }
 
+    #[test]
+    fn test_repr_insertion_order() {
+        assert::all_true(
+            r#"
+x = {"z": 1, 3: 2, "a": 3, None: 4}
+repr(x) == '{"z": 1, 3: 2, "a": 3, None: 4}'
+repr(x) == repr({k: v for k, v in x.items()})
+"#,
+        );
+        // Removing and re-adding moves a key to the end, even in big (indexed) dicts.
+        assert::is_true(
+            r#"
+x = {str(i): i for i in range(100, 0, -1)}
+x.pop("50")
+x["50"] = 50
+x["100"] = 0
+items = [(str(i), i) for i in range(99, 0, -1) if i != 50]
+expected = ", ".join([repr(k) + ": " + repr(v) for k, v in [("100", 0)] + items + [("50", 50)]])
+repr(x) == "{" + expected + "}"
+"#,
+        );
+        // Frozen dicts and lists keep the same order.
+        let mut a = assert::Assert::new();
+        a.module(
+            "m.star",
+            "d = {'b': 1, 'a': 2, 'c': 3}\nl = ['b', 'a', 'c']",
+        );
+        a.is_true(
+            r#"
+load("m.star", "d", "l")
+repr(d) == '{"b": 1, "a": 2, "c": 3}' and repr(l) == '["b", "a", "c"]' and list(d) == l
+"#,
+        );
+    }
+
     #[test]
     fn test_get_str() -> anyhow::Result<()> {
         let heap = Heap::new();
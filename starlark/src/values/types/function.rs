@@ -26,7 +26,7 @@ use gazebo::{any::AnyLifetime, coerce::Coerce};
 use crate as starlark;
 use crate::{
     codemap::Span,
-    eval::{Arguments, Evaluator, ParametersParser, ParametersSpec},
+    eval::{Arguments, Evaluator, EvaluatorError, ParametersParser, ParametersSpec},
     values::{
         AllocFrozenValue, AllocValue, FrozenHeap, FrozenValue, Heap, SimpleValue, StarlarkValue,
         Trace, Value, ValueLike,
@@ -77,6 +77,7 @@ pub struct NativeFunction {
     function: Box<dyn NativeFunc>,
     name: String,
     typ: Option<FrozenValue>,
+    speculative_exec_safe: bool,
 }
 
 impl AllocFrozenValue for NativeFunction {
@@ -100,6 +101,7 @@ impl NativeFunction {
             function: box function,
             name,
             typ: None,
+            speculative_exec_safe: false,
         }
     }
 
@@ -132,6 +134,7 @@ impl NativeFunction {
             },
             name,
             typ: None,
+            speculative_exec_safe: false,
         }
     }
 
@@ -139,6 +142,16 @@ impl NativeFunction {
     pub fn set_type(&mut self, typ: FrozenValue) {
         self.typ = Some(typ)
     }
+
+    /// Mark the function as having no side effects, so a call where every argument is a constant
+    /// may be evaluated when the code is compiled. Specified using `#[speculative_exec_safe]`.
+    pub fn set_speculative_exec_safe(&mut self) {
+        self.speculative_exec_safe = true
+    }
+
+    pub(crate) fn is_speculative_exec_safe(&self) -> bool {
+        self.speculative_exec_safe
+    }
 }
 
 impl SimpleValue for NativeFunction {}
@@ -185,6 +198,28 @@ impl<'v> StarlarkValue<'v> for NativeFunction {
     }
 }
 
+/// Stands in for a global function disabled by
+/// [`Evaluator::disable_builtins`](crate::eval::Evaluator::disable_builtins).
+#[derive(Debug, AnyLifetime, Display)]
+#[display(fmt = "{}", _0)]
+pub(crate) struct DisabledBuiltin(pub(crate) String);
+
+impl SimpleValue for DisabledBuiltin {}
+
+impl<'v> StarlarkValue<'v> for DisabledBuiltin {
+    starlark_type!(FUNCTION_TYPE);
+
+    fn invoke(
+        &self,
+        _me: Value<'v>,
+        _location: Option<Span>,
+        _args: Arguments<'v, '_>,
+        _eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        Err(EvaluatorError::DisabledBuiltin(self.0.clone()).into())
+    }
+}
+
 /// Used by the `#[attribute]` tag of [`#[starlark_module]`](macro@starlark_module)
 /// to define a function that pretends to be an attribute.
 #[derive(Derivative, Display)]
@@ -164,57 +164,75 @@ impl<'v> List<'v> {
     }
 
     #[inline(always)]
-    fn reserve_additional(&self, additional: usize, heap: &'v Heap) {
+    fn reserve_additional(&self, additional: usize, heap: &'v Heap) -> anyhow::Result<()> {
+        heap.check_collection_length(self.len() + additional)?;
         if likely(self.content.get().as_ref().remaining_capacity() >= additional) {
-            return;
+            return Ok(());
         }
 
         self.reserve_additional_slow(additional, heap);
+        Ok(())
     }
 
-    pub(crate) fn double(&self, heap: &'v Heap) {
-        self.reserve_additional(self.len(), heap);
+    pub(crate) fn double(&self, heap: &'v Heap) -> anyhow::Result<()> {
+        self.reserve_additional(self.len(), heap)?;
         self.content.get().double();
+        Ok(())
     }
 
     #[inline]
-    pub(crate) fn extend<I: IntoIterator<Item = Value<'v>>>(&self, iter: I, heap: &'v Heap) {
+    pub(crate) fn extend<I: IntoIterator<Item = Value<'v>>>(
+        &self,
+        iter: I,
+        heap: &'v Heap,
+    ) -> anyhow::Result<()> {
         let iter = iter.into_iter();
         let (lo, hi) = iter.size_hint();
         match hi {
             Some(hi) if lo == hi => {
                 // Exact size iterator.
-                self.reserve_additional(lo, heap);
+                self.reserve_additional(lo, heap)?;
                 // Extend will panic if upper bound is provided incorrectly.
                 self.content.get().extend(iter);
             }
-            Some(hi) if self.content.get().remaining_capacity() >= hi => {
-                // Enough capacity for upper bound.
+            Some(hi)
+                if self.content.get().remaining_capacity() >= hi
+                    && heap.check_collection_length(self.len() + hi).is_ok() =>
+            {
+                // Enough capacity for upper bound, which is within the length limit.
                 // Extend will panic if upper bound is provided incorrectly.
                 self.content.get().extend(iter);
             }
             _ => {
                 // Default slow version.
-                self.reserve_additional(iter.size_hint().0, heap);
+                self.reserve_additional(iter.size_hint().0, heap)?;
                 for item in iter {
-                    self.push(item, heap);
+                    self.push(item, heap)?;
                 }
             }
         }
+        Ok(())
     }
 
-    pub(crate) fn push(&self, value: Value<'v>, heap: &'v Heap) {
-        self.reserve_additional(1, heap);
+    pub(crate) fn push(&self, value: Value<'v>, heap: &'v Heap) -> anyhow::Result<()> {
+        self.reserve_additional(1, heap)?;
         self.content.get().push(value);
+        Ok(())
     }
 
     pub(crate) fn clear(&self) {
         self.content.get().clear();
     }
 
-    pub(crate) fn insert(&self, index: usize, value: Value<'v>, heap: &'v Heap) {
-        self.reserve_additional(1, heap);
+    pub(crate) fn insert(
+        &self,
+        index: usize,
+        value: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<()> {
+        self.reserve_additional(1, heap)?;
         self.content.get().insert(index, value);
+        Ok(())
     }
 
     pub(crate) fn remove(&self, index: usize) -> Value<'v> {
@@ -302,6 +320,12 @@ impl<'v> List<'v> {
         ListGen::<FrozenList>::get_type_value_static()
     }
 
+    /// The methods of lists, without needing a list value.
+    pub(crate) fn get_methods_static() -> &'static Globals {
+        static RES: GlobalsStatic = GlobalsStatic::new();
+        RES.globals(crate::stdlib::list::list_methods)
+    }
+
     pub(crate) fn new(content: ValueTyped<'v, Array<'v>>) -> Self {
         List {
             content: Cell::new(content),
@@ -445,8 +469,7 @@ where
     starlark_type!(List::TYPE);
 
     fn get_methods(&self) -> Option<&'static Globals> {
-        static RES: GlobalsStatic = GlobalsStatic::new();
-        RES.methods(crate::stdlib::list::list_methods)
+        Some(List::get_methods_static())
     }
 
     fn collect_repr(&self, s: &mut String) {
@@ -546,6 +569,7 @@ where
 
     fn add(&self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         if let Some(other) = List::from_value(other) {
+            heap.check_collection_length(self.0.content().len() + other.content().len())?;
             Ok(heap.alloc_list_concat(self.0.content(), other.content()))
         } else {
             ValueError::unsupported_with(self, "+", other)
@@ -554,7 +578,13 @@ where
 
     fn mul(&self, other: Value, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         let l = i32::unpack_param(other)?;
-        let mut result = Vec::with_capacity(self.0.content().len() * cmp::max(0, l) as usize);
+        let len = self
+            .0
+            .content()
+            .len()
+            .saturating_mul(cmp::max(0, l) as usize);
+        heap.check_collection_length(len)?;
+        let mut result = Vec::with_capacity(len);
         for _ in 0..l {
             result.extend(self.0.content().iter());
         }
@@ -624,6 +654,25 @@ impl<'v, V: UnpackValue<'v>> Deref for ListOf<'v, V> {
 mod tests {
     use crate::assert::{self, Assert};
 
+    #[test]
+    fn test_equals() {
+        assert::all_true(
+            r#"
+[] == []
+[1, [2, "x"]] == [1, [2, "x"]]
+[1, 2] != [1, 2, 3]
+[1, 2, 3] != [1, 2]
+[0, 2, 3] != [1, 2, 3]
+[1, 2, 3] != [1, 2, 4]
+(1, (2, "x")) == (1, (2, "x"))
+(1, 2) != (1, 2, 3)
+(0, 2, 3) != (1, 2, 3)
+[1, 2] != (1, 2)
+list(range(1000)) != list(range(1001))
+"#,
+        );
+    }
+
     #[test]
     fn test_to_str() {
         assert::all_true(
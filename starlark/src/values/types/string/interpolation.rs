@@ -18,7 +18,7 @@
 //! String interpolation-related code.
 //! Based on <https://docs.python.org/3/library/stdtypes.html#printf-style-string-formatting>
 
-use std::{fmt::Write, str::FromStr};
+use std::{cmp, fmt::Write, iter, mem, str::FromStr};
 
 use anyhow::anyhow;
 use gazebo::{cast, prelude::*};
@@ -27,8 +27,8 @@ use thiserror::Error;
 use crate::{
     collections::string_pool::StringPool,
     values::{
-        dict::Dict, float, num, num::Num, tuple::Tuple, Heap, StringValue, UnpackValue, Value,
-        ValueError, ValueLike,
+        dict::Dict, float, num, num::Num, tuple::Tuple, FrozenStringValue, Heap, StringValue,
+        UnpackValue, Value, ValueError, ValueLike,
     },
 };
 
@@ -41,11 +41,296 @@ enum StringInterpolationError {
     /// Interpolation parameter is too small for the format string.
     #[error("Not enough arguments for format string")]
     NotEnoughParameters,
+    /// Width is larger than `MAX_WIDTH`.
+    #[error("Width too big, the maximum is {}", MAX_WIDTH)]
+    WidthTooBig,
+    /// Precision is larger than `MAX_WIDTH`.
+    #[error("Precision too big, the maximum is {}", MAX_WIDTH)]
+    PrecisionTooBig,
+    /// A flag, width or precision is followed by something which isn't a conversion.
+    #[error("Unsupported format character '{0}' at index {1}")]
+    UnsupportedConversion(char, usize),
+    /// A flag, width or precision is at the end of the format string.
+    #[error("Incomplete format")]
+    IncompleteFormat,
 }
 
-pub(crate) fn percent(format: &str, value: Value) -> anyhow::Result<String> {
+/// The largest width or precision allowed in a format spec. Python fails above `i32::MAX`,
+/// but a width that large would need gigabytes of padding.
+const MAX_WIDTH: usize = 1_000_000;
+
+/// Flags, width and precision of a `%` conversion, e.g. the `-5.2` in `%-5.2f`.
+#[derive(Default)]
+struct PercentSpec {
+    /// `-`: pad on the right rather than the left.
+    left: bool,
+    /// `+`: always write the sign of a number.
+    plus: bool,
+    /// ` `: write a space before a positive number.
+    space: bool,
+    /// `0`: pad numbers with zeros after the sign.
+    zero: bool,
+    /// `#`: write the `0o`/`0x` prefix of octal and hex numbers.
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl PercentSpec {
+    /// Parse the spec starting at `format[*i]`, leaving `*i` at the conversion character.
+    /// A width or precision of `*` is taken from the next argument.
+    fn parse<'v>(
+        format: &[u8],
+        i: &mut usize,
+        mut next_value: impl FnMut() -> anyhow::Result<Value<'v>>,
+    ) -> anyhow::Result<PercentSpec> {
+        fn number(format: &[u8], i: &mut usize) -> usize {
+            let mut res: usize = 0;
+            while let Some(c @ b'0'..=b'9') = format.get(*i) {
+                res = res.saturating_mul(10).saturating_add((c - b'0') as usize);
+                *i += 1;
+            }
+            res
+        }
+
+        fn check(x: usize, err: StringInterpolationError) -> anyhow::Result<usize> {
+            if x > MAX_WIDTH {
+                Err(err.into())
+            } else {
+                Ok(x)
+            }
+        }
+
+        let mut spec = PercentSpec::default();
+        loop {
+            match format.get(*i) {
+                Some(b'-') => spec.left = true,
+                Some(b'+') => spec.plus = true,
+                Some(b' ') => spec.space = true,
+                Some(b'0') => spec.zero = true,
+                Some(b'#') => spec.alternate = true,
+                _ => break,
+            }
+            *i += 1;
+        }
+        if format.get(*i) == Some(&b'*') {
+            *i += 1;
+            let width = next_value()?.to_int()?;
+            spec.left |= width < 0;
+            spec.width = width.unsigned_abs() as usize;
+        } else {
+            spec.width = number(format, i);
+        }
+        spec.width = check(spec.width, StringInterpolationError::WidthTooBig)?;
+        if format.get(*i) == Some(&b'.') {
+            *i += 1;
+            let precision = if format.get(*i) == Some(&b'*') {
+                *i += 1;
+                cmp::max(next_value()?.to_int()?, 0) as usize
+            } else {
+                number(format, i)
+            };
+            spec.precision = Some(check(precision, StringInterpolationError::PrecisionTooBig)?);
+        }
+        Ok(spec)
+    }
+
+    fn is_default(&self) -> bool {
+        !(self.left || self.plus || self.space || self.zero || self.alternate)
+            && self.width == 0
+            && self.precision.is_none()
+    }
+
+    /// Write `value` with conversion `conv`, applying this spec.
+    fn write(&self, out: &mut String, conv: u8, value: Value, heap: &Heap) -> anyhow::Result<()> {
+        let mut body = String::new();
+        write_conversion(&mut body, conv, value, self.precision)?;
+        let (sign, prefix, digits) = match conv {
+            b's' | b'r' => ("", "", body.as_str()),
+            _ => {
+                let (sign, digits) = match body.strip_prefix('-') {
+                    Some(digits) => ("-", digits),
+                    None if self.plus => ("+", body.as_str()),
+                    None if self.space => (" ", body.as_str()),
+                    None => ("", body.as_str()),
+                };
+                let prefix = match conv {
+                    b'o' if self.alternate => "0o",
+                    b'x' if self.alternate => "0x",
+                    b'X' if self.alternate => "0X",
+                    _ => "",
+                };
+                (sign, prefix, digits)
+            }
+        };
+
+        // For integers, the precision is the minimum number of digits.
+        let min_digits = match (conv, self.precision) {
+            (b'd' | b'i' | b'o' | b'x' | b'X', Some(precision)) => precision,
+            _ => 0,
+        };
+        let digit_zeros = min_digits.saturating_sub(digits.len());
+        let len = sign.len() + prefix.len() + digit_zeros + digits.chars().count();
+        let padding = self.width.saturating_sub(len);
+        heap.check_string_length(out.len() + len + padding)?;
+        let zero_pad = self.zero
+            && !self.left
+            && !matches!(conv, b's' | b'r')
+            && digits.starts_with(|c: char| c.is_ascii_digit());
+
+        if !self.left && !zero_pad {
+            out.extend(iter::repeat(' ').take(padding));
+        }
+        out.push_str(sign);
+        out.push_str(prefix);
+        if zero_pad {
+            out.extend(iter::repeat('0').take(padding));
+        }
+        out.extend(iter::repeat('0').take(digit_zeros));
+        out.push_str(digits);
+        if self.left {
+            out.extend(iter::repeat(' ').take(padding));
+        }
+        Ok(())
+    }
+}
+
+/// Write `x` in scientific notation with `precision` digits after the point,
+/// and at least two digits of exponent, as Python does.
+fn write_scientific_precision(
+    out: &mut String,
+    x: f64,
+    precision: usize,
+    exponent_char: char,
+    strip_trailing_zeros: bool,
+) {
+    let formatted = format!("{:.*e}", precision, x);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap();
+    let mantissa = if strip_trailing_zeros && mantissa.contains('.') {
+        mantissa.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        mantissa
+    };
+    let exponent: i32 = exponent.parse().unwrap();
+    write!(out, "{}{}{:+03}", mantissa, exponent_char, exponent).unwrap();
+}
+
+/// Write `x` with `precision` significant digits, using scientific notation
+/// for large and small exponents, as Python's `%g` does.
+fn write_compact_precision(out: &mut String, x: f64, precision: usize, exponent_char: char) {
+    let precision = cmp::max(precision, 1);
+    // Round to the precision first, since that can change the exponent.
+    let exponent: i32 = format!("{:.*e}", precision - 1, x)
+        .split_once('e')
+        .unwrap()
+        .1
+        .parse()
+        .unwrap();
+    if exponent < -4 || exponent >= precision as i32 {
+        write_scientific_precision(out, x, precision - 1, exponent_char, true);
+    } else {
+        let decimals = (precision as i32 - 1 - exponent) as usize;
+        let formatted = format!("{:.*}", decimals, x);
+        if formatted.contains('.') {
+            out.push_str(formatted.trim_end_matches('0').trim_end_matches('.'));
+        } else {
+            out.push_str(&formatted);
+        }
+    }
+}
+
+/// Write `value` with the `%` conversion `conv`, e.g. `b'd'` for `%d`.
+/// With a `precision`, strings are truncated and floats rounded to it.
+fn write_conversion(
+    out: &mut String,
+    conv: u8,
+    value: Value,
+    precision: Option<usize>,
+) -> anyhow::Result<()> {
+    let to_float =
+        |value: Value| -> anyhow::Result<f64> { Ok(Num::unpack_param(value)?.as_float()) };
+    match (conv, precision) {
+        (b's', None) => value.collect_str(out),
+        (b's', Some(precision)) => out.extend(value.to_str().chars().take(precision)),
+        (b'r', None) => value.collect_repr(out),
+        (b'r', Some(precision)) => out.extend(value.to_repr().chars().take(precision)),
+        (b'd' | b'i', _) => {
+            if let Some(num::Num::Float(v)) = value.unpack_num() {
+                match num::Num::Float(v.trunc()).as_int() {
+                    None => {
+                        return ValueError::unsupported(&float::StarlarkFloat(v), "%d");
+                    }
+                    Some(v) => write!(out, "{}", v).unwrap(),
+                }
+            } else {
+                write!(out, "{}", value.to_int()?).unwrap()
+            }
+        }
+        (b'o', _) => {
+            let v = value.to_int()?;
+            write!(
+                out,
+                "{}{:o}",
+                if v < 0 { "-" } else { "" },
+                v.wrapping_abs() as u64
+            )
+            .unwrap();
+        }
+        (b'x', _) => {
+            let v = value.to_int()?;
+            write!(
+                out,
+                "{}{:x}",
+                if v < 0 { "-" } else { "" },
+                v.wrapping_abs() as u64
+            )
+            .unwrap();
+        }
+        (b'X', _) => {
+            let v = value.to_int()?;
+            write!(
+                out,
+                "{}{:X}",
+                if v < 0 { "-" } else { "" },
+                v.wrapping_abs() as u64
+            )
+            .unwrap()
+        }
+        (b'e' | b'E', precision) => {
+            let v = to_float(value)?;
+            let exponent_char = if conv == b'e' { 'e' } else { 'E' };
+            match precision {
+                Some(precision) if v.is_finite() => {
+                    write_scientific_precision(out, v, precision, exponent_char, false)
+                }
+                _ => float::write_scientific(out, v, exponent_char, false).unwrap(),
+            }
+        }
+        (b'f' | b'F', precision) => {
+            let v = to_float(value)?;
+            match precision {
+                Some(precision) if v.is_finite() => write!(out, "{:.*}", precision, v).unwrap(),
+                _ => float::write_decimal(out, v).unwrap(),
+            }
+        }
+        (b'g' | b'G', precision) => {
+            let v = to_float(value)?;
+            let exponent_char = if conv == b'g' { 'e' } else { 'E' };
+            match precision {
+                Some(precision) if v.is_finite() => {
+                    write_compact_precision(out, v, precision, exponent_char)
+                }
+                _ => float::write_compact(out, v, exponent_char).unwrap(),
+            }
+        }
+        _ => unreachable!("not a conversion: {}", conv as char),
+    }
+    Ok(())
+}
+
+pub(crate) fn percent(format: &str, value: Value, heap: &Heap) -> anyhow::Result<String> {
     // For performance reasons, we treat format as a list of bytes
-    // (which is fine, the only thing we care about are '%' and ASCII digits).
+    // (which is fine, the only thing we care about are '%', flags and ASCII digits).
     // As a result, we accumulate into a Vec<u8>, which we know at any point
     // we are at the end or at a '%' must be a valid UTF8 buffer.
 
@@ -70,95 +355,47 @@ pub(crate) fn percent(format: &str, value: Value) -> anyhow::Result<String> {
     };
 
     // because of the way format is defined, we can deal with it as bytes
-    let mut format = format.as_bytes().iter().copied();
-    while let Some(c) = format.next() {
-        if c == b'%' {
-            if let Some(c) = format.next() {
-                let out: &mut String = unsafe { cast::ptr_mut(&mut res) };
-                match c {
-                    b'%' => res.push(b'%'),
-                    b's' => {
-                        let arg = next_value()?;
-                        match arg.unpack_str() {
-                            None => arg.collect_repr(out),
-                            Some(s) => out.push_str(s),
-                        }
-                    }
-                    b'r' => next_value()?.collect_repr(out),
-                    b'd' => {
-                        let value = next_value()?;
-                        if let Some(num::Num::Float(v)) = value.unpack_num() {
-                            match num::Num::Float(v.trunc()).as_int() {
-                                None => {
-                                    return ValueError::unsupported(&float::StarlarkFloat(v), "%d");
-                                }
-                                Some(v) => write!(out, "{}", v).unwrap(),
-                            }
-                        } else {
-                            write!(out, "{}", value.to_int()?).unwrap()
-                        }
-                    }
-                    b'o' => {
-                        let v = next_value()?.to_int()?;
-                        write!(
-                            out,
-                            "{}{:o}",
-                            if v < 0 { "-" } else { "" },
-                            v.wrapping_abs() as u64
-                        )
-                        .unwrap();
-                    }
-                    b'x' => {
-                        let v = next_value()?.to_int()?;
-                        write!(
-                            out,
-                            "{}{:x}",
-                            if v < 0 { "-" } else { "" },
-                            v.wrapping_abs() as u64
-                        )
-                        .unwrap();
-                    }
-                    b'X' => {
-                        let v = next_value()?.to_int()?;
-                        write!(
-                            out,
-                            "{}{:X}",
-                            if v < 0 { "-" } else { "" },
-                            v.wrapping_abs() as u64
-                        )
-                        .unwrap()
-                    }
-                    b'e' => {
-                        let v = Num::unpack_param(next_value()?)?.as_float();
-                        float::write_scientific(out, v, 'e', false).unwrap()
-                    }
-                    b'E' => {
-                        let v = Num::unpack_param(next_value()?)?.as_float();
-                        float::write_scientific(out, v, 'E', false).unwrap()
-                    }
-                    b'f' | b'F' => {
-                        let v = Num::unpack_param(next_value()?)?.as_float();
-                        float::write_decimal(out, v).unwrap()
-                    }
-                    b'g' => {
-                        let v = Num::unpack_param(next_value()?)?.as_float();
-                        float::write_compact(out, v, 'e').unwrap()
-                    }
-                    b'G' => {
-                        let v = Num::unpack_param(next_value()?)?.as_float();
-                        float::write_compact(out, v, 'E').unwrap()
-                    }
-                    c => {
-                        res.push(b'%');
-                        res.push(c);
-                    }
+    let format_str = format;
+    let format = format.as_bytes();
+    let mut i = 0;
+    while let Some(&c) = format.get(i) {
+        i += 1;
+        if c != b'%' {
+            res.push(c);
+            continue;
+        }
+        let start = i - 1;
+        let spec = PercentSpec::parse(format, &mut i, &mut next_value)?;
+        let out: &mut String = unsafe { cast::ptr_mut(&mut res) };
+        match format.get(i) {
+            Some(b'%') if spec.is_default() => out.push('%'),
+            Some(
+                conv @ (b's' | b'r' | b'd' | b'i' | b'o' | b'x' | b'X' | b'e' | b'E' | b'f' | b'F'
+                | b'g' | b'G'),
+            ) => {
+                let value = next_value()?;
+                if spec.is_default() {
+                    write_conversion(out, *conv, value, None)?;
+                } else {
+                    spec.write(out, *conv, value, heap)?;
                 }
-            } else {
-                res.push(b'%');
             }
-        } else {
-            res.push(c);
+            // A spec must be followed by a conversion, as the spec may have consumed arguments.
+            Some(_) if !spec.is_default() => {
+                let conv = format_str[i..].chars().next().unwrap();
+                let index = format_str[..i].chars().count();
+                return Err(StringInterpolationError::UnsupportedConversion(conv, index).into());
+            }
+            None if !spec.is_default() => {
+                return Err(StringInterpolationError::IncompleteFormat.into());
+            }
+            // Not a conversion, so write it out unchanged.
+            _ => {
+                let end = cmp::min(i + 1, format.len());
+                res.extend_from_slice(&format[start..end]);
+            }
         }
+        i += 1;
     }
     if values.next().is_some() {
         Err(StringInterpolationError::TooManyParameters.into())
@@ -203,6 +440,32 @@ pub(crate) fn parse_format_one(s: &str) -> Option<(String, String)> {
     Some((before, after))
 }
 
+/// Try parse `"aaa{}bbb{}ccc"` and return `["aaa", "bbb", "ccc"]`,
+/// the literal text around each `{}`.
+///
+/// Returns `None` if any field is named, indexed or has a conversion or format spec.
+pub(crate) fn parse_format_many(s: &str) -> Option<Vec<String>> {
+    let mut segments = Vec::new();
+    let mut segment = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => match chars.next()? {
+                '{' => segment.push('{'),
+                '}' => segments.push(mem::take(&mut segment)),
+                _ => return None,
+            },
+            '}' => match chars.next()? {
+                '}' => segment.push('}'),
+                _ => return None,
+            },
+            c => segment.push(c),
+        }
+    }
+    segments.push(segment);
+    Some(segments)
+}
+
 /// Try parse `"aaa%sbbb"` and return `("aaa", "bbb")`.
 pub(crate) fn parse_percent_s_one(format: &str) -> Option<(String, String)> {
     let mut before = String::with_capacity(format.len());
@@ -231,6 +494,28 @@ pub(crate) fn parse_percent_s_one(format: &str) -> Option<(String, String)> {
     Some((before, after))
 }
 
+/// Try parse `"aaa%sbbb%sccc"` and return `["aaa", "bbb", "ccc"]`,
+/// the literal text around each `%s`.
+///
+/// Returns `None` if there is any conversion other than `%s` and `%%`.
+pub(crate) fn parse_percent_s_many(format: &str) -> Option<Vec<String>> {
+    let mut segments = Vec::new();
+    let mut segment = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => match chars.next()? {
+                '%' => segment.push('%'),
+                's' => segments.push(mem::take(&mut segment)),
+                _ => return None,
+            },
+            c => segment.push(c),
+        }
+    }
+    segments.push(segment);
+    Some(segments)
+}
+
 /// Evaluate `"<before>{}<after>".format(arg)`.
 pub(crate) fn format_one<'v>(
     before: &str,
@@ -239,17 +524,42 @@ pub(crate) fn format_one<'v>(
     heap: &'v Heap,
 ) -> anyhow::Result<Value<'v>> {
     Ok(match StringValue::new(arg) {
-        Some(arg) => heap.alloc_str_concat3(before, &arg, after),
+        Some(arg) => {
+            heap.check_string_length(before.len() + arg.len() + after.len())?;
+            heap.alloc_str_concat3(before, &arg, after)
+        }
         None => {
             let mut result = String::with_capacity(before.len() + after.len() + 10);
             result.push_str(before);
-            arg.collect_repr(&mut result);
+            arg.collect_str(&mut result);
             result.push_str(after);
+            heap.check_string_length(result.len())?;
             heap.alloc_str(&result)
         }
     })
 }
 
+/// Evaluate `"<s0>{}<s1>{}<s2>".format(a0, a1)`, or equivalently `"<s0>%s<s1>%s<s2>" % (a0, a1)`,
+/// where there is one more segment than there are args.
+pub(crate) fn format_many<'v>(
+    segments: &[FrozenStringValue],
+    args: &[Value<'v>],
+    heap: &'v Heap,
+) -> anyhow::Result<Value<'v>> {
+    debug_assert!(segments.len() == args.len() + 1);
+    let mut result = String::with_capacity(segments.iter().map(|s| s.len()).sum::<usize>() + 10);
+    for (segment, arg) in segments.iter().zip(args) {
+        result.push_str(segment.as_str());
+        match StringValue::new(*arg) {
+            Some(arg) => result.push_str(arg.as_str()),
+            None => arg.collect_str(&mut result),
+        }
+    }
+    result.push_str(segments.last().unwrap().as_str());
+    heap.check_string_length(result.len())?;
+    Ok(heap.alloc_str(&result))
+}
+
 /// Evaluate `"<before>%s<after>" % arg`.
 pub(crate) fn percent_s_one<'v>(
     before: &str,
@@ -258,7 +568,10 @@ pub(crate) fn percent_s_one<'v>(
     heap: &'v Heap,
 ) -> anyhow::Result<Value<'v>> {
     Ok(match StringValue::new(arg) {
-        Some(arg) => heap.alloc_str_concat3(before, &arg, after),
+        Some(arg) => {
+            heap.check_string_length(before.len() + arg.len() + after.len())?;
+            heap.alloc_str_concat3(before, &arg, after)
+        }
         None => {
             let one = match Tuple::from_value(arg) {
                 Some(tuple) => match tuple.content() {
@@ -352,7 +665,7 @@ pub(crate) fn format<'v>(
                 capture.clear();
             }
             ('}', ..) => {
-                format_capture(&capture, &mut args, &kwargs, &mut result)?;
+                format_capture(&capture, &mut args, &kwargs, heap, &mut result)?;
                 capture.clear();
             }
             (.., "}") => return Err(anyhow!("Standalone '}}' in format string `{}`", this)),
@@ -362,6 +675,7 @@ pub(crate) fn format<'v>(
     match capture.as_str() {
         "}" => Err(anyhow!("Standalone '}}' in format string `{}`", this)),
         "" => {
+            heap.check_string_length(result.len())?;
             let r = heap.alloc_string_value(&result);
             string_pool.release(result);
             string_pool.release(capture);
@@ -371,41 +685,335 @@ pub(crate) fn format<'v>(
     }
 }
 
+/// Escape the non-ASCII characters of `s` as Python's `ascii()` does.
+fn collect_ascii(s: &str, result: &mut String) {
+    for c in s.chars() {
+        match c as u32 {
+            0..=0x7f => result.push(c),
+            x @ 0x80..=0xff => write!(result, "\\x{:02x}", x).unwrap(),
+            x @ 0x100..=0xffff => write!(result, "\\u{:04x}", x).unwrap(),
+            x => write!(result, "\\U{:08x}", x).unwrap(),
+        }
+    }
+}
+
+/// How a `str.format` replacement field is aligned within its width.
+#[derive(Clone, Copy, Dupe, PartialEq, Eq)]
+enum Align {
+    /// `<`
+    Left,
+    /// `>`
+    Right,
+    /// `^`
+    Center,
+    /// `=`: pad numbers after their sign and prefix.
+    AfterSign,
+}
+
+/// The format spec of a `str.format` replacement field, e.g. the `*^8` in `{:*^8}`.
+/// Based on <https://docs.python.org/3/library/string.html#format-specification-mini-language>,
+/// without the grouping options.
+#[derive(Default)]
+struct FormatSpec {
+    fill: Option<char>,
+    align: Option<Align>,
+    /// `+`: always write the sign of a number.
+    plus: bool,
+    /// ` `: write a space before a positive number.
+    space: bool,
+    /// `#`: write the `0b`/`0o`/`0x` prefix of binary, octal and hex numbers.
+    alternate: bool,
+    /// `0`: pad with zeros, after the sign of numbers.
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    /// The presentation type, e.g. `d` or `x`.
+    ty: Option<char>,
+}
+
+impl FormatSpec {
+    fn parse(spec: &str) -> anyhow::Result<FormatSpec> {
+        fn align(c: char) -> Option<Align> {
+            match c {
+                '<' => Some(Align::Left),
+                '>' => Some(Align::Right),
+                '^' => Some(Align::Center),
+                '=' => Some(Align::AfterSign),
+                _ => None,
+            }
+        }
+
+        fn number(chars: &mut iter::Peekable<std::str::Chars>) -> Option<usize> {
+            let mut res = None;
+            while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+                chars.next();
+                res = Some(
+                    res.unwrap_or(0usize)
+                        .saturating_mul(10)
+                        .saturating_add(d as usize),
+                );
+            }
+            res
+        }
+
+        let mut res = FormatSpec::default();
+        let mut chars = spec.chars().peekable();
+        let mut lookahead = spec.chars();
+        match (lookahead.next(), lookahead.next().and_then(align)) {
+            (Some(fill), Some(a)) => {
+                res.fill = Some(fill);
+                res.align = Some(a);
+                chars.nth(1);
+            }
+            (Some(c), None) if align(c).is_some() => {
+                res.align = align(c);
+                chars.next();
+            }
+            _ => {}
+        }
+        if let Some(sign @ ('+' | '-' | ' ')) = chars.peek().copied() {
+            res.plus = sign == '+';
+            res.space = sign == ' ';
+            chars.next();
+        }
+        if chars.peek() == Some(&'#') {
+            res.alternate = true;
+            chars.next();
+        }
+        if chars.peek() == Some(&'0') {
+            res.zero = true;
+            chars.next();
+        }
+        res.width = number(&mut chars).unwrap_or(0);
+        if res.width > MAX_WIDTH {
+            return Err(StringInterpolationError::WidthTooBig.into());
+        }
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            let precision =
+                number(&mut chars).ok_or_else(|| anyhow!("Format specifier missing precision"))?;
+            if precision > MAX_WIDTH {
+                return Err(StringInterpolationError::PrecisionTooBig.into());
+            }
+            res.precision = Some(precision);
+        }
+        match chars.next() {
+            None => {}
+            Some(
+                c @ ('s' | 'd' | 'b' | 'o' | 'x' | 'X' | 'e' | 'E' | 'f' | 'F' | 'g' | 'G' | '%'),
+            ) if chars.peek().is_none() => res.ty = Some(c),
+            Some(_) => return Err(anyhow!("Invalid format specifier `{}`", spec)),
+        }
+        Ok(res)
+    }
+
+    /// Write `value` applying this spec. If the field had a conversion (e.g. `!r`),
+    /// `converted` is its result, which is formatted as a string.
+    fn write(
+        &self,
+        out: &mut String,
+        value: Value,
+        converted: Option<&str>,
+        heap: &Heap,
+    ) -> anyhow::Result<()> {
+        let numeric = match self.ty {
+            Some('s') => false,
+            Some(_) => true,
+            None => converted.is_none() && value.unpack_num().is_some(),
+        };
+        if numeric {
+            self.write_number(out, value, converted, heap)
+        } else {
+            self.write_string(out, value, converted, heap)
+        }
+    }
+
+    fn write_string(
+        &self,
+        out: &mut String,
+        value: Value,
+        converted: Option<&str>,
+        heap: &Heap,
+    ) -> anyhow::Result<()> {
+        if self.plus || self.space {
+            return Err(anyhow!("Sign not allowed in string format specifier"));
+        }
+        if self.alternate {
+            return Err(anyhow!(
+                "Alternate form (#) not allowed in string format specifier"
+            ));
+        }
+        if self.align == Some(Align::AfterSign) {
+            return Err(anyhow!(
+                "'=' alignment not allowed in string format specifier"
+            ));
+        }
+        let mut body = String::new();
+        match converted {
+            Some(converted) => body.push_str(converted),
+            None => value.collect_str(&mut body),
+        }
+        if let Some((i, _)) = self.precision.and_then(|p| body.char_indices().nth(p)) {
+            body.truncate(i);
+        }
+        self.pad(out, "", "", &body, Align::Left, heap)
+    }
+
+    fn write_number(
+        &self,
+        out: &mut String,
+        value: Value,
+        converted: Option<&str>,
+        heap: &Heap,
+    ) -> anyhow::Result<()> {
+        if let Some(converted) = converted {
+            return Err(anyhow!(
+                "Format specifier `{}` not allowed for string `{}`",
+                self.ty.unwrap(),
+                converted
+            ));
+        }
+        let is_int = match self.ty {
+            Some(ty) => matches!(ty, 'd' | 'b' | 'o' | 'x' | 'X'),
+            None => matches!(value.unpack_num(), Some(Num::Int(_))),
+        };
+        if is_int && self.precision.is_some() {
+            return Err(anyhow!("Precision not allowed in integer format specifier"));
+        }
+
+        let mut body = String::new();
+        match self.ty {
+            Some('b') => {
+                let v = value.to_int()?;
+                write!(
+                    body,
+                    "{}{:b}",
+                    if v < 0 { "-" } else { "" },
+                    v.wrapping_abs() as u64
+                )
+                .unwrap();
+            }
+            Some('d') => write!(body, "{}", value.to_int()?).unwrap(),
+            Some('%') => {
+                let v = Num::unpack_param(value)?.as_float() * 100.0;
+                if v.is_finite() {
+                    write!(body, "{:.*}", self.precision.unwrap_or(6), v).unwrap();
+                } else {
+                    float::write_decimal(&mut body, v).unwrap();
+                }
+                body.push('%');
+            }
+            // Python defaults to 6 digits of precision for floats.
+            Some(ty @ ('e' | 'E' | 'f' | 'F' | 'g' | 'G')) => write_conversion(
+                &mut body,
+                ty as u8,
+                value,
+                Some(self.precision.unwrap_or(6)),
+            )?,
+            Some(ty) => write_conversion(&mut body, ty as u8, value, None)?,
+            None => match self.precision {
+                None => value.collect_str(&mut body),
+                precision => write_conversion(&mut body, b'g', value, precision)?,
+            },
+        }
+
+        let (sign, digits) = match body.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None if self.plus => ("+", body.as_str()),
+            None if self.space => (" ", body.as_str()),
+            None => ("", body.as_str()),
+        };
+        let prefix = match self.ty {
+            Some('b') if self.alternate => "0b",
+            Some('o') if self.alternate => "0o",
+            Some('x') if self.alternate => "0x",
+            Some('X') if self.alternate => "0X",
+            _ => "",
+        };
+        self.pad(out, sign, prefix, digits, Align::Right, heap)
+    }
+
+    /// Write `sign`, `prefix` and `body`, filled to the width, failing if the result
+    /// would be longer than the heap allows.
+    /// Without an explicit alignment, uses `default`, or zero-padding after the sign.
+    fn pad(
+        &self,
+        out: &mut String,
+        sign: &str,
+        prefix: &str,
+        body: &str,
+        default: Align,
+        heap: &Heap,
+    ) -> anyhow::Result<()> {
+        let fill = self.fill.unwrap_or(if self.zero { '0' } else { ' ' });
+        let align = match self.align {
+            Some(align) => align,
+            None if self.zero && default == Align::Right => Align::AfterSign,
+            None => default,
+        };
+        let len = sign.len() + prefix.len() + body.chars().count();
+        let padding = self.width.saturating_sub(len);
+        heap.check_string_length(out.len() + len + padding * fill.len_utf8())?;
+        let (before, after) = match align {
+            Align::Left => (0, padding),
+            Align::Right => (padding, 0),
+            Align::Center => (padding / 2, padding - padding / 2),
+            Align::AfterSign => {
+                out.push_str(sign);
+                out.push_str(prefix);
+                out.extend(iter::repeat(fill).take(padding));
+                out.push_str(body);
+                return Ok(());
+            }
+        };
+        out.extend(iter::repeat(fill).take(before));
+        out.push_str(sign);
+        out.push_str(prefix);
+        out.push_str(body);
+        out.extend(iter::repeat(fill).take(after));
+        Ok(())
+    }
+}
+
 fn format_capture<'v, T: Iterator<Item = Value<'v>>>(
     capture: &str,
     args: &mut FormatArgs<'v, T>,
     kwargs: &Dict,
+    heap: &Heap,
     result: &mut String,
 ) -> anyhow::Result<()> {
-    let (n, conv) = {
-        if let Some(x) = capture.find('!') {
-            (capture.get(1..x).unwrap(), capture.get(x + 1..).unwrap())
-        } else {
-            (capture.get(1..).unwrap(), "s")
-        }
+    // The field name ends at the first `!` or `:`, so both can be used as a fill character.
+    let field = capture.get(1..).unwrap();
+    let (n, conv_name, spec) = match field.find(|c| c == '!' || c == ':') {
+        None => (field, None, None),
+        Some(x) if field.as_bytes()[x] == b':' => (&field[..x], None, Some(&field[x + 1..])),
+        Some(x) => match field[x + 1..].split_once(':') {
+            None => (&field[..x], Some(&field[x + 1..]), None),
+            Some((conv, spec)) => (&field[..x], Some(conv), Some(spec)),
+        },
     };
     let conv_s = |x: Value, result: &mut String| x.collect_str(result);
     let conv_r = |x: Value, result: &mut String| x.collect_repr(result);
-    let conv: &dyn Fn(Value, &mut String) = match conv {
+    let conv_a = |x: Value, result: &mut String| collect_ascii(&x.to_repr(), result);
+    let conv: &dyn Fn(Value, &mut String) = match conv_name.unwrap_or("s") {
         "s" => &conv_s,
         "r" => &conv_r,
+        "a" => &conv_a,
         c => {
             return Err(anyhow!(
                 concat!(
                     "'{}' is not a valid format string specifier, only ",
-                    "'s' and 'r' are valid specifiers",
+                    "'s', 'r' and 'a' are valid specifiers",
                 ),
                 c
             ));
         }
     };
-    if n.is_empty() {
-        conv(args.next_ordered()?, result);
-        Ok(())
+    let value = if n.is_empty() {
+        args.next_ordered()?
     } else if n.chars().all(|c| c.is_ascii_digit()) {
         let i = usize::from_str(n).unwrap();
-        conv(args.by_index(i)?, result);
-        Ok(())
+        args.by_index(i)?
     } else {
         if let Some(x) = n.chars().find(|c| match c {
             '.' | ',' | '[' | ']' => true,
@@ -417,13 +1025,25 @@ fn format_capture<'v, T: Iterator<Item = Value<'v>>>(
             ));
         }
         match kwargs.get_str(n) {
-            None => Err(ValueError::KeyNotFound(n.to_owned()).into()),
-            Some(v) => {
-                conv(v, result);
-                Ok(())
+            None => return Err(ValueError::KeyNotFound(n.to_owned()).into()),
+            Some(v) => v,
+        }
+    };
+    match spec {
+        None | Some("") => conv(value, result),
+        Some(spec) => {
+            let spec = FormatSpec::parse(spec)?;
+            match conv_name {
+                None => spec.write(result, value, None, heap)?,
+                Some(_) => {
+                    let mut converted = String::new();
+                    conv(value, &mut converted);
+                    spec.write(result, value, Some(&converted), heap)?
+                }
             }
         }
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -437,7 +1057,7 @@ mod tests {
         kwargs: &Dict,
     ) -> anyhow::Result<String> {
         let mut result = String::new();
-        super::format_capture(capture, args, kwargs, &mut result)?;
+        super::format_capture(capture, args, kwargs, &Heap::new(), &mut result)?;
         Ok(result)
     }
 
@@ -473,6 +1093,7 @@ mod tests {
             "x"
         );
         assert!(format_capture_for_test("{1", &mut args, &kwargs).is_err());
+        assert!(format_capture_for_test("{!x", &mut args, &kwargs).is_err());
         let mut args = FormatArgs::new(original_args.iter().copied());
         assert_eq!(
             format_capture_for_test("{1", &mut args, &kwargs).unwrap(),
@@ -481,6 +1102,79 @@ mod tests {
         assert!(format_capture_for_test("{", &mut args, &kwargs).is_err());
     }
 
+    #[test]
+    fn test_format_conversion() {
+        crate::assert::all_true(
+            r#"
+"{!r}".format("x") == '"x"'
+"{!s}".format(5) == "5"
+"{!s}".format("x") == "x"
+"{0!r} {0!s}".format("é") == '"é" é'
+"{x!a}".format(x = "é界😀") == '"\\xe9\\u754c\\U0001f600"'
+"{!a}".format(["a"]) == '["a"]'
+"#,
+        );
+    }
+
+    #[test]
+    fn test_format_spec() {
+        crate::assert::all_true(
+            r#"
+# Alignment and fill
+"[{:>10}]".format("abc") == "[       abc]"
+"[{:<6}]".format("abc") == "[abc   ]"
+"[{:6}]".format("abc") == "[abc   ]"
+"[{:6}]".format(42) == "[    42]"
+"[{:*^8}]".format("abc") == "[**abc***]"
+"[{:^7}]".format(1) == "[   1   ]"
+"[{:!>5}]".format("a") == "[!!!!a]"
+"[{::<4}]".format("a") == "[a:::]"
+"[{:=+6}]".format(42) == "[+   42]"
+"[{:2}]".format("abcd") == "[abcd]"
+"[{0:>3}|{0:<3}]".format("x") == "[  x|x  ]"
+"[{name:_^7}]".format(name = "mid") == "[__mid__]"
+# Zero-padding
+"{:05d}".format(42) == "00042"
+"{:05}".format(-42) == "-0042"
+"{:08.3f}".format(-3.14159) == "-003.142"
+"{:<05}".format(7) == "70000"
+# Integers
+"{:d}".format(42) == "42"
+"{:x}".format(255) == "ff"
+"{:X}".format(255) == "FF"
+"{:#x}".format(255) == "0xff"
+"{:#06x}".format(255) == "0x00ff"
+"{:b}".format(5) == "101"
+"{:#b}".format(-5) == "-0b101"
+"{:o}".format(8) == "10"
+"{:+d}".format(3) == "+3"
+"{: d}".format(3) == " 3"
+# Floats
+"{:f}".format(1.5) == "1.500000"
+"{:.2f}".format(3.14159) == "3.14"
+"{:.1f}".format(2) == "2.0"
+"{:e}".format(12345.678) == "1.234568e+04"
+"{:.3g}".format(0.0001234) == "0.000123"
+"{:.1%}".format(0.256) == "25.6%"
+"{:.2}".format(3.14159) == "3.1"
+# Strings
+"{:.2}".format("abc") == "ab"
+"{:s}".format("abc") == "abc"
+"[{!r:>5}]".format("a") == '[  "a"]'
+"[{:4}]".format([1]) == "[[1] ]"
+"#,
+        );
+        crate::assert::fail("'{:d}'.format('a')", "not supported");
+        crate::assert::fail("'{:+}'.format('a')", "Sign not allowed");
+        crate::assert::fail("'{:=5}'.format('a')", "'=' alignment not allowed");
+        crate::assert::fail("'{:.2d}'.format(1)", "Precision not allowed");
+        crate::assert::fail("'{!r:d}'.format(1)", "not allowed for string");
+        crate::assert::fail("'{:q}'.format(1)", "Invalid format specifier");
+        crate::assert::fail("'{:,}'.format(1000)", "Invalid format specifier");
+        crate::assert::fail("'{:99999999999999999999}'.format(1)", "Width too big");
+        crate::assert::fail("'{:.99999999999f}'.format(1.0)", "Precision too big");
+    }
+
     #[test]
     fn test_parse_format_one() {
         assert_eq!(
@@ -496,6 +1190,24 @@ mod tests {
         assert_eq!(None, parse_format_one("{x}"));
     }
 
+    #[test]
+    fn test_parse_format_many() {
+        assert_eq!(
+            Some(vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]),
+            parse_format_many("a{}b{}c")
+        );
+        assert_eq!(
+            Some(vec!["{".to_owned(), "}".to_owned(), "".to_owned()]),
+            parse_format_many("{{{}}}{}")
+        );
+        assert_eq!(Some(vec!["abc".to_owned()]), parse_format_many("abc"));
+        assert_eq!(None, parse_format_many("a{}{"));
+        assert_eq!(None, parse_format_many("{}}"));
+        assert_eq!(None, parse_format_many("{}{x}"));
+        assert_eq!(None, parse_format_many("{0}{1}"));
+        assert_eq!(None, parse_format_many("{}{!r}"));
+    }
+
     #[test]
     fn test_parse_percent_s_one() {
         assert_eq!(
@@ -511,4 +1223,77 @@ mod tests {
         assert_eq!(None, parse_percent_s_one("a%s%s"));
         assert_eq!(None, parse_percent_s_one("%d"));
     }
+
+    #[test]
+    fn test_parse_percent_s_many() {
+        assert_eq!(
+            Some(vec!["a".to_owned(), "b%".to_owned(), "c".to_owned()]),
+            parse_percent_s_many("a%sb%%%sc")
+        );
+        assert_eq!(Some(vec!["abc".to_owned()]), parse_percent_s_many("abc"));
+        assert_eq!(None, parse_percent_s_many("%s%"));
+        assert_eq!(None, parse_percent_s_many("%s%d"));
+        assert_eq!(None, parse_percent_s_many("%s%r"));
+    }
+
+    #[test]
+    fn test_percent_spec() {
+        crate::assert::all_true(
+            r#"
+# Width
+"[%5d]" % 42 == "[   42]"
+"[%5s]" % "ab" == "[   ab]"
+"[%*d]" % (4, 7) == "[   7]"
+"[%2d]" % 12345 == "[12345]"
+# Precision
+"%.2f" % 3.14159 == "3.14"
+"%5.1f" % 2.26 == "  2.3"
+"%.0f" % 2.7 == "3"
+"%.3e" % 12345.678 == "1.235e+04"
+"%.3g" % 12345.678 == "1.23e+04"
+"%.3g" % 0.0001234 == "0.000123"
+"%.3g" % 1.5 == "1.5"
+"%.3d" % 7 == "007"
+"%.2s" % "abc" == "ab"
+"%.*f" % (1, 0.26) == "0.3"
+# Left-justify
+"[%-5d]" % 42 == "[42   ]"
+"[%-6s]" % "ab" == "[ab    ]"
+"[%*s]" % (-3, "a") == "[a  ]"
+"[%-05d]" % 42 == "[42   ]"
+# Zero-pad
+"%05d" % 42 == "00042"
+"%05d" % -42 == "-0042"
+"%08.3f" % -3.14159 == "-003.142"
+"%05s" % "ab" == "   ab"
+# Explicit sign
+"%+d" % 42 == "+42"
+"%+d" % -42 == "-42"
+"%+.1f" % 1.0 == "+1.0"
+"% d" % 42 == " 42"
+"%+05d" % 42 == "+0042"
+# Alternate forms
+"%#x" % 255 == "0xff"
+"%#X" % 255 == "0XFF"
+"%#o" % 8 == "0o10"
+"%#06x" % 255 == "0x00ff"
+# Plain conversions are unchanged
+"%d%%" % 50 == "50%"
+"%z" % () == "%z"
+"%" % () == "%"
+"%i" % 3 == "3"
+"#,
+        );
+        crate::assert::fail("'%*d' % 3", "Not enough arguments");
+        crate::assert::fail("'%99999999999999999999d' % 1", "Width too big");
+        crate::assert::fail("'%*d' % (2147483647, 1)", "Width too big");
+        crate::assert::fail("'%.1000001f' % 1.0", "Precision too big");
+        crate::assert::fail("'%5%' % ()", "Unsupported format character '%' at index 2");
+        crate::assert::fail(
+            "'%*%' % (3,)",
+            "Unsupported format character '%' at index 2",
+        );
+        crate::assert::fail("'%-z' % ()", "Unsupported format character 'z' at index 2");
+        crate::assert::fail("'%.2' % 1.0", "Incomplete format");
+    }
 }
@@ -33,7 +33,7 @@ use gazebo::{any::AnyLifetime, coerce::Coerce, prelude::OptionExt};
 
 use crate as starlark;
 use crate::{
-    collections::{BorrowHashed, SmallHashResult, StarlarkHasher},
+    collections::{BorrowHashed, SmallHashResult, SmallHasher, StarlarkHasher},
     environment::{Globals, GlobalsStatic},
     values::{
         index::apply_slice, string::repr::string_repr, AllocFrozenValue, AllocValue, Freeze,
@@ -238,6 +238,10 @@ impl<'v> StarlarkValue<'v> for StarlarkStr {
         string_repr(self.unpack(), buffer)
     }
 
+    fn collect_str(&self, buffer: &mut String) {
+        buffer.push_str(self.unpack())
+    }
+
     fn to_json(&self) -> anyhow::Result<String> {
         Ok(json_escape(self.unpack()))
     }
@@ -261,6 +265,7 @@ impl<'v> StarlarkValue<'v> for StarlarkStr {
 
     fn compare(&self, other: Value) -> anyhow::Result<Ordering> {
         if let Some(other) = other.unpack_str() {
+            // Comparing UTF-8 bytes orders strings by Unicode code point, independent of locale.
             Ok(self.unpack().cmp(other))
         } else {
             ValueError::unsupported_with(self, "cmp()", other)
@@ -377,6 +382,7 @@ impl<'v> StarlarkValue<'v> for StarlarkStr {
             if self.is_empty() {
                 Ok(other)
             } else {
+                heap.check_string_length(self.len() + other_str.len())?;
                 Ok(heap.alloc_str_concat(self.unpack(), other_str))
             }
         } else {
@@ -387,7 +393,9 @@ impl<'v> StarlarkValue<'v> for StarlarkStr {
     fn mul(&self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         let l = i32::unpack_param(other)?;
         let s = self.unpack();
-        let mut result = String::with_capacity(s.len() * cmp::max(0, l) as usize);
+        let len = s.len().saturating_mul(cmp::max(0, l) as usize);
+        heap.check_string_length(len)?;
+        let mut result = String::with_capacity(len);
         for _i in 0..l {
             result.push_str(s)
         }
@@ -395,7 +403,7 @@ impl<'v> StarlarkValue<'v> for StarlarkStr {
     }
 
     fn percent(&self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
-        Ok(heap.alloc(interpolation::percent(self.unpack(), other)?))
+        Ok(heap.alloc(interpolation::percent(self.unpack(), other, heap)?))
     }
 }
 
@@ -484,6 +492,28 @@ mod tests {
         assert::fail("''[2]", "out of bound");
     }
 
+    #[test]
+    fn test_compare_code_points() {
+        // Strings are ordered by code point, so all ASCII sorts before anything else.
+        assert::all_true(
+            r#"
+"a" < "b"
+"Z" < "a"
+"z" < "é"
+"é" < "ê"
+"e\u0301" < "é"
+"Д" < "界"
+"界" < "😀"
+"ab" < "abc"
+"" < "\x00"
+sorted(["😀", "é", "b", "", "界", "B", "e\u0301", "ab"]) == ["", "B", "ab", "b", "e\u0301", "é", "界", "😀"]
+"é" * 3 == "ééé"
+2 * "界" == "界界"
+"é" * -1 == ""
+"#,
+        );
+    }
+
     #[test]
     fn test_escape_characters() {
         // Test cases from the Starlark spec
@@ -597,6 +627,25 @@ len("😿") == 1
         );
     }
 
+    #[test]
+    fn test_slice_string_negative_stride() {
+        assert::all_true(
+            r#"
+"hello"[::-1] == "olleh"
+"héllo"[::-1] == "olléh"
+""[::-1] == ""
+"abcdef"[::-2] == "fdb"
+"abcdef"[4:1:-2] == "ec"
+"abcdef"[5:0:-3] == "fc"
+"abcdef"[-1:-4:-1] == "fed"
+"abcdef"[:2:-1] == "fed"
+"abcdef"[0::-1] == "a"
+"abcdef"[10:-10:-1] == "fedcba"
+"abcdef"[1:4:-1] == ""
+"#,
+        );
+    }
+
     #[test]
     fn test_string_is_in() {
         assert::all_true(
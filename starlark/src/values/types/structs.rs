@@ -198,6 +198,15 @@ where
         coerce_ref(&self.fields).contains_key(attribute)
     }
 
+    /// A string is `in` a struct if it is the name of one of its fields.
+    /// Methods like `to_json` don't count, as they aren't fields.
+    fn is_in(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        match other.unpack_str() {
+            Some(name) => Ok(coerce_ref(&self.fields).contains_key(name)),
+            None => ValueError::unsupported_owned(other.get_type(), "in", Some(self.get_type())),
+        }
+    }
+
     fn dir_attr(&self) -> Vec<String> {
         self.fields
             .keys()
@@ -278,4 +287,30 @@ struct(foo = [struct(bar = "some")]).to_json() == '{"foo":[{"bar":"some"}]}'
 "#,
         );
     }
+
+    #[test]
+    fn test_field_order() {
+        // `dir` is sorted, but everything else uses the order the fields were given in.
+        assert::all_true(
+            r#"
+dir(struct(b=1, a=2)) == ["a", "b", "to_json"]
+dir(struct(a=2, b=1)) == ["a", "b", "to_json"]
+repr(struct(b=1, a=2)) == "struct(b=1, a=2)"
+struct(b=1, a=2).to_json() == '{"b":1,"a":2}'
+"#,
+        );
+    }
+
+    #[test]
+    fn test_in() {
+        assert::all_true(
+            r#"
+"a" in struct(a=1)
+"b" not in struct(a=1)
+"to_json" not in struct(a=1)
+"a" not in struct()
+"#,
+        );
+        assert::fail("1 in struct(a=1)", "not supported");
+    }
 }
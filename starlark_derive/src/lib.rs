@@ -73,6 +73,9 @@ mod util;
 ///   an attribute on the value. Such a function must take exactly one argument, namely a value
 ///   of the type you have attached it to.
 /// * The attribute `#[starlark_type("test")]` causes `f.type` to return `"test"`.
+/// * The attribute `#[speculative_exec_safe]` marks a function with no side effects,
+///   so a call where every argument is a constant may be evaluated at compile time.
+///   Calls which fail are left to fail at runtime.
 ///
 /// All these functions interoperate properly with `dir()`, `getattr()` and `hasattr()`.
 ///
@@ -55,6 +55,10 @@ fn is_attribute_attribute(x: &Attribute) -> bool {
     x.path.is_ident("attribute")
 }
 
+fn is_attribute_speculative_exec_safe(x: &Attribute) -> bool {
+    x.path.is_ident("speculative_exec_safe")
+}
+
 fn is_attribute_type(x: &Attribute) -> Option<NestedMeta> {
     if x.path.is_ident("starlark_type") {
         if let Ok(Meta::List(MetaList { nested, .. })) = x.parse_meta() {
@@ -74,18 +78,22 @@ fn is_attribute_type(x: &Attribute) -> Option<NestedMeta> {
 struct ProcessedAttributes {
     is_attribute: bool,
     type_attribute: Option<NestedMeta>,
+    speculative_exec_safe: bool,
     /// Rest attributes
     attrs: Vec<Attribute>,
 }
 
-/// (#[attribute], #[starlark_type(x)], rest)
+/// (#[attribute], #[starlark_type(x)], #[speculative_exec_safe], rest)
 fn process_attributes(xs: Vec<Attribute>) -> ProcessedAttributes {
     let mut attrs = Vec::with_capacity(xs.len());
     let mut is_attribute = false;
     let mut type_attribute = None;
+    let mut speculative_exec_safe = false;
     for x in xs {
         if is_attribute_attribute(&x) {
             is_attribute = true;
+        } else if is_attribute_speculative_exec_safe(&x) {
+            speculative_exec_safe = true;
         } else if let Some(t) = is_attribute_type(&x) {
             type_attribute = Some(t);
         } else {
@@ -97,10 +105,15 @@ fn process_attributes(xs: Vec<Attribute>) -> ProcessedAttributes {
             type_attribute.is_none(),
             "Can't be an attribute with a .type"
         );
+        assert!(
+            !speculative_exec_safe,
+            "Can't be an attribute and speculative_exec_safe"
+        );
     }
     ProcessedAttributes {
         is_attribute,
         type_attribute,
+        speculative_exec_safe,
         attrs,
     }
 }
@@ -110,6 +123,7 @@ fn parse_fun(func: ItemFn) -> StarStmt {
     let ProcessedAttributes {
         is_attribute,
         type_attribute,
+        speculative_exec_safe,
         attrs,
     } = process_attributes(func.attrs);
 
@@ -142,6 +156,7 @@ fn parse_fun(func: ItemFn) -> StarStmt {
         StarStmt::Fun(StarFun {
             name: func.sig.ident,
             type_attribute,
+            speculative_exec_safe,
             attrs,
             args,
             return_type: *return_type,
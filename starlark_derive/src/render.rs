@@ -118,6 +118,7 @@ fn render_fun(x: StarFun) -> TokenStream {
     let StarFun {
         name,
         type_attribute,
+        speculative_exec_safe,
         attrs,
         args: _,
         return_type,
@@ -136,6 +137,12 @@ fn render_fun(x: StarFun) -> TokenStream {
         }
     });
 
+    let set_speculative_exec_safe = if speculative_exec_safe {
+        quote_spanned! {span=> func.set_speculative_exec_safe();}
+    } else {
+        quote_spanned! {span=> }
+    };
+
     let signature_arg = signature.as_ref().map(
         |_| quote_spanned! {span=> __signature: &starlark::eval::ParametersSpec<starlark::values::FrozenValue>,},
     );
@@ -180,6 +187,7 @@ fn render_fun(x: StarFun) -> TokenStream {
                 #name_str.to_owned(),
             );
             #set_type
+            #set_speculative_exec_safe
             globals_builder.set(#name_str, func);
         }
     }
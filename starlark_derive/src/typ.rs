@@ -79,6 +79,7 @@ impl StarConst {
 pub(crate) struct StarFun {
     pub name: Ident,
     pub type_attribute: Option<NestedMeta>,
+    pub speculative_exec_safe: bool,
     pub attrs: Vec<Attribute>,
     pub args: Vec<StarArg>,
     pub return_type: Type,